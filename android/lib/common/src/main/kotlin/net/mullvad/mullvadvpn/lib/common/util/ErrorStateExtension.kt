@@ -55,7 +55,8 @@ fun ErrorStateCause.errorMessageId(): Int {
         is ErrorStateCause.TunnelParameterError -> {
             when (error) {
                 ParameterGenerationError.NoMatchingRelay,
-                ParameterGenerationError.NoMatchingBridgeRelay -> {
+                ParameterGenerationError.NoMatchingBridgeRelay,
+                ParameterGenerationError.QuantumResistanceUnsupported -> {
                     R.string.no_matching_relay
                 }
                 ParameterGenerationError.NoWireguardKey -> R.string.no_wireguard_key
@@ -238,6 +238,8 @@ internal fun ManagementInterface.ErrorState.GenerationError.toDomain(): Paramete
             ParameterGenerationError.NoWireguardKey
         ManagementInterface.ErrorState.GenerationError.CUSTOM_TUNNEL_HOST_RESOLUTION_ERROR ->
             ParameterGenerationError.CustomTunnelHostResultionError
+        ManagementInterface.ErrorState.GenerationError.QUANTUM_RESISTANCE_UNSUPPORTED ->
+            ParameterGenerationError.QuantumResistanceUnsupported
         ManagementInterface.ErrorState.GenerationError.UNRECOGNIZED ->
             throw IllegalArgumentException("Unrecognized parameter generation error")
     }
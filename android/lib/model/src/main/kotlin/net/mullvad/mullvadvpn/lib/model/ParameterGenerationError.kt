@@ -4,5 +4,6 @@ enum class ParameterGenerationError {
     NoMatchingRelay,
     NoMatchingBridgeRelay,
     NoWireguardKey,
-    CustomTunnelHostResultionError
+    CustomTunnelHostResultionError,
+    QuantumResistanceUnsupported
 }
@@ -1100,6 +1100,8 @@ function convertFromParameterError(
       return TunnelParameterError.noWireguardKey;
     case grpcTypes.ErrorState.GenerationError.CUSTOM_TUNNEL_HOST_RESOLUTION_ERROR:
       return TunnelParameterError.customTunnelHostResolutionError;
+    case grpcTypes.ErrorState.GenerationError.QUANTUM_RESISTANCE_UNSUPPORTED:
+      return TunnelParameterError.quantumResistanceUnsupported;
   }
 }
 
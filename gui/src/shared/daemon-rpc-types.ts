@@ -63,6 +63,7 @@ export enum TunnelParameterError {
   noMatchingBridgeRelay,
   noWireguardKey,
   customTunnelHostResolutionError,
+  quantumResistanceUnsupported,
 }
 
 export type ErrorState =
@@ -226,6 +226,11 @@ export class ErrorNotificationProvider
           'notifications',
           'Unable to resolve host of custom tunnel. Try changing your settings.',
         );
+      case TunnelParameterError.quantumResistanceUnsupported:
+        return messages.pgettext(
+          'notifications',
+          'The selected custom server does not support quantum-resistant tunnels. Try changing your settings.',
+        );
     }
   }
 
@@ -76,6 +76,8 @@ impl DevicesProxy {
                     pubkey,
                     hijack_dns,
                     created,
+                    pubkey_created: None,
+                    last_connection: None,
                 },
                 mullvad_types::wireguard::AssociatedAddresses {
                     ipv4_address,
@@ -170,3 +172,160 @@ impl DevicesProxy {
         }
     }
 }
+
+/// Tests against a local mock of the API. These require the `api-override` feature, since TLS has
+/// to be disabled to talk to the mock.
+#[cfg(all(test, feature = "api-override"))]
+mod test {
+    use super::*;
+    use crate::{ApiEndpoint, Runtime, API};
+    use std::sync::{Arc, Mutex};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    const ACCOUNT: &str = "1234123412341234";
+    const DEVICE_ID: &str = "d9f6f9b3-8a9c-4a1c-9bbd-2f9c1f7b0a3e";
+
+    /// A request received by the mock API: the request line, and the authorization header.
+    type Received = (String, Option<String>);
+
+    /// Serve `response` for every request to a path other than the token endpoint, and record
+    /// all received requests.
+    async fn mock_api(response: String) -> (DevicesProxy, Arc<Mutex<Vec<Received>>>) {
+        let _ = API.override_init(ApiEndpoint {
+            host: None,
+            address: None,
+            disable_address_cache: true,
+            disable_tls: true,
+            force_direct: true,
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_copy = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let request = read_request(stream, &response).await;
+                received_copy.lock().unwrap().push(request);
+            }
+        });
+
+        let runtime = Runtime::new(tokio::runtime::Handle::current()).unwrap();
+        let handle = runtime.static_mullvad_rest_handle(addr.to_string());
+        (DevicesProxy::new(handle), received)
+    }
+
+    async fn read_request(mut stream: TcpStream, response: &str) -> Received {
+        let mut buffer = vec![];
+        let head_len = loop {
+            let mut chunk = [0u8; 1024];
+            let n = stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "connection closed before request was complete");
+            buffer.extend_from_slice(&chunk[..n]);
+            if let Some(pos) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+        let head = String::from_utf8(buffer[..head_len].to_vec()).unwrap();
+        let header = |name: &str| {
+            head.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.eq_ignore_ascii_case(name)
+                    .then(|| value.trim().to_owned())
+            })
+        };
+        let content_length: usize = header("content-length")
+            .map(|length| length.parse().unwrap())
+            .unwrap_or(0);
+        while buffer.len() < head_len + content_length {
+            let mut chunk = [0u8; 1024];
+            let n = stream.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..n]);
+        }
+
+        let request_line = head.lines().next().unwrap().to_owned();
+        let response = if request_line.starts_with("POST /auth/v1/token ") {
+            let body = r#"{"access_token":"mock-token","expiry":"2100-01-01T00:00:00Z"}"#;
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        } else {
+            response.to_owned()
+        };
+        stream.write_all(response.as_bytes()).await.unwrap();
+        (request_line, header("authorization"))
+    }
+
+    fn json_response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[tokio::test]
+    async fn test_list_devices() {
+        let body = format!(
+            r#"[{{"id":"{DEVICE_ID}","name":"happy seagull","pubkey":"{}","hijack_dns":false,"created":"2024-01-01T12:00:00Z","ipv4_address":"10.64.0.2/32","ipv6_address":"fc00:bbbb:bbbb:bb01::2/128","ports":[]}}]"#,
+            wireguard::PrivateKey::new_from_random().public_key()
+        );
+        let (proxy, received) = mock_api(json_response("200 OK", &body)).await;
+
+        let devices = proxy.list(ACCOUNT.to_owned()).await.unwrap();
+
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].id, DEVICE_ID);
+        assert_eq!(devices[0].pretty_name(), "Happy Seagull");
+        assert_eq!(devices[0].created.to_rfc3339(), "2024-01-01T12:00:00+00:00");
+        // Only the local daemon knows about key age and connections
+        assert!(devices[0].pubkey_created.is_none());
+        assert!(devices[0].last_connection.is_none());
+
+        let received = received.lock().unwrap();
+        let (request_line, authorization) = received.last().unwrap();
+        assert_eq!(request_line, "GET /accounts/v1/devices HTTP/1.1");
+        assert_eq!(authorization.as_deref(), Some("Bearer mock-token"));
+    }
+
+    #[tokio::test]
+    async fn test_remove_device() {
+        let (proxy, received) =
+            mock_api("HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n".to_owned()).await;
+
+        proxy
+            .remove(ACCOUNT.to_owned(), DEVICE_ID.to_owned())
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        let (request_line, _) = received.last().unwrap();
+        assert_eq!(
+            request_line,
+            &format!("DELETE /accounts/v1/devices/{DEVICE_ID} HTTP/1.1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_unknown_device() {
+        let (proxy, _received) = mock_api(json_response(
+            "404 Not Found",
+            r#"{"code":"DEVICE_NOT_FOUND","detail":"The device was not found"}"#,
+        ))
+        .await;
+
+        let error = proxy
+            .remove(ACCOUNT.to_owned(), DEVICE_ID.to_owned())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            rest::Error::ApiError(StatusCode::NOT_FOUND, code) if code == crate::DEVICE_NOT_FOUND
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+//! A minimal HTTP client for hosts outside of the Mullvad infrastructure, such as DNS blocklist
+//! sources. Unlike the API client, it connects directly, verifies certificates against the
+//! bundled Mozilla root certificates, and does not follow redirects.
+
+use crate::{
+    proxy::{ApiConnection, ConnectionDecorator},
+    tls_stream::TlsStream,
+};
+use futures::{future::BoxFuture, FutureExt};
+use http::uri::Scheme;
+use hyper::{header, service::Service, Body, HeaderMap, StatusCode, Uri};
+use std::{
+    io,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::net::TcpStream;
+
+const USER_AGENT: &str = "mullvad-app";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid URL")]
+    InvalidUrl(#[source] http::uri::InvalidUri),
+
+    #[error("Only HTTP and HTTPS URLs are supported")]
+    UnsupportedScheme,
+
+    #[error("Request failed")]
+    Hyper(#[source] hyper::Error),
+
+    #[error("Request timed out")]
+    TimedOut,
+}
+
+/// A complete HTTP response.
+#[derive(Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// Send a GET request to `url` and read the whole response. The request fails if it does not
+/// complete within `timeout`.
+pub async fn get(url: &str, timeout: Duration) -> Result<Response, Error> {
+    let uri: Uri = url.parse().map_err(Error::InvalidUrl)?;
+    if uri.scheme() != Some(&Scheme::HTTP) && uri.scheme() != Some(&Scheme::HTTPS) {
+        return Err(Error::UnsupportedScheme);
+    }
+
+    let client = hyper::Client::builder().build::<_, Body>(Connector);
+    let mut request = hyper::Request::new(Body::empty());
+    *request.uri_mut() = uri;
+    request.headers_mut().insert(
+        header::USER_AGENT,
+        header::HeaderValue::from_static(USER_AGENT),
+    );
+
+    let response = async {
+        let response = client.request(request).await?;
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        Ok(Response {
+            status: parts.status,
+            headers: parts.headers,
+            body: body.to_vec(),
+        })
+    };
+    tokio::time::timeout(timeout, response)
+        .await
+        .map_err(|_| Error::TimedOut)?
+        .map_err(Error::Hyper)
+}
+
+/// Connects to the host in the URI, using TLS for the `https` scheme.
+#[derive(Clone)]
+struct Connector;
+
+impl Service<Uri> for Connector {
+    type Response = ApiConnection;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, io::Result<ApiConnection>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        async move {
+            let host = uri
+                .host()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing host"))?
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_owned();
+            let https = uri.scheme() == Some(&Scheme::HTTPS);
+            let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+
+            let stream = TcpStream::connect((host.as_str(), port)).await?;
+            if https {
+                let stream = TlsStream::connect_https_public(stream, &host).await?;
+                Ok(ApiConnection::new(Box::new(stream)))
+            } else {
+                Ok(ApiConnection::new(Box::new(ConnectionDecorator(stream))))
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Serve a single HTTP request with `response`.
+    async fn serve_once(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/test", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let url =
+            serve_once("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello")
+                .await;
+        let response = get(&url, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_not_followed() {
+        let url = serve_once(
+            "HTTP/1.1 302 Found\r\nLocation: http://portal.example/\r\nContent-Length: 0\r\n\r\n",
+        )
+        .await;
+        let response = get(&url, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(response.status, StatusCode::FOUND);
+        assert_eq!(
+            response.headers.get(header::LOCATION).unwrap(),
+            "http://portal.example/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_scheme() {
+        assert!(matches!(
+            get("ftp://example.com/list.txt", Duration::from_secs(5)).await,
+            Err(Error::UnsupportedScheme)
+        ));
+    }
+}
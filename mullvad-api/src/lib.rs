@@ -1,4 +1,5 @@
 #![allow(rustdoc::private_intra_doc_links)]
+use chrono::{DateTime, Utc};
 #[cfg(target_os = "android")]
 use futures::channel::mpsc;
 use hyper::Method;
@@ -25,6 +26,7 @@ use availability::{ApiAvailability, ApiAvailabilityHandle};
 pub mod rest;
 
 mod abortable_stream;
+pub mod http_client;
 mod https_client_with_sni;
 pub mod proxy;
 mod tls_stream;
@@ -309,6 +311,7 @@ pub struct Runtime {
     handle: tokio::runtime::Handle,
     address_cache: AddressCache,
     api_availability: availability::ApiAvailability,
+    request_stats: rest::RequestStatsHandle,
     #[cfg(target_os = "android")]
     socket_bypass_tx: Option<mpsc::Sender<SocketBypassRequest>>,
 }
@@ -341,6 +344,7 @@ impl Runtime {
             handle,
             address_cache: AddressCache::with_static_addr(address),
             api_availability: ApiAvailability::new(availability::State::default()),
+            request_stats: rest::RequestStatsHandle::default(),
         }
     }
 
@@ -352,6 +356,7 @@ impl Runtime {
             handle,
             address_cache: AddressCache::new(None)?,
             api_availability: ApiAvailability::new(availability::State::default()),
+            request_stats: rest::RequestStatsHandle::default(),
             #[cfg(target_os = "android")]
             socket_bypass_tx,
         })
@@ -400,6 +405,7 @@ impl Runtime {
             handle,
             address_cache,
             api_availability: ApiAvailability::new(availability::State::default()),
+            request_stats: rest::RequestStatsHandle::default(),
             #[cfg(target_os = "android")]
             socket_bypass_tx,
         })
@@ -417,6 +423,7 @@ impl Runtime {
             self.api_availability.handle(),
             self.address_cache.clone(),
             connection_mode_provider,
+            self.request_stats.clone(),
             #[cfg(target_os = "android")]
             socket_bypass_tx,
         )
@@ -474,6 +481,11 @@ impl Runtime {
     pub fn address_cache(&self) -> &AddressCache {
         &self.address_cache
     }
+
+    /// Returns a handle to the latencies and errors of requests made through this runtime.
+    pub fn request_stats(&self) -> rest::RequestStatsHandle {
+        self.request_stats.clone()
+    }
 }
 
 #[derive(Clone)]
@@ -750,4 +762,21 @@ impl ApiProxy {
         let response = self.handle.service.request(request).await?;
         Ok(response.status().is_success())
     }
+
+    /// Returns the current time according to the `Date` header sent by the API, if any.
+    pub async fn get_server_time(&self) -> Result<Option<DateTime<Utc>>, rest::Error> {
+        let request = self
+            .handle
+            .factory
+            .head(&format!("{APP_URL_PREFIX}/api-addrs"))?
+            .expected_status(&[StatusCode::OK]);
+
+        let response = self.handle.service.request(request).await?;
+        Ok(response
+            .headers()
+            .get(hyper::header::DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+            .map(|date| date.with_timezone(&Utc)))
+    }
 }
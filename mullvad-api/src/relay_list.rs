@@ -158,6 +158,7 @@ fn into_mullvad_relay(
         owned: relay.owned,
         provider: relay.provider,
         weight: relay.weight,
+        custom: false,
         endpoint_data,
         location: Some(location),
     }
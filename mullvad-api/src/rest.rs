@@ -19,10 +19,11 @@ use hyper::{
 use mullvad_types::account::AccountToken;
 use std::{
     borrow::Cow,
+    collections::BTreeMap,
     error::Error as StdError,
     str::FromStr,
-    sync::{Arc, Weak},
-    time::Duration,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
 };
 use talpid_types::ErrorExt;
 
@@ -93,6 +94,22 @@ impl Error {
         matches!(self, Error::Aborted)
     }
 
+    /// Returns a short name for the kind of error, for counting errors by kind.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::RestServiceDown => "service_down",
+            Error::Aborted => "aborted",
+            Error::HyperError(_) => "network",
+            Error::TimeoutError => "timeout",
+            Error::ApiError(..) => "status",
+            Error::DeserializeError(_) => "deserialize",
+            Error::InvalidHeaderError
+            | Error::HttpError(_)
+            | Error::InvalidUri
+            | Error::NoAccessTokenStore => "request",
+        }
+    }
+
     /// Returns a new instance for which `abortable_stream::Aborted` is mapped to `Self::Aborted`.
     fn map_aborted(self) -> Self {
         if let Error::HyperError(error) = &self {
@@ -113,6 +130,76 @@ impl Error {
     }
 }
 
+/// Upper bounds of the latency buckets in [`RequestStats`].
+pub const LATENCY_BUCKETS: [Duration; 7] = [
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// Latencies and errors of all requests made by this process.
+#[derive(Debug, Clone)]
+pub struct RequestStats {
+    /// Number of requests that received a response, or failed while waiting for one.
+    pub count: u64,
+    /// Sum of the latencies of the requests in `count`.
+    pub latency_sum: Duration,
+    /// Number of requests with a latency of at most the corresponding [`LATENCY_BUCKETS`] entry.
+    pub latency_buckets: [u64; LATENCY_BUCKETS.len()],
+    /// Number of failed requests, by [`Error::kind`].
+    pub errors: BTreeMap<&'static str, u64>,
+}
+
+impl Default for RequestStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            latency_sum: Duration::ZERO,
+            latency_buckets: [0; LATENCY_BUCKETS.len()],
+            errors: BTreeMap::new(),
+        }
+    }
+}
+
+impl RequestStats {
+    fn record_latency(&mut self, latency: Duration) {
+        self.count += 1;
+        self.latency_sum += latency;
+        for (bucket, count) in LATENCY_BUCKETS.iter().zip(self.latency_buckets.iter_mut()) {
+            if latency <= *bucket {
+                *count += 1;
+            }
+        }
+    }
+
+    fn record_error(&mut self, error: &Error) {
+        *self.errors.entry(error.kind()).or_default() += 1;
+    }
+}
+
+/// Handle to the [`RequestStats`] of the request services created by a [`crate::Runtime`].
+#[derive(Debug, Default, Clone)]
+pub struct RequestStatsHandle(Arc<Mutex<RequestStats>>);
+
+impl RequestStatsHandle {
+    /// Returns the latencies and errors of all requests made so far.
+    pub fn get(&self) -> RequestStats {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        self.0.lock().unwrap().record_latency(latency);
+    }
+
+    fn record_error(&self, error: &Error) {
+        self.0.lock().unwrap().record_error(error);
+    }
+}
+
 /// A service that executes HTTP requests, allowing for on-demand termination of all in-flight
 /// requests
 pub(crate) struct RequestService<T: ConnectionModeProvider> {
@@ -123,6 +210,7 @@ pub(crate) struct RequestService<T: ConnectionModeProvider> {
     connection_mode_provider: T,
     connection_mode_generation: usize,
     api_availability: ApiAvailabilityHandle,
+    request_stats: RequestStatsHandle,
 }
 
 impl<T: ConnectionModeProvider + 'static> RequestService<T> {
@@ -132,6 +220,7 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
         api_availability: ApiAvailabilityHandle,
         address_cache: AddressCache,
         connection_mode_provider: T,
+        request_stats: RequestStatsHandle,
         #[cfg(target_os = "android")] socket_bypass_tx: Option<mpsc::Sender<SocketBypassRequest>>,
     ) -> RequestServiceHandle {
         let (connector, connector_handle) = HttpsConnectorWithSni::new(
@@ -156,6 +245,7 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
             connection_mode_provider,
             connection_mode_generation: 0,
             api_availability,
+            request_stats,
         };
         let handle = RequestServiceHandle { tx: command_tx };
         tokio::spawn(service.into_future());
@@ -209,12 +299,20 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
         let tx = self.command_tx.upgrade();
 
         let api_availability = self.api_availability.clone();
-        let request_future = request.into_future(self.client.clone(), api_availability.clone());
+        let request_stats = self.request_stats.clone();
+        let request_future = request.into_future(
+            self.client.clone(),
+            api_availability.clone(),
+            request_stats.clone(),
+        );
 
         let connection_mode_generation = self.connection_mode_generation;
 
         tokio::spawn(async move {
             let response = request_future.await.map_err(|error| error.map_aborted());
+            if let Err(error) = &response {
+                request_stats.record_error(error);
+            }
 
             // Switch API endpoint if the request failed due to a network error
             if let Err(err) = &response {
@@ -340,9 +438,11 @@ impl Request {
         self,
         hyper_client: hyper::Client<C>,
         api_availability: ApiAvailabilityHandle,
+        request_stats: RequestStatsHandle,
     ) -> Result<Response> {
         let timeout = self.timeout;
-        let inner_fut = self.into_future_without_timeout(hyper_client, api_availability);
+        let inner_fut =
+            self.into_future_without_timeout(hyper_client, api_availability, request_stats);
         tokio::time::timeout(timeout, inner_fut)
             .await
             .map_err(|_| Error::TimeoutError)?
@@ -352,6 +452,7 @@ impl Request {
         mut self,
         hyper_client: hyper::Client<C>,
         api_availability: ApiAvailabilityHandle,
+        request_stats: RequestStatsHandle,
     ) -> Result<Response> {
         let _ = api_availability.wait_for_unsuspend().await;
 
@@ -366,10 +467,12 @@ impl Request {
         }
 
         // Make request to hyper client
+        let started = Instant::now();
         let response = hyper_client
             .request(self.request)
             .await
             .map_err(Error::from);
+        request_stats.record_latency(started.elapsed());
 
         // Notify access token store of expired tokens
         if let (Some(account), Some(store)) = (&self.account, &self.access_token_store) {
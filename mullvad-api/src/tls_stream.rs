@@ -1,4 +1,5 @@
-//! Provides a TLS 1.3 stream with SNI and LE root cert only.
+//! Provides a TLS 1.3 stream with SNI and LE root cert only, and a TLS stream for hosts outside
+//! of the Mullvad infrastructure.
 use std::{
     io::{self, ErrorKind},
     pin::Pin,
@@ -36,7 +37,29 @@ where
             Arc::new(config)
         });
 
-        let connector = TlsConnector::from(TLS_CONFIG.clone());
+        Self::connect(TLS_CONFIG.clone(), stream, domain).await
+    }
+
+    /// Connect to a host outside of the Mullvad infrastructure. Its certificate is verified
+    /// against the bundled Mozilla root certificates.
+    pub async fn connect_https_public(stream: S, domain: &str) -> io::Result<TlsStream<S>> {
+        static TLS_CONFIG: Lazy<Arc<ClientConfig>> = Lazy::new(|| {
+            let config = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(public_cert_store())
+                .with_no_client_auth();
+            Arc::new(config)
+        });
+
+        Self::connect(TLS_CONFIG.clone(), stream, domain).await
+    }
+
+    async fn connect(
+        config: Arc<ClientConfig>,
+        stream: S,
+        domain: &str,
+    ) -> io::Result<TlsStream<S>> {
+        let connector = TlsConnector::from(config);
 
         let host = match ServerName::try_from(domain) {
             Ok(n) => n,
@@ -67,6 +90,18 @@ fn read_cert_store() -> rustls::RootCertStore {
     cert_store
 }
 
+fn public_cert_store() -> rustls::RootCertStore {
+    let mut cert_store = rustls::RootCertStore::empty();
+    cert_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+    cert_store
+}
+
 impl<S> AsyncRead for TlsStream<S>
 where
     S: AsyncRead + AsyncWrite + Unpin,
@@ -118,4 +153,9 @@ mod test {
     fn test_cert_loading() {
         let _certs = read_cert_store();
     }
+
+    #[test]
+    fn test_public_cert_loading() {
+        assert!(!public_cert_store().is_empty());
+    }
 }
@@ -1,10 +1,16 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use clap::Subcommand;
 use itertools::Itertools;
 use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::{account::AccountToken, device::DeviceState};
+use mullvad_types::{
+    account::{AccountToken, SavedAccount},
+    device::{Device, DeviceId, DeviceState},
+};
+use serde_json::json;
 use std::io::{self, Write};
 
+use crate::format;
+
 const NOT_LOGGED_IN_MESSAGE: &str = "Not logged in on any account";
 const REVOKED_MESSAGE: &str = "The current device has been revoked";
 
@@ -50,11 +56,70 @@ pub enum Account {
         account: Option<String>,
     },
 
+    /// Revoke all devices that were created more than a given time ago. The current device is
+    /// never revoked
+    PruneDevices {
+        /// Revoke devices that are older than this, e.g. "30d", "12h" or "2w"
+        #[arg(long, value_parser = parse_age)]
+        older_than: chrono::Duration,
+
+        /// Only list the devices that would be revoked
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Mullvad account number (current account if not specified)
+        #[arg(long, short = 'a')]
+        account: Option<String>,
+    },
+
+    /// Save an account under a name so that it can be switched to
+    Save {
+        /// Name to save the account under
+        name: String,
+
+        /// Mullvad account number (current account if not specified)
+        #[arg(long, short = 'a')]
+        account: Option<String>,
+    },
+
+    /// Remove a saved account. Its device is removed unless the account is in use
+    Forget {
+        /// Name of the saved account
+        name: String,
+    },
+
+    /// List saved accounts
+    ListSaved,
+
+    /// Log in on a saved account. The device of the current account is kept if
+    /// it is saved, so that switching back does not use up another device
+    Switch {
+        /// Name of the saved account
+        name: String,
+    },
+
     /// Redeem a voucher
     Redeem {
         /// Voucher code to submit
         voucher: String,
     },
+
+    /// Configure when to be warned that the account is about to expire
+    #[clap(subcommand)]
+    ExpiryNotifications(ExpiryNotifications),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExpiryNotifications {
+    /// Display the notification thresholds
+    Get,
+
+    /// Set the notification thresholds
+    Set {
+        /// Hours before the account expires at which to notify. Pass no values to only be
+        /// notified when the account has run out of time
+        hours: Vec<u32>,
+    },
 }
 
 impl Account {
@@ -77,13 +142,25 @@ impl Account {
             Account::RevokeDevice { device, account } => {
                 Self::revoke_device(&mut rpc, device, account).await
             }
+            Account::PruneDevices {
+                older_than,
+                dry_run,
+                account,
+            } => Self::prune_devices(&mut rpc, older_than, dry_run, account).await,
+            Account::Save { name, account } => Self::save(&mut rpc, name, account).await,
+            Account::Forget { name } => Self::forget(&mut rpc, name).await,
+            Account::ListSaved => Self::list_saved(&mut rpc).await,
+            Account::Switch { name } => Self::switch(&mut rpc, name).await,
             Account::Redeem { voucher } => Self::redeem_voucher(&mut rpc, voucher).await,
+            Account::ExpiryNotifications(cmd) => cmd.handle(&mut rpc).await,
         }
     }
 
     async fn create(rpc: &mut MullvadProxyClient) -> Result<()> {
         rpc.create_new_account().await?;
-        println!("New account created!");
+        if !format::json_output() {
+            println!("New account created!");
+        }
         Self::get(rpc, false).await
     }
 
@@ -104,6 +181,10 @@ impl Account {
 
         let state = rpc.get_device().await?;
 
+        if format::json_output() {
+            return Self::print_json(rpc, state).await;
+        }
+
         match state {
             DeviceState::LoggedIn(device) => {
                 println!("{:<20}{}", "Mullvad account:", device.account_token);
@@ -117,6 +198,9 @@ impl Account {
                 if verbose {
                     println!("{:<20}{}", "Account id:", data.id);
                 }
+                if let Some(notification) = rpc.get_account_expiry_state().await? {
+                    println!("{}", format::format_expiry_notification(&notification));
+                }
 
                 println!("{:<20}{}", "Device name:", device.device.pretty_name());
                 if verbose {
@@ -139,6 +223,29 @@ impl Account {
         Ok(())
     }
 
+    async fn print_json(rpc: &mut MullvadProxyClient, state: DeviceState) -> Result<()> {
+        let output = match state {
+            DeviceState::LoggedIn(device) => {
+                let data = rpc.get_account_data(device.account_token.clone()).await?;
+                let expiry_notification = rpc.get_account_expiry_state().await?;
+                json!({
+                    "state": "logged_in",
+                    "account_token": device.account_token,
+                    "account_id": data.id,
+                    "expiry": data.expiry,
+                    "expiry_notification": expiry_notification,
+                    "device": device.device,
+                })
+            }
+            DeviceState::LoggedOut => json!({ "state": "logged_out" }),
+            DeviceState::Revoked => json!({
+                "state": "revoked",
+                "account_token": rpc.get_account_history().await?,
+            }),
+        };
+        format::print_json(&output)
+    }
+
     async fn list_devices(
         rpc: &mut MullvadProxyClient,
         account: Option<String>,
@@ -146,27 +253,88 @@ impl Account {
     ) -> Result<()> {
         let token = account_else_current(rpc, account).await?;
         let mut device_list = rpc.list_devices(token).await?;
+        device_list.sort_unstable_by_key(|dev| dev.created.timestamp());
+
+        if format::json_output() {
+            return format::print_json(&device_list);
+        }
 
         println!("Devices on the account:");
-        device_list.sort_unstable_by_key(|dev| dev.created.timestamp());
         for device in device_list {
             if verbose {
                 println!();
-                println!("Name      : {}", device.pretty_name());
-                println!("Id        : {}", device.id);
-                println!("Public key: {}", device.pubkey);
+                println!("Name           : {}", device.pretty_name());
+                println!("Id             : {}", device.id);
+                println!("Public key     : {}", device.pubkey);
                 println!(
-                    "Created   : {}",
+                    "Created        : {}",
                     device.created.with_timezone(&chrono::Local)
                 );
+                if let Some(pubkey_created) = device.pubkey_created {
+                    let age = (chrono::Utc::now() - pubkey_created).num_seconds().max(0);
+                    println!("Key age        : {}", format_duration(age as u64));
+                }
+                if let Some(connection) = &device.last_connection {
+                    println!(
+                        "Last connection: {} ({})",
+                        connection.relay,
+                        connection.time.with_timezone(&chrono::Local)
+                    );
+                }
             } else {
-                println!("{}", device.pretty_name());
+                let last_used = match &device.last_connection {
+                    Some(connection) => format!(
+                        ", last connected to {} on {}",
+                        connection.relay,
+                        connection.time.with_timezone(&chrono::Local).date_naive()
+                    ),
+                    None => String::new(),
+                };
+                println!(
+                    "{} (created {}{last_used})",
+                    device.pretty_name(),
+                    device.created.with_timezone(&chrono::Local).date_naive()
+                );
             }
         }
 
         Ok(())
     }
 
+    async fn prune_devices(
+        rpc: &mut MullvadProxyClient,
+        older_than: chrono::Duration,
+        dry_run: bool,
+        account: Option<String>,
+    ) -> Result<()> {
+        let token = account_else_current(rpc, account).await?;
+        let current_device = match rpc.get_device().await? {
+            DeviceState::LoggedIn(device) => Some(device.device.id),
+            DeviceState::LoggedOut | DeviceState::Revoked => None,
+        };
+
+        let cutoff = chrono::Utc::now() - older_than;
+        let devices = rpc.list_devices(token.clone()).await?;
+        let stale = select_stale_devices(devices, current_device.as_ref(), cutoff);
+
+        if stale.is_empty() {
+            println!("No devices to remove");
+            return Ok(());
+        }
+        for device in stale {
+            if !dry_run {
+                rpc.remove_device(token.clone(), device.id.clone()).await?;
+            }
+            println!(
+                "{} {} (created {})",
+                if dry_run { "Would remove" } else { "Removed" },
+                device.pretty_name(),
+                device.created.with_timezone(&chrono::Local).date_naive()
+            );
+        }
+        Ok(())
+    }
+
     async fn revoke_device(
         rpc: &mut MullvadProxyClient,
         device: String,
@@ -188,6 +356,54 @@ impl Account {
         Ok(())
     }
 
+    async fn save(
+        rpc: &mut MullvadProxyClient,
+        name: String,
+        account: Option<String>,
+    ) -> Result<()> {
+        let account_token = account_else_current(rpc, account).await?;
+        rpc.save_account(SavedAccount {
+            name: name.clone(),
+            account_token,
+        })
+        .await?;
+        println!("Saved account as \"{name}\"");
+        Ok(())
+    }
+
+    async fn forget(rpc: &mut MullvadProxyClient, name: String) -> Result<()> {
+        rpc.remove_saved_account(name.clone()).await?;
+        println!("Removed saved account \"{name}\"");
+        Ok(())
+    }
+
+    async fn list_saved(rpc: &mut MullvadProxyClient) -> Result<()> {
+        let accounts = rpc.list_saved_accounts().await?;
+        if format::json_output() {
+            return format::print_json(&accounts);
+        }
+
+        let current = match rpc.get_device().await? {
+            DeviceState::LoggedIn(device) => Some(device.account_token),
+            DeviceState::LoggedOut | DeviceState::Revoked => None,
+        };
+        for account in accounts {
+            let marker = if current.as_ref() == Some(&account.account_token) {
+                " (current)"
+            } else {
+                ""
+            };
+            println!("{:<20}{}{marker}", account.name, account.account_token);
+        }
+        Ok(())
+    }
+
+    async fn switch(rpc: &mut MullvadProxyClient, name: String) -> Result<()> {
+        rpc.switch_account(name.clone()).await?;
+        println!("Switched to account \"{name}\"");
+        Ok(())
+    }
+
     async fn redeem_voucher(rpc: &mut MullvadProxyClient, mut voucher: String) -> Result<()> {
         voucher.retain(|c| c.is_alphanumeric());
 
@@ -204,6 +420,37 @@ impl Account {
     }
 }
 
+impl ExpiryNotifications {
+    async fn handle(self, rpc: &mut MullvadProxyClient) -> Result<()> {
+        match self {
+            ExpiryNotifications::Get => {
+                let hours = rpc.get_settings().await?.expiry_notification_hours;
+                if format::json_output() {
+                    return format::print_json(&json!({ "expiry_notification_hours": hours }));
+                }
+                println!("Expiry notifications: {}", format_hours(&hours));
+            }
+            ExpiryNotifications::Set { hours } => {
+                rpc.set_expiry_notification_hours(hours).await?;
+                let hours = rpc.get_settings().await?.expiry_notification_hours;
+                println!("Expiry notifications: {}", format_hours(&hours));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_hours(hours: &[u32]) -> String {
+    if hours.is_empty() {
+        return "only when expired".to_owned();
+    }
+    hours
+        .iter()
+        .map(|hours| format!("{hours} hours"))
+        .join(", ")
+        + " before expiry"
+}
+
 async fn account_else_current(
     rpc: &mut MullvadProxyClient,
     token: Option<String>,
@@ -242,6 +489,42 @@ fn from_stdin(prompt_str: &'static str) -> String {
     val.split_whitespace().join("")
 }
 
+/// Return the devices in `devices` that were created before `cutoff`. The current device is never
+/// included. The API does not report when other devices were last used, so their age is the only
+/// thing that can be compared.
+fn select_stale_devices(
+    devices: Vec<Device>,
+    current_device: Option<&DeviceId>,
+    cutoff: chrono::DateTime<chrono::Utc>,
+) -> Vec<Device> {
+    devices
+        .into_iter()
+        .filter(|device| Some(&device.id) != current_device && device.created < cutoff)
+        .collect()
+}
+
+/// Parse an age such as "30d", where the unit is one of "m", "h", "d" or "w".
+fn parse_age(s: &str) -> Result<chrono::Duration> {
+    let s = s.trim();
+    let Some(unit) = s.chars().last() else {
+        bail!("Empty duration");
+    };
+    let value: i64 = s[..s.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| anyhow!("Invalid duration: {s}"))?;
+    if value <= 0 {
+        bail!("The duration must be greater than zero");
+    }
+    let age = match unit {
+        'm' => chrono::Duration::try_minutes(value),
+        'h' => chrono::Duration::try_hours(value),
+        'd' => chrono::Duration::try_days(value),
+        'w' => chrono::Duration::try_weeks(value),
+        _ => bail!("Unknown unit '{unit}'. Use one of m, h, d or w"),
+    };
+    age.ok_or_else(|| anyhow!("Duration is too long: {s}"))
+}
+
 fn format_duration(seconds: u64) -> String {
     let dur = chrono::Duration::seconds(seconds as i64);
     if dur.num_days() > 0 {
@@ -254,3 +537,50 @@ fn format_duration(seconds: u64) -> String {
         format!("{} seconds", dur.num_seconds())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::wireguard::PrivateKey;
+
+    fn device(id: &str, created_days_ago: i64) -> Device {
+        let now = chrono::Utc::now();
+        Device {
+            id: id.to_owned(),
+            name: id.to_owned(),
+            pubkey: PrivateKey::new_from_random().public_key(),
+            hijack_dns: false,
+            created: now - chrono::Duration::days(created_days_ago),
+            pubkey_created: None,
+            last_connection: None,
+        }
+    }
+
+    fn ids(devices: &[Device]) -> Vec<&str> {
+        devices.iter().map(|device| device.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_select_stale_devices() {
+        let devices = vec![device("current", 100), device("old", 100), device("new", 1)];
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(30);
+
+        let stale = select_stale_devices(devices.clone(), Some(&"current".to_owned()), cutoff);
+        assert_eq!(ids(&stale), ["old"]);
+
+        let stale = select_stale_devices(devices, None, cutoff);
+        assert_eq!(ids(&stale), ["current", "old"]);
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30d").unwrap(), chrono::Duration::days(30));
+        assert_eq!(parse_age("2w").unwrap(), chrono::Duration::weeks(2));
+        assert_eq!(parse_age("12h").unwrap(), chrono::Duration::hours(12));
+        assert!(parse_age("0d").is_err());
+        assert!(parse_age("-1d").is_err());
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("").is_err());
+        assert!(parse_age(&format!("{}w", i64::MAX)).is_err());
+    }
+}
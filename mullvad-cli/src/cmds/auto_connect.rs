@@ -1,8 +1,10 @@
 use anyhow::Result;
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
+use serde_json::json;
 
 use super::BooleanOption;
+use crate::format;
 
 #[derive(Subcommand, Debug)]
 pub enum AutoConnect {
@@ -29,7 +31,11 @@ impl AutoConnect {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let auto_connect = BooleanOption::from(rpc.get_settings().await?.auto_connect);
+        let auto_connect = rpc.get_settings().await?.auto_connect;
+        if format::json_output() {
+            return format::print_json(&json!({ "auto_connect": auto_connect }));
+        }
+        let auto_connect = BooleanOption::from(auto_connect);
         println!("Autoconnect: {auto_connect}");
         Ok(())
     }
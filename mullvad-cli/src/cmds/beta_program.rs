@@ -1,8 +1,10 @@
 use anyhow::{anyhow, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
+use serde_json::json;
 
 use super::BooleanOption;
+use crate::format;
 
 #[derive(Subcommand, Debug)]
 pub enum BetaProgram {
@@ -36,7 +38,11 @@ impl BetaProgram {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let opt = BooleanOption::from(rpc.get_settings().await?.show_beta_releases);
+        let show_beta_releases = rpc.get_settings().await?.show_beta_releases;
+        if format::json_output() {
+            return format::print_json(&json!({ "show_beta_releases": show_beta_releases }));
+        }
+        let opt = BooleanOption::from(show_beta_releases);
         println!("Beta program: {opt}");
         Ok(())
     }
@@ -9,9 +9,10 @@ use mullvad_types::{
     },
     relay_list::RelayEndpointData,
 };
+use serde_json::json;
 use talpid_types::net::proxy::{CustomProxy, Shadowsocks, Socks5Local, Socks5Remote};
 
-use crate::cmds::proxies::pp::CustomProxyFormatter;
+use crate::{cmds::proxies::pp::CustomProxyFormatter, format};
 
 use super::{
     proxies::{ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd},
@@ -176,6 +177,12 @@ impl Bridge {
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let settings = rpc.get_settings().await?;
+        if format::json_output() {
+            return format::print_json(&json!({
+                "bridge_state": settings.bridge_state,
+                "bridge_settings": settings.bridge_settings,
+            }));
+        }
         println!("Bridge state: {}", settings.bridge_state);
         println!(
             "Active bridge type: {}",
@@ -227,14 +234,23 @@ impl Bridge {
         }
 
         countries.sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-        for mut country in countries {
+        for country in &mut countries {
             country
                 .cities
                 .sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-            println!("{} ({})", country.name, country.code);
-            for mut city in country.cities {
+            for city in &mut country.cities {
                 city.relays
                     .sort_by(|r1, r2| natord::compare_ignore_case(&r1.hostname, &r2.hostname));
+            }
+        }
+
+        if format::json_output() {
+            return format::print_json(&countries);
+        }
+
+        for country in countries {
+            println!("{} ({})", country.name, country.code);
+            for city in country.cities {
                 println!(
                     "\t{} ({}) @ {:.5}°N, {:.5}°W",
                     city.name, city.code, city.latitude, city.longitude
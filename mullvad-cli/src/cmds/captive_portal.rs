@@ -0,0 +1,35 @@
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use std::time::Duration;
+
+#[derive(Subcommand, Debug)]
+pub enum CaptivePortal {
+    /// Allow logging in to a captive portal while all other traffic is blocked, e.g. in lockdown
+    /// mode. The network is probed for a portal, and if one is found, web traffic to it and DNS to
+    /// the local gateway and system resolvers is allowed for a limited time. Not allowed if
+    /// lockdown mode is locked by policy
+    Allow {
+        /// Number of minutes to allow the portal for
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..=30))]
+        minutes: u64,
+    },
+}
+
+impl CaptivePortal {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            CaptivePortal::Allow { minutes } => Self::allow(minutes).await,
+        }
+    }
+
+    async fn allow(minutes: u64) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let url = rpc
+            .allow_captive_portal(Duration::from_secs(minutes * 60))
+            .await?;
+        println!("Captive portal detected. Log in at {url} within {minutes} minutes");
+        println!("Traffic to the portal is blocked again afterwards");
+        Ok(())
+    }
+}
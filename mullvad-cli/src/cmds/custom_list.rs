@@ -1,4 +1,5 @@
 use super::{relay::resolve_location_constraint, relay_constraints::LocationArgs};
+use crate::format;
 use anyhow::{anyhow, bail, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
@@ -86,6 +87,10 @@ impl CustomList {
     /// Print all custom lists.
     async fn list() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
+        if format::json_output() {
+            let custom_lists = rpc.get_settings().await?.custom_lists;
+            return format::print_json(&custom_lists);
+        }
         let cache = rpc.get_relay_locations().await?;
         for custom_list in rpc.get_settings().await?.custom_lists {
             Self::print_custom_list(&custom_list, &cache)
@@ -98,6 +103,9 @@ impl CustomList {
     async fn get(name: String) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let custom_list = find_list_by_name(&mut rpc, &name).await?;
+        if format::json_output() {
+            return format::print_json(&custom_list);
+        }
         let cache = rpc.get_relay_locations().await?;
         Self::print_custom_list_content(&custom_list, &cache);
         Ok(())
@@ -0,0 +1,201 @@
+use crate::format;
+use anyhow::{bail, Result};
+use clap::Subcommand;
+use ipnetwork::IpNetwork;
+use itertools::Itertools;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{
+    custom_relay::{CustomRelay as CustomRelaySettings, CUSTOM_RELAY_COUNTRY_CODE},
+    wireguard::QuantumResistantState,
+};
+use std::{
+    io::BufRead,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use talpid_types::net::{
+    all_of_the_internet,
+    wireguard::{PresharedKey, PublicKey},
+};
+
+#[derive(Subcommand, Debug)]
+pub enum CustomRelay {
+    /// Add a self-hosted WireGuard server. It can then be selected like any other relay, for
+    /// example with 'mullvad relay set location <NAME>', or as a multihop entry. The server must
+    /// accept this device's WireGuard key, which is shown by 'mullvad custom-relay list'. The key
+    /// changes whenever it is rotated, and the server must then be updated
+    #[clap(arg_required_else_help = true)]
+    Add {
+        /// A unique name for the relay. It is used as its hostname
+        name: String,
+        /// IP address and port of the server, e.g. 203.0.113.1:51820
+        endpoint: SocketAddr,
+        /// Base64 encoded public key of the server
+        #[arg(value_parser = PublicKey::from_base64)]
+        public_key: PublicKey,
+        /// IP addresses that the server has assigned to this device
+        #[arg(required = true, num_args = 1..)]
+        addresses: Vec<IpAddr>,
+        /// In-tunnel IPv4 address of the server
+        #[arg(long)]
+        v4_gateway: Ipv4Addr,
+        /// In-tunnel IPv6 address of the server
+        #[arg(long)]
+        v6_gateway: Option<Ipv6Addr>,
+        /// Networks to route through the server. Defaults to all traffic
+        #[arg(long = "allowed-ip")]
+        allowed_ips: Vec<IpNetwork>,
+        /// Read a base64 encoded preshared key from standard input
+        #[arg(long)]
+        psk: bool,
+        /// Connect through the server without a quantum-resistant tunnel when one is enabled.
+        /// Custom relays cannot negotiate one, so connecting fails without this
+        #[arg(long)]
+        allow_without_quantum_resistance: bool,
+    },
+
+    /// Remove a custom relay
+    Remove {
+        /// Name of the relay
+        name: String,
+    },
+
+    /// List all custom relays
+    List,
+}
+
+impl CustomRelay {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            CustomRelay::Add {
+                name,
+                endpoint,
+                public_key,
+                addresses,
+                v4_gateway,
+                v6_gateway,
+                allowed_ips,
+                psk,
+                allow_without_quantum_resistance,
+            } => {
+                let psk = if psk { Some(read_psk().await?) } else { None };
+                let allowed_ips = if allowed_ips.is_empty() {
+                    all_of_the_internet()
+                } else {
+                    allowed_ips
+                };
+                Self::add(CustomRelaySettings {
+                    name,
+                    endpoint,
+                    public_key,
+                    allowed_ips,
+                    addresses,
+                    ipv4_gateway: v4_gateway,
+                    ipv6_gateway: v6_gateway,
+                    psk,
+                    allow_without_quantum_resistance,
+                })
+                .await
+            }
+            CustomRelay::Remove { name } => Self::remove(name).await,
+            CustomRelay::List => Self::list().await,
+        }
+    }
+
+    async fn add(custom_relay: CustomRelaySettings) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let name = custom_relay.name.clone();
+        let allow_without_quantum_resistance = custom_relay.allow_without_quantum_resistance;
+        rpc.add_custom_relay(custom_relay).await?;
+        println!("Added custom relay {name}");
+        println!("Select it with: mullvad relay set location {name}");
+        if let Ok(key) = rpc.get_wireguard_key().await {
+            println!(
+                "The server must accept this device's public key: {}",
+                key.key
+            );
+            println!("The key changes when it is rotated, and the server must then be updated");
+        }
+        if rpc
+            .get_settings()
+            .await?
+            .tunnel_options
+            .wireguard
+            .quantum_resistant
+            == QuantumResistantState::On
+        {
+            if allow_without_quantum_resistance {
+                println!("Connections through {name} will not be quantum-resistant");
+            } else {
+                println!(
+                    "Custom relays do not support quantum-resistant tunnels, so connecting through \
+                     {name} will fail. Disable quantum-resistant tunnels, or add the relay with \
+                     --allow-without-quantum-resistance"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.remove_custom_relay(name.clone()).await?;
+        println!("Removed custom relay {name}");
+        Ok(())
+    }
+
+    async fn list() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let custom_relays = rpc.get_settings().await?.custom_relays;
+        if format::json_output() {
+            return format::print_json(&custom_relays);
+        }
+        if custom_relays.is_empty() {
+            println!("No custom relays");
+            return Ok(());
+        }
+        if let Ok(key) = rpc.get_wireguard_key().await {
+            println!(
+                "This device's public key: {} (created {})",
+                key.key,
+                key.created.with_timezone(&chrono::Local)
+            );
+        }
+        println!("Custom relays (location '{CUSTOM_RELAY_COUNTRY_CODE}'):");
+        for relay in custom_relays {
+            println!("\t{} - {}", relay.name, relay.endpoint);
+            println!("\t\tPublic key: {}", relay.public_key);
+            println!("\t\tAddresses: {}", relay.addresses.iter().join(", "));
+            println!("\t\tAllowed IPs: {}", relay.allowed_ips.iter().join(", "));
+            match relay.ipv6_gateway {
+                Some(ipv6_gateway) => {
+                    println!("\t\tGateways: {}, {ipv6_gateway}", relay.ipv4_gateway)
+                }
+                None => println!("\t\tGateway: {}", relay.ipv4_gateway),
+            }
+            println!(
+                "\t\tPreshared key: {}",
+                if relay.psk.is_some() { "set" } else { "none" }
+            );
+            if relay.allow_without_quantum_resistance {
+                println!("\t\tAllowed without quantum-resistant tunnel");
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn read_psk() -> Result<PresharedKey> {
+    println!("Reading preshared key from standard input");
+
+    let psk_str = tokio::task::spawn_blocking(|| {
+        let mut psk_str = String::new();
+        let _ = std::io::stdin().lock().read_line(&mut psk_str);
+        psk_str.trim().to_owned()
+    })
+    .await
+    .unwrap();
+    if psk_str.is_empty() {
+        bail!("Expected to read preshared key from standard input");
+    }
+    Ok(PresharedKey::from_base64(&psk_str)?)
+}
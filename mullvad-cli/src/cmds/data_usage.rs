@@ -0,0 +1,128 @@
+use super::BooleanOption;
+use crate::format;
+use anyhow::Result;
+use chrono::{Days, Local};
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{
+    constraints::Constraint,
+    data_usage::{ByteCount, DataCap},
+};
+
+#[derive(Subcommand, Debug)]
+pub enum DataUsage {
+    /// Display the data transferred through the tunnel, per day and relay
+    Get {
+        /// Number of days to display, including today
+        #[arg(long, default_value_t = 7)]
+        days: u64,
+    },
+
+    /// Set a limit on the data transferred through the tunnel each calendar month. Clients are
+    /// notified when it has been reached
+    #[clap(arg_required_else_help = true)]
+    SetCap {
+        /// Bytes in both directions combined, with an optional K, M, G or T suffix, or 'any'
+        #[arg(value_parser = parse_quota)]
+        quota: Constraint<u64>,
+        /// Whether to disconnect the tunnel once the limit has been reached
+        #[arg(long, default_value_t = BooleanOption::from(false))]
+        disconnect: BooleanOption,
+    },
+}
+
+impl DataUsage {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            DataUsage::Get { days } => Self::get(days).await,
+            DataUsage::SetCap { quota, disconnect } => Self::set_cap(quota, *disconnect).await,
+        }
+    }
+
+    async fn get(days: u64) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let usage = rpc.get_data_usage().await?;
+        let data_cap = rpc.get_settings().await?.data_cap;
+        if format::json_output() {
+            return format::print_json(&usage);
+        }
+
+        let today = Local::now().date_naive();
+        let oldest = today - Days::new(days.saturating_sub(1));
+        for (date, relays) in usage.days.range(oldest..) {
+            println!("{date}");
+            for (relay, count) in relays {
+                println!("\t{relay:<20}{}", format_byte_count(count));
+            }
+        }
+
+        let month_total = usage.month_total(today);
+        match data_cap.monthly_quota {
+            Some(quota) => println!(
+                "This month: {} of {}",
+                format_byte_count(&month_total),
+                format_bytes(quota)
+            ),
+            None => println!("This month: {}", format_byte_count(&month_total)),
+        }
+        Ok(())
+    }
+
+    async fn set_cap(quota: Constraint<u64>, disconnect: bool) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.set_data_cap(DataCap {
+            monthly_quota: quota.option(),
+            disconnect,
+        })
+        .await?;
+        println!("Updated data cap");
+        Ok(())
+    }
+}
+
+fn format_byte_count(count: &ByteCount) -> String {
+    format!(
+        "{} (received {}, sent {})",
+        format_bytes(count.total()),
+        format_bytes(count.rx_bytes),
+        format_bytes(count.tx_bytes)
+    )
+}
+
+const UNITS: [(&str, u64); 4] = [
+    ("T", 1_000_000_000_000),
+    ("G", 1_000_000_000),
+    ("M", 1_000_000),
+    ("K", 1_000),
+];
+
+fn format_bytes(bytes: u64) -> String {
+    for (unit, size) in UNITS {
+        if bytes >= size {
+            return format!("{:.2} {unit}B", bytes as f64 / size as f64);
+        }
+    }
+    format!("{bytes} B")
+}
+
+fn parse_quota(value: &str) -> Result<Constraint<u64>, String> {
+    if value.eq_ignore_ascii_case("any") {
+        return Ok(Constraint::Any);
+    }
+    let invalid = || format!("Invalid size: {value}");
+    let value = value.trim_end_matches(['B', 'b']);
+    let (number, multiplier) = UNITS
+        .iter()
+        .find_map(|(unit, size)| {
+            let number = value
+                .strip_suffix(unit)
+                .or_else(|| value.strip_suffix(&unit.to_ascii_lowercase()))?;
+            Some((number, *size))
+        })
+        .unwrap_or((value, 1));
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(invalid());
+    }
+    Ok(Constraint::Only((number * multiplier as f64) as u64))
+}
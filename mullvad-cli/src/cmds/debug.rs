@@ -1,14 +1,79 @@
-use anyhow::Result;
+use super::BooleanOption;
+use crate::format;
+use anyhow::{Context, Result};
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
+    log_level::LogLevel,
     relay_constraints::{RelayConstraints, RelaySettings},
 };
+use std::{path::PathBuf, time::Duration};
 
 #[derive(clap::Subcommand, Debug)]
 pub enum DebugCommands {
     /// Block all internet connection by setting an invalid relay constraint.
     BlockConnection,
+
+    /// Run a set of checks to troubleshoot connection problems.
+    ///
+    /// The report can be included in a problem report by passing the output file to
+    /// `mullvad-problem-report collect`.
+    Diagnose {
+        /// Also write the report to this file
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+
+    /// Send daemon logs to journald, syslog or a JSON file, in addition to the log file.
+    ///
+    /// Sinks can also be enabled with the `--log-sink` daemon flag.
+    #[clap(subcommand)]
+    LogSinks(LogSinksCommand),
+
+    /// Change how verbosely individual modules log, without restarting the daemon.
+    #[clap(subcommand)]
+    LogLevel(LogLevelCommand),
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum LogSinksCommand {
+    /// Display which log sinks are enabled in the settings
+    Get,
+
+    /// Enable or disable log sinks. Sinks that are not specified are left unchanged
+    Set {
+        /// Send logs to systemd-journald, with structured fields. Only supported on Linux
+        #[arg(long)]
+        journald: Option<BooleanOption>,
+        /// Send logs to the local syslog daemon, formatted according to RFC 5424
+        #[arg(long)]
+        syslog: Option<BooleanOption>,
+        /// Write logs to a file with one JSON object per line, next to the daemon log
+        #[arg(long)]
+        json: Option<BooleanOption>,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum LogLevelCommand {
+    /// Display the log levels that have been changed
+    Get,
+
+    /// Set the log level of a module and its submodules, e.g. `talpid_wireguard` or
+    /// `mullvad_api::rest`
+    Set {
+        /// Module path, as shown in log messages
+        module: String,
+        #[arg(value_enum)]
+        level: LogLevel,
+        /// Revert to the default level after this many minutes. 0 keeps the level until the
+        /// daemon restarts
+        #[arg(long, default_value_t = 30)]
+        revert_after: u64,
+    },
+
+    /// Revert all changed log levels
+    Reset,
 }
 
 impl DebugCommands {
@@ -41,6 +106,115 @@ impl DebugCommands {
                 eprintln!("WARNING: ENTERED BLOCKED MODE");
                 Ok(())
             }
+            DebugCommands::Diagnose { output } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                println!("Running diagnostics. This may take a while...");
+                let report = rpc.run_diagnostics().await?;
+
+                println!();
+                print!("{report}");
+
+                if let Some(output) = output {
+                    std::fs::write(&output, report.to_string()).with_context(|| {
+                        format!("Failed to write report to {}", output.display())
+                    })?;
+                    println!();
+                    println!("Report written to {}", output.display());
+                    println!("Include it in a problem report using:");
+                    println!(
+                        "    mullvad-problem-report collect --output <report path> {}",
+                        output.display()
+                    );
+                }
+                Ok(())
+            }
+            DebugCommands::LogSinks(command) => command.handle().await,
+            DebugCommands::LogLevel(command) => command.handle().await,
+        }
+    }
+}
+
+impl LogSinksCommand {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut sinks = rpc.get_settings().await?.log_sinks;
+        match self {
+            LogSinksCommand::Get => {
+                if format::json_output() {
+                    return format::print_json(&sinks);
+                }
+                println!("journald: {}", BooleanOption::from(sinks.journald));
+                println!("syslog: {}", BooleanOption::from(sinks.syslog));
+                println!("json: {}", BooleanOption::from(sinks.json));
+            }
+            LogSinksCommand::Set {
+                journald,
+                syslog,
+                json,
+            } => {
+                if let Some(journald) = journald {
+                    sinks.journald = *journald;
+                }
+                if let Some(syslog) = syslog {
+                    sinks.syslog = *syslog;
+                }
+                if let Some(json) = json {
+                    sinks.json = *json;
+                }
+                rpc.set_log_sinks(sinks).await?;
+                println!("Updated log sinks");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LogLevelCommand {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match self {
+            LogLevelCommand::Get => {
+                let levels = rpc.get_log_levels().await?;
+                if format::json_output() {
+                    return format::print_json(&levels);
+                }
+                if levels.is_empty() {
+                    println!("No log levels have been changed");
+                }
+                for level in levels {
+                    match level.revert_at {
+                        Some(revert_at) => println!(
+                            "{}={} (until {})",
+                            level.module,
+                            level.level,
+                            revert_at.with_timezone(&chrono::Local).format("%X"),
+                        ),
+                        None => println!("{}={}", level.module, level.level),
+                    }
+                }
+            }
+            LogLevelCommand::Set {
+                module,
+                level,
+                revert_after,
+            } => {
+                let revert_after_duration =
+                    (revert_after > 0).then(|| Duration::from_secs(revert_after * 60));
+                rpc.set_log_level(module.clone(), level, revert_after_duration)
+                    .await?;
+                if revert_after > 0 {
+                    println!(
+                        "Logging {module} at level {level} for the next {revert_after} minutes"
+                    );
+                } else {
+                    println!("Logging {module} at level {level} until the daemon restarts");
+                }
+            }
+            LogLevelCommand::Reset => {
+                rpc.reset_log_levels().await?;
+                println!("Reverted all log levels");
+            }
         }
+        Ok(())
     }
 }
@@ -1,8 +1,13 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
+use mullvad_types::settings::{
+    CustomDnsOptions, DefaultDnsOptions, DnsBlocklist, DnsOptions, DnsState,
+};
 use std::net::IpAddr;
+use talpid_types::net::dns::{EncryptedDnsProtocol, EncryptedDnsServer};
+
+use crate::format;
 
 #[derive(Subcommand, Debug)]
 pub enum Dns {
@@ -14,6 +19,34 @@ pub enum Dns {
         #[clap(subcommand)]
         cmd: DnsSet,
     },
+
+    /// Check that DNS requests only reach the resolvers used by the tunnel.
+    /// Requires the tunnel to be connected.
+    LeakTest,
+
+    /// Manage lists of domains to block. Blocklists apply on top of the
+    /// content blockers and custom DNS servers.
+    #[clap(subcommand)]
+    Blocklist(BlocklistCmd),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BlocklistCmd {
+    /// Add a blocklist in hosts file or AdBlock format. URLs are downloaded
+    /// again once a day.
+    Add {
+        /// Path to a local file, or an http(s) URL
+        source: DnsBlocklist,
+    },
+
+    /// Remove a blocklist
+    Remove {
+        /// Path or URL of the blocklist to remove
+        source: DnsBlocklist,
+    },
+
+    /// List the configured blocklists
+    List,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -52,6 +85,24 @@ pub enum DnsSet {
         #[arg(required(true), num_args = 1..)]
         servers: Vec<IpAddr>,
     },
+
+    /// Use DNS-over-HTTPS or DNS-over-TLS resolvers. Queries are sent to a local forwarder,
+    /// which relays them to the resolvers through the tunnel.
+    Encrypted {
+        /// Protocol used to reach the resolvers: 'https' or 'tls'
+        protocol: EncryptedDnsProtocol,
+
+        /// Name used to verify the TLS certificate of the resolvers
+        server_name: String,
+
+        /// One or more IP addresses of the resolvers
+        #[arg(required(true), num_args = 1..)]
+        servers: Vec<IpAddr>,
+
+        /// Port of the resolvers. Defaults to 443 for 'https' and 853 for 'tls'
+        #[arg(long)]
+        port: Option<u16>,
+    },
 }
 
 impl Dns {
@@ -82,6 +133,19 @@ impl Dns {
             Dns::Set {
                 cmd: DnsSet::Custom { servers },
             } => Self::set_custom(servers).await,
+            Dns::Set {
+                cmd:
+                    DnsSet::Encrypted {
+                        protocol,
+                        server_name,
+                        servers,
+                        port,
+                    },
+            } => Self::set_encrypted(protocol, server_name, servers, port).await,
+            Dns::LeakTest => Self::leak_test().await,
+            Dns::Blocklist(BlocklistCmd::Add { source }) => Self::add_blocklist(source).await,
+            Dns::Blocklist(BlocklistCmd::Remove { source }) => Self::remove_blocklist(source).await,
+            Dns::Blocklist(BlocklistCmd::List) => Self::list_blocklists().await,
         }
     }
 
@@ -89,6 +153,10 @@ impl Dns {
         let mut rpc = MullvadProxyClient::new().await?;
         let options = rpc.get_settings().await?.tunnel_options.dns_options;
 
+        if format::json_output() {
+            return format::print_json(&options);
+        }
+
         match options.state {
             DnsState::Default => {
                 println!("Custom DNS: no");
@@ -105,6 +173,12 @@ impl Dns {
                     options.default_options.block_social_media
                 );
             }
+            DnsState::Custom if !options.custom_options.encrypted_servers.is_empty() => {
+                println!("Custom DNS: yes\nEncrypted servers:");
+                for server in &options.custom_options.encrypted_servers {
+                    println!("{server}");
+                }
+            }
             DnsState::Custom => {
                 println!("Custom DNS: yes\nServers:");
                 for server in &options.custom_options.addresses {
@@ -112,6 +186,12 @@ impl Dns {
                 }
             }
         }
+        if !options.blocklists.is_empty() {
+            println!("Blocklists:");
+            for blocklist in &options.blocklists {
+                println!("{blocklist}");
+            }
+        }
 
         Ok(())
     }
@@ -148,11 +228,149 @@ impl Dns {
         let settings = rpc.get_settings().await?;
         rpc.set_dns_options(DnsOptions {
             state: DnsState::Custom,
-            custom_options: CustomDnsOptions { addresses: servers },
+            custom_options: CustomDnsOptions {
+                addresses: servers,
+                encrypted_servers: vec![],
+            },
             ..settings.tunnel_options.dns_options
         })
         .await?;
         println!("Updated DNS settings");
         Ok(())
     }
+
+    async fn set_encrypted(
+        protocol: EncryptedDnsProtocol,
+        server_name: String,
+        servers: Vec<IpAddr>,
+        port: Option<u16>,
+    ) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let settings = rpc.get_settings().await?;
+        let port = port.unwrap_or(protocol.default_port());
+        let encrypted_servers = servers
+            .into_iter()
+            .map(|address| EncryptedDnsServer {
+                protocol,
+                address,
+                port,
+                server_name: server_name.clone(),
+            })
+            .collect();
+        rpc.set_dns_options(DnsOptions {
+            state: DnsState::Custom,
+            custom_options: CustomDnsOptions {
+                addresses: vec![],
+                encrypted_servers,
+            },
+            ..settings.tunnel_options.dns_options
+        })
+        .await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn leak_test() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let result = rpc.test_dns_leaks().await?;
+        if format::json_output() {
+            return format::print_json(&result);
+        }
+
+        let format_resolvers = |resolvers: &[IpAddr]| {
+            if resolvers.is_empty() {
+                "none".to_owned()
+            } else {
+                resolvers
+                    .iter()
+                    .map(IpAddr::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        };
+        println!(
+            "Tunnel resolvers: {}",
+            format_resolvers(&result.expected_resolvers)
+        );
+        println!(
+            "System resolvers: {}",
+            format_resolvers(&result.system_resolvers)
+        );
+        println!(
+            "Leaking resolvers: {}",
+            format_resolvers(&result.leaking_resolvers)
+        );
+        if result.answered_by_unexpected_resolver {
+            println!("A DNS query was answered by a resolver outside the tunnel");
+        }
+
+        if result.is_leaking() {
+            println!("DNS leak detected");
+        } else {
+            println!("No DNS leak detected");
+        }
+        Ok(())
+    }
+
+    async fn add_blocklist(source: DnsBlocklist) -> Result<()> {
+        // The daemon does not share our working directory
+        let source = match source {
+            DnsBlocklist::File(path) => DnsBlocklist::File(
+                std::fs::canonicalize(&path)
+                    .with_context(|| format!("Failed to find {}", path.display()))?,
+            ),
+            url => url,
+        };
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut dns_options = rpc.get_settings().await?.tunnel_options.dns_options;
+        if dns_options.blocklists.contains(&source) {
+            bail!("Blocklist {source} has already been added");
+        }
+        dns_options.blocklists.push(source);
+        rpc.set_dns_options(dns_options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn remove_blocklist(source: DnsBlocklist) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut dns_options = rpc.get_settings().await?.tunnel_options.dns_options;
+
+        let canonical_source = match &source {
+            DnsBlocklist::File(path) => std::fs::canonicalize(path).ok().map(DnsBlocklist::File),
+            DnsBlocklist::Url(_) => None,
+        };
+        let index = dns_options
+            .blocklists
+            .iter()
+            .position(|blocklist| {
+                blocklist == &source || Some(blocklist) == canonical_source.as_ref()
+            })
+            .ok_or_else(|| anyhow!("Blocklist {source} has not been added"))?;
+        dns_options.blocklists.remove(index);
+        rpc.set_dns_options(dns_options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn list_blocklists() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let blocklists = rpc
+            .get_settings()
+            .await?
+            .tunnel_options
+            .dns_options
+            .blocklists;
+        if format::json_output() {
+            return format::print_json(&blocklists);
+        }
+        if blocklists.is_empty() {
+            println!("No blocklists");
+        }
+        for blocklist in blocklists {
+            println!("{blocklist}");
+        }
+        Ok(())
+    }
 }
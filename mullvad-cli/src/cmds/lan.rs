@@ -1,8 +1,10 @@
 use anyhow::Result;
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
+use serde_json::json;
 
 use super::BooleanOption;
+use crate::format;
 
 #[derive(Subcommand, Debug)]
 pub enum Lan {
@@ -33,8 +35,11 @@ impl Lan {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let allow_lan =
-            BooleanOption::with_labels(rpc.get_settings().await?.allow_lan, "allow", "block");
+        let allow_lan = rpc.get_settings().await?.allow_lan;
+        if format::json_output() {
+            return format::print_json(&json!({ "allow_lan": allow_lan }));
+        }
+        let allow_lan = BooleanOption::with_labels(allow_lan, "allow", "block");
         println!("Local network sharing setting: {allow_lan}");
         Ok(())
     }
@@ -1,8 +1,10 @@
 use anyhow::Result;
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
+use serde_json::json;
 
 use super::BooleanOption;
+use crate::format;
 
 #[derive(Subcommand, Debug)]
 pub enum LockdownMode {
@@ -29,7 +31,11 @@ impl LockdownMode {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let state = BooleanOption::from(rpc.get_settings().await?.block_when_disconnected);
+        let state = rpc.get_settings().await?.block_when_disconnected;
+        if format::json_output() {
+            return format::print_json(&json!({ "block_when_disconnected": state }));
+        }
+        let state = BooleanOption::from(state);
         println!("Block traffic when the VPN is disconnected: {state}");
         Ok(())
     }
@@ -6,7 +6,10 @@ pub mod api_access;
 pub mod auto_connect;
 pub mod beta_program;
 pub mod bridge;
+pub mod captive_portal;
 pub mod custom_list;
+pub mod custom_relay;
+pub mod data_usage;
 pub mod debug;
 pub mod dns;
 pub mod lan;
@@ -17,6 +20,7 @@ pub mod proxies;
 pub mod relay;
 pub mod relay_constraints;
 pub mod reset;
+pub mod schedule;
 pub mod split_tunnel;
 pub mod status;
 pub mod tunnel;
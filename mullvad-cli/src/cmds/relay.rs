@@ -23,7 +23,7 @@ use talpid_types::net::{
 };
 
 use super::{relay_constraints::LocationArgs, BooleanOption};
-use crate::{cmds::receive_confirmation, print_option};
+use crate::{cmds::receive_confirmation, format, print_option};
 
 #[derive(Subcommand, Debug)]
 pub enum Relay {
@@ -35,7 +35,10 @@ pub enum Relay {
     Set(SetCommands),
 
     /// List available relays
-    List,
+    List {
+        #[clap(subcommand)]
+        cmd: Option<ListCommands>,
+    },
 
     /// Update the relay list
     Update,
@@ -45,6 +48,28 @@ pub enum Relay {
     Override(OverrideCommands),
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum ListCommands {
+    /// Show recent changes to the relay list
+    Changes,
+
+    /// Write the current relay list to a file, so that it can be imported on a machine that
+    /// cannot reach the API
+    Export {
+        /// Path to write the relay list to, or "-" for standard output
+        file: String,
+    },
+
+    /// Replace the current relay list with an exported one. The list is rejected if it has been
+    /// corrupted or if it is older than the current list. This does not protect against
+    /// deliberate modification, so only import lists from sources that you trust. Importing
+    /// requires access to the "account" category of commands
+    Import {
+        /// Path to read the relay list from, or "-" for standard input
+        file: String,
+    },
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum SetCommands {
     /// Select a relay using country, city or hostname.
@@ -262,7 +287,16 @@ impl Relay {
     pub async fn handle(self) -> Result<()> {
         match self {
             Relay::Get => Self::get().await,
-            Relay::List => Self::list().await,
+            Relay::List { cmd: None } => Self::list().await,
+            Relay::List {
+                cmd: Some(ListCommands::Changes),
+            } => Self::list_changes().await,
+            Relay::List {
+                cmd: Some(ListCommands::Export { file }),
+            } => Self::export_list(file).await,
+            Relay::List {
+                cmd: Some(ListCommands::Import { file }),
+            } => Self::import_list(file).await,
             Relay::Update => Self::update().await,
             Relay::Set(subcmd) => Self::set(subcmd).await,
             Relay::Override(subcmd) => Self::r#override(subcmd).await,
@@ -274,6 +308,10 @@ impl Relay {
         let settings = rpc.get_settings().await?;
         let relay_settings = settings.relay_settings;
 
+        if format::json_output() {
+            return format::print_json(&relay_settings);
+        }
+
         match relay_settings {
             RelaySettings::CustomTunnelEndpoint(endpoint) => {
                 println!("Custom endpoint: {endpoint}")
@@ -345,14 +383,23 @@ impl Relay {
     async fn list() -> Result<()> {
         let mut countries = get_active_relays().await?;
         countries.sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-        for mut country in countries {
+        for country in &mut countries {
             country
                 .cities
                 .sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-            println!("{} ({})", country.name, country.code);
-            for mut city in country.cities {
+            for city in &mut country.cities {
                 city.relays
                     .sort_by(|r1, r2| natord::compare_ignore_case(&r1.hostname, &r2.hostname));
+            }
+        }
+
+        if format::json_output() {
+            return format::print_json(&countries);
+        }
+
+        for country in countries {
+            println!("{} ({})", country.name, country.code);
+            for city in country.cities {
                 println!(
                     "\t{} ({}) @ {:.5}°N, {:.5}°W",
                     city.name, city.code, city.latitude, city.longitude
@@ -386,6 +433,58 @@ impl Relay {
         Ok(())
     }
 
+    async fn list_changes() -> Result<()> {
+        let changes = MullvadProxyClient::new()
+            .await?
+            .get_relay_list_changes()
+            .await?;
+        if format::json_output() {
+            return format::print_json(&changes);
+        }
+        if changes.is_empty() {
+            println!("The relay list has not changed since the daemon started");
+        }
+        for diff in &changes {
+            format::print_relay_list_diff(diff);
+        }
+        Ok(())
+    }
+
+    async fn export_list(dest: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let blob = rpc
+            .export_relay_list()
+            .await
+            .context("Failed to export relay list")?;
+
+        match dest.as_str() {
+            "-" => {
+                println!("{blob}");
+                Ok(())
+            }
+            _ => tokio::fs::write(&dest, blob)
+                .await
+                .context(format!("Failed to write to path {dest}")),
+        }
+    }
+
+    async fn import_list(source: String) -> Result<()> {
+        let blob = tokio::task::spawn_blocking(move || match source.as_str() {
+            "-" => std::io::read_to_string(std::io::stdin()).context("Failed to read from stdin"),
+            _ => std::fs::read_to_string(&source)
+                .context(format!("Failed to read from path: {source}")),
+        })
+        .await
+        .unwrap()?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.import_relay_list(blob)
+            .await
+            .context("Failed to import relay list")?;
+        println!("Relay list imported");
+        Ok(())
+    }
+
     async fn update() -> Result<()> {
         MullvadProxyClient::new()
             .await?
@@ -768,6 +867,10 @@ impl Relay {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?;
 
+                if format::json_output() {
+                    return format::print_json(&settings.relay_overrides);
+                }
+
                 let mut overrides = HashMap::new();
                 for relay_override in settings.relay_overrides {
                     overrides.insert(relay_override.hostname.clone(), relay_override);
@@ -0,0 +1,145 @@
+use super::{relay::resolve_location_constraint, relay_constraints::LocationArgs};
+use crate::format;
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{
+    constraints::Constraint,
+    relay_constraints::{LocationConstraint, LocationConstraintFormatter},
+    relay_list::RelayEndpointData,
+    schedule::{CronExpression, ScheduleAction, ScheduleRule, Tz},
+};
+
+#[derive(Subcommand, Debug)]
+pub enum Schedule {
+    /// Add a rule that connects or disconnects the tunnel during a recurring time window. Rules
+    /// that were added first take precedence. When no rule applies anymore, the tunnel returns to
+    /// the state it was in before the first rule applied
+    #[clap(arg_required_else_help = true)]
+    Add {
+        /// A unique name for the rule
+        name: String,
+        /// When the rule applies, as "MINUTE HOUR DAY-OF-MONTH MONTH DAY-OF-WEEK". Each field is
+        /// '*', a value, a range or a list, optionally followed by a step. The minutes and hours
+        /// must each form a single range. For example, "* 9-16 * * mon-fri" applies from 9:00 to
+        /// 16:59 on weekdays
+        window: CronExpression,
+        /// Time zone of the window, such as 'Europe/Stockholm'. Defaults to the system time zone
+        #[arg(long)]
+        timezone: Option<Tz>,
+        #[clap(subcommand)]
+        action: Action,
+    },
+
+    /// Remove a rule
+    Remove {
+        /// Name of the rule
+        name: String,
+    },
+
+    /// List all rules
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Connect the tunnel while the rule applies, optionally to a specific location
+    Connect {
+        #[command(flatten)]
+        location: Option<LocationArgs>,
+    },
+    /// Disconnect the tunnel while the rule applies
+    Disconnect,
+}
+
+impl Schedule {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            Schedule::Add {
+                name,
+                window,
+                timezone,
+                action,
+            } => Self::add(name, window, timezone, action).await,
+            Schedule::Remove { name } => Self::remove(name).await,
+            Schedule::List => Self::list().await,
+        }
+    }
+
+    async fn add(
+        name: String,
+        window: CronExpression,
+        timezone: Option<Tz>,
+        action: Action,
+    ) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let action = match action {
+            Action::Connect {
+                location: Some(location_args),
+            } => {
+                let location = resolve_location_constraint(&mut rpc, location_args, |relay| {
+                    relay.active && relay.endpoint_data != RelayEndpointData::Bridge
+                })
+                .await?;
+                ScheduleAction::Connect {
+                    location: match location {
+                        Constraint::Only(location) => Some(LocationConstraint::from(location)),
+                        Constraint::Any => None,
+                    },
+                }
+            }
+            Action::Connect { location: None } => ScheduleAction::Connect { location: None },
+            Action::Disconnect => ScheduleAction::Disconnect,
+        };
+        rpc.add_schedule_rule(ScheduleRule {
+            name: name.clone(),
+            window,
+            timezone,
+            action,
+        })
+        .await?;
+        println!("Added schedule rule {name}");
+        Ok(())
+    }
+
+    async fn remove(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.remove_schedule_rule(name.clone()).await?;
+        println!("Removed schedule rule {name}");
+        Ok(())
+    }
+
+    async fn list() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let settings = rpc.get_settings().await?;
+        if format::json_output() {
+            return format::print_json(&settings.schedule);
+        }
+        if settings.schedule.rules.is_empty() {
+            println!("No schedule rules");
+            return Ok(());
+        }
+        println!("Schedule rules, in order of precedence:");
+        for rule in settings.schedule.rules {
+            let timezone = rule
+                .timezone
+                .map(|timezone| timezone.name().to_owned())
+                .unwrap_or_else(|| "system time zone".to_owned());
+            println!("\t{} - {} ({timezone})", rule.name, rule.window);
+            match rule.action {
+                ScheduleAction::Connect {
+                    location: Some(ref location),
+                } => {
+                    let location = LocationConstraintFormatter {
+                        constraint: location,
+                        custom_lists: &settings.custom_lists,
+                    };
+                    println!("\t\tConnect to {location}");
+                }
+                ScheduleAction::Connect { location: None } => println!("\t\tConnect"),
+                ScheduleAction::Disconnect => println!("\t\tDisconnect"),
+            }
+        }
+        Ok(())
+    }
+}
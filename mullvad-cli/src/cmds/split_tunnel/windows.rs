@@ -8,6 +8,7 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::super::BooleanOption;
+use crate::format;
 
 /// Set options for applications to exclude from the tunnel.
 #[derive(Subcommand, Debug)]
@@ -43,6 +44,26 @@ impl SplitTunnel {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?.split_tunnel;
 
+                if format::json_output() {
+                    let mut output = serde_json::to_value(&settings)?;
+                    if list_processes {
+                        let processes: Vec<_> = rpc
+                            .get_excluded_processes()
+                            .await?
+                            .into_iter()
+                            .map(|process| {
+                                serde_json::json!({
+                                    "pid": process.pid,
+                                    "image": process.image,
+                                    "inherited": process.inherited,
+                                })
+                            })
+                            .collect();
+                        output["excluded_processes"] = processes.into();
+                    }
+                    return format::print_json(&output);
+                }
+
                 let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
 
                 println!("Split tunneling state: {enable_exclusions}");
@@ -1,8 +1,9 @@
 use anyhow::Result;
 use clap::{Args, Subcommand};
 use futures::StreamExt;
-use mullvad_management_interface::{client::DaemonEvent, MullvadProxyClient};
+use mullvad_management_interface::{client::DaemonEvent, Code, MullvadProxyClient};
 use mullvad_types::{device::DeviceState, states::TunnelState};
+use serde_json::json;
 
 use crate::format;
 
@@ -99,18 +100,94 @@ impl Status {
                         println!("New access method: {access_method:#?}");
                     }
                 }
+                DaemonEvent::AccountExpiry(notification) => {
+                    println!("{}", format::format_expiry_notification(&notification));
+                }
+                DaemonEvent::RelayListChanged(diff) => {
+                    if args.debug {
+                        format::print_relay_list_diff(&diff);
+                    } else {
+                        for hostname in &diff.removed_pinned {
+                            println!(
+                                "Warning: Relay {hostname} was removed but is still used by the settings"
+                            );
+                        }
+                    }
+                }
+                DaemonEvent::DataCapReached(event) => {
+                    print!(
+                        "Monthly data cap reached: {} of {} bytes used",
+                        event.usage, event.quota
+                    );
+                    if event.disconnected {
+                        print!(", disconnecting");
+                    }
+                    println!();
+                }
             }
         }
         Ok(())
     }
+
+    /// Print every daemon event as a JSON object on its own line.
+    async fn listen_json(mut rpc: MullvadProxyClient) -> Result<()> {
+        let mut event_stream = rpc.events_listen().await?;
+        while let Some(event) = event_stream.next().await {
+            let (name, data) = match event? {
+                DaemonEvent::TunnelState(state) => ("tunnel_state", serde_json::to_value(state)?),
+                DaemonEvent::Settings(settings) => ("settings", serde_json::to_value(settings)?),
+                DaemonEvent::RelayList(relay_list) => {
+                    ("relay_list", serde_json::to_value(relay_list)?)
+                }
+                DaemonEvent::AppVersionInfo(info) => ("version_info", serde_json::to_value(info)?),
+                DaemonEvent::Device(device) => ("device", serde_json::to_value(device)?),
+                DaemonEvent::RemoveDevice(device) => {
+                    ("remove_device", serde_json::to_value(device)?)
+                }
+                DaemonEvent::NewAccessMethod(method) => {
+                    ("new_access_method", serde_json::to_value(method)?)
+                }
+                DaemonEvent::AccountExpiry(notification) => {
+                    ("account_expiry", serde_json::to_value(notification)?)
+                }
+                DaemonEvent::RelayListChanged(diff) => {
+                    ("relay_list_changed", serde_json::to_value(diff)?)
+                }
+                DaemonEvent::DataCapReached(event) => {
+                    ("data_cap_reached", serde_json::to_value(event)?)
+                }
+            };
+            format::print_json(&json!({ "event": name, "data": data }))?;
+        }
+        Ok(())
+    }
 }
 
 pub async fn handle(cmd: Option<Status>, args: StatusArgs) -> Result<()> {
     let mut rpc = MullvadProxyClient::new().await?;
     let state = rpc.get_tunnel_state().await?;
-    let device = rpc.get_device().await?;
 
-    print_account_logged_out(&state, &device);
+    if format::json_output() {
+        let locked_settings = rpc.get_settings().await?.locked_settings;
+        let account_expiry = rpc.get_account_expiry_state().await?;
+        format::print_json(&json!({
+            "tunnel_state": state,
+            "locked_settings": locked_settings,
+            "account_expiry": account_expiry,
+        }))?;
+        if cmd == Some(Status::Listen) {
+            Status::listen_json(rpc).await?;
+        }
+        return Ok(());
+    }
+
+    match rpc.get_device().await {
+        Ok(device) => print_account_logged_out(&state, &device),
+        // The access policy may not allow this user to see the account
+        Err(mullvad_management_interface::Error::Rpc(status))
+            if status.code() == Code::PermissionDenied => {}
+        Err(error) => return Err(error.into()),
+    }
 
     if args.debug {
         println!("Tunnel state: {state:#?}");
@@ -119,6 +196,16 @@ pub async fn handle(cmd: Option<Status>, args: StatusArgs) -> Result<()> {
         format::print_location(&state);
     }
 
+    let locked_settings = rpc.get_settings().await?.locked_settings;
+    if !locked_settings.is_empty() {
+        let locked_settings: Vec<_> = locked_settings.iter().map(ToString::to_string).collect();
+        println!("Locked by administrator: {}", locked_settings.join(", "));
+    }
+
+    if let Some(notification) = rpc.get_account_expiry_state().await? {
+        println!("{}", format::format_expiry_notification(&notification));
+    }
+
     if cmd == Some(Status::Listen) {
         Status::listen(rpc, args).await?;
     }
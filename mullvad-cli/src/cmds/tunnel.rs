@@ -5,11 +5,15 @@ use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
     constraints::Constraint,
-    wireguard::{QuantumResistantState, RotationInterval, DEFAULT_ROTATION_INTERVAL},
+    wireguard::{
+        QuantumResistantAlgorithms, QuantumResistantState, RotationInterval,
+        DEFAULT_ROTATION_INTERVAL,
+    },
 };
+use std::time::Duration;
 
 use super::BooleanOption;
-use crate::print_option;
+use crate::{format, print_option};
 
 #[derive(Subcommand, Debug)]
 pub enum Tunnel {
@@ -40,6 +44,14 @@ pub enum TunnelOptions {
         /// Configure quantum-resistant key exchange
         #[arg(long)]
         quantum_resistant: Option<QuantumResistantState>,
+        /// Configure which KEMs to use for quantum-resistant key exchange:
+        /// 'prefer-ml-kem', 'require-ml-kem', or 'kyber'
+        #[arg(long)]
+        quantum_resistant_algorithms: Option<QuantumResistantAlgorithms>,
+        /// How often to renegotiate the quantum-resistant PSK while connected. Number of
+        /// minutes, or 'any' to never renegotiate it
+        #[arg(long)]
+        quantum_resistant_rekey_interval: Option<Constraint<u64>>,
         /// Configure whether to enable DAITA
         #[cfg(daita)]
         #[arg(long)]
@@ -75,6 +87,10 @@ impl Tunnel {
         let mut rpc = MullvadProxyClient::new().await?;
         let tunnel_options = rpc.get_settings().await?.tunnel_options;
 
+        if format::json_output() {
+            return format::print_json(&tunnel_options);
+        }
+
         println!("OpenVPN options");
 
         print_option!(
@@ -100,6 +116,17 @@ impl Tunnel {
             "Quantum resistance",
             tunnel_options.wireguard.quantum_resistant,
         );
+        print_option!(
+            "Quantum-resistant algorithms",
+            tunnel_options.wireguard.quantum_resistant_algorithms,
+        );
+        print_option!(
+            "Quantum-resistant rekey interval",
+            match tunnel_options.wireguard.quantum_resistant_rekey_interval {
+                Some(interval) => format!("{} minutes", interval.as_secs() / 60),
+                None => "never".to_string(),
+            },
+        );
 
         #[cfg(daita)]
         print_option!("DAITA", tunnel_options.wireguard.daita.enabled);
@@ -138,6 +165,8 @@ impl Tunnel {
             TunnelOptions::Wireguard {
                 mtu,
                 quantum_resistant,
+                quantum_resistant_algorithms,
+                quantum_resistant_rekey_interval,
                 #[cfg(daita)]
                 daita,
                 rotation_interval,
@@ -146,6 +175,8 @@ impl Tunnel {
                 Self::handle_wireguard(
                     mtu,
                     quantum_resistant,
+                    quantum_resistant_algorithms,
+                    quantum_resistant_rekey_interval,
                     #[cfg(daita)]
                     daita,
                     rotation_interval,
@@ -178,6 +209,8 @@ impl Tunnel {
     async fn handle_wireguard(
         mtu: Option<Constraint<u16>>,
         quantum_resistant: Option<QuantumResistantState>,
+        quantum_resistant_algorithms: Option<QuantumResistantAlgorithms>,
+        quantum_resistant_rekey_interval: Option<Constraint<u64>>,
         #[cfg(daita)] daita: Option<BooleanOption>,
         rotation_interval: Option<Constraint<RotationInterval>>,
         rotate_key: Option<RotateKey>,
@@ -189,11 +222,27 @@ impl Tunnel {
             println!("MTU parameter has been updated");
         }
 
-        if let Some(quantum_resistant) = quantum_resistant {
-            rpc.set_quantum_resistant_tunnel(quantum_resistant).await?;
+        if quantum_resistant.is_some() || quantum_resistant_algorithms.is_some() {
+            let quantum_resistant = match quantum_resistant {
+                Some(quantum_resistant) => quantum_resistant,
+                None => {
+                    let settings = rpc.get_settings().await?;
+                    settings.tunnel_options.wireguard.quantum_resistant
+                }
+            };
+            rpc.set_quantum_resistant_tunnel(quantum_resistant, quantum_resistant_algorithms)
+                .await?;
             println!("Quantum resistant setting has been updated");
         }
 
+        if let Some(interval) = quantum_resistant_rekey_interval {
+            let interval = interval
+                .option()
+                .map(|minutes| Duration::from_secs(minutes.saturating_mul(60)));
+            rpc.set_quantum_resistant_rekey_interval(interval).await?;
+            println!("Quantum resistant rekey interval has been updated");
+        }
+
         #[cfg(daita)]
         if let Some(daita) = daita {
             rpc.set_daita_settings(DaitaSettings { enabled: *daita })
@@ -220,6 +269,12 @@ impl Tunnel {
         if matches!(rotate_key, Some(RotateKey::RotateKey)) {
             rpc.rotate_wireguard_key().await?;
             println!("Rotated WireGuard key");
+            if !rpc.get_settings().await?.custom_relays.is_empty() {
+                println!(
+                    "Custom relays must be updated to accept the new public key, which is shown \
+                     by 'mullvad custom-relay list'"
+                );
+            }
         }
 
         Ok(())
@@ -1,11 +1,13 @@
 use itertools::Itertools;
 use mullvad_types::{
-    auth_failed::AuthFailed, features::FeatureIndicators, location::GeoIpLocation,
-    states::TunnelState,
+    account::ExpiryNotification, auth_failed::AuthFailed, features::FeatureIndicators,
+    location::GeoIpLocation, relay_list::RelayListDiff, secrets, states::TunnelState,
 };
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use talpid_types::{
     net::{Endpoint, TunnelEndpoint},
-    tunnel::ErrorState,
+    tunnel::{ErrorDiagnostics, ErrorState},
 };
 
 #[macro_export]
@@ -18,11 +20,78 @@ macro_rules! print_option {
     }};
 }
 
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Make commands print JSON instead of text. Set by the global `--json` flag.
+pub fn enable_json_output() {
+    JSON_OUTPUT.store(true, Ordering::Relaxed);
+}
+
+/// Whether commands should print JSON instead of text.
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Print `value` as JSON on a single line, with credentials removed.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<()> {
+    println!("{}", to_json(value)?);
+    Ok(())
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    let mut value = serde_json::to_value(value)?;
+    secrets::remove_secrets(&mut value);
+    serde_json::to_string(&value)
+}
+
+/// Describe how long remains until the account expires.
+pub fn format_expiry_notification(notification: &ExpiryNotification) -> String {
+    let remaining = notification.expiry - chrono::Utc::now();
+    if notification.threshold_hours.is_none() || remaining <= chrono::Duration::zero() {
+        "Warning: The account has run out of time".to_owned()
+    } else if remaining.num_days() > 0 {
+        format!(
+            "Warning: The account expires in {} days",
+            remaining.num_days()
+        )
+    } else if remaining.num_hours() > 0 {
+        format!(
+            "Warning: The account expires in {} hours",
+            remaining.num_hours()
+        )
+    } else {
+        "Warning: The account expires in less than an hour".to_owned()
+    }
+}
+
+/// Print the relays that were added, removed or changed in a relay list update.
+pub fn print_relay_list_diff(diff: &RelayListDiff) {
+    println!(
+        "Relay list updated at {}",
+        diff.time.with_timezone(&chrono::Local)
+    );
+    for hostname in &diff.added {
+        println!("\t+ {hostname}");
+    }
+    for hostname in &diff.removed {
+        println!("\t- {hostname}");
+    }
+    for change in &diff.changed {
+        println!(
+            "\t~ {}: {:?} changed from \"{}\" to \"{}\"",
+            change.hostname, change.kind, change.old, change.new
+        );
+    }
+    for hostname in &diff.removed_pinned {
+        println!("Warning: Relay {hostname} was removed but is still used by the settings");
+    }
+}
+
 pub fn print_state(state: &TunnelState, verbose: bool) {
     use TunnelState::*;
 
     match state {
-        Error(error) => print_error_state(error),
+        Error(error) => print_error_state(error, verbose),
         Connected {
             endpoint,
             location,
@@ -215,7 +284,7 @@ fn format_endpoint(hostname: Option<&str>, endpoint: &Endpoint, verbose: bool) -
     }
 }
 
-fn print_error_state(error_state: &ErrorState) {
+fn print_error_state(error_state: &ErrorState, verbose: bool) {
     if error_state.block_failure().is_some() {
         eprintln!("Mullvad daemon failed to setup firewall rules!");
         eprintln!("Daemon cannot block traffic from flowing, non-local traffic will leak");
@@ -250,6 +319,35 @@ fn print_error_state(error_state: &ErrorState) {
         }
         cause => println!("Blocked: {cause}"),
     }
+
+    if verbose {
+        if let Some(diagnostics) = error_state.diagnostics() {
+            print_error_diagnostics(diagnostics);
+        }
+    }
+}
+
+fn print_error_diagnostics(diagnostics: &ErrorDiagnostics) {
+    println!("Diagnostics:");
+    print_option!("Subsystem", diagnostics.subsystem);
+    print_option!(
+        "Remediation",
+        format!(
+            "{} ({})",
+            diagnostics.remediation,
+            diagnostics.remediation.as_str()
+        )
+    );
+    for (key, value) in &diagnostics.environment {
+        print_option!("Environment", format!("{key}: {value}"));
+    }
+    for (index, error) in diagnostics.error_chain.iter().enumerate() {
+        if index == 0 {
+            print_option!("Error", error);
+        } else {
+            print_option!("Caused by", error);
+        }
+    }
 }
 
 const fn get_auth_failed_message(auth_failed: AuthFailed) -> &'static str {
@@ -265,3 +363,39 @@ const fn get_auth_failed_message(auth_failed: AuthFailed) -> &'static str {
         AuthFailed::Unknown => UNKNOWN_MSG,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::custom_relay::CustomRelay;
+    use talpid_types::net::wireguard::{PresharedKey, PrivateKey};
+
+    #[test]
+    fn test_json_output_removes_psk() {
+        let psk = PresharedKey::from([7u8; 32]);
+        let relays = vec![CustomRelay {
+            name: "home".to_owned(),
+            endpoint: "192.0.2.1:51820".parse().unwrap(),
+            public_key: PrivateKey::new_from_random().public_key(),
+            allowed_ips: vec!["0.0.0.0/0".parse().unwrap()],
+            addresses: vec!["10.0.0.2".parse().unwrap()],
+            ipv4_gateway: "10.0.0.1".parse().unwrap(),
+            ipv6_gateway: None,
+            psk: Some(psk.clone()),
+            allow_without_quantum_resistance: false,
+        }];
+        assert!(serde_json::to_string(&relays)
+            .unwrap()
+            .contains(&psk.to_base64()));
+
+        let output = to_json(&relays).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value[0]["psk"], secrets::REMOVED);
+        assert_eq!(value[0]["name"], "home");
+        assert!(!output.contains(&psk.to_base64()));
+        assert!(
+            !output.contains('\n'),
+            "JSON output should be a single line"
+        );
+    }
+}
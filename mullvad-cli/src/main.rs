@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 mod cmds;
 mod format;
@@ -10,7 +10,18 @@ pub const BIN_NAME: &str = env!("CARGO_BIN_NAME");
 #[derive(Debug, Parser)]
 #[command(author, version = mullvad_version::VERSION, about, long_about = None)]
 #[command(propagate_version = true)]
-enum Cli {
+struct Cli {
+    /// Print the output of commands that display information as JSON. Events are printed as
+    /// one JSON object per line. Passwords and private keys are replaced with "<redacted>"
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
     /// Control and display information about your Mullvad account
     #[clap(subcommand)]
     Account(account::Account),
@@ -135,6 +146,22 @@ enum Cli {
     #[clap(subcommand)]
     CustomList(custom_list::CustomList),
 
+    /// Manage self-hosted WireGuard servers that can be used as relays
+    #[clap(subcommand)]
+    CustomRelay(custom_relay::CustomRelay),
+
+    /// Connect or disconnect the tunnel at certain times
+    #[clap(subcommand)]
+    Schedule(schedule::Schedule),
+
+    /// Show the data transferred through the tunnel and manage the monthly data cap
+    #[clap(subcommand)]
+    DataUsage(data_usage::DataUsage),
+
+    /// Log in to captive portals while the firewall blocks traffic
+    #[clap(subcommand)]
+    CaptivePortal(captive_portal::CaptivePortal),
+
     /// Apply a JSON patch generated by 'export-settings'
     #[clap(arg_required_else_help = true)]
     ImportSettings {
@@ -152,32 +179,41 @@ enum Cli {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    match Cli::parse() {
-        Cli::Account(cmd) => cmd.handle().await,
-        Cli::Bridge(cmd) => cmd.handle().await,
-        Cli::Connect { wait } => tunnel_state::connect(wait).await,
-        Cli::Reconnect { wait } => tunnel_state::reconnect(wait).await,
-        Cli::Debug(cmd) => cmd.handle().await,
-        Cli::Disconnect { wait } => tunnel_state::disconnect(wait).await,
-        Cli::AutoConnect(cmd) => cmd.handle().await,
-        Cli::BetaProgram(cmd) => cmd.handle().await,
-        Cli::LockdownMode(cmd) => cmd.handle().await,
-        Cli::Dns(cmd) => cmd.handle().await,
-        Cli::Lan(cmd) => cmd.handle().await,
-        Cli::Obfuscation(cmd) => cmd.handle().await,
-        Cli::ApiAccess(cmd) => cmd.handle().await,
-        Cli::Version => version::print().await,
-        Cli::FactoryReset => reset::handle().await,
-        Cli::Relay(cmd) => cmd.handle().await,
-        Cli::Tunnel(cmd) => cmd.handle().await,
-        Cli::SplitTunnel(cmd) => cmd.handle().await,
-        Cli::Status { cmd, args } => status::handle(cmd, args).await,
-        Cli::CustomList(cmd) => cmd.handle().await,
-        Cli::ImportSettings { file } => patch::import(file).await,
-        Cli::ExportSettings { file } => patch::export(file).await,
+    let cli = Cli::parse();
+    if cli.json {
+        format::enable_json_output();
+    }
+
+    match cli.command {
+        Command::Account(cmd) => cmd.handle().await,
+        Command::Bridge(cmd) => cmd.handle().await,
+        Command::Connect { wait } => tunnel_state::connect(wait).await,
+        Command::Reconnect { wait } => tunnel_state::reconnect(wait).await,
+        Command::Debug(cmd) => cmd.handle().await,
+        Command::Disconnect { wait } => tunnel_state::disconnect(wait).await,
+        Command::AutoConnect(cmd) => cmd.handle().await,
+        Command::BetaProgram(cmd) => cmd.handle().await,
+        Command::LockdownMode(cmd) => cmd.handle().await,
+        Command::Dns(cmd) => cmd.handle().await,
+        Command::Lan(cmd) => cmd.handle().await,
+        Command::Obfuscation(cmd) => cmd.handle().await,
+        Command::ApiAccess(cmd) => cmd.handle().await,
+        Command::Version => version::print().await,
+        Command::FactoryReset => reset::handle().await,
+        Command::Relay(cmd) => cmd.handle().await,
+        Command::Tunnel(cmd) => cmd.handle().await,
+        Command::SplitTunnel(cmd) => cmd.handle().await,
+        Command::Status { cmd, args } => status::handle(cmd, args).await,
+        Command::CustomList(cmd) => cmd.handle().await,
+        Command::CustomRelay(cmd) => cmd.handle().await,
+        Command::Schedule(cmd) => cmd.handle().await,
+        Command::DataUsage(cmd) => cmd.handle().await,
+        Command::CaptivePortal(cmd) => cmd.handle().await,
+        Command::ImportSettings { file } => patch::import(file).await,
+        Command::ExportSettings { file } => patch::export(file).await,
 
         #[cfg(all(unix, not(target_os = "android")))]
-        Cli::ShellCompletions { shell, dir } => {
+        Command::ShellCompletions { shell, dir } => {
             use anyhow::Context;
             use clap::CommandFactory;
 
@@ -0,0 +1,228 @@
+//! Restricts which local users may use which parts of the management interface.
+//!
+//! The policy is read from [`POLICY_FILENAME`] in the settings directory when the daemon starts.
+//! Without a policy file, any user that can open the management interface socket has full access.
+//! The policy file is a JSON object like the following:
+//!
+//! ```json
+//! {
+//!     "default": ["status", "tunnel"],
+//!     "users": { "alice": ["status", "tunnel", "settings", "account"] },
+//!     "groups": { "sudo": ["status", "tunnel", "settings", "account"] }
+//! }
+//! ```
+//!
+//! A client is granted the union of the categories of its user, the groups that its user is a
+//! member of, and `default`. The root user always has full access. Policies are only supported on
+//! Linux and macOS.
+
+use mullvad_management_interface::access::{PeerCredentials, RpcCategory};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::Path,
+};
+use talpid_types::ErrorExt;
+
+pub const POLICY_FILENAME: &str = "access-policy.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read access policy")]
+    Read(#[source] io::Error),
+
+    #[error("Failed to parse access policy")]
+    Parse(#[source] serde_json::Error),
+
+    #[error("Invalid access policy")]
+    InvalidCategory(#[source] mullvad_management_interface::access::RpcCategoryParseError),
+}
+
+/// The format of the policy file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default)]
+    default: Vec<String>,
+    #[serde(default)]
+    users: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    groups: HashMap<String, Vec<String>>,
+}
+
+type Categories = HashSet<RpcCategory>;
+
+/// Decides which categories of RPCs a client may use.
+#[derive(Debug, Clone)]
+pub struct AccessPolicy {
+    /// `None` if all clients have full access.
+    rules: Option<Rules>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Rules {
+    default: Categories,
+    users: HashMap<String, Categories>,
+    groups: HashMap<String, Categories>,
+}
+
+impl AccessPolicy {
+    /// A policy that grants full access to everyone.
+    pub fn allow_all() -> Self {
+        AccessPolicy { rules: None }
+    }
+
+    /// Load the policy from `settings_dir`. If the policy file exists but cannot be used, only
+    /// the root user is granted access.
+    pub fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(POLICY_FILENAME);
+        match std::fs::read_to_string(&path) {
+            Ok(_) if cfg!(not(any(target_os = "linux", target_os = "macos"))) => {
+                log::warn!(
+                    "Ignoring {}: access policies are not supported on this platform",
+                    path.display()
+                );
+                Self::allow_all()
+            }
+            Ok(contents) => match Self::parse(&contents) {
+                Ok(policy) => {
+                    log::info!("Loaded management interface access policy");
+                    policy
+                }
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Only root may use the management interface")
+                    );
+                    AccessPolicy {
+                        rules: Some(Rules::default()),
+                    }
+                }
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Self::allow_all(),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    Error::Read(error)
+                        .display_chain_with_msg("Only root may use the management interface")
+                );
+                AccessPolicy {
+                    rules: Some(Rules::default()),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn parse(contents: &str) -> Result<Self, Error> {
+        let file: PolicyFile = serde_json::from_str(contents).map_err(Error::Parse)?;
+        let parse_categories = |categories: Vec<String>| {
+            categories
+                .iter()
+                .map(|category| category.parse().map_err(Error::InvalidCategory))
+                .collect::<Result<Categories, _>>()
+        };
+        let parse_map = |map: HashMap<String, Vec<String>>| {
+            map.into_iter()
+                .map(|(name, categories)| Ok((name, parse_categories(categories)?)))
+                .collect::<Result<HashMap<_, _>, Error>>()
+        };
+
+        Ok(AccessPolicy {
+            rules: Some(Rules {
+                default: parse_categories(file.default)?,
+                users: parse_map(file.users)?,
+                groups: parse_map(file.groups)?,
+            }),
+        })
+    }
+
+    /// Return whether a client with the credentials `peer` may use RPCs in `category`.
+    pub fn allows(&self, peer: Option<&PeerCredentials>, category: RpcCategory) -> bool {
+        let Some(ref rules) = self.rules else {
+            return true;
+        };
+        if rules.default.contains(&category) {
+            return true;
+        }
+        let Some(peer) = peer else {
+            return false;
+        };
+        if peer.uid == 0 {
+            return true;
+        }
+        rules.allows(peer, category)
+    }
+}
+
+impl Rules {
+    #[cfg(unix)]
+    fn allows(&self, peer: &PeerCredentials, category: RpcCategory) -> bool {
+        use nix::unistd::{Gid, Group, Uid, User};
+
+        let user = match User::from_uid(Uid::from_raw(peer.uid)) {
+            Ok(Some(user)) => user,
+            Ok(None) => return false,
+            Err(error) => {
+                log::error!("Failed to look up user {}: {error}", peer.uid);
+                return false;
+            }
+        };
+        if self
+            .users
+            .get(&user.name)
+            .is_some_and(|categories| categories.contains(&category))
+        {
+            return true;
+        }
+
+        self.groups
+            .iter()
+            .filter(|(_name, categories)| categories.contains(&category))
+            .any(|(name, _categories)| match Group::from_name(name) {
+                Ok(Some(group)) => {
+                    group.gid == Gid::from_raw(peer.gid)
+                        || group.gid == user.gid
+                        || group.mem.contains(&user.name)
+                }
+                Ok(None) => false,
+                Err(error) => {
+                    log::error!("Failed to look up group {name}: {error}");
+                    false
+                }
+            })
+    }
+
+    #[cfg(windows)]
+    fn allows(&self, _peer: &PeerCredentials, _category: RpcCategory) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_categories() {
+        let policy = AccessPolicy::parse(r#"{ "default": ["status", "tunnel"] }"#).unwrap();
+        let peer = PeerCredentials {
+            uid: 1000,
+            gid: 1000,
+            pid: None,
+        };
+
+        assert!(policy.allows(Some(&peer), RpcCategory::Tunnel));
+        assert!(policy.allows(None, RpcCategory::Status));
+        assert!(!policy.allows(None, RpcCategory::Settings));
+        assert!(policy.allows(
+            Some(&PeerCredentials { uid: 0, ..peer }),
+            RpcCategory::Account
+        ));
+    }
+
+    #[test]
+    fn test_invalid_category() {
+        assert!(AccessPolicy::parse(r#"{ "default": ["everything"] }"#).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! Notifies clients when the account is about to run out of time.
+
+use crate::{device::AccountManagerHandle, DaemonEventSender, InternalDaemonEvent};
+use chrono::{DateTime, Utc};
+use futures::future::{abortable, AbortHandle};
+use mullvad_types::account::ExpiryNotification;
+use std::time::Duration;
+use talpid_core::mpsc::Sender;
+use talpid_types::ErrorExt;
+
+/// How often the account expiry is refreshed from the API.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Sent when the next notification threshold has been reached.
+pub struct ExpiryTimerEvent;
+
+impl From<ExpiryTimerEvent> for InternalDaemonEvent {
+    fn from(_: ExpiryTimerEvent) -> Self {
+        InternalDaemonEvent::AccountExpiryTimer
+    }
+}
+
+/// Keeps track of the account expiry and determines when clients should be notified about it.
+/// The expiry itself is learned through [`crate::device::AccountEvent::Expiry`], which is
+/// requested periodically for as long as the monitor is alive.
+pub struct ExpiryMonitor {
+    thresholds: Vec<u32>,
+    expiry: Option<DateTime<Utc>>,
+    notification: Option<ExpiryNotification>,
+    timer: Option<AbortHandle>,
+    checker: AbortHandle,
+    event_tx: DaemonEventSender<ExpiryTimerEvent>,
+}
+
+impl ExpiryMonitor {
+    pub fn new(
+        thresholds: Vec<u32>,
+        account_manager: AccountManagerHandle,
+        event_tx: &DaemonEventSender,
+    ) -> Self {
+        let (checker, checker_handle) = abortable(check_expiry_periodically(account_manager));
+        tokio::spawn(checker);
+
+        ExpiryMonitor {
+            thresholds,
+            expiry: None,
+            notification: None,
+            timer: None,
+            checker: checker_handle,
+            event_tx: event_tx.to_specialized_sender(),
+        }
+    }
+
+    /// Return the notification that currently applies, if any.
+    pub fn notification(&self) -> Option<ExpiryNotification> {
+        self.notification.clone()
+    }
+
+    /// Update the known account expiry. `None` means that there is no account.
+    /// Returns a notification if a new threshold has been passed.
+    pub fn set_expiry(&mut self, expiry: Option<DateTime<Utc>>) -> Option<ExpiryNotification> {
+        if self.expiry == expiry {
+            return None;
+        }
+        self.expiry = expiry;
+        self.update()
+    }
+
+    /// Change the notification thresholds, in hours before the expiry.
+    /// Returns a notification if a new threshold has been passed.
+    pub fn set_thresholds(&mut self, thresholds: Vec<u32>) -> Option<ExpiryNotification> {
+        if self.thresholds == thresholds {
+            return None;
+        }
+        self.thresholds = thresholds;
+        self.update()
+    }
+
+    /// Re-evaluate the notification state, e.g. when the timer has fired.
+    /// Returns a notification if a new threshold has been passed.
+    pub fn update(&mut self) -> Option<ExpiryNotification> {
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+
+        let Some(expiry) = self.expiry else {
+            self.notification = None;
+            return None;
+        };
+
+        let now = Utc::now();
+        let (notification, next_update) = evaluate(expiry, &self.thresholds, now);
+
+        if let Some(next_update) = next_update {
+            let delay = (next_update - now).to_std().unwrap_or_default();
+            let event_tx = self.event_tx.clone();
+            let (timer, timer_handle) = abortable(async move {
+                talpid_time::sleep(delay).await;
+                let _ = event_tx.send(ExpiryTimerEvent);
+            });
+            tokio::spawn(timer);
+            self.timer = Some(timer_handle);
+        }
+
+        let changed = self.notification != notification;
+        self.notification = notification;
+        if changed {
+            self.notification.clone()
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for ExpiryMonitor {
+    fn drop(&mut self) {
+        self.checker.abort();
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+    }
+}
+
+/// Return the notification that applies at `now`, and the time at which it next changes.
+fn evaluate(
+    expiry: DateTime<Utc>,
+    thresholds: &[u32],
+    now: DateTime<Utc>,
+) -> (Option<ExpiryNotification>, Option<DateTime<Utc>>) {
+    if expiry <= now {
+        let notification = ExpiryNotification {
+            expiry,
+            threshold_hours: None,
+        };
+        return (Some(notification), None);
+    }
+
+    let crosses_at = |hours: u32| expiry - chrono::Duration::hours(i64::from(hours));
+
+    let passed = thresholds
+        .iter()
+        .copied()
+        .filter(|&hours| crosses_at(hours) <= now)
+        .min();
+    let next_update = thresholds
+        .iter()
+        .copied()
+        .filter(|&hours| crosses_at(hours) > now)
+        .max()
+        .map(crosses_at)
+        .unwrap_or(expiry);
+
+    let notification = passed.map(|hours| ExpiryNotification {
+        expiry,
+        threshold_hours: Some(hours),
+    });
+    (notification, Some(next_update))
+}
+
+/// Ask the account manager to refresh the account expiry at a fixed interval.
+async fn check_expiry_periodically(account_manager: AccountManagerHandle) {
+    loop {
+        if let Err(error) = account_manager.check_expiry().await {
+            log::debug!(
+                "{}",
+                error.display_chain_with_msg("Failed to check account expiry")
+            );
+        }
+        talpid_time::sleep(EXPIRY_CHECK_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let now = Utc::now();
+        let thresholds = [7 * 24, 3 * 24, 24];
+
+        let expiry = now + chrono::Duration::days(10);
+        let (notification, next) = evaluate(expiry, &thresholds, now);
+        assert_eq!(notification, None);
+        assert_eq!(next, Some(expiry - chrono::Duration::days(7)));
+
+        let expiry = now + chrono::Duration::hours(48);
+        let (notification, next) = evaluate(expiry, &thresholds, now);
+        assert_eq!(notification.unwrap().threshold_hours, Some(3 * 24));
+        assert_eq!(next, Some(expiry - chrono::Duration::hours(24)));
+
+        let expiry = now + chrono::Duration::hours(1);
+        let (notification, next) = evaluate(expiry, &thresholds, now);
+        assert_eq!(notification.unwrap().threshold_hours, Some(24));
+        assert_eq!(next, Some(expiry));
+
+        let expiry = now - chrono::Duration::hours(1);
+        let (notification, next) = evaluate(expiry, &thresholds, now);
+        assert_eq!(notification.unwrap().threshold_hours, None);
+        assert_eq!(next, None);
+    }
+}
@@ -1,7 +1,7 @@
-use mullvad_types::account::AccountToken;
+use mullvad_types::account::{AccountToken, SavedAccount};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use talpid_types::ErrorExt;
 use tokio::{
     fs,
@@ -23,13 +23,21 @@ pub enum Error {
 
     #[error("Write task panicked or was cancelled")]
     WriteCancelled(#[source] tokio::task::JoinError),
+
+    #[error("There is no saved account named \"{0}\"")]
+    UnknownAccountName(String),
 }
 
 static ACCOUNT_HISTORY_FILE: &str = "account-history.json";
+/// Saved accounts are kept in a separate file, so that the account history file keeps the bare
+/// token format that older versions read.
+static SAVED_ACCOUNTS_FILE: &str = "saved-accounts.json";
 
 pub struct AccountHistory {
     file: io::BufWriter<fs::File>,
     token: Option<AccountToken>,
+    saved_path: PathBuf,
+    saved: Vec<SavedAccount>,
 }
 
 static ACCOUNT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[0-9]+$").unwrap());
@@ -72,8 +80,16 @@ impl AccountHistory {
                 }
             };
 
+        let saved_path = settings_dir.join(SAVED_ACCOUNTS_FILE);
+        let saved = read_saved_accounts(&saved_path).await;
+
         let file = io::BufWriter::new(reader.into_inner());
-        let mut history = AccountHistory { file, token };
+        let mut history = AccountHistory {
+            file,
+            token,
+            saved_path,
+            saved,
+        };
         if should_save {
             if let Err(error) = history.save_to_disk().await {
                 log::error!(
@@ -96,12 +112,63 @@ impl AccountHistory {
         self.save_to_disk().await
     }
 
-    /// Remove account history
+    /// Remove account history. Saved accounts are kept.
     pub async fn clear(&mut self) -> Result<()> {
         self.token = None;
         self.save_to_disk().await
     }
 
+    /// Remove account history and all saved accounts.
+    pub async fn clear_all(&mut self) -> Result<()> {
+        self.token = None;
+        self.saved.clear();
+        self.save_to_disk().await?;
+        match fs::remove_file(&self.saved_path).await {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(Error::Write(error)),
+        }
+    }
+
+    /// Return all saved accounts
+    pub fn saved_accounts(&self) -> Vec<SavedAccount> {
+        self.saved.clone()
+    }
+
+    /// Return the account token saved under `name`
+    pub fn saved_account(&self, name: &str) -> Result<AccountToken> {
+        self.saved
+            .iter()
+            .find(|account| account.name == name)
+            .map(|account| account.account_token.clone())
+            .ok_or_else(|| Error::UnknownAccountName(name.to_owned()))
+    }
+
+    /// Save an account under a name, replacing any account that already has that name
+    pub async fn save_account(&mut self, account: SavedAccount) -> Result<()> {
+        match self
+            .saved
+            .iter_mut()
+            .find(|saved| saved.name == account.name)
+        {
+            Some(saved) => *saved = account,
+            None => self.saved.push(account),
+        }
+        self.save_saved_accounts().await
+    }
+
+    /// Remove the account saved under `name`, and return it
+    pub async fn remove_saved_account(&mut self, name: &str) -> Result<SavedAccount> {
+        let index = self
+            .saved
+            .iter()
+            .position(|account| account.name == name)
+            .ok_or_else(|| Error::UnknownAccountName(name.to_owned()))?;
+        let account = self.saved.remove(index);
+        self.save_saved_accounts().await?;
+        Ok(account)
+    }
+
     async fn save_to_disk(&mut self) -> Result<()> {
         self.file.get_mut().set_len(0).await.map_err(Error::Write)?;
         self.file
@@ -117,4 +184,101 @@ impl AccountHistory {
         self.file.flush().await.map_err(Error::Write)?;
         self.file.get_mut().sync_all().await.map_err(Error::Write)
     }
+
+    async fn save_saved_accounts(&self) -> Result<()> {
+        let data = serde_json::to_vec(&self.saved).map_err(Error::Serialize)?;
+        let mut options = fs::OpenOptions::new();
+        #[cfg(unix)]
+        {
+            options.mode(0o600);
+        }
+        let mut file = options
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.saved_path)
+            .await
+            .map_err(Error::Write)?;
+        file.write_all(&data).await.map_err(Error::Write)?;
+        file.sync_all().await.map_err(Error::Write)
+    }
+}
+
+async fn read_saved_accounts(path: &Path) -> Vec<SavedAccount> {
+    match fs::read(path).await {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|error| {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to parse saved accounts")
+            );
+            vec![]
+        }),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+        Err(error) => {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to read saved accounts")
+            );
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clear_all() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut history = AccountHistory::new(dir.path(), Some("1234".to_owned()))
+            .await
+            .unwrap();
+        history
+            .save_account(SavedAccount {
+                name: "work".to_owned(),
+                account_token: "5678".to_owned(),
+            })
+            .await
+            .unwrap();
+
+        history.clear().await.unwrap();
+        assert_eq!(history.get(), None);
+        assert_eq!(history.saved_accounts().len(), 1);
+
+        history.clear_all().await.unwrap();
+        drop(history);
+
+        let history = AccountHistory::new(dir.path(), None).await.unwrap();
+        assert_eq!(history.get(), None);
+        assert!(history.saved_accounts().is_empty());
+    }
+
+    /// The account history file must keep the bare token format, so that older versions can
+    /// still read it after a downgrade.
+    #[tokio::test]
+    async fn test_history_file_format() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut history = AccountHistory::new(dir.path(), None).await.unwrap();
+        history.set("1234".to_owned()).await.unwrap();
+        history
+            .save_account(SavedAccount {
+                name: "work".to_owned(),
+                account_token: "5678".to_owned(),
+            })
+            .await
+            .unwrap();
+        drop(history);
+
+        let contents = fs::read_to_string(dir.path().join(ACCOUNT_HISTORY_FILE))
+            .await
+            .unwrap();
+        assert_eq!(contents, "1234");
+
+        let history = AccountHistory::new(dir.path(), None).await.unwrap();
+        assert_eq!(history.get(), Some("1234".to_owned()));
+        assert_eq!(history.saved_account("work").unwrap(), "5678");
+    }
 }
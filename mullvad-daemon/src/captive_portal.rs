@@ -0,0 +1,348 @@
+//! Lets the user log in to a captive portal while the firewall blocks all other traffic, e.g.
+//! because of lockdown mode.
+//!
+//! A known HTTP endpoint is probed with DNS access to the gateways and resolvers of the local
+//! network. If the probe is redirected, or a page other than the expected one is served in its
+//! place, the network is assumed to have a captive portal. HTTP and HTTPS traffic to the resolved
+//! addresses of the portal is then allowed until the time window ends, after which the block is
+//! enforced again. The window is measured in wall-clock time, so that it also ends while the
+//! computer is suspended. Firewall policies that are applied after the window has ended never
+//! include the exception.
+
+use crate::{Daemon, EventListener, ResponseTx};
+use futures::{
+    channel::{mpsc::UnboundedSender, oneshot},
+    future::abortable,
+};
+use hyper::{header, StatusCode, Uri};
+use mullvad_api::http_client::{self, Response};
+use mullvad_types::{settings::LockedSetting, states::TunnelState};
+use std::{
+    io,
+    net::IpAddr,
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+use talpid_core::tunnel_state_machine::TunnelCommand;
+use talpid_types::ErrorExt;
+
+/// Host that is probed for a captive portal. It responds with [`PROBE_RESPONSE`] if there is no
+/// captive portal.
+const PROBE_HOST: &str = "detectportal.firefox.com";
+const PROBE_PATH: &str = "/success.txt";
+const PROBE_RESPONSE: &str = "success";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a captive portal may be reached at most.
+const MAX_WINDOW: Duration = Duration::from_secs(30 * 60);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Captive portals can only be allowed while the firewall blocks all traffic")]
+    NotBlocking,
+
+    #[error("Captive portals cannot be allowed since lockdown mode is locked by policy")]
+    LockedByPolicy,
+
+    #[error("The time window must be between 1 second and 30 minutes")]
+    InvalidWindow,
+
+    #[error("Failed to resolve {0}")]
+    Resolve(String, #[source] io::Error),
+
+    #[error("Failed to probe for a captive portal")]
+    Probe(#[source] http_client::Error),
+
+    #[error("No captive portal was detected")]
+    NoCaptivePortal,
+
+    #[error("The tunnel state machine is not running")]
+    TunnelStateMachineDown,
+}
+
+type CommandSender = Weak<UnboundedSender<TunnelCommand>>;
+
+impl<L> Daemon<L>
+where
+    L: EventListener,
+{
+    /// Probe for a captive portal. If one is detected, allow traffic to it for `window`, and send
+    /// the URL of the portal to `tx`.
+    pub(crate) fn on_allow_captive_portal(
+        &mut self,
+        tx: ResponseTx<String, Error>,
+        window: Duration,
+    ) {
+        if self
+            .settings
+            .locked_settings
+            .contains(&LockedSetting::BlockWhenDisconnected)
+        {
+            Self::oneshot_send(
+                tx,
+                Err(Error::LockedByPolicy),
+                "allow_captive_portal response",
+            );
+            return;
+        }
+        let blocking = match &self.tunnel_state {
+            TunnelState::Disconnected { locked_down, .. } => *locked_down,
+            TunnelState::Error(error_state) => error_state.is_blocking(),
+            _ => false,
+        };
+        if !blocking {
+            Self::oneshot_send(tx, Err(Error::NotBlocking), "allow_captive_portal response");
+            return;
+        }
+        if window.is_zero() || window > MAX_WINDOW {
+            Self::oneshot_send(
+                tx,
+                Err(Error::InvalidWindow),
+                "allow_captive_portal response",
+            );
+            return;
+        }
+
+        // The new window replaces any previous one
+        if let Some(previous) = self.captive_portal.take() {
+            previous.abort();
+        }
+        let command_tx = Arc::downgrade(self.tunnel_state_machine_handle.command_tx());
+        let (task, handle) = abortable(allow_captive_portal(command_tx, window, tx));
+        tokio::spawn(task);
+        self.captive_portal = Some(handle);
+    }
+}
+
+async fn allow_captive_portal(
+    command_tx: CommandSender,
+    window: Duration,
+    tx: ResponseTx<String, Error>,
+) {
+    let deadline = SystemTime::now() + window;
+    let result = detect_captive_portal(&command_tx, deadline).await;
+    let detected = result.is_ok();
+    match &result {
+        Ok(url) => log::info!(
+            "Allowing captive portal at {url} for {} seconds",
+            window.as_secs()
+        ),
+        Err(error) => log::info!(
+            "{}",
+            error.display_chain_with_msg("Not allowing captive portal")
+        ),
+    }
+    let _ = tx.send(result);
+
+    if detected {
+        let remaining = deadline
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        talpid_time::sleep(remaining).await;
+        log::info!("Blocking captive portal again");
+    }
+    if let Err(error) = set_captive_portal_hosts(&command_tx, None).await {
+        log::error!(
+            "{}",
+            error.display_chain_with_msg("Failed to block captive portal")
+        );
+    }
+}
+
+/// Probe for a captive portal and allow traffic to it until `deadline`. Returns the URL of the
+/// portal.
+async fn detect_captive_portal(
+    command_tx: &CommandSender,
+    deadline: SystemTime,
+) -> Result<String, Error> {
+    // DNS to the gateways and resolvers is allowed first, so that the probed host can be resolved
+    set_captive_portal_hosts(command_tx, Some((vec![], deadline))).await?;
+    let probe_hosts = resolve(PROBE_HOST, 80).await?;
+    set_captive_portal_hosts(command_tx, Some((probe_hosts.clone(), deadline))).await?;
+
+    let probe_url = format!("http://{PROBE_HOST}{PROBE_PATH}");
+    let response = http_client::get(&probe_url, PROBE_TIMEOUT)
+        .await
+        .map_err(Error::Probe)?;
+
+    let (portal_url, mut hosts) = match classify_probe_response(&response) {
+        ProbeResponse::NoPortal => return Err(Error::NoCaptivePortal),
+        ProbeResponse::Redirect(url) => {
+            let host = url
+                .host()
+                .ok_or(Error::NoCaptivePortal)?
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+            let default_port = if url.scheme_str() == Some("https") {
+                443
+            } else {
+                80
+            };
+            let hosts = resolve(host, url.port_u16().unwrap_or(default_port)).await?;
+            (url.to_string(), hosts)
+        }
+        // The portal was served in place of the expected response
+        ProbeResponse::Intercepted => (probe_url, probe_hosts),
+    };
+
+    // Only the portal remains reachable, not the probed host
+    hosts.sort();
+    hosts.dedup();
+    set_captive_portal_hosts(command_tx, Some((hosts, deadline))).await?;
+    Ok(portal_url)
+}
+
+/// What the response to the probe shows about the network.
+#[derive(Debug, PartialEq)]
+enum ProbeResponse {
+    /// There is no captive portal, or the response does not show one.
+    NoPortal,
+    /// The probe was redirected to the portal at this URL.
+    Redirect(Uri),
+    /// The portal was served in place of the expected response.
+    Intercepted,
+}
+
+/// Classify the response to the probe. Only a redirect to an HTTP or HTTPS URL, or a page served
+/// in place of the expected response, shows that there is a captive portal. Any other response,
+/// such as an error from the probed host, does not.
+fn classify_probe_response(response: &Response) -> ProbeResponse {
+    let status = response.status;
+    if status.is_redirection() {
+        return response
+            .headers
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(resolve_location)
+            .map(ProbeResponse::Redirect)
+            .unwrap_or(ProbeResponse::NoPortal);
+    }
+    let body = String::from_utf8_lossy(&response.body);
+    let body = body.trim();
+    match status {
+        StatusCode::NETWORK_AUTHENTICATION_REQUIRED => ProbeResponse::Intercepted,
+        StatusCode::OK if !body.is_empty() && body != PROBE_RESPONSE => ProbeResponse::Intercepted,
+        _ => ProbeResponse::NoPortal,
+    }
+}
+
+/// Resolve the `Location` header of a redirect from the probed URL.
+fn resolve_location(location: &str) -> Option<Uri> {
+    let location = if location.starts_with("//") {
+        format!("http:{location}")
+    } else if location.starts_with('/') {
+        format!("http://{PROBE_HOST}{location}")
+    } else {
+        location.to_owned()
+    };
+    let url: Uri = location.parse().ok()?;
+    let supported_scheme = matches!(url.scheme_str(), Some("http" | "https"));
+    (supported_scheme && url.host().is_some()).then_some(url)
+}
+
+async fn resolve(host: &str, port: u16) -> Result<Vec<IpAddr>, Error> {
+    tokio::net::lookup_host((host, port))
+        .await
+        .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+        .map_err(|error| Error::Resolve(host.to_owned(), error))
+}
+
+/// Allow DNS to the gateways and resolvers, and HTTP and HTTPS to `hosts`, until the deadline, or
+/// block them if `hosts` is `None`.
+async fn set_captive_portal_hosts(
+    command_tx: &CommandSender,
+    hosts: Option<(Vec<IpAddr>, SystemTime)>,
+) -> Result<(), Error> {
+    let (tx, rx) = oneshot::channel();
+    command_tx
+        .upgrade()
+        .ok_or(Error::TunnelStateMachineDown)?
+        .unbounded_send(TunnelCommand::AllowCaptivePortal(hosts, tx))
+        .map_err(|_| Error::TunnelStateMachineDown)?;
+    rx.await.map_err(|_| Error::TunnelStateMachineDown)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hyper::HeaderMap;
+
+    fn response(status: StatusCode, location: Option<&str>, body: &str) -> Response {
+        let mut headers = HeaderMap::new();
+        if let Some(location) = location {
+            headers.insert(header::LOCATION, location.parse().unwrap());
+        }
+        Response {
+            status,
+            headers,
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_expected_response() {
+        let expected = response(StatusCode::OK, None, "success\n");
+        assert_eq!(classify_probe_response(&expected), ProbeResponse::NoPortal);
+    }
+
+    #[test]
+    fn test_redirect() {
+        let redirect = response(
+            StatusCode::FOUND,
+            Some("https://portal.example:8443/login?next=1"),
+            "",
+        );
+        assert_eq!(
+            classify_probe_response(&redirect),
+            ProbeResponse::Redirect(Uri::from_static("https://portal.example:8443/login?next=1"))
+        );
+
+        let relative = response(StatusCode::TEMPORARY_REDIRECT, Some("/login"), "");
+        assert_eq!(
+            classify_probe_response(&relative),
+            ProbeResponse::Redirect(Uri::from_static("http://detectportal.firefox.com/login"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_redirect() {
+        for location in [None, Some("ftp://portal.example/"), Some("login")] {
+            let redirect = response(StatusCode::FOUND, location, "");
+            assert_eq!(classify_probe_response(&redirect), ProbeResponse::NoPortal);
+        }
+        let not_modified = response(StatusCode::NOT_MODIFIED, None, "");
+        assert_eq!(
+            classify_probe_response(&not_modified),
+            ProbeResponse::NoPortal
+        );
+    }
+
+    #[test]
+    fn test_intercepted() {
+        let portal = response(StatusCode::OK, None, "<html>Log in</html>");
+        assert_eq!(classify_probe_response(&portal), ProbeResponse::Intercepted);
+
+        let auth_required = response(StatusCode::NETWORK_AUTHENTICATION_REQUIRED, None, "");
+        assert_eq!(
+            classify_probe_response(&auth_required),
+            ProbeResponse::Intercepted
+        );
+    }
+
+    /// Errors and empty responses do not show that there is a portal
+    #[test]
+    fn test_other_responses() {
+        for status in [
+            StatusCode::NOT_FOUND,
+            StatusCode::FORBIDDEN,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_GATEWAY,
+            StatusCode::NO_CONTENT,
+        ] {
+            let error = response(status, None, "<html>Error</html>");
+            assert_eq!(classify_probe_response(&error), ProbeResponse::NoPortal);
+        }
+        let empty = response(StatusCode::OK, None, "");
+        assert_eq!(classify_probe_response(&empty), ProbeResponse::NoPortal);
+    }
+}
@@ -1,5 +1,8 @@
-use clap::{Args, Parser};
+use clap::{Args, Parser, ValueEnum};
+use mullvad_types::settings::LogSinks;
 use once_cell::sync::Lazy;
+#[cfg(not(target_os = "android"))]
+use std::net::SocketAddr;
 
 static ENV_DESC: Lazy<String> = Lazy::new(|| {
     format!(
@@ -35,11 +38,35 @@ struct Cli {
     /// Don't log timestamps when logging to stdout, useful when running as a systemd service
     #[arg(long)]
     disable_stdout_timestamps: bool,
+    /// Also send logs to this destination. Can be repeated. Sinks can also be enabled in the
+    /// settings
+    #[arg(long, value_enum, value_name = "SINK")]
+    log_sink: Vec<LogSink>,
+    /// Serve the management interface as HTTP/JSON on this loopback address, e.g.
+    /// 127.0.0.1:8080. Clients must authenticate using the token in the settings directory
+    #[cfg(not(target_os = "android"))]
+    #[arg(long, value_name = "ADDRESS")]
+    http_gateway: Option<SocketAddr>,
+    /// Serve Prometheus metrics on this loopback address, e.g. 127.0.0.1:9100, at /metrics
+    #[cfg(not(target_os = "android"))]
+    #[arg(long, value_name = "ADDRESS")]
+    metrics: Option<SocketAddr>,
 
     #[command(flatten)]
     command: CommandFlags,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogSink {
+    /// systemd-journald, with structured fields
+    #[cfg(target_os = "linux")]
+    Journald,
+    /// The local syslog daemon, formatted according to RFC 5424
+    Syslog,
+    /// A file with one JSON object per line, next to the log file
+    Json,
+}
+
 #[derive(Debug, Args)]
 #[group(multiple = false, required = false)]
 pub struct CommandFlags {
@@ -69,6 +96,11 @@ pub struct Config {
     pub log_level: log::LevelFilter,
     pub log_to_file: bool,
     pub log_stdout_timestamps: bool,
+    pub log_sinks: LogSinks,
+    #[cfg(not(target_os = "android"))]
+    pub http_gateway: Option<SocketAddr>,
+    #[cfg(not(target_os = "android"))]
+    pub metrics: Option<SocketAddr>,
 
     pub command: Command,
 }
@@ -136,6 +168,18 @@ fn create_config() -> Config {
         log_level,
         log_to_file: !app.disable_log_to_file,
         log_stdout_timestamps: !app.disable_stdout_timestamps,
+        log_sinks: LogSinks {
+            #[cfg(target_os = "linux")]
+            journald: app.log_sink.contains(&LogSink::Journald),
+            #[cfg(not(target_os = "linux"))]
+            journald: false,
+            syslog: app.log_sink.contains(&LogSink::Syslog),
+            json: app.log_sink.contains(&LogSink::Json),
+        },
+        #[cfg(not(target_os = "android"))]
+        http_gateway: app.http_gateway,
+        #[cfg(not(target_os = "android"))]
+        metrics: app.metrics,
         command: app.command.into(),
     }
 }
@@ -0,0 +1,82 @@
+use crate::{new_selector_config, Daemon, Error, EventListener};
+use mullvad_types::custom_relay::{self, CustomRelay};
+
+impl<L> Daemon<L>
+where
+    L: EventListener,
+{
+    /// Add a self-hosted WireGuard server to the relay list.
+    ///
+    /// Returns an error if the relay is incomplete, or if its name is already used by another
+    /// relay.
+    pub async fn add_custom_relay(&mut self, new_relay: CustomRelay) -> Result<(), Error> {
+        // Custom relays are identified by hostname, so they must not shadow a Mullvad relay
+        let shadows_relay = self
+            .relay_selector
+            .get_relays()
+            .relays()
+            .any(|relay| relay.hostname.eq_ignore_ascii_case(&new_relay.name));
+
+        let settings_changed = self
+            .settings
+            .try_update(|settings| {
+                new_relay.validate()?;
+                if shadows_relay
+                    || settings
+                        .custom_relays
+                        .iter()
+                        .any(|relay| relay.name.eq_ignore_ascii_case(&new_relay.name))
+                {
+                    return Err(custom_relay::Error::DuplicateName);
+                }
+                settings.custom_relays.push(new_relay);
+                Ok(())
+            })
+            .await
+            .map_err(Error::SettingsError)?;
+
+        if settings_changed {
+            self.relay_selector
+                .set_config(new_selector_config(&self.settings));
+        }
+        Ok(())
+    }
+
+    /// Remove a custom relay.
+    ///
+    /// Returns an error if there is no custom relay with the given name.
+    pub async fn remove_custom_relay(&mut self, name: String) -> Result<(), Error> {
+        let settings_changed = self
+            .settings
+            .try_update(|settings| {
+                let index = settings
+                    .custom_relays
+                    .iter()
+                    .position(|relay| relay.name == name)
+                    .ok_or(custom_relay::Error::NotFound)?;
+                settings.custom_relays.remove(index);
+                Ok(())
+            })
+            .await
+            .map_err(Error::SettingsError)?;
+
+        if settings_changed {
+            self.relay_selector
+                .set_config(new_selector_config(&self.settings));
+
+            let in_use = self
+                .parameters_generator
+                .get_last_location()
+                .await
+                .is_some_and(|location| {
+                    location.hostname.as_deref() == Some(name.as_str())
+                        || location.entry_hostname.as_deref() == Some(name.as_str())
+                });
+            if in_use {
+                log::info!("Initiating tunnel restart because the custom relay in use was removed");
+                self.reconnect_tunnel();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+//! Keeps a persistent record of the data transferred through the tunnel, and enforces the monthly
+//! data cap in the settings.
+//!
+//! The byte counters of the tunnel are read periodically and whenever the tunnel state changes.
+//! They are also read once more when the tunnel is stopped, and when reconfiguring it resets
+//! them, e.g. when its ephemeral peer is renegotiated. The difference since the last reading is
+//! attributed to the relay that the tunnel was connecting to, on the current day in local time.
+
+use crate::{Daemon, DaemonEventSender, EventListener, InternalDaemonEvent};
+use chrono::{Local, NaiveDate};
+use futures::future::{abortable, AbortHandle};
+use mullvad_types::{
+    data_usage::{first_day_of_month, ByteCount, DataCap, DataCapReached, DataUsage},
+    states::TargetState,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use talpid_core::mpsc::Sender;
+use talpid_types::ErrorExt;
+use talpid_wireguard::stats::StatsMap;
+use tokio::{fs, io};
+
+const DATA_USAGE_FILE: &str = "data-usage.json";
+
+/// How often the byte counters of the tunnel are read.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Number of days for which the daily usage is kept.
+const RETENTION_DAYS: u64 = 400;
+
+/// Name that traffic is attributed to if the relay is unknown.
+const UNKNOWN_RELAY: &str = "unknown";
+
+/// Sent when the byte counters of the tunnel should be read.
+pub struct DataUsageTimerEvent;
+
+impl From<DataUsageTimerEvent> for InternalDaemonEvent {
+    fn from(_: DataUsageTimerEvent) -> Self {
+        InternalDaemonEvent::DataUsageTimer
+    }
+}
+
+/// The contents of [`DATA_USAGE_FILE`].
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct StoredUsage {
+    usage: DataUsage,
+    /// The first day of the most recent month in which the data cap was reached.
+    cap_reached: Option<NaiveDate>,
+}
+
+pub struct DataUsageTracker {
+    stored: StoredUsage,
+    cache_path: PathBuf,
+    /// Byte counters of each peer when they were last read.
+    last_counters: HashMap<[u8; 32], ByteCount>,
+    /// Hostname of the relay that the counters belong to.
+    relay: Option<String>,
+    timer: AbortHandle,
+}
+
+impl DataUsageTracker {
+    pub async fn new(cache_dir: &Path, event_tx: &DaemonEventSender) -> Self {
+        let cache_path = cache_dir.join(DATA_USAGE_FILE);
+        let stored = match fs::read(&cache_path).await {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse data usage")
+                );
+                StoredUsage::default()
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => StoredUsage::default(),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read data usage")
+                );
+                StoredUsage::default()
+            }
+        };
+
+        let (timer, timer_handle) =
+            abortable(sample_periodically(event_tx.to_specialized_sender()));
+        tokio::spawn(timer);
+
+        DataUsageTracker {
+            stored,
+            cache_path,
+            last_counters: HashMap::new(),
+            relay: None,
+            timer: timer_handle,
+        }
+    }
+
+    /// Attribute data transferred from now on to `relay`. The data of the previous tunnel must
+    /// have been recorded before this is called.
+    pub fn set_relay(&mut self, relay: Option<String>) {
+        self.relay = relay;
+    }
+
+    pub fn usage(&self) -> &DataUsage {
+        &self.stored.usage
+    }
+
+    /// Attribute the data transferred since the counters were last read to the current relay.
+    /// `final_stats` are the last stats of tunnels that have been stopped or whose counters were
+    /// reset by reconfiguring them, and `stats` those of the running tunnel, if there is one.
+    /// Returns whether any data was transferred.
+    fn record(
+        &mut self,
+        final_stats: Vec<StatsMap>,
+        stats: Option<StatsMap>,
+        today: NaiveDate,
+    ) -> bool {
+        let mut transferred = false;
+        for stats in final_stats {
+            transferred |= self.record_tunnel(stats, today);
+            // The next tunnel starts counting from zero
+            self.last_counters.clear();
+        }
+        match stats {
+            Some(stats) => transferred |= self.record_tunnel(stats, today),
+            None => self.last_counters.clear(),
+        }
+        transferred
+    }
+
+    fn record_tunnel(&mut self, stats: StatsMap, today: NaiveDate) -> bool {
+        let mut transferred = ByteCount::default();
+        let mut counters = HashMap::with_capacity(stats.len());
+        for (peer, stats) in stats {
+            let last = self.last_counters.get(&peer).copied().unwrap_or_default();
+            // Should the counters have been reset without their last values being recorded,
+            // only the data transferred since the reset is known
+            let since_last = |current: u64, last: u64| current.checked_sub(last).unwrap_or(current);
+            transferred += ByteCount {
+                rx_bytes: since_last(stats.rx_bytes, last.rx_bytes),
+                tx_bytes: since_last(stats.tx_bytes, last.tx_bytes),
+            };
+            counters.insert(
+                peer,
+                ByteCount {
+                    rx_bytes: stats.rx_bytes,
+                    tx_bytes: stats.tx_bytes,
+                },
+            );
+        }
+        self.last_counters = counters;
+
+        if transferred.total() == 0 {
+            return false;
+        }
+        let relay = self.relay.as_deref().unwrap_or(UNKNOWN_RELAY);
+        self.stored.usage.add(today, relay, transferred);
+        self.stored
+            .usage
+            .prune(today - chrono::Days::new(RETENTION_DAYS));
+        true
+    }
+
+    /// Return the usage of this month if it has reached the quota of `cap`, unless that has
+    /// already been reported this month.
+    fn check_cap(&mut self, cap: &DataCap, today: NaiveDate) -> Option<(u64, u64)> {
+        let quota = cap.monthly_quota?;
+        let month = first_day_of_month(today);
+        if self.stored.cap_reached == Some(month) {
+            return None;
+        }
+        let usage = self.stored.usage.month_total(today).total();
+        if usage < quota {
+            return None;
+        }
+        self.stored.cap_reached = Some(month);
+        Some((usage, quota))
+    }
+
+    /// Return whether `cap` has disconnected the tunnel this month. The tunnel should then only be
+    /// connected by the user, and not automatically, e.g. by a schedule rule.
+    pub fn is_disconnected_by_cap(&self, cap: &DataCap, today: NaiveDate) -> bool {
+        cap.disconnect && self.stored.cap_reached == Some(first_day_of_month(today))
+    }
+
+    /// Allow the data cap to be reached again this month, e.g. because it has changed.
+    pub fn reset_cap(&mut self) {
+        self.stored.cap_reached = None;
+    }
+
+    async fn save(&self) {
+        match serde_json::to_vec(&self.stored) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.cache_path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write data usage")
+                    );
+                }
+            }
+            Err(error) => log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to serialize data usage")
+            ),
+        }
+    }
+}
+
+impl Drop for DataUsageTracker {
+    fn drop(&mut self) {
+        self.timer.abort();
+    }
+}
+
+async fn sample_periodically(event_tx: DaemonEventSender<DataUsageTimerEvent>) {
+    loop {
+        talpid_time::sleep(SAMPLE_INTERVAL).await;
+        if event_tx.send(DataUsageTimerEvent).is_err() {
+            break;
+        }
+    }
+}
+
+impl<L> Daemon<L>
+where
+    L: EventListener,
+{
+    /// Read the byte counters of the tunnel and update the data usage. If the monthly quota is
+    /// reached, clients are notified, and the tunnel is disconnected if the cap says so.
+    pub(crate) async fn update_data_usage(&mut self) {
+        let today = Local::now().date_naive();
+
+        // Take the final stats first, so that they are not mistaken for those of a new tunnel
+        let final_stats = self.tunnel_stats.take_final();
+        let stats = self.tunnel_stats.current();
+        if !self.data_usage.record(final_stats, stats, today) {
+            return;
+        }
+
+        if let Some((usage, quota)) = self.data_usage.check_cap(&self.settings.data_cap, today) {
+            let disconnect = self.settings.data_cap.disconnect;
+            log::warn!("Monthly data cap reached: {usage} of {quota} bytes used");
+            if disconnect {
+                log::info!("Disconnecting since the monthly data cap has been reached");
+                self.set_target_state(TargetState::Unsecured).await;
+            }
+            self.event_listener.notify_data_cap_reached(DataCapReached {
+                usage,
+                quota,
+                disconnected: disconnect,
+            });
+        }
+        self.data_usage.save().await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_wireguard::stats::Stats;
+
+    fn stats(rx_bytes: u64, tx_bytes: u64) -> Option<StatsMap> {
+        let stats = Stats {
+            rx_bytes,
+            tx_bytes,
+            last_handshake: None,
+        };
+        Some(StatsMap::from([([0u8; 32], stats)]))
+    }
+
+    #[test]
+    fn test_record_and_cap() {
+        let (timer, timer_handle) = abortable(futures::future::pending::<()>());
+        drop(timer);
+        let mut tracker = DataUsageTracker {
+            stored: StoredUsage::default(),
+            cache_path: PathBuf::new(),
+            last_counters: HashMap::new(),
+            relay: Some("se-got-wg-001".to_owned()),
+            timer: timer_handle,
+        };
+        let today = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let cap = DataCap {
+            monthly_quota: Some(1000),
+            disconnect: true,
+        };
+
+        assert!(tracker.record(vec![], stats(300, 100), today));
+        assert!(!tracker.record(vec![], stats(300, 100), today));
+        assert_eq!(tracker.check_cap(&cap, today), None);
+        assert!(!tracker.is_disconnected_by_cap(&cap, today));
+
+        // A new tunnel starts counting from zero
+        assert!(!tracker.record(vec![], None, today));
+        assert!(tracker.record(vec![], stats(500, 100), today));
+        assert_eq!(tracker.usage().month_total(today).total(), 1000);
+
+        assert_eq!(tracker.check_cap(&cap, today), Some((1000, 1000)));
+        assert_eq!(tracker.check_cap(&cap, today), None);
+        assert!(tracker.is_disconnected_by_cap(&cap, today));
+
+        // The cap only applies to the month that it was reached in
+        let next_month = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        assert!(!tracker.is_disconnected_by_cap(&cap, next_month));
+        let notify_only = DataCap {
+            disconnect: false,
+            ..cap
+        };
+        assert!(!tracker.is_disconnected_by_cap(&notify_only, today));
+    }
+
+    #[test]
+    fn test_record_final_stats() {
+        let (timer, timer_handle) = abortable(futures::future::pending::<()>());
+        drop(timer);
+        let mut tracker = DataUsageTracker {
+            stored: StoredUsage::default(),
+            cache_path: PathBuf::new(),
+            last_counters: HashMap::new(),
+            relay: Some("se-got-wg-001".to_owned()),
+            timer: timer_handle,
+        };
+        let today = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+
+        assert!(tracker.record(vec![], stats(100, 100), today));
+        // The final stats of the stopped tunnel are recorded before those of the new one
+        let final_stats = stats(300, 100).unwrap();
+        assert!(tracker.record(vec![final_stats], stats(50, 50), today));
+        assert_eq!(tracker.usage().month_total(today).total(), 500);
+
+        // Data of the new tunnel is attributed to the new relay
+        tracker.set_relay(Some("de-fra-wg-001".to_owned()));
+        assert!(tracker.record(vec![], stats(150, 50), today));
+        let relays = &tracker.usage().days[&today];
+        assert_eq!(relays["se-got-wg-001"].total(), 500);
+        assert_eq!(relays["de-fra-wg-001"].total(), 100);
+    }
+}
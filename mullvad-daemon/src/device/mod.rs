@@ -11,13 +11,14 @@ use mullvad_types::{
     account::{AccountToken, VoucherSubmission},
     device::{
         AccountAndDevice, Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceName, DeviceState,
+        LastConnection,
     },
     wireguard::{self, RotationInterval, WireguardData},
 };
 
 use std::{
     future::Future,
-    path::Path,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -42,6 +43,9 @@ pub(crate) use service::{AccountService, DeviceService};
 /// File that used to store account and device data.
 const DEVICE_CACHE_FILENAME: &str = "device.json";
 
+/// File that stores the devices of accounts that have been switched away from.
+const STORED_DEVICES_FILENAME: &str = "stored-devices.json";
+
 /// How long to keep the known status for [AccountManagerHandle::validate_device].
 const VALIDITY_CACHE_TIMEOUT: Duration = Duration::from_secs(10);
 
@@ -189,6 +193,9 @@ pub struct PrivateDevice {
     // no longer need to be supported.
     #[serde(default = "Utc::now")]
     pub created: DateTime<Utc>,
+    /// The most recent connection made using this device. This is only known locally.
+    #[serde(default)]
+    pub last_connection: Option<LastConnection>,
 }
 
 impl PrivateDevice {
@@ -207,6 +214,7 @@ impl PrivateDevice {
             wg_data,
             hijack_dns: device.hijack_dns,
             created: device.created,
+            last_connection: None,
         })
     }
 
@@ -232,10 +240,21 @@ impl From<PrivateDevice> for Device {
             name: device.name,
             hijack_dns: device.hijack_dns,
             created: device.created,
+            pubkey_created: Some(device.wg_data.created),
+            last_connection: device.last_connection,
         }
     }
 }
 
+/// Device of an account that is not currently in use. It is kept so that the account can be
+/// switched back to without registering a new device.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub struct StoredDevice {
+    pub data: PrivateAccountAndDevice,
+    /// Last known expiry of the account.
+    pub expiry: Option<DateTime<Utc>>,
+}
+
 #[derive(Clone)]
 pub(crate) enum AccountEvent {
     /// Emitted when the device state changes.
@@ -298,8 +317,12 @@ type ResponseTx<T> = oneshot::Sender<Result<T, Error>>;
 
 enum AccountManagerCommand {
     Login(AccountToken, ResponseTx<()>),
+    Switch(AccountToken, bool, ResponseTx<()>),
+    ForgetStoredDevice(AccountToken, ResponseTx<()>),
+    ForgetAllStoredDevices(ResponseTx<()>),
     Logout(ResponseTx<()>),
     SetData(PrivateAccountAndDevice, ResponseTx<()>),
+    RecordConnection(LastConnection, ResponseTx<()>),
     GetData(ResponseTx<PrivateDeviceState>),
     GetDataAfterLogin(ResponseTx<PrivateDeviceState>),
     RotateKey(ResponseTx<()>),
@@ -327,6 +350,25 @@ impl AccountManagerHandle {
             .await
     }
 
+    /// Switch to another account, reusing its stored device if there is a valid one. If
+    /// `keep_current` is true, the current device is stored so that it can be switched back to.
+    pub async fn switch(&self, token: AccountToken, keep_current: bool) -> Result<(), Error> {
+        self.send_command(|tx| AccountManagerCommand::Switch(token, keep_current, tx))
+            .await
+    }
+
+    /// Remove the stored device of an account that is not in use.
+    pub async fn forget_stored_device(&self, token: AccountToken) -> Result<(), Error> {
+        self.send_command(|tx| AccountManagerCommand::ForgetStoredDevice(token, tx))
+            .await
+    }
+
+    /// Remove all stored devices, e.g. when the app is reset.
+    pub async fn forget_all_stored_devices(&self) -> Result<(), Error> {
+        self.send_command(AccountManagerCommand::ForgetAllStoredDevices)
+            .await
+    }
+
     pub async fn logout(&self) -> Result<(), Error> {
         self.send_command(AccountManagerCommand::Logout).await
     }
@@ -336,6 +378,12 @@ impl AccountManagerHandle {
             .await
     }
 
+    /// Remember the most recent connection made using the current device.
+    pub async fn record_connection(&self, connection: LastConnection) -> Result<(), Error> {
+        self.send_command(|tx| AccountManagerCommand::RecordConnection(connection, tx))
+            .await
+    }
+
     pub async fn data(&self) -> Result<PrivateDeviceState, Error> {
         self.send_command(AccountManagerCommand::GetData).await
     }
@@ -405,6 +453,7 @@ pub(crate) struct AccountManager {
     account_service: AccountService,
     device_service: DeviceService,
     data: PrivateDeviceState,
+    expiry: Option<DateTime<Utc>>,
     rotation_interval: RotationInterval,
     listeners: Vec<Box<dyn Sender<AccountEvent> + Send>>,
     last_validation: Option<SystemTime>,
@@ -437,6 +486,7 @@ impl AccountManager {
             account_service: account_service.clone(),
             device_service: device_service.clone(),
             data: data.clone(),
+            expiry: None,
             rotation_interval: initial_rotation_interval,
             listeners: vec![Box::new(listener_tx)],
             last_validation: None,
@@ -482,6 +532,23 @@ impl AccountManager {
                                 .generate_for_account(token);
                             current_api_call.set_login(Box::pin(job), tx);
                         }
+                        Some(AccountManagerCommand::Switch(token, keep_current, tx)) => {
+                            match self.switch_call(token, keep_current).await {
+                                Ok(Some(job)) => current_api_call.set_login(Box::pin(job), tx),
+                                Ok(None) => {
+                                    let _ = tx.send(Ok(()));
+                                }
+                                Err(err) => {
+                                    let _ = tx.send(Err(err));
+                                }
+                            }
+                        }
+                        Some(AccountManagerCommand::ForgetStoredDevice(token, tx)) => {
+                            let _ = tx.send(self.forget_stored_device(token).await);
+                        }
+                        Some(AccountManagerCommand::ForgetAllStoredDevices(tx)) => {
+                            let _ = tx.send(self.forget_all_stored_devices().await);
+                        }
                         Some(AccountManagerCommand::Logout(tx)) => {
                             current_api_call.clear();
                             self.logout(tx).await;
@@ -489,6 +556,9 @@ impl AccountManager {
                         Some(AccountManagerCommand::SetData(data, tx)) => {
                             let _ = tx.send(self.set(PrivateDeviceEvent::Login(data)).await);
                         }
+                        Some(AccountManagerCommand::RecordConnection(connection, tx)) => {
+                            let _ = tx.send(self.record_connection(connection).await);
+                        }
                         Some(AccountManagerCommand::GetData(tx)) => {
                             let _ = tx.send(Ok(self.data.clone()));
                         }
@@ -745,6 +815,8 @@ impl AccountManager {
     ) {
         match &response {
             Ok(submission) => {
+                self.expiry = Some(submission.new_expiry);
+
                 // Send expiry update event
                 let event = AccountEvent::Expiry(submission.new_expiry);
                 self.listeners
@@ -769,6 +841,7 @@ impl AccountManager {
                 } else {
                     log::debug!("Account has no time left");
                 }
+                self.expiry = Some(expiry);
 
                 // Send expiry update event
                 let event = AccountEvent::Expiry(expiry);
@@ -1026,6 +1099,16 @@ impl AccountManager {
         }
     }
 
+    /// Update the last connection of the current device. Since this is not a change that clients
+    /// care about, it is saved to disk without emitting a device event.
+    async fn record_connection(&mut self, connection: LastConnection) -> Result<(), Error> {
+        let PrivateDeviceState::LoggedIn(ref mut config) = self.data else {
+            return Err(Error::NoDevice);
+        };
+        config.device.last_connection = Some(connection);
+        self.cacher.write(&self.data).await
+    }
+
     async fn set(&mut self, event: PrivateDeviceEvent) -> Result<(), Error> {
         let device_state = event.clone().state();
         if device_state == self.data {
@@ -1036,17 +1119,120 @@ impl AccountManager {
         self.last_validation = None;
 
         if let Some(old_config) = self.data.logout() {
-            if device_state.device().map(|d| &d.device.id) != Some(&old_config.device.id) {
+            if device_state.device().map(|d| &d.device.id) != Some(&old_config.device.id)
+                && !self.cacher.is_stored(&old_config.device.id)
+            {
                 tokio::spawn(self.logout_api_call(old_config));
             }
         }
 
+        let expiry = match device_state.device() {
+            Some(config) => self.take_stored_device(config).await,
+            None => None,
+        };
+        self.expiry = expiry;
+
         self.data = device_state;
 
         let event = AccountEvent::Device(event);
         self.listeners
             .retain(|listener| listener.send(event.clone()).is_ok());
 
+        if let Some(expiry) = expiry {
+            let event = AccountEvent::Expiry(expiry);
+            self.listeners
+                .retain(|listener| listener.send(event.clone()).is_ok());
+        }
+
+        Ok(())
+    }
+
+    /// Remove the stored device of the account that `config` belongs to, since it is now in use.
+    /// If `config` is a different device, the stored one is removed from the account. Returns the
+    /// cached account expiry if the stored device is the one in use.
+    async fn take_stored_device(
+        &mut self,
+        config: &PrivateAccountAndDevice,
+    ) -> Option<DateTime<Utc>> {
+        match self.cacher.remove_stored(&config.account_token).await {
+            Ok(Some(stored)) if stored.data.device.id == config.device.id => stored.expiry,
+            Ok(Some(stored)) => {
+                tokio::spawn(self.logout_api_call(stored.data));
+                None
+            }
+            Ok(None) => None,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to update stored devices")
+                );
+                None
+            }
+        }
+    }
+
+    /// Set the current device aside if `keep_current` is true, and return a job that restores the
+    /// stored device of the account identified by `token`. A new device is registered if there is
+    /// no stored device, or if it has been removed from the account. Returns `None` if the
+    /// account is already in use.
+    async fn switch_call(
+        &mut self,
+        token: AccountToken,
+        keep_current: bool,
+    ) -> Result<
+        Option<impl Future<Output = Result<PrivateAccountAndDevice, Error>> + Send + 'static>,
+        Error,
+    > {
+        if let Some(current) = self.data.device() {
+            if current.account_token == token {
+                return Ok(None);
+            }
+            if keep_current {
+                let stored = StoredDevice {
+                    data: current.clone(),
+                    expiry: self.expiry,
+                };
+                self.cacher.store(stored).await?;
+            }
+        }
+
+        let stored = self.cacher.stored_device(&token).cloned();
+        let device_service = self.device_service.clone();
+        Ok(Some(async move {
+            if let Some(StoredDevice { mut data, .. }) = stored {
+                match device_service
+                    .get(token.clone(), data.device.id.clone())
+                    .await
+                {
+                    Ok(device) => {
+                        data.device.update(device)?;
+                        return Ok(data);
+                    }
+                    Err(Error::InvalidDevice) => {
+                        log::info!("Stored device has been removed. Creating a new device");
+                    }
+                    Err(error) if error.is_network_error() => {
+                        log::warn!("Failed to validate stored device. Using it anyway");
+                        return Ok(data);
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            device_service.generate_for_account(token).await
+        }))
+    }
+
+    async fn forget_stored_device(&mut self, token: AccountToken) -> Result<(), Error> {
+        if let Some(stored) = self.cacher.remove_stored(&token).await? {
+            tokio::spawn(self.logout_api_call(stored.data));
+        }
+        Ok(())
+    }
+
+    async fn forget_all_stored_devices(&mut self) -> Result<(), Error> {
+        for stored in self.cacher.remove_all_stored().await? {
+            tokio::spawn(self.logout_api_call(stored.data));
+        }
         Ok(())
     }
 
@@ -1145,6 +1331,8 @@ impl AccountManager {
 pub struct DeviceCacher {
     file: io::BufWriter<fs::File>,
     path: std::path::PathBuf,
+    stored_path: PathBuf,
+    stored: Vec<StoredDevice>,
 }
 
 impl DeviceCacher {
@@ -1182,9 +1370,30 @@ impl DeviceCacher {
             PrivateDeviceState::LoggedOut
         };
 
+        let stored_path = settings_dir.join(STORED_DEVICES_FILENAME);
+        let stored = match fs::read(&stored_path).await {
+            Ok(buffer) => serde_json::from_slice(&buffer).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse stored devices")
+                );
+                vec![]
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read stored devices")
+                );
+                vec![]
+            }
+        };
+
         let mut store = DeviceCacher {
             file: io::BufWriter::new(file),
             path,
+            stored_path,
+            stored,
         };
 
         if should_save {
@@ -1211,7 +1420,7 @@ impl DeviceCacher {
     }
 
     pub async fn write(&mut self, device: &PrivateDeviceState) -> Result<(), Error> {
-        let data = serde_json::to_vec_pretty(&device).unwrap();
+        let data = serde_json::to_vec_pretty(&device)?;
 
         self.file.get_mut().set_len(0).await?;
         self.file.seek(io::SeekFrom::Start(0)).await?;
@@ -1222,9 +1431,73 @@ impl DeviceCacher {
         Ok(())
     }
 
+    /// Return the stored device of the given account, if there is one.
+    pub fn stored_device(&self, token: &AccountToken) -> Option<&StoredDevice> {
+        self.stored
+            .iter()
+            .find(|stored| &stored.data.account_token == token)
+    }
+
+    /// Return whether the given device is stored.
+    pub fn is_stored(&self, id: &DeviceId) -> bool {
+        self.stored
+            .iter()
+            .any(|stored| &stored.data.device.id == id)
+    }
+
+    /// Store a device, replacing any stored device of the same account.
+    pub async fn store(&mut self, device: StoredDevice) -> Result<(), Error> {
+        self.stored
+            .retain(|stored| stored.data.account_token != device.data.account_token);
+        self.stored.push(device);
+        self.write_stored().await
+    }
+
+    /// Remove and return the stored device of the given account.
+    pub async fn remove_stored(
+        &mut self,
+        token: &AccountToken,
+    ) -> Result<Option<StoredDevice>, Error> {
+        let Some(index) = self
+            .stored
+            .iter()
+            .position(|stored| &stored.data.account_token == token)
+        else {
+            return Ok(None);
+        };
+        let device = self.stored.remove(index);
+        self.write_stored().await?;
+        Ok(Some(device))
+    }
+
+    /// Remove and return all stored devices, and delete the file that they are stored in.
+    pub async fn remove_all_stored(&mut self) -> Result<Vec<StoredDevice>, Error> {
+        match fs::remove_file(&self.stored_path).await {
+            Ok(()) => (),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => return Err(error.into()),
+        }
+        Ok(std::mem::take(&mut self.stored))
+    }
+
+    async fn write_stored(&self) -> Result<(), Error> {
+        let data = serde_json::to_vec_pretty(&self.stored)?;
+
+        let mut file = fs::OpenOptions::from(Self::file_options())
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.stored_path)
+            .await?;
+        file.write_all(&data).await?;
+        file.sync_data().await?;
+
+        Ok(())
+    }
+
     pub async fn remove(self) -> Result<(), Error> {
         let path = {
-            let DeviceCacher { path, file } = self;
+            let DeviceCacher { path, file, .. } = self;
             let std_file = file.into_inner().into_std().await;
             let _ = tokio::task::spawn_blocking(move || drop(std_file)).await;
             path
@@ -1374,7 +1647,10 @@ mod test {
     };
     use talpid_types::tunnel::TunnelStateTransition;
 
-    use super::{Error, TunnelStateChangeHandler, WG_DEVICE_CHECK_THRESHOLD};
+    use super::{
+        DeviceCacher, Error, TunnelStateChangeHandler, STORED_DEVICES_FILENAME,
+        WG_DEVICE_CHECK_THRESHOLD,
+    };
 
     const TIMEOUT_ERROR: Error = Error::OtherRestError(mullvad_api::rest::Error::TimeoutError);
 
@@ -1459,4 +1735,19 @@ mod test {
             "device check should no longer happen after successful check"
         );
     }
+
+    /// Removing all stored devices must not leave the stored devices file behind
+    #[tokio::test]
+    async fn test_remove_all_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let stored_path = dir.path().join(STORED_DEVICES_FILENAME);
+        tokio::fs::write(&stored_path, b"[]").await.unwrap();
+
+        let (mut cacher, _device) = DeviceCacher::new(dir.path()).await.unwrap();
+        assert!(cacher.remove_all_stored().await.unwrap().is_empty());
+        assert!(!stored_path.exists());
+
+        // Removing stored devices when there are none is not an error
+        cacher.remove_all_stored().await.unwrap();
+    }
 }
@@ -0,0 +1,260 @@
+//! Builds WireGuard handshake initiation messages, so that relays can be probed without setting up
+//! a tunnel. See section 5.4.2 of the WireGuard whitepaper for the message format.
+
+use blake2::{
+    digest::{consts::U16, FixedOutput, KeyInit, Update},
+    Blake2s256, Blake2sMac, Digest,
+};
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+use talpid_types::net::wireguard::{PrivateKey, PublicKey};
+use x25519_dalek::StaticSecret;
+
+const CONSTRUCTION: &[u8] = b"Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s";
+const IDENTIFIER: &[u8] = b"WireGuard v1 zx2c4 Jason@zx2c4.com";
+const LABEL_MAC1: &[u8] = b"mac1----";
+
+const HANDSHAKE_INITIATION: u8 = 1;
+const HANDSHAKE_RESPONSE: u8 = 2;
+const COOKIE_REPLY: u8 = 3;
+
+const INITIATION_LEN: usize = 148;
+const RESPONSE_LEN: usize = 92;
+const COOKIE_REPLY_LEN: usize = 64;
+
+/// Offset of the TAI64N label from the Unix epoch, as used by WireGuard.
+const TAI64_EPOCH: u64 = 0x400000000000000a;
+
+type HmacBlake2s = hmac::SimpleHmac<Blake2s256>;
+
+/// A handshake initiation to send to a peer.
+pub struct Initiation {
+    message: [u8; INITIATION_LEN],
+    sender_index: u32,
+}
+
+impl Initiation {
+    /// Create a handshake initiation from `private_key` to the peer with `peer_public_key`.
+    pub fn new(private_key: &PrivateKey, peer_public_key: &PublicKey, now: SystemTime) -> Self {
+        let ephemeral = StaticSecret::random();
+        let static_secret = StaticSecret::from(private_key.to_bytes());
+        let static_public = x25519_dalek::PublicKey::from(&static_secret);
+        let peer_public = x25519_dalek::PublicKey::from(*peer_public_key.as_bytes());
+        let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral);
+        let sender_index = u32::from_le_bytes(ephemeral_public.as_bytes()[..4].try_into().unwrap());
+
+        let chaining_key = hash(&[CONSTRUCTION]);
+        let handshake_hash = hash(&[&chaining_key, IDENTIFIER]);
+        let handshake_hash = hash(&[&handshake_hash, peer_public.as_bytes()]);
+
+        let chaining_key = kdf1(&chaining_key, ephemeral_public.as_bytes());
+        let handshake_hash = hash(&[&handshake_hash, ephemeral_public.as_bytes()]);
+
+        let (chaining_key, key) = kdf2(
+            &chaining_key,
+            ephemeral.diffie_hellman(&peer_public).as_bytes(),
+        );
+        let encrypted_static = seal(&key, static_public.as_bytes(), &handshake_hash);
+        let handshake_hash = hash(&[&handshake_hash, &encrypted_static]);
+
+        let (_chaining_key, key) = kdf2(
+            &chaining_key,
+            static_secret.diffie_hellman(&peer_public).as_bytes(),
+        );
+        let encrypted_timestamp = seal(&key, &tai64n(now), &handshake_hash);
+
+        let mut message = [0u8; INITIATION_LEN];
+        message[0] = HANDSHAKE_INITIATION;
+        message[4..8].copy_from_slice(&sender_index.to_le_bytes());
+        message[8..40].copy_from_slice(ephemeral_public.as_bytes());
+        message[40..88].copy_from_slice(&encrypted_static);
+        message[88..116].copy_from_slice(&encrypted_timestamp);
+        let mac1 = mac(
+            &hash(&[LABEL_MAC1, peer_public.as_bytes()]),
+            &message[..116],
+        );
+        message[116..132].copy_from_slice(&mac1);
+        // mac2 is only required when the peer is under load, and is left as zero
+
+        Initiation {
+            message,
+            sender_index,
+        }
+    }
+
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Return whether `packet` is a handshake response or a cookie reply to this initiation.
+    /// Either shows that the peer received the initiation.
+    pub fn is_answered_by(&self, packet: &[u8]) -> bool {
+        let receiver_index = match (packet.first(), packet.len()) {
+            (Some(&HANDSHAKE_RESPONSE), RESPONSE_LEN) => &packet[8..12],
+            (Some(&COOKIE_REPLY), COOKIE_REPLY_LEN) => &packet[4..8],
+            _ => return false,
+        };
+        receiver_index == self.sender_index.to_le_bytes()
+    }
+}
+
+fn hash(inputs: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Blake2s256::new();
+    for input in inputs {
+        Digest::update(&mut hasher, input);
+    }
+    hasher.finalize().into()
+}
+
+fn hmac(key: &[u8], inputs: &[&[u8]]) -> [u8; 32] {
+    let mut hmac = HmacBlake2s::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for input in inputs {
+        hmac.update(input);
+    }
+    hmac.finalize_fixed().into()
+}
+
+fn mac(key: &[u8], input: &[u8]) -> [u8; 16] {
+    let mut mac = Blake2sMac::<U16>::new_from_slice(key).expect("key is at most 32 bytes");
+    mac.update(input);
+    mac.finalize_fixed().into()
+}
+
+fn kdf1(chaining_key: &[u8], input: &[u8]) -> [u8; 32] {
+    let secret = hmac(chaining_key, &[input]);
+    hmac(&secret, &[&[1]])
+}
+
+fn kdf2(chaining_key: &[u8], input: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let secret = hmac(chaining_key, &[input]);
+    let first = hmac(&secret, &[&[1]]);
+    let second = hmac(&secret, &[&first, &[2]]);
+    (first, second)
+}
+
+/// Encrypt `plaintext` with a zero nonce, which is only used once per key during a handshake.
+fn seal(key: &[u8; 32], plaintext: &[u8], additional_data: &[u8]) -> Vec<u8> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(
+            Nonce::from_slice(&[0u8; 12]),
+            Payload {
+                msg: plaintext,
+                aad: additional_data,
+            },
+        )
+        .expect("encryption does not fail")
+}
+
+fn tai64n(now: SystemTime) -> [u8; 12] {
+    let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut timestamp = [0u8; 12];
+    timestamp[..8].copy_from_slice(&(TAI64_EPOCH + since_epoch.as_secs()).to_be_bytes());
+    timestamp[8..].copy_from_slice(&since_epoch.subsec_nanos().to_be_bytes());
+    timestamp
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn open(key: &[u8; 32], ciphertext: &[u8], additional_data: &[u8]) -> Vec<u8> {
+        ChaCha20Poly1305::new(Key::from_slice(key))
+            .decrypt(
+                Nonce::from_slice(&[0u8; 12]),
+                Payload {
+                    msg: ciphertext,
+                    aad: additional_data,
+                },
+            )
+            .unwrap()
+    }
+
+    /// Consume the initiation as the responder would, and check that the initiator's static key
+    /// and timestamp can be decrypted.
+    #[test]
+    fn test_initiation_is_accepted_by_responder() {
+        let initiator = PrivateKey::new_from_random();
+        let responder = PrivateKey::new_from_random();
+        let now = UNIX_EPOCH + Duration::new(1_700_000_000, 123);
+        let initiation = Initiation::new(&initiator, &responder.public_key(), now);
+        let message = initiation.message();
+
+        assert_eq!(message.len(), INITIATION_LEN);
+        assert_eq!(message[..4], [HANDSHAKE_INITIATION, 0, 0, 0]);
+        assert_eq!(message[132..], [0u8; 16]);
+
+        let responder_secret = StaticSecret::from(responder.to_bytes());
+        let responder_public = responder.public_key();
+        let mac1 = mac(
+            &hash(&[LABEL_MAC1, responder_public.as_bytes()]),
+            &message[..116],
+        );
+        assert_eq!(message[116..132], mac1);
+
+        let ephemeral_public =
+            x25519_dalek::PublicKey::from(<[u8; 32]>::try_from(&message[8..40]).unwrap());
+        let chaining_key = hash(&[CONSTRUCTION]);
+        let handshake_hash = hash(&[&chaining_key, IDENTIFIER]);
+        let handshake_hash = hash(&[&handshake_hash, responder_public.as_bytes()]);
+        let chaining_key = kdf1(&chaining_key, ephemeral_public.as_bytes());
+        let handshake_hash = hash(&[&handshake_hash, ephemeral_public.as_bytes()]);
+
+        let (chaining_key, key) = kdf2(
+            &chaining_key,
+            responder_secret
+                .diffie_hellman(&ephemeral_public)
+                .as_bytes(),
+        );
+        let initiator_public = open(&key, &message[40..88], &handshake_hash);
+        assert_eq!(initiator_public, initiator.public_key().as_bytes());
+        let handshake_hash = hash(&[&handshake_hash, &message[40..88]]);
+
+        let initiator_public =
+            x25519_dalek::PublicKey::from(<[u8; 32]>::try_from(&initiator_public[..]).unwrap());
+        let (_chaining_key, key) = kdf2(
+            &chaining_key,
+            responder_secret
+                .diffie_hellman(&initiator_public)
+                .as_bytes(),
+        );
+        let timestamp = open(&key, &message[88..116], &handshake_hash);
+        assert_eq!(timestamp, tai64n(now));
+    }
+
+    #[test]
+    fn test_tai64n() {
+        let timestamp = tai64n(UNIX_EPOCH + Duration::new(1, 2));
+        assert_eq!(timestamp[..8], (TAI64_EPOCH + 1).to_be_bytes());
+        assert_eq!(timestamp[8..], 2u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_is_answered_by() {
+        let initiation = Initiation::new(
+            &PrivateKey::new_from_random(),
+            &PrivateKey::new_from_random().public_key(),
+            SystemTime::now(),
+        );
+        let index = initiation.sender_index.to_le_bytes();
+
+        let mut response = [0u8; RESPONSE_LEN];
+        response[0] = HANDSHAKE_RESPONSE;
+        response[8..12].copy_from_slice(&index);
+        assert!(initiation.is_answered_by(&response));
+
+        let mut cookie_reply = [0u8; COOKIE_REPLY_LEN];
+        cookie_reply[0] = COOKIE_REPLY;
+        cookie_reply[4..8].copy_from_slice(&index);
+        assert!(initiation.is_answered_by(&cookie_reply));
+
+        // Replies to other initiations, and truncated or unrelated packets, are ignored
+        response[8] = response[8].wrapping_add(1);
+        assert!(!initiation.is_answered_by(&response));
+        assert!(!initiation.is_answered_by(&cookie_reply[..32]));
+        assert!(!initiation.is_answered_by(&[0u8; 4]));
+    }
+}
@@ -0,0 +1,726 @@
+//! Connection troubleshooter. Runs a set of checks that help to figure out why the app is unable
+//! to connect, and summarizes the outcome in a [`DiagnosticsReport`].
+
+mod handshake;
+
+use crate::{api, Daemon, EventListener, InternalDaemonEvent};
+use chrono::Utc;
+use futures::{channel::oneshot, future};
+use handshake::Initiation;
+use mullvad_api::ApiProxy;
+use mullvad_relay_selector::{query::builder::RelayQueryBuilder, GetRelay};
+use mullvad_types::{
+    diagnostics::{CheckCategory, CheckStatus, DiagnosticCheck, DiagnosticsReport},
+    settings::DnsState,
+    states::TunnelState,
+};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::{Duration, SystemTime},
+};
+use talpid_core::dns::leak_test;
+use talpid_types::{
+    net::{
+        obfuscation::ObfuscatorConfig,
+        wireguard::{PrivateKey, PublicKey},
+        AllowedEndpoint,
+    },
+    ErrorExt,
+};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// How long to wait for a relay to answer a handshake initiation.
+const UDP_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long to wait for a TCP connection to a relay to be established.
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait for a response from a public DNS resolver.
+const DNS_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Maximum number of WireGuard ports to probe.
+const MAX_WIREGUARD_PORTS: usize = 16;
+/// Clock differences up to this amount are considered harmless.
+const CLOCK_SKEW_WARNING: i64 = 60;
+/// Clock differences above this amount will likely break the connection.
+const CLOCK_SKEW_ERROR: i64 = 60 * 60;
+
+/// Public resolvers that DNS requests must never reach while connected, unless they are used as
+/// custom DNS servers.
+const PUBLIC_RESOLVERS: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)),
+    IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+];
+/// Hostname used for DNS leak probes.
+const DNS_PROBE_HOSTNAME: &str = "am.i.mullvad.net";
+
+/// Diagnostics whose inputs have been collected off the daemon's event loop, and which are ready
+/// to run.
+pub(crate) struct PreparedDiagnostics {
+    tx: oneshot::Sender<DiagnosticsReport>,
+    relay_probes: Vec<RelayProbe>,
+    tunnel_state: TunnelState,
+    custom_dns_servers: Vec<IpAddr>,
+    access_methods: Vec<ResolvedAccessMethod>,
+    /// The key of the current device, if logged in.
+    private_key: Option<PrivateKey>,
+}
+
+impl From<PreparedDiagnostics> for InternalDaemonEvent {
+    fn from(prepared: PreparedDiagnostics) -> Self {
+        InternalDaemonEvent::DiagnosticsPrepared(Box::new(prepared))
+    }
+}
+
+/// The outcome of resolving an API access method.
+struct ResolvedAccessMethod {
+    name: String,
+    enabled: bool,
+    result: Result<api::ResolvedConnectionMode, api::Error>,
+}
+
+/// An API access method that has been resolved and is ready to be tested.
+enum AccessMethodTest {
+    Ready {
+        name: String,
+        enabled: bool,
+        endpoint: AllowedEndpoint,
+        api_proxy: ApiProxy,
+    },
+    Failed(DiagnosticCheck),
+}
+
+/// A relay endpoint that should be probed for reachability.
+enum RelayProbe {
+    /// Plain WireGuard over UDP.
+    Udp {
+        port: u16,
+        address: SocketAddr,
+        public_key: PublicKey,
+    },
+    /// WireGuard obfuscated using UDP-over-TCP.
+    Udp2Tcp {
+        port: u16,
+        address: SocketAddr,
+    },
+    Failed(DiagnosticCheck),
+}
+
+impl<L> Daemon<L>
+where
+    L: EventListener,
+{
+    /// Collect the inputs of the diagnostics. Resolving access methods and reading the device
+    /// key may take a while, so this is done in a separate task, which sends the result back as
+    /// [`InternalDaemonEvent::DiagnosticsPrepared`].
+    pub(crate) fn on_run_diagnostics(&mut self, tx: oneshot::Sender<DiagnosticsReport>) {
+        let settings: Vec<_> = self.settings.api_access_methods.iter().cloned().collect();
+        let access_mode_handler = self.access_mode_handler.clone();
+        let account_manager = self.account_manager.clone();
+        let daemon_tx = self.tx.to_specialized_sender();
+
+        let mut prepared = PreparedDiagnostics {
+            tx,
+            relay_probes: self.prepare_relay_probes(),
+            tunnel_state: self.tunnel_state.clone(),
+            custom_dns_servers: match self.settings.tunnel_options.dns_options.state {
+                DnsState::Custom => self
+                    .settings
+                    .tunnel_options
+                    .dns_options
+                    .custom_options
+                    .addresses
+                    .clone(),
+                DnsState::Default => vec![],
+            },
+            access_methods: Vec::with_capacity(settings.len()),
+            private_key: None,
+        };
+
+        tokio::spawn(async move {
+            for setting in settings {
+                prepared.access_methods.push(ResolvedAccessMethod {
+                    name: setting.name.clone(),
+                    enabled: setting.enabled(),
+                    result: access_mode_handler.resolve(setting).await,
+                });
+            }
+            prepared.private_key = account_manager
+                .data()
+                .await
+                .ok()
+                .and_then(|state| state.into_device())
+                .map(|device| device.device.wg_data.private_key);
+
+            if daemon_tx.send(prepared).is_err() {
+                log::warn!("Failed to run diagnostics since the daemon has stopped");
+            }
+        });
+    }
+
+    /// Run the diagnostics collected by [`Self::on_run_diagnostics`].
+    pub(crate) fn on_diagnostics_prepared(&mut self, prepared: PreparedDiagnostics) {
+        let PreparedDiagnostics {
+            tx,
+            relay_probes,
+            tunnel_state,
+            custom_dns_servers,
+            access_methods,
+            private_key,
+        } = prepared;
+
+        let access_method_tests: Vec<_> = access_methods
+            .into_iter()
+            .map(|method| self.prepare_access_method_test(method))
+            .collect();
+        let api_proxy = ApiProxy::new(self.api_handle.clone());
+        let access_method_selector = self.access_mode_handler.clone();
+        let daemon_event_sender = self.tx.to_specialized_sender();
+
+        tokio::spawn(async move {
+            let mut checks = vec![];
+
+            for test in access_method_tests {
+                let check = match test {
+                    AccessMethodTest::Ready {
+                        name,
+                        enabled,
+                        endpoint,
+                        api_proxy,
+                    } => {
+                        let result = Self::test_access_method(
+                            endpoint,
+                            access_method_selector.clone(),
+                            daemon_event_sender.clone(),
+                            api_proxy,
+                        )
+                        .await;
+                        access_method_check(name, enabled, result)
+                    }
+                    AccessMethodTest::Failed(check) => check,
+                };
+                checks.push(check);
+            }
+
+            checks.extend(check_relays(&tunnel_state, relay_probes, private_key.as_ref()).await);
+            checks.push(check_dns_leak(&tunnel_state, &custom_dns_servers).await);
+            checks.push(check_firewall(&tunnel_state).await);
+            checks.push(check_routing(&tunnel_state).await);
+            checks.push(check_clock(api_proxy).await);
+
+            let report = DiagnosticsReport {
+                created: Utc::now(),
+                checks,
+            };
+            Self::oneshot_send(tx, report, "run_diagnostics response");
+        });
+    }
+
+    /// Turn a resolved API access method into something that can be tested.
+    fn prepare_access_method_test(&mut self, method: ResolvedAccessMethod) -> AccessMethodTest {
+        match method.result {
+            Ok(resolved) => AccessMethodTest::Ready {
+                name: method.name,
+                enabled: method.enabled,
+                endpoint: resolved.endpoint,
+                api_proxy: self.create_limited_api_proxy(resolved.connection_mode),
+            },
+            Err(error) => AccessMethodTest::Failed(DiagnosticCheck::new(
+                CheckCategory::ApiAccess,
+                method.name,
+                CheckStatus::Fail,
+                format!("Failed to resolve access method: {}", error.display_chain()),
+            )),
+        }
+    }
+
+    /// Select relays to probe for every WireGuard port range and UDP-over-TCP port.
+    fn prepare_relay_probes(&mut self) -> Vec<RelayProbe> {
+        let relay_list = self.relay_selector.get_relays();
+        let mut probes = vec![];
+
+        for port in probe_ports(&relay_list.wireguard.port_ranges) {
+            let query = RelayQueryBuilder::new().wireguard().port(port).build();
+            probes.push(match self.relay_selector.get_relay_by_query(query) {
+                Ok(GetRelay::Wireguard { endpoint, .. }) => RelayProbe::Udp {
+                    port,
+                    address: endpoint.peer.endpoint,
+                    public_key: endpoint.peer.public_key,
+                },
+                Ok(_) => continue,
+                Err(error) => RelayProbe::Failed(DiagnosticCheck::new(
+                    CheckCategory::RelayReachability,
+                    format!("WireGuard port {port}"),
+                    CheckStatus::Fail,
+                    format!("No relay could be selected: {}", error.display_chain()),
+                )),
+            });
+        }
+
+        for port in relay_list.wireguard.udp2tcp_ports.clone() {
+            let query = RelayQueryBuilder::new()
+                .wireguard()
+                .udp2tcp()
+                .udp2tcp_port(port)
+                .build();
+            probes.push(match self.relay_selector.get_relay_by_query(query) {
+                Ok(GetRelay::Wireguard {
+                    obfuscator: Some(obfuscator),
+                    ..
+                }) => match obfuscator.config {
+                    ObfuscatorConfig::Udp2Tcp { endpoint } => RelayProbe::Udp2Tcp {
+                        port,
+                        address: endpoint,
+                    },
+                },
+                Ok(_) => continue,
+                Err(error) => RelayProbe::Failed(DiagnosticCheck::new(
+                    CheckCategory::RelayReachability,
+                    format!("UDP-over-TCP port {port}"),
+                    CheckStatus::Fail,
+                    format!("No relay could be selected: {}", error.display_chain()),
+                )),
+            });
+        }
+
+        probes
+    }
+}
+
+/// Return the WireGuard ports to probe: the first, middle and last port of every range, since
+/// filtering often only applies to parts of a range.
+fn probe_ports(port_ranges: &[(u16, u16)]) -> Vec<u16> {
+    let mut ports: Vec<u16> = port_ranges
+        .iter()
+        .flat_map(|&(start, end)| {
+            let (start, end) = (start.min(end), start.max(end));
+            [start, start + (end - start) / 2, end]
+        })
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports.truncate(MAX_WIREGUARD_PORTS);
+    ports
+}
+
+fn access_method_check(
+    name: String,
+    enabled: bool,
+    result: Result<bool, crate::access_method::Error>,
+) -> DiagnosticCheck {
+    let (status, details) = match result {
+        Ok(true) => (CheckStatus::Pass, "The API is reachable".to_owned()),
+        Ok(false) => (
+            CheckStatus::Fail,
+            "The API returned an unexpected response".to_owned(),
+        ),
+        Err(error) => (
+            CheckStatus::Fail,
+            format!("The API is unreachable: {}", error.display_chain()),
+        ),
+    };
+    let details = if enabled {
+        details
+    } else {
+        format!("{details} (access method is disabled)")
+    };
+    DiagnosticCheck::new(CheckCategory::ApiAccess, name, status, details)
+}
+
+async fn check_relays(
+    tunnel_state: &TunnelState,
+    probes: Vec<RelayProbe>,
+    private_key: Option<&PrivateKey>,
+) -> Vec<DiagnosticCheck> {
+    match tunnel_state {
+        TunnelState::Connected { endpoint, .. } => {
+            return vec![DiagnosticCheck::new(
+                CheckCategory::RelayReachability,
+                "Current relay",
+                CheckStatus::Pass,
+                format!("Connected to {}", endpoint.endpoint),
+            )];
+        }
+        TunnelState::Disconnected {
+            locked_down: false, ..
+        } => (),
+        _ => {
+            return vec![DiagnosticCheck::new(
+                CheckCategory::RelayReachability,
+                "Relays",
+                CheckStatus::Skipped,
+                "Traffic to relays is blocked by the firewall in the current tunnel state",
+            )];
+        }
+    }
+
+    let checks = probes.into_iter().map(|probe| async move {
+        match probe {
+            RelayProbe::Udp {
+                port,
+                address,
+                public_key,
+            } => probe_udp(port, address, &public_key, private_key).await,
+            RelayProbe::Udp2Tcp { port, address } => probe_tcp(port, address).await,
+            RelayProbe::Failed(check) => check,
+        }
+    });
+    future::join_all(checks).await
+}
+
+/// Send a WireGuard handshake initiation to the relay and wait for a handshake response or a
+/// cookie reply. Relays only answer initiations from known keys, so the device key is used if
+/// there is one. Otherwise, the lack of a response is inconclusive.
+async fn probe_udp(
+    port: u16,
+    address: SocketAddr,
+    public_key: &PublicKey,
+    private_key: Option<&PrivateKey>,
+) -> DiagnosticCheck {
+    let name = format!("WireGuard port {port}");
+    let logged_in = private_key.is_some();
+    let private_key = private_key
+        .cloned()
+        .unwrap_or_else(PrivateKey::new_from_random);
+    let initiation = Initiation::new(&private_key, public_key, SystemTime::now());
+
+    let result = async {
+        let socket = UdpSocket::bind(unspecified_address(&address)).await?;
+        socket.connect(address).await?;
+        socket.send(initiation.message()).await?;
+        let mut buf = [0u8; 256];
+        let wait_for_reply = async {
+            loop {
+                let len = socket.recv(&mut buf).await?;
+                if initiation.is_answered_by(&buf[..len]) {
+                    return Ok::<_, io::Error>(());
+                }
+            }
+        };
+        match tokio::time::timeout(UDP_PROBE_TIMEOUT, wait_for_reply).await {
+            Ok(result) => result.map(|()| true),
+            Err(_timeout) => Ok(false),
+        }
+    }
+    .await;
+
+    match result {
+        Ok(true) => DiagnosticCheck::new(
+            CheckCategory::RelayReachability,
+            name,
+            CheckStatus::Pass,
+            format!("Received a handshake response from {address}"),
+        ),
+        Ok(false) if !logged_in => DiagnosticCheck::new(
+            CheckCategory::RelayReachability,
+            name,
+            CheckStatus::Warn,
+            format!(
+                "No response from {address}. Relays only respond to known devices, so log in \
+                 to check whether the port is blocked"
+            ),
+        ),
+        Ok(false) => DiagnosticCheck::new(
+            CheckCategory::RelayReachability,
+            name,
+            CheckStatus::Warn,
+            format!("No response from {address}. The port may be blocked or filtered"),
+        ),
+        Err(error) => DiagnosticCheck::new(
+            CheckCategory::RelayReachability,
+            name,
+            CheckStatus::Fail,
+            format!("{address} is unreachable: {error}"),
+        ),
+    }
+}
+
+async fn probe_tcp(port: u16, address: SocketAddr) -> DiagnosticCheck {
+    let name = format!("UDP-over-TCP port {port}");
+    let result = tokio::time::timeout(TCP_CONNECT_TIMEOUT, TcpStream::connect(address))
+        .await
+        .unwrap_or_else(|_timeout| Err(io::Error::from(io::ErrorKind::TimedOut)));
+
+    match result {
+        Ok(_stream) => DiagnosticCheck::new(
+            CheckCategory::RelayReachability,
+            name,
+            CheckStatus::Pass,
+            format!("Connected to {address}"),
+        ),
+        Err(error) => DiagnosticCheck::new(
+            CheckCategory::RelayReachability,
+            name,
+            CheckStatus::Fail,
+            format!("Failed to connect to {address}: {error}"),
+        ),
+    }
+}
+
+/// Verify that DNS requests to a public resolver are blocked while connected.
+async fn check_dns_leak(
+    tunnel_state: &TunnelState,
+    custom_dns_servers: &[IpAddr],
+) -> DiagnosticCheck {
+    const NAME: &str = "Requests to public resolvers";
+
+    if !tunnel_state.is_connected() {
+        return DiagnosticCheck::new(
+            CheckCategory::DnsLeak,
+            NAME,
+            CheckStatus::Skipped,
+            "DNS leaks can only be checked while connected",
+        );
+    }
+    let Some(resolver) = PUBLIC_RESOLVERS
+        .iter()
+        .find(|resolver| !custom_dns_servers.contains(resolver))
+    else {
+        return DiagnosticCheck::new(
+            CheckCategory::DnsLeak,
+            NAME,
+            CheckStatus::Skipped,
+            "All public test resolvers are used as custom DNS servers",
+        );
+    };
+    let resolver = SocketAddr::new(*resolver, 53);
+
+    let result = async {
+        let socket = UdpSocket::bind(unspecified_address(&resolver)).await?;
+        socket.connect(resolver).await?;
+        socket
+            .send(&leak_test::build_query(DNS_PROBE_HOSTNAME))
+            .await?;
+        let mut buf = [0u8; 512];
+        tokio::time::timeout(DNS_PROBE_TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_timeout| io::Error::from(io::ErrorKind::TimedOut))?
+    }
+    .await;
+
+    match result {
+        Ok(_) => DiagnosticCheck::new(
+            CheckCategory::DnsLeak,
+            NAME,
+            CheckStatus::Fail,
+            format!("A DNS request to {resolver} was answered. DNS requests are leaking"),
+        ),
+        Err(_) => DiagnosticCheck::new(
+            CheckCategory::DnsLeak,
+            NAME,
+            CheckStatus::Pass,
+            format!("DNS requests to {resolver} are blocked"),
+        ),
+    }
+}
+
+fn unspecified_address(address: &SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        SocketAddr::V6(_) => SocketAddr::new(std::net::Ipv6Addr::UNSPECIFIED.into(), 0),
+    }
+}
+
+/// Whether the firewall is expected to enforce a policy in `tunnel_state`.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn firewall_expected(tunnel_state: &TunnelState) -> bool {
+    !matches!(
+        tunnel_state,
+        TunnelState::Disconnected {
+            locked_down: false,
+            ..
+        }
+    )
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+async fn check_firewall(tunnel_state: &TunnelState) -> DiagnosticCheck {
+    #[cfg(target_os = "linux")]
+    let (name, output) = (
+        "nftables table",
+        command_output("nft", &["list", "table", "inet", "mullvad"]).await,
+    );
+    #[cfg(target_os = "macos")]
+    let (name, output) = (
+        "PF anchor",
+        command_output("pfctl", &["-a", "mullvad", "-s", "rules"]).await,
+    );
+
+    let present = match output {
+        Ok(output) => !output.trim().is_empty(),
+        Err(error) => {
+            return DiagnosticCheck::new(
+                CheckCategory::Firewall,
+                name,
+                CheckStatus::Warn,
+                format!("Failed to list firewall rules: {error}"),
+            );
+        }
+    };
+
+    match (present, firewall_expected(tunnel_state)) {
+        (true, _) => DiagnosticCheck::new(
+            CheckCategory::Firewall,
+            name,
+            CheckStatus::Pass,
+            "Firewall rules are present",
+        ),
+        (false, true) => DiagnosticCheck::new(
+            CheckCategory::Firewall,
+            name,
+            CheckStatus::Fail,
+            "Firewall rules are missing. Traffic may leak",
+        ),
+        (false, false) => DiagnosticCheck::new(
+            CheckCategory::Firewall,
+            name,
+            CheckStatus::Pass,
+            "No firewall rules are needed in the current tunnel state",
+        ),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+async fn check_firewall(_tunnel_state: &TunnelState) -> DiagnosticCheck {
+    DiagnosticCheck::new(
+        CheckCategory::Firewall,
+        "Firewall rules",
+        CheckStatus::Skipped,
+        "Not supported on this platform",
+    )
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+async fn check_routing(tunnel_state: &TunnelState) -> DiagnosticCheck {
+    let TunnelState::Connected { endpoint, .. } = tunnel_state else {
+        return DiagnosticCheck::new(
+            CheckCategory::Routing,
+            "Tunnel routes",
+            CheckStatus::Skipped,
+            "Routes can only be checked while connected",
+        );
+    };
+
+    #[cfg(target_os = "linux")]
+    let (name, output, expected) = (
+        "Routing rules",
+        command_output("ip", &["rule", "list"]).await,
+        format!("lookup {}", mullvad_types::TUNNEL_TABLE_ID),
+    );
+    #[cfg(target_os = "macos")]
+    let (name, output, expected) = (
+        "Default route",
+        command_output("route", &["-n", "get", "1.1.1.1"]).await,
+        format!(
+            "interface: {}",
+            endpoint.tunnel_interface.as_deref().unwrap_or_default()
+        ),
+    );
+    #[cfg(target_os = "linux")]
+    let _ = endpoint;
+
+    match output {
+        Ok(output) if output.contains(&expected) => DiagnosticCheck::new(
+            CheckCategory::Routing,
+            name,
+            CheckStatus::Pass,
+            "Traffic is routed through the tunnel",
+        ),
+        Ok(_) => DiagnosticCheck::new(
+            CheckCategory::Routing,
+            name,
+            CheckStatus::Fail,
+            format!("Expected \"{expected}\" was not found. Traffic may bypass the tunnel"),
+        ),
+        Err(error) => DiagnosticCheck::new(
+            CheckCategory::Routing,
+            name,
+            CheckStatus::Warn,
+            format!("Failed to list routes: {error}"),
+        ),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+async fn check_routing(_tunnel_state: &TunnelState) -> DiagnosticCheck {
+    DiagnosticCheck::new(
+        CheckCategory::Routing,
+        "Tunnel routes",
+        CheckStatus::Skipped,
+        "Not supported on this platform",
+    )
+}
+
+/// Run `program` and return its standard output.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+async fn command_output(program: &str, args: &[&str]) -> io::Result<String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .output()
+        .await?;
+    if !output.status.success() {
+        // A missing table or anchor is reported as a failure by the tools
+        return Ok(String::new());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Compare the local clock against the time reported by the API.
+async fn check_clock(api_proxy: ApiProxy) -> DiagnosticCheck {
+    const NAME: &str = "System clock";
+
+    let server_time = match api_proxy.get_server_time().await {
+        Ok(Some(server_time)) => server_time,
+        Ok(None) => {
+            return DiagnosticCheck::new(
+                CheckCategory::Clock,
+                NAME,
+                CheckStatus::Skipped,
+                "The API did not report the current time",
+            );
+        }
+        Err(error) => {
+            return DiagnosticCheck::new(
+                CheckCategory::Clock,
+                NAME,
+                CheckStatus::Skipped,
+                format!("Failed to reach the API: {}", error.display_chain()),
+            );
+        }
+    };
+
+    let skew = (Utc::now() - server_time).num_seconds();
+    let status = match skew.abs() {
+        skew if skew > CLOCK_SKEW_ERROR => CheckStatus::Fail,
+        skew if skew > CLOCK_SKEW_WARNING => CheckStatus::Warn,
+        _ => CheckStatus::Pass,
+    };
+    DiagnosticCheck::new(
+        CheckCategory::Clock,
+        NAME,
+        status,
+        format!("The system clock differs from the API by {skew} seconds"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_probe_ports() {
+        assert_eq!(probe_ports(&[(53, 53)]), vec![53]);
+        assert_eq!(
+            probe_ports(&[(4000, 33433), (51820, 51820), (53, 53)]),
+            vec![53, 4000, 18716, 33433, 51820]
+        );
+        // Reversed ranges are treated like ordinary ones
+        assert_eq!(probe_ports(&[(10, 0)]), vec![0, 5, 10]);
+    }
+
+    #[test]
+    fn test_probe_ports_limit() {
+        let ranges: Vec<_> = (0..10).map(|i| (i * 100, i * 100 + 10)).collect();
+        let ports = probe_ports(&ranges);
+        assert_eq!(ports.len(), MAX_WIREGUARD_PORTS);
+        assert_eq!(ports[..3], [0, 5, 10]);
+    }
+}
@@ -1,5 +1,24 @@
+#[cfg(not(target_os = "android"))]
+use crate::{DaemonEventSender, InternalDaemonEvent};
+#[cfg(not(target_os = "android"))]
+use futures::future::{abortable, AbortHandle};
+#[cfg(not(target_os = "android"))]
+use mullvad_types::settings::DnsBlocklist;
 use mullvad_types::settings::{DnsOptions, DnsState};
 use std::net::{IpAddr, Ipv4Addr};
+#[cfg(not(target_os = "android"))]
+use std::{collections::HashMap, io, sync::Arc, time::Duration};
+use talpid_core::tunnel_state_machine::DnsConfig;
+#[cfg(not(target_os = "android"))]
+use talpid_core::{
+    dns::{
+        blocklist::Blocklist,
+        forwarder::{self, DnsForwarder, Upstreams},
+    },
+    mpsc::Sender,
+};
+#[cfg(not(target_os = "android"))]
+use talpid_types::ErrorExt;
 
 /// When we want to block certain contents with the help of DNS server side,
 /// we compute the resolver IP to use based on these constants. The last
@@ -12,6 +31,290 @@ const DNS_ADULT_BLOCKING_IP_BIT: u8 = 1 << 3; // 0b00001000
 const DNS_GAMBLING_BLOCKING_IP_BIT: u8 = 1 << 4; // 0b00010000
 const DNS_SOCIAL_MEDIA_BLOCKING_IP_BIT: u8 = 1 << 5; // 0b00100000
 
+/// How often blocklists are reloaded from their sources.
+#[cfg(not(target_os = "android"))]
+const BLOCKLIST_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long to wait before retrying when some blocklist could not be loaded.
+#[cfg(not(target_os = "android"))]
+const BLOCKLIST_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Timeout for downloading a single blocklist.
+#[cfg(not(target_os = "android"))]
+const BLOCKLIST_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[cfg(not(target_os = "android"))]
+#[derive(thiserror::Error, Debug)]
+pub enum BlocklistError {
+    #[error("Failed to read blocklist file")]
+    Read(#[source] io::Error),
+
+    #[error("Failed to download blocklist")]
+    Download(#[source] mullvad_api::http_client::Error),
+
+    #[error("Unexpected response status: {0}")]
+    UnexpectedStatus(mullvad_api::StatusCode),
+}
+
+/// Blocked domains loaded from the configured blocklists.
+#[cfg(not(target_os = "android"))]
+pub struct BlocklistUpdate {
+    sources: Vec<DnsBlocklist>,
+    blocklist: Arc<Blocklist>,
+}
+
+#[cfg(not(target_os = "android"))]
+impl From<BlocklistUpdate> for InternalDaemonEvent {
+    fn from(update: BlocklistUpdate) -> Self {
+        InternalDaemonEvent::DnsBlocklistEvent(update)
+    }
+}
+
+/// Resolves the DNS settings into the resolvers that the tunnel should use. Encrypted DNS servers
+/// and domain blocklists are served by a local forwarder, which is kept running for as long as
+/// either is configured.
+#[cfg(not(target_os = "android"))]
+pub struct DnsConfigurator {
+    forwarder: Option<DnsForwarder>,
+    /// Whether the forwarder sends queries to the tunnel gateway.
+    forward_to_gateway: bool,
+    /// The gateway of the current tunnel, if known.
+    tunnel_gateway: Option<IpAddr>,
+    blocklist_sources: Vec<DnsBlocklist>,
+    blocklist: Arc<Blocklist>,
+    blocklist_loader: Option<AbortHandle>,
+    event_tx: DaemonEventSender<BlocklistUpdate>,
+}
+
+#[cfg(not(target_os = "android"))]
+impl DnsConfigurator {
+    pub fn new(event_tx: &DaemonEventSender) -> Self {
+        DnsConfigurator {
+            forwarder: None,
+            forward_to_gateway: false,
+            tunnel_gateway: None,
+            blocklist_sources: vec![],
+            blocklist: Arc::default(),
+            blocklist_loader: None,
+            event_tx: event_tx.to_specialized_sender(),
+        }
+    }
+
+    /// Return the resolvers that the tunnel should use given `options`, starting or stopping the
+    /// local forwarder as needed.
+    ///
+    /// When blocklists are enabled but no other resolver is configured, the forwarder uses the
+    /// tunnel gateway, which is set by [`Self::set_tunnel_gateway`] once the tunnel is up.
+    pub async fn resolvers(&mut self, options: &DnsOptions) -> DnsConfig {
+        self.set_blocklist_sources(&options.blocklists);
+
+        let encrypted_servers = match options.state {
+            DnsState::Custom => options.custom_options.encrypted_servers.clone(),
+            DnsState::Default => vec![],
+        };
+        let configured_servers = addresses_from_options(options);
+        self.forward_to_gateway = encrypted_servers.is_empty()
+            && !options.blocklists.is_empty()
+            && configured_servers.is_none();
+
+        let upstreams = if !encrypted_servers.is_empty() {
+            Upstreams::Encrypted(encrypted_servers)
+        } else if self.forward_to_gateway {
+            Upstreams::Plain(self.tunnel_gateway.into_iter().collect())
+        } else if !options.blocklists.is_empty() {
+            Upstreams::Plain(configured_servers.unwrap_or_default())
+        } else {
+            self.forwarder = None;
+            return DnsConfig {
+                servers: configured_servers,
+                forwarder_upstreams: vec![],
+                forward_to_gateway: false,
+            };
+        };
+        // Plain queries to the upstreams must be let through by the firewall. The tunnel gateway
+        // is allowed by the tunnel state machine, since only it knows the current gateway.
+        let forwarder_upstreams = match &upstreams {
+            Upstreams::Plain(_) if self.forward_to_gateway => vec![],
+            Upstreams::Plain(addresses) => addresses.clone(),
+            Upstreams::Encrypted(_) => vec![],
+        };
+
+        match self.forwarder {
+            Some(ref mut forwarder) => forwarder.set_upstreams(upstreams),
+            None => match DnsForwarder::start(upstreams, self.blocklist.clone()).await {
+                Ok(forwarder) => self.forwarder = Some(forwarder),
+                Err(error) => {
+                    // Still point the tunnel at the forwarder address. Nothing answers there,
+                    // but no queries will bypass the forwarder either.
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to start DNS forwarder")
+                    );
+                }
+            },
+        }
+        DnsConfig {
+            servers: Some(vec![IpAddr::V4(forwarder::LISTEN_ADDR)]),
+            forwarder_upstreams,
+            forward_to_gateway: self.forward_to_gateway,
+        }
+    }
+
+    /// Update the gateway of the current tunnel, which the forwarder may use as its upstream.
+    pub fn set_tunnel_gateway(&mut self, gateway: IpAddr) {
+        self.tunnel_gateway = Some(gateway);
+        if !self.forward_to_gateway {
+            return;
+        }
+        if let Some(forwarder) = self.forwarder.as_mut() {
+            forwarder.set_upstreams(Upstreams::Plain(vec![gateway]));
+        }
+    }
+
+    /// Apply newly loaded blocklists, unless the configured sources have changed since.
+    pub fn handle_blocklist_update(&mut self, update: BlocklistUpdate) {
+        if update.sources != self.blocklist_sources {
+            return;
+        }
+        log::debug!("Blocking {} domains", update.blocklist.len());
+        self.set_blocklist(update.blocklist);
+    }
+
+    fn set_blocklist(&mut self, blocklist: Arc<Blocklist>) {
+        self.blocklist = blocklist;
+        if let Some(forwarder) = self.forwarder.as_mut() {
+            forwarder.set_blocklist(self.blocklist.clone());
+        }
+    }
+
+    /// Load the blocklists in `sources` in the background, and reload them periodically.
+    fn set_blocklist_sources(&mut self, sources: &[DnsBlocklist]) {
+        if sources == self.blocklist_sources {
+            return;
+        }
+        if let Some(loader) = self.blocklist_loader.take() {
+            loader.abort();
+        }
+        self.blocklist_sources = sources.to_vec();
+        self.set_blocklist(Arc::default());
+
+        if sources.is_empty() {
+            return;
+        }
+        let (future, abort_handle) = abortable(Box::pin(load_blocklists(
+            sources.to_vec(),
+            self.event_tx.clone(),
+        )));
+        tokio::spawn(future);
+        self.blocklist_loader = Some(abort_handle);
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+impl Drop for DnsConfigurator {
+    fn drop(&mut self) {
+        if let Some(loader) = self.blocklist_loader.take() {
+            loader.abort();
+        }
+    }
+}
+
+/// Resolves the DNS settings into the resolvers that the tunnel should use.
+#[cfg(target_os = "android")]
+pub struct DnsConfigurator(());
+
+#[cfg(target_os = "android")]
+impl DnsConfigurator {
+    pub fn new(_event_tx: &crate::DaemonEventSender) -> Self {
+        DnsConfigurator(())
+    }
+
+    /// Return the resolvers that the tunnel should use given `options`.
+    pub async fn resolvers(&mut self, options: &DnsOptions) -> DnsConfig {
+        if options.state == DnsState::Custom && !options.custom_options.encrypted_servers.is_empty()
+        {
+            log::warn!("Encrypted DNS servers are not supported on this platform");
+        }
+        if !options.blocklists.is_empty() {
+            log::warn!("DNS blocklists are not supported on this platform");
+        }
+        DnsConfig {
+            servers: addresses_from_options(options),
+            forwarder_upstreams: vec![],
+            forward_to_gateway: false,
+        }
+    }
+
+    pub fn set_tunnel_gateway(&mut self, _gateway: IpAddr) {}
+}
+
+/// Load `sources` and send the combined blocklist to the daemon, then repeat periodically.
+/// Lists that fail to load are retried sooner, and the last successfully loaded version of each
+/// list is used in the meantime.
+#[cfg(not(target_os = "android"))]
+async fn load_blocklists(sources: Vec<DnsBlocklist>, event_tx: DaemonEventSender<BlocklistUpdate>) {
+    let mut loaded: HashMap<DnsBlocklist, Blocklist> = HashMap::new();
+    loop {
+        let mut all_loaded = true;
+        for source in &sources {
+            match load_blocklist(source).await {
+                Ok(blocklist) => {
+                    log::info!("Loaded {} blocked domains from {source}", blocklist.len());
+                    loaded.insert(source.clone(), blocklist);
+                }
+                Err(error) => {
+                    all_loaded = false;
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg(&format!(
+                            "Failed to load DNS blocklist {source}"
+                        ))
+                    );
+                }
+            }
+        }
+
+        let mut blocklist = Blocklist::default();
+        for list in loaded.values() {
+            blocklist.extend(list);
+        }
+        let update = BlocklistUpdate {
+            sources: sources.clone(),
+            blocklist: Arc::new(blocklist),
+        };
+        if event_tx.send(update).is_err() {
+            return;
+        }
+
+        tokio::time::sleep(if all_loaded {
+            BLOCKLIST_REFRESH_INTERVAL
+        } else {
+            BLOCKLIST_RETRY_INTERVAL
+        })
+        .await;
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+async fn load_blocklist(source: &DnsBlocklist) -> Result<Blocklist, BlocklistError> {
+    let contents = match source {
+        DnsBlocklist::File(path) => tokio::fs::read_to_string(path)
+            .await
+            .map_err(BlocklistError::Read)?,
+        DnsBlocklist::Url(url) => download(url).await?,
+    };
+    Ok(Blocklist::parse(&contents))
+}
+
+#[cfg(not(target_os = "android"))]
+async fn download(url: &str) -> Result<String, BlocklistError> {
+    let response = mullvad_api::http_client::get(url, BLOCKLIST_DOWNLOAD_TIMEOUT)
+        .await
+        .map_err(BlocklistError::Download)?;
+    if !response.status.is_success() {
+        return Err(BlocklistError::UnexpectedStatus(response.status));
+    }
+    Ok(String::from_utf8_lossy(&response.body).into_owned())
+}
+
 /// Return the resolvers as a vector of `IpAddr`s. Returns `None` when no special resolvers
 /// are requested and the tunnel default gateway should be used.
 pub fn addresses_from_options(options: &DnsOptions) -> Option<Vec<IpAddr>> {
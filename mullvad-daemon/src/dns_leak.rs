@@ -0,0 +1,93 @@
+//! Checks for DNS leaks while connected. Leaks are reported using the
+//! [`FeatureIndicator::DnsLeak`](mullvad_types::features::FeatureIndicator::DnsLeak) indicator.
+
+use crate::{Daemon, DaemonCommand, EventListener, InternalDaemonEvent};
+use futures::{channel::oneshot, future::abortable};
+use std::time::Duration;
+use talpid_core::{mpsc::Sender, tunnel_state_machine::TunnelCommand};
+use talpid_types::net::dns::DnsLeakTestResult;
+
+/// How long to wait after connecting before testing for DNS leaks. This gives the DNS
+/// configuration time to settle.
+const FIRST_TEST_DELAY: Duration = Duration::from_secs(10);
+/// How often to test for DNS leaks while connected.
+const TEST_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+impl<L> Daemon<L>
+where
+    L: EventListener,
+{
+    pub(crate) fn on_test_dns_leaks(&mut self, tx: oneshot::Sender<Option<DnsLeakTestResult>>) {
+        let (result_tx, result_rx) = oneshot::channel();
+        self.send_tunnel_command(TunnelCommand::DnsLeakTest(result_tx));
+
+        let daemon_tx = self.tx.clone();
+        tokio::spawn(async move {
+            let result = result_rx.await.ok().flatten();
+            if let Some(ref result) = result {
+                let _ = daemon_tx.send(InternalDaemonEvent::DnsLeakTestEvent(result.clone()));
+            }
+            Self::oneshot_send(tx, result, "test_dns_leaks response");
+        });
+    }
+
+    pub(crate) fn handle_dns_leak_test_result(&mut self, result: DnsLeakTestResult) {
+        // Ignore results that arrive after the tunnel has gone down
+        if !self.tunnel_state.is_connected() {
+            return;
+        }
+
+        if result.is_leaking() {
+            log::warn!(
+                "DNS leak detected. Expected resolvers: {:?}, leaking resolvers: {:?}, answered by \
+                 unexpected resolver: {}",
+                result.expected_resolvers,
+                result.leaking_resolvers,
+                result.answered_by_unexpected_resolver,
+            );
+        } else {
+            log::debug!("No DNS leak detected");
+        }
+        self.dns_leak_detected = result.is_leaking();
+        self.handle_feature_indicator_event();
+    }
+
+    /// Test for DNS leaks periodically while connected, and forget about earlier leaks when the
+    /// tunnel goes down.
+    pub(crate) fn update_dns_leak_monitor(&mut self, connected: bool) {
+        if !connected {
+            self.stop_dns_leak_monitor();
+            self.dns_leak_detected = false;
+            return;
+        }
+        if self.dns_leak_monitor.is_some() {
+            return;
+        }
+
+        let daemon_command_tx = self.tx.to_specialized_sender();
+        let (future, abort_handle) = abortable(Box::pin(async move {
+            let mut delay = FIRST_TEST_DELAY;
+            loop {
+                tokio::time::sleep(delay).await;
+                let (tx, rx) = oneshot::channel();
+                if daemon_command_tx
+                    .send(DaemonCommand::TestDnsLeaks(tx))
+                    .is_err()
+                {
+                    break;
+                }
+                let _ = rx.await;
+                delay = TEST_INTERVAL;
+            }
+        }));
+
+        tokio::spawn(future);
+        self.dns_leak_monitor = Some(abort_handle);
+    }
+
+    fn stop_dns_leak_monitor(&mut self) {
+        if let Some(job) = self.dns_leak_monitor.take() {
+            job.abort();
+        }
+    }
+}
@@ -25,6 +25,7 @@ pub async fn initialize_firewall() -> Result<(), Error> {
     let policy = FirewallPolicy::Blocked {
         allow_lan,
         allowed_endpoint: None,
+        captive_portal: None,
     };
     log::info!("Applying firewall policy {policy}");
     firewall.apply_policy(policy)?;
@@ -0,0 +1,616 @@
+//! Optional HTTP/JSON gateway to the management interface, for clients that cannot speak gRPC
+//! over the management interface socket.
+//!
+//! The gateway is only started if the daemon is given `--http-gateway <ADDRESS>`, and only
+//! listens on loopback addresses. Every RPC is available as `POST /v1/<RpcName>`, e.g.
+//! `POST /v1/SetAllowLan` with the body `true`. Arguments and results use the same JSON
+//! representation as the settings file. `GET /v1/events` streams daemon events as server-sent
+//! events. Request bodies may not exceed [`MAX_BODY_SIZE`] bytes.
+//!
+//! All requests must carry the header `Authorization: Bearer <token>`, where the token is read
+//! from [`TOKEN_FILE`] in the settings directory. A random token is created if the file does not
+//! exist.
+//!
+//! The gateway connects to the management interface socket as the daemon user, so it checks the
+//! [`AccessPolicy`] itself before each RPC. Since the local user behind an HTTP request is unknown,
+//! gateway clients are treated like management interface clients whose credentials could not be
+//! determined: if there is a policy file, they may only use the `default` categories.
+
+use crate::access_policy::AccessPolicy;
+use futures::StreamExt;
+use hyper::{
+    body::{Bytes, HttpBody},
+    header::{self, HeaderValue},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use mullvad_management_interface::{
+    access::RpcCategory, client::DaemonEvent, Code, MullvadProxyClient,
+};
+use mullvad_types::{
+    access_method::AccessMethod,
+    account::AccountToken,
+    device::DeviceId,
+    log_level::LogLevel,
+    wireguard::{QuantumResistantAlgorithms, QuantumResistantState, RotationInterval},
+};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{convert::Infallible, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+};
+
+pub const TOKEN_FILE: &str = "http-gateway-token";
+
+const TOKEN_LENGTH: usize = 32;
+
+/// Largest accepted request body. Imported relay lists are by far the largest arguments.
+const MAX_BODY_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read or create the gateway token")]
+    Token(#[source] io::Error),
+
+    #[error("The gateway token is empty")]
+    EmptyToken,
+
+    #[error("The HTTP gateway may only be served on a loopback address, not {0}")]
+    NotLoopback(SocketAddr),
+
+    #[error("Failed to listen on {0}")]
+    Bind(SocketAddr, #[source] hyper::Error),
+}
+
+/// Start serving the gateway on `address`.
+pub async fn spawn(
+    address: SocketAddr,
+    settings_dir: &Path,
+    access_policy: AccessPolicy,
+) -> Result<(), Error> {
+    if !address.ip().is_loopback() {
+        return Err(Error::NotLoopback(address));
+    }
+
+    let token = load_or_create_token(&settings_dir.join(TOKEN_FILE))
+        .await
+        .map_err(Error::Token)?;
+    if token.is_empty() {
+        return Err(Error::EmptyToken);
+    }
+    let token = Arc::new(token);
+    let access_policy = Arc::new(access_policy);
+
+    let make_service = make_service_fn(move |_connection| {
+        let token = token.clone();
+        let access_policy = access_policy.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, token.clone(), access_policy.clone())
+            }))
+        }
+    });
+    let server = Server::try_bind(&address)
+        .map_err(|error| Error::Bind(address, error))?
+        .serve(make_service);
+
+    log::info!("HTTP gateway listening on {address}");
+    tokio::spawn(async move {
+        if let Err(error) = server.await {
+            log::error!("{}", error.display_chain_with_msg("HTTP gateway failed"));
+        }
+    });
+    Ok(())
+}
+
+async fn load_or_create_token(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path).await {
+        Ok(token) => return Ok(token.trim().to_owned()),
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+        Err(_) => (),
+    }
+
+    let token: String = rand::thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    file.write_all(token.as_bytes()).await?;
+    file.sync_all().await?;
+
+    log::info!("Created HTTP gateway token in {}", path.display());
+    Ok(token)
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    token: Arc<String>,
+    access_policy: Arc<AccessPolicy>,
+) -> Result<Response<Body>, Infallible> {
+    if !is_authorized(&request, &token) {
+        return Ok(ApiError::new(StatusCode::UNAUTHORIZED, "Missing or invalid token").into());
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let Some(name) = path.strip_prefix("/v1/") else {
+        return Ok(ApiError::new(StatusCode::NOT_FOUND, "Not found").into());
+    };
+
+    let rpc_name = match name {
+        "events" => "EventsListen",
+        name => name,
+    };
+    if let Err(error) = authorize(&access_policy, rpc_name) {
+        return Ok(error.into());
+    }
+
+    let response = match name {
+        "events" if method == Method::GET => events().await,
+        _ if method == Method::POST => match read_body(request.into_body()).await {
+            Ok(body) => call(name, body).await,
+            Err(error) => Err(error),
+        },
+        _ => Err(ApiError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Use POST for RPCs and GET for events",
+        )),
+    };
+    Ok(response.unwrap_or_else(Response::from))
+}
+
+/// Check that gateway clients may use the RPC named `name`.
+fn authorize(access_policy: &AccessPolicy, name: &str) -> Result<(), ApiError> {
+    let category = RpcCategory::from_method(name);
+    if access_policy.allows(None, category) {
+        Ok(())
+    } else {
+        log::warn!("Denied {name} to HTTP gateway client");
+        Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("Access to RPCs in the category \"{category}\" is denied"),
+        ))
+    }
+}
+
+/// Read the request body, but no more than [`MAX_BODY_SIZE`] bytes of it.
+async fn read_body(mut body: Body) -> Result<Bytes, ApiError> {
+    let too_large = || {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("The request body may not exceed {MAX_BODY_SIZE} bytes"),
+        )
+    };
+
+    // Reject requests with a too large `Content-Length` up front
+    if body.size_hint().lower() > MAX_BODY_SIZE as u64 {
+        return Err(too_large());
+    }
+
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk =
+            chunk.map_err(|error| ApiError::new(StatusCode::BAD_REQUEST, error.to_string()))?;
+        if buffer.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err(too_large());
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buffer))
+}
+
+fn is_authorized(request: &Request<Body>, token: &str) -> bool {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| constant_time_eq(provided.as_bytes(), token.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Argument of `RemoveDevice`.
+#[derive(Deserialize)]
+struct DeviceRemoval {
+    account_token: AccountToken,
+    device_id: DeviceId,
+}
+
+/// Argument of `AddApiAccessMethod`.
+#[derive(Deserialize)]
+struct NewAccessMethod {
+    name: String,
+    enabled: bool,
+    access_method: AccessMethod,
+}
+
+/// Argument of `SetLogLevel`.
+#[derive(Deserialize)]
+struct LogLevelChange {
+    module: String,
+    level: LogLevel,
+    revert_after_secs: Option<u64>,
+}
+
+/// Argument of `SetQuantumResistantTunnel`.
+#[derive(Deserialize)]
+struct QuantumResistantChange {
+    state: QuantumResistantState,
+    #[serde(default)]
+    algorithms: Option<QuantumResistantAlgorithms>,
+}
+
+/// Call the RPC named `name`, with `body` as its JSON encoded argument.
+async fn call(name: &str, body: Bytes) -> Result<Response<Body>, ApiError> {
+    let mut rpc = MullvadProxyClient::new().await?;
+
+    match name {
+        "ConnectTunnel" => json(rpc.connect_tunnel().await?),
+        "DisconnectTunnel" => json(rpc.disconnect_tunnel().await?),
+        "ReconnectTunnel" => json(rpc.reconnect_tunnel().await?),
+        "GetTunnelState" => json(rpc.get_tunnel_state().await?),
+        "GetRecentErrors" => json(rpc.get_recent_errors().await?),
+
+        "PrepareRestart" => json(rpc.prepare_restart().await?),
+        "PrepareRestartV2" => json(rpc.prepare_restart_v2(arg(&body)?).await?),
+        "FactoryReset" => json(rpc.factory_reset().await?),
+        "GetCurrentVersion" => json(rpc.get_current_version().await?),
+        "GetVersionInfo" => json(rpc.get_version_info().await?),
+
+        "UpdateRelayLocations" => json(rpc.update_relay_locations().await?),
+        "GetRelayLocations" => json(rpc.get_relay_locations().await?),
+        "GetRelayListChanges" => json(rpc.get_relay_list_changes().await?),
+        "ExportRelayList" => json(rpc.export_relay_list().await?),
+        "ImportRelayList" => json(rpc.import_relay_list(arg(&body)?).await?),
+        "SetRelaySettings" => json(rpc.set_relay_settings(arg(&body)?).await?),
+        "SetBridgeSettings" => json(rpc.set_bridge_settings(arg(&body)?).await?),
+        "SetBridgeState" => json(rpc.set_bridge_state(arg(&body)?).await?),
+        "SetObfuscationSettings" => json(rpc.set_obfuscation_settings(arg(&body)?).await?),
+
+        "GetSettings" => json(rpc.get_settings().await?),
+        "ResetSettings" => json(rpc.reset_settings().await?),
+        "SetAllowLan" => json(rpc.set_allow_lan(arg(&body)?).await?),
+        "SetShowBetaReleases" => json(rpc.set_show_beta_releases(arg(&body)?).await?),
+        "SetBlockWhenDisconnected" => json(rpc.set_block_when_disconnected(arg(&body)?).await?),
+        "SetAutoConnect" => json(rpc.set_auto_connect(arg(&body)?).await?),
+        "SetOpenvpnMssfix" => json(rpc.set_openvpn_mssfix(arg(&body)?).await?),
+        "SetWireguardMtu" => json(rpc.set_wireguard_mtu(arg(&body)?).await?),
+        "SetEnableIpv6" => json(rpc.set_enable_ipv6(arg(&body)?).await?),
+        "SetQuantumResistantTunnel" => {
+            let change: QuantumResistantChange = arg(&body)?;
+            json(
+                rpc.set_quantum_resistant_tunnel(change.state, change.algorithms)
+                    .await?,
+            )
+        }
+        "SetQuantumResistantRekeyInterval" => {
+            let minutes: Option<u64> = arg(&body)?;
+            let interval = minutes.map(|minutes| Duration::from_secs(minutes * 60));
+            json(rpc.set_quantum_resistant_rekey_interval(interval).await?)
+        }
+        #[cfg(daita)]
+        "SetDaitaSettings" => json(rpc.set_daita_settings(arg(&body)?).await?),
+        "SetDnsOptions" => json(rpc.set_dns_options(arg(&body)?).await?),
+        "SetRelayOverride" => json(rpc.set_relay_override(arg(&body)?).await?),
+        "ClearAllRelayOverrides" => json(rpc.clear_all_relay_overrides().await?),
+        "SetLogSinks" => json(rpc.set_log_sinks(arg(&body)?).await?),
+        "ApplyJsonSettings" => json(rpc.apply_json_settings(arg(&body)?).await?),
+        "ExportJsonSettings" => json(rpc.export_json_settings().await?),
+        "GetFeatureIndicators" => json(rpc.get_feature_indicators().await?),
+
+        "CreateNewAccount" => json(rpc.create_new_account().await?),
+        "LoginAccount" => json(rpc.login_account(arg(&body)?).await?),
+        "LogoutAccount" => json(rpc.logout_account().await?),
+        "GetAccountData" => json(rpc.get_account_data(arg(&body)?).await?),
+        "GetAccountHistory" => json(rpc.get_account_history().await?),
+        "ClearAccountHistory" => json(rpc.clear_account_history().await?),
+        "ListSavedAccounts" => json(rpc.list_saved_accounts().await?),
+        "SaveAccount" => json(rpc.save_account(arg(&body)?).await?),
+        "RemoveSavedAccount" => json(rpc.remove_saved_account(arg(&body)?).await?),
+        "SwitchAccount" => json(rpc.switch_account(arg(&body)?).await?),
+        "GetAccountExpiryState" => json(rpc.get_account_expiry_state().await?),
+        "SetExpiryNotificationHours" => json(rpc.set_expiry_notification_hours(arg(&body)?).await?),
+        "SubmitVoucher" => json(rpc.submit_voucher(arg(&body)?).await?),
+
+        "GetDevice" => json(rpc.get_device().await?),
+        "UpdateDevice" => json(rpc.update_device().await?),
+        "ListDevices" => json(rpc.list_devices(arg(&body)?).await?),
+        "RemoveDevice" => {
+            let removal: DeviceRemoval = arg(&body)?;
+            json(
+                rpc.remove_device(removal.account_token, removal.device_id)
+                    .await?,
+            )
+        }
+
+        "SetWireguardRotationInterval" => {
+            let hours: u64 = arg(&body)?;
+            let interval = RotationInterval::new(Duration::from_secs(hours * 60 * 60))
+                .map_err(|error| ApiError::new(StatusCode::BAD_REQUEST, error.to_string()))?;
+            json(rpc.set_wireguard_rotation_interval(interval).await?)
+        }
+        "ResetWireguardRotationInterval" => json(rpc.reset_wireguard_rotation_interval().await?),
+        "RotateWireguardKey" => json(rpc.rotate_wireguard_key().await?),
+        "GetWireguardKey" => json(rpc.get_wireguard_key().await?),
+
+        "CreateCustomList" => json(rpc.create_custom_list(arg(&body)?).await?),
+        "DeleteCustomList" => json(rpc.delete_custom_list(arg(&body)?).await?),
+        "UpdateCustomList" => json(rpc.update_custom_list(arg(&body)?).await?),
+        "ClearCustomLists" => json(rpc.clear_custom_lists().await?),
+        "AddCustomRelay" => json(rpc.add_custom_relay(arg(&body)?).await?),
+        "RemoveCustomRelay" => json(rpc.remove_custom_relay(arg(&body)?).await?),
+        "AddScheduleRule" => json(rpc.add_schedule_rule(arg(&body)?).await?),
+        "RemoveScheduleRule" => json(rpc.remove_schedule_rule(arg(&body)?).await?),
+        "GetDataUsage" => json(rpc.get_data_usage().await?),
+        "SetDataCap" => json(rpc.set_data_cap(arg(&body)?).await?),
+        "AllowCaptivePortal" => {
+            let minutes: u64 = arg(&body)?;
+            let window = Duration::from_secs(minutes * 60);
+            json(rpc.allow_captive_portal(window).await?)
+        }
+
+        "AddApiAccessMethod" => {
+            let method: NewAccessMethod = arg(&body)?;
+            json(
+                rpc.add_access_method(method.name, method.enabled, method.access_method)
+                    .await?,
+            )
+        }
+        "RemoveApiAccessMethod" => json(rpc.remove_access_method(arg(&body)?).await?),
+        "SetApiAccessMethod" => json(rpc.set_access_method(arg(&body)?).await?),
+        "UpdateApiAccessMethod" => json(rpc.update_access_method(arg(&body)?).await?),
+        "ClearCustomApiAccessMethods" => json(rpc.clear_custom_access_methods().await?),
+        "GetCurrentApiAccessMethod" => json(rpc.get_current_api_access_method().await?),
+        "TestCustomApiAccessMethod" => json(rpc.test_custom_api_access_method(arg(&body)?).await?),
+        "TestApiAccessMethodById" => json(rpc.test_api_access_method(arg(&body)?).await?),
+
+        "GetSplitTunnelProcesses" => json(rpc.get_split_tunnel_processes().await?),
+        "AddSplitTunnelProcess" => json(rpc.add_split_tunnel_process(arg(&body)?).await?),
+        "RemoveSplitTunnelProcess" => json(rpc.remove_split_tunnel_process(arg(&body)?).await?),
+        "ClearSplitTunnelProcesses" => json(rpc.clear_split_tunnel_processes().await?),
+        "AddSplitTunnelApp" => json(rpc.add_split_tunnel_app::<String>(arg(&body)?).await?),
+        "RemoveSplitTunnelApp" => json(rpc.remove_split_tunnel_app::<String>(arg(&body)?).await?),
+        "ClearSplitTunnelApps" => json(rpc.clear_split_tunnel_apps().await?),
+        "SetSplitTunnelState" => json(rpc.set_split_tunnel_state(arg(&body)?).await?),
+
+        "RunDiagnostics" => json(rpc.run_diagnostics().await?),
+        "TestDnsLeaks" => json(rpc.test_dns_leaks().await?),
+
+        "SetLogLevel" => {
+            let change: LogLevelChange = arg(&body)?;
+            json(
+                rpc.set_log_level(
+                    change.module,
+                    change.level,
+                    change.revert_after_secs.map(Duration::from_secs),
+                )
+                .await?,
+            )
+        }
+        "GetLogLevels" => json(rpc.get_log_levels().await?),
+        "ResetLogLevels" => json(rpc.reset_log_levels().await?),
+
+        _ => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("Unknown RPC: {name}"),
+        )),
+    }
+}
+
+/// Stream daemon events as server-sent events, until the client disconnects.
+async fn events() -> Result<Response<Body>, ApiError> {
+    let mut rpc = MullvadProxyClient::new().await?;
+    let (mut sender, body) = Body::channel();
+
+    tokio::spawn(async move {
+        let mut events = match rpc.events_listen().await {
+            Ok(events) => events,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to listen for events")
+                );
+                return;
+            }
+        };
+        while let Some(event) = events.next().await {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to receive event")
+                    );
+                    return;
+                }
+            };
+            let message = match server_sent_event(&event) {
+                Ok(message) => message,
+                Err(error) => {
+                    log::error!("{}", error.display_chain_with_msg("Failed to encode event"));
+                    continue;
+                }
+            };
+            if sender.send_data(Bytes::from(message)).await.is_err() {
+                // The client disconnected
+                return;
+            }
+        }
+    });
+
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/event-stream"),
+    );
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    Ok(response)
+}
+
+fn server_sent_event(event: &DaemonEvent) -> Result<String, serde_json::Error> {
+    let (name, data) = match event {
+        DaemonEvent::TunnelState(state) => ("tunnel_state", serde_json::to_string(state)?),
+        DaemonEvent::Settings(settings) => ("settings", serde_json::to_string(settings)?),
+        DaemonEvent::RelayList(relay_list) => ("relay_list", serde_json::to_string(relay_list)?),
+        DaemonEvent::AppVersionInfo(info) => ("version_info", serde_json::to_string(info)?),
+        DaemonEvent::Device(device) => ("device", serde_json::to_string(device)?),
+        DaemonEvent::RemoveDevice(device) => ("remove_device", serde_json::to_string(device)?),
+        DaemonEvent::NewAccessMethod(method) => {
+            ("new_access_method", serde_json::to_string(method)?)
+        }
+        DaemonEvent::AccountExpiry(notification) => {
+            ("account_expiry", serde_json::to_string(notification)?)
+        }
+        DaemonEvent::RelayListChanged(diff) => ("relay_list_changed", serde_json::to_string(diff)?),
+        DaemonEvent::DataCapReached(event) => ("data_cap_reached", serde_json::to_string(event)?),
+    };
+    Ok(format!("event: {name}\ndata: {data}\n\n"))
+}
+
+fn arg<T: DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body).map_err(|error| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid argument: {error}"),
+        )
+    })
+}
+
+fn json<T: Serialize>(value: T) -> Result<Response<Body>, ApiError> {
+    let body = serde_json::to_vec(&value)
+        .map_err(|error| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))?;
+    Ok(json_response(StatusCode::OK, body))
+}
+
+fn json_response(status: StatusCode, body: Vec<u8>) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// An error that is returned to the client as `{ "error": "<message>" }`.
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<mullvad_management_interface::Error> for ApiError {
+    fn from(error: mullvad_management_interface::Error) -> Self {
+        use mullvad_management_interface::Error;
+
+        match error {
+            Error::Rpc(status) => {
+                let http_status = match status.code() {
+                    Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+                        StatusCode::BAD_REQUEST
+                    }
+                    Code::NotFound => StatusCode::NOT_FOUND,
+                    Code::AlreadyExists => StatusCode::CONFLICT,
+                    Code::PermissionDenied | Code::Unauthenticated => StatusCode::FORBIDDEN,
+                    Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                ApiError::new(http_status, status.message())
+            }
+            Error::GrpcTransportError(_) => {
+                ApiError::new(StatusCode::SERVICE_UNAVAILABLE, error.display_chain())
+            }
+            Error::InvalidResponse(_) => {
+                ApiError::new(StatusCode::BAD_GATEWAY, error.display_chain())
+            }
+            // The remaining errors are mapped from RPC errors by the client, e.g. when the
+            // account has too many devices
+            error => ApiError::new(StatusCode::BAD_REQUEST, error.to_string()),
+        }
+    }
+}
+
+impl From<ApiError> for Response<Body> {
+    fn from(error: ApiError) -> Self {
+        let body = serde_json::json!({ "error": error.message });
+        json_response(error.status, body.to_string().into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_not_loopback() {
+        let address = "0.0.0.0:0".parse().unwrap();
+        let result = spawn(
+            address,
+            Path::new("/nonexistent"),
+            AccessPolicy::allow_all(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::NotLoopback(_))));
+    }
+
+    #[tokio::test]
+    async fn test_body_size_limit() {
+        let body = read_body(Body::from(vec![0u8; MAX_BODY_SIZE])).await;
+        assert_eq!(body.ok().map(|body| body.len()), Some(MAX_BODY_SIZE));
+
+        let body = read_body(Body::from(vec![0u8; MAX_BODY_SIZE + 1])).await;
+        assert_eq!(
+            body.err().map(|error| error.status),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+
+        // A body without a known length must be limited while it is being read
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            let chunk = Bytes::from(vec![0u8; 1024 * 1024]);
+            while sender.send_data(chunk.clone()).await.is_ok() {}
+        });
+        assert_eq!(
+            read_body(body).await.err().map(|error| error.status),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+    }
+
+    #[test]
+    fn test_authorize() {
+        assert!(authorize(&AccessPolicy::allow_all(), "FactoryReset").is_ok());
+
+        let policy = AccessPolicy::parse(r#"{ "default": ["status", "tunnel"] }"#).unwrap();
+        assert!(authorize(&policy, "GetTunnelState").is_ok());
+        assert!(authorize(&policy, "ConnectTunnel").is_ok());
+        assert!(authorize(&policy, "EventsListen").is_ok());
+        for name in ["SetAllowLan", "LoginAccount", "FactoryReset", "UnknownRpc"] {
+            assert_eq!(
+                authorize(&policy, name).err().map(|error| error.status),
+                Some(StatusCode::FORBIDDEN),
+                "{name} should be denied"
+            );
+        }
+    }
+}
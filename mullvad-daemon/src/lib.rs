@@ -2,25 +2,38 @@
 #![allow(rustdoc::private_intra_doc_links)]
 
 mod access_method;
+pub mod access_policy;
+mod account_expiry;
 pub mod account_history;
 mod api;
 mod api_address_updater;
 #[cfg(not(target_os = "android"))]
+mod captive_portal;
+#[cfg(not(target_os = "android"))]
 mod cleanup;
 mod custom_list;
+mod custom_relay;
+mod data_usage;
 pub mod device;
+mod diagnostics;
 mod dns;
+mod dns_leak;
 pub mod exception_logging;
 mod geoip;
+#[cfg(not(target_os = "android"))]
+pub mod http_gateway;
 pub mod logging;
 #[cfg(target_os = "macos")]
 mod macos;
 pub mod management_interface;
+#[cfg(not(target_os = "android"))]
+pub mod metrics;
 mod migrations;
 mod relay_list;
 #[cfg(not(target_os = "android"))]
 pub mod rpc_uniqueness_check;
 pub mod runtime;
+mod schedule;
 pub mod settings;
 pub mod shutdown;
 mod target_state;
@@ -30,7 +43,7 @@ mod version_check;
 
 use crate::target_state::PersistentTargetState;
 use api::AccessMethodEvent;
-use device::{AccountEvent, PrivateAccountAndDevice, PrivateDeviceEvent};
+use device::{AccountEvent, PrivateAccountAndDevice, PrivateDeviceEvent, PrivateDeviceState};
 use futures::{
     channel::{mpsc, oneshot},
     future::{abortable, AbortHandle, Future},
@@ -48,20 +61,27 @@ use mullvad_types::settings::SplitApp;
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
     access_method::{AccessMethod, AccessMethodSetting},
-    account::{AccountData, AccountToken, VoucherSubmission},
+    account::{AccountData, AccountToken, ExpiryNotification, SavedAccount, VoucherSubmission},
     auth_failed::AuthFailed,
     custom_list::CustomList,
-    device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, RemoveDeviceEvent},
+    custom_relay::{custom_relay_country, CustomRelay},
+    data_usage::{DataCap, DataCapReached, DataUsage},
+    device::{
+        Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, LastConnection,
+        RemoveDeviceEvent,
+    },
+    diagnostics::DiagnosticsReport,
     features::{FeatureIndicator, FeatureIndicators},
     location::{GeoIpLocation, LocationEventData},
     relay_constraints::{
         BridgeSettings, BridgeState, BridgeType, ObfuscationSettings, RelayOverride, RelaySettings,
     },
-    relay_list::RelayList,
-    settings::{DnsOptions, DnsState, Settings},
-    states::{TargetState, TunnelState},
+    relay_list::{RelayList, RelayListDiff},
+    schedule::ScheduleRule,
+    settings::{DnsOptions, DnsState, LogSinks, Settings},
+    states::{RecentError, TargetState, TunnelState},
     version::{AppVersion, AppVersionInfo},
-    wireguard::{PublicKey, QuantumResistantState, RotationInterval},
+    wireguard::{PublicKey, QuantumResistantAlgorithms, QuantumResistantState, RotationInterval},
 };
 use relay_list::{RelayListUpdater, RelayListUpdaterHandle, RELAYS_FILENAME};
 use settings::SettingsPersister;
@@ -70,6 +90,7 @@ use std::collections::HashSet;
 #[cfg(target_os = "android")]
 use std::os::unix::io::RawFd;
 use std::{
+    collections::VecDeque,
     marker::PhantomData,
     path::PathBuf,
     pin::Pin,
@@ -86,7 +107,7 @@ use talpid_types::android::AndroidContext;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 use talpid_types::{
-    net::{IpVersion, ObfuscationType, TunnelType},
+    net::{dns::DnsLeakTestResult, IpVersion, ObfuscationType, TunnelType},
     tunnel::{ErrorStateCause, TunnelStateTransition},
     ErrorExt,
 };
@@ -95,6 +116,9 @@ use tokio::io;
 /// Delay between generating a new WireGuard key and reconnecting
 const WG_RECONNECT_DELAY: Duration = Duration::from_secs(4 * 60);
 
+/// How many error states to remember for problem reports.
+const MAX_RECENT_ERRORS: usize = 10;
+
 pub type ResponseTx<T, E> = oneshot::Sender<Result<T, E>>;
 
 #[derive(thiserror::Error, Debug)]
@@ -207,6 +231,8 @@ pub enum DaemonCommand {
     Reconnect(oneshot::Sender<bool>),
     /// Request the current state.
     GetState(oneshot::Sender<TunnelState>),
+    /// Return the error states that the tunnel has recently entered, oldest first.
+    GetRecentErrors(oneshot::Sender<Vec<RecentError>>),
     CreateNewAccount(ResponseTx<String, Error>),
     /// Request the metadata for an account.
     GetAccountData(
@@ -221,11 +247,30 @@ pub enum DaemonCommand {
     GetAccountHistory(oneshot::Sender<Option<AccountToken>>),
     /// Remove the last used account, if there is one
     ClearAccountHistory(ResponseTx<(), Error>),
+    /// Return all accounts that have been saved under a name.
+    ListSavedAccounts(oneshot::Sender<Vec<SavedAccount>>),
+    /// Save an account under a name, so that it can be switched to.
+    SaveAccount(ResponseTx<(), Error>, SavedAccount),
+    /// Remove a saved account. Its stored device is removed unless the account is in use.
+    RemoveSavedAccount(ResponseTx<(), Error>, String),
+    /// Log in on the account saved under the given name, reusing its stored device if possible.
+    SwitchAccount(ResponseTx<(), Error>, String),
+    /// Return the expiry notification that currently applies, if the account is about to run
+    /// out of time or already has.
+    GetAccountExpiryState(oneshot::Sender<Option<ExpiryNotification>>),
+    /// Set the hours before the account expires at which clients are notified.
+    SetExpiryNotificationHours(ResponseTx<(), settings::Error>, Vec<u32>),
     /// Get the list of countries and cities where there are relays.
     GetRelayLocations(oneshot::Sender<RelayList>),
     /// Trigger an asynchronous relay list update. This returns before the relay list is actually
     /// updated.
     UpdateRelayLocations,
+    /// Return recent changes to the relay list, oldest first.
+    GetRelayListChanges(oneshot::Sender<Vec<RelayListDiff>>),
+    /// Serialize the current relay list so that it can be imported on another machine.
+    ExportRelayList(ResponseTx<String, relay_list::Error>),
+    /// Validate and replace the current relay list with an exported one.
+    ImportRelayList(ResponseTx<(), relay_list::Error>, String),
     /// Log in with a given account and create a new device.
     LoginAccount(ResponseTx<(), Error>, AccountToken),
     /// Log out of the current account and remove the device, if they exist.
@@ -256,8 +301,14 @@ pub enum DaemonCommand {
     SetBridgeState(ResponseTx<(), settings::Error>, BridgeState),
     /// Set if IPv6 should be enabled in the tunnel
     SetEnableIpv6(ResponseTx<(), settings::Error>, bool),
-    /// Set whether to enable PQ PSK exchange in the tunnel
-    SetQuantumResistantTunnel(ResponseTx<(), settings::Error>, QuantumResistantState),
+    /// Set whether to enable PQ PSK exchange in the tunnel, and which KEMs to use if given
+    SetQuantumResistantTunnel(
+        ResponseTx<(), settings::Error>,
+        QuantumResistantState,
+        Option<QuantumResistantAlgorithms>,
+    ),
+    /// Set how often to renegotiate the PQ-safe PSK while connected
+    SetQuantumResistantRekeyInterval(ResponseTx<(), settings::Error>, Option<Duration>),
     /// Set DAITA settings for the tunnel
     #[cfg(daita)]
     SetDaitaSettings(ResponseTx<(), settings::Error>, DaitaSettings),
@@ -267,6 +318,8 @@ pub enum DaemonCommand {
     SetRelayOverride(ResponseTx<(), settings::Error>, RelayOverride),
     /// Remove all relay override options
     ClearAllRelayOverrides(ResponseTx<(), settings::Error>),
+    /// Set additional destinations for daemon logs
+    SetLogSinks(ResponseTx<(), settings::Error>, LogSinks),
     /// Toggle macOS network check leak
     /// Set MTU for wireguard tunnels
     SetWireguardMtu(ResponseTx<(), settings::Error>, Option<u16>),
@@ -288,6 +341,21 @@ pub enum DaemonCommand {
     UpdateCustomList(ResponseTx<(), Error>, CustomList),
     /// Remove all custom lists
     ClearCustomLists(ResponseTx<(), Error>),
+    /// Add a self-hosted WireGuard server to the relay list
+    AddCustomRelay(ResponseTx<(), Error>, CustomRelay),
+    /// Remove a custom relay by name
+    RemoveCustomRelay(ResponseTx<(), Error>, String),
+    /// Add a rule that connects or disconnects the tunnel at certain times
+    AddScheduleRule(ResponseTx<(), Error>, ScheduleRule),
+    /// Remove a schedule rule by name
+    RemoveScheduleRule(ResponseTx<(), Error>, String),
+    /// Get the data transferred through the tunnel, per day and relay
+    GetDataUsage(oneshot::Sender<DataUsage>),
+    /// Set the monthly limit on the data transferred through the tunnel
+    SetDataCap(ResponseTx<(), settings::Error>, DataCap),
+    /// Probe for a captive portal, and allow traffic to it for some time if one is found
+    #[cfg(not(target_os = "android"))]
+    AllowCaptivePortal(ResponseTx<String, captive_portal::Error>, Duration),
     /// Add API access methods
     AddApiAccessMethod(
         ResponseTx<mullvad_types::access_method::Id, Error>,
@@ -372,6 +440,11 @@ pub enum DaemonCommand {
     ExportJsonSettings(ResponseTx<String, settings::patch::Error>),
     /// Request the current feature indicators.
     GetFeatureIndicators(oneshot::Sender<FeatureIndicators>),
+    /// Run the connection troubleshooter
+    RunDiagnostics(oneshot::Sender<DiagnosticsReport>),
+    /// Check that DNS requests only reach the resolvers used by the tunnel. `None` is returned
+    /// unless the tunnel is connected.
+    TestDnsLeaks(oneshot::Sender<Option<DnsLeakTestResult>>),
 }
 
 /// All events that can happen in the daemon. Sent from various threads and exposed interfaces.
@@ -398,6 +471,21 @@ pub(crate) enum InternalDaemonEvent {
     LocationEvent(LocationEventData),
     /// A generic event for when any settings change.
     SettingsChanged,
+    /// The next account expiry notification threshold has been reached.
+    AccountExpiryTimer,
+    /// A new minute has started, so a different schedule rule may apply.
+    ScheduleTimer,
+    /// The byte counters of the tunnel should be read.
+    DataUsageTimer,
+    /// A new relay list that differs from the previous one has been received.
+    RelayListChanged(RelayListDiff),
+    /// A DNS leak test has completed while connected.
+    DnsLeakTestEvent(DnsLeakTestResult),
+    /// The inputs of the connection troubleshooter have been collected.
+    DiagnosticsPrepared(Box<diagnostics::PreparedDiagnostics>),
+    /// The configured DNS blocklists have been (re)loaded.
+    #[cfg(not(target_os = "android"))]
+    DnsBlocklistEvent(dns::BlocklistUpdate),
     /// The split tunnel paths or state were updated.
     #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     ExcludedPathsEvent(ExcludedPathsUpdate, oneshot::Sender<Result<(), Error>>),
@@ -433,6 +521,12 @@ impl From<AccountEvent> for InternalDaemonEvent {
     }
 }
 
+impl From<RelayListDiff> for InternalDaemonEvent {
+    fn from(diff: RelayListDiff) -> Self {
+        InternalDaemonEvent::RelayListChanged(diff)
+    }
+}
+
 impl From<(AccessMethodEvent, oneshot::Sender<()>)> for InternalDaemonEvent {
     fn from(event: (AccessMethodEvent, oneshot::Sender<()>)) -> Self {
         InternalDaemonEvent::AccessMethodEvent {
@@ -572,6 +666,15 @@ pub trait EventListener: Clone + Send + Sync + 'static {
 
     /// Notify that the api access method changed.
     fn notify_new_access_method_event(&self, new_access_method: AccessMethodSetting);
+
+    /// Notify that the account is about to run out of time, or has run out of time.
+    fn notify_account_expiry(&self, notification: ExpiryNotification);
+
+    /// Notify that relays were added, removed or changed.
+    fn notify_relay_list_changed(&self, diff: RelayListDiff);
+
+    /// Notify that the monthly data cap has been reached.
+    fn notify_data_cap_reached(&self, event: DataCapReached);
 }
 
 pub struct Daemon<L: EventListener> {
@@ -582,12 +685,23 @@ pub struct Daemon<L: EventListener> {
     rx: mpsc::UnboundedReceiver<InternalDaemonEvent>,
     tx: DaemonEventSender,
     reconnection_job: Option<AbortHandle>,
+    dns_leak_monitor: Option<AbortHandle>,
+    dns_leak_detected: bool,
+    #[cfg(not(target_os = "android"))]
+    captive_portal: Option<AbortHandle>,
+    dns_configurator: dns::DnsConfigurator,
     event_listener: L,
     migration_complete: migrations::MigrationComplete,
     settings: SettingsPersister,
     account_history: account_history::AccountHistory,
     device_checker: device::TunnelStateChangeHandler,
     account_manager: device::AccountManagerHandle,
+    expiry_monitor: account_expiry::ExpiryMonitor,
+    scheduler: schedule::Scheduler,
+    data_usage: data_usage::DataUsageTracker,
+    tunnel_stats: talpid_wireguard::stats::StatsHandle,
+    relay_list_changes: VecDeque<RelayListDiff>,
+    recent_errors: VecDeque<RecentError>,
     access_mode_handler: api::AccessModeSelectorHandle,
     api_runtime: mullvad_api::Runtime,
     api_handle: mullvad_api::rest::MullvadRestHandle,
@@ -651,6 +765,9 @@ where
             settings_event_listener.notify_settings(settings.to_owned());
         });
 
+        logging::set_sinks(settings.log_sinks);
+        settings.register_change_listener(|settings| logging::set_sinks(settings.log_sinks));
+
         let initial_selector_config = new_selector_config(&settings);
         let relay_selector = RelaySelector::new(
             initial_selector_config,
@@ -658,6 +775,9 @@ where
             cache_dir.join(RELAYS_FILENAME),
         );
 
+        #[cfg(not(target_os = "android"))]
+        metrics::set_relay_selector(relay_selector.clone());
+
         let settings_relay_selector = relay_selector.clone();
         settings.register_change_listener(move |settings| {
             // Notify relay selector of changes to the settings/selector config
@@ -716,6 +836,9 @@ where
         .await
         .map_err(Error::LoadAccountManager)?;
 
+        #[cfg(not(target_os = "android"))]
+        metrics::set_key_created(data.device().map(|device| device.device.wg_data.created));
+
         let account_history = account_history::AccountHistory::new(
             &settings_dir,
             data.device().map(|device| device.account_token.clone()),
@@ -723,13 +846,22 @@ where
         .await
         .map_err(Error::LoadAccountHistory)?;
 
-        let target_state = if settings.auto_connect {
+        let mut target_state = if settings.auto_connect {
             log::info!("Automatically connecting since auto-connect is turned on");
             PersistentTargetState::force(&cache_dir, TargetState::Secured).await
         } else {
             PersistentTargetState::new(&cache_dir).await
         };
 
+        // An active schedule rule takes precedence over auto-connect. The state that would
+        // otherwise have been used is restored when the rule no longer applies.
+        let mut scheduler = schedule::Scheduler::new(&internal_event_tx, &settings_dir).await;
+        if let Some(rule) = settings.schedule.active_rule(chrono::Utc::now()) {
+            log::info!("Applying schedule rule \"{}\"", rule.name);
+            scheduler.save_target_state(*target_state).await;
+            target_state.set(rule.action.target_state()).await;
+        }
+
         #[cfg(any(windows, target_os = "android", target_os = "macos"))]
         let exclude_paths = if settings.split_tunnel.enable_exclusions {
             settings
@@ -767,6 +899,25 @@ where
             let _ = settings_changed_event_sender.send(InternalDaemonEvent::SettingsChanged);
         });
 
+        let expiry_monitor = account_expiry::ExpiryMonitor::new(
+            settings.expiry_notification_hours.clone(),
+            account_manager.clone(),
+            &internal_event_tx,
+        );
+
+        let data_usage = data_usage::DataUsageTracker::new(&cache_dir, &internal_event_tx).await;
+        let tunnel_stats = talpid_wireguard::stats::StatsHandle::default();
+        #[cfg(not(target_os = "android"))]
+        {
+            metrics::set_tunnel_stats(tunnel_stats.clone());
+            metrics::set_request_stats(api_runtime.request_stats());
+        }
+
+        let mut dns_configurator = dns::DnsConfigurator::new(&internal_event_tx);
+        let dns_config = dns_configurator
+            .resolvers(&settings.tunnel_options.dns_options)
+            .await;
+
         let (offline_state_tx, offline_state_rx) = mpsc::unbounded();
         #[cfg(target_os = "windows")]
         let (volume_update_tx, volume_update_rx) = mpsc::unbounded();
@@ -774,7 +925,7 @@ where
             tunnel_state_machine::InitialTunnelState {
                 allow_lan: settings.allow_lan,
                 block_when_disconnected: settings.block_when_disconnected,
-                dns_servers: dns::addresses_from_options(&settings.tunnel_options.dns_options),
+                dns_config,
                 allowed_endpoint: access_mode_handler
                     .get_current()
                     .await
@@ -789,6 +940,7 @@ where
             resource_dir.clone(),
             internal_event_tx.to_specialized_sender(),
             offline_state_tx,
+            tunnel_stats.clone(),
             #[cfg(target_os = "windows")]
             volume_update_rx,
             #[cfg(target_os = "android")]
@@ -805,8 +957,12 @@ where
         api::forward_offline_state(api_availability.clone(), offline_state_rx);
 
         let relay_list_listener = event_listener.clone();
-        let on_relay_list_update = move |relay_list: &RelayList| {
+        let relay_list_diff_tx = internal_event_tx.to_specialized_sender();
+        let on_relay_list_update = move |relay_list: &RelayList, diff: RelayListDiff| {
             relay_list_listener.notify_relay_list(relay_list.clone());
+            if !diff.is_empty() {
+                let _ = relay_list_diff_tx.send(diff);
+            }
         };
 
         let mut relay_list_updater = RelayListUpdater::spawn(
@@ -844,12 +1000,23 @@ where
             rx: internal_event_rx,
             tx: internal_event_tx,
             reconnection_job: None,
+            dns_leak_monitor: None,
+            dns_leak_detected: false,
+            #[cfg(not(target_os = "android"))]
+            captive_portal: None,
+            dns_configurator,
             event_listener,
             migration_complete,
             settings,
             account_history,
             device_checker: device::TunnelStateChangeHandler::new(account_manager.clone()),
             account_manager,
+            expiry_monitor,
+            scheduler,
+            data_usage,
+            tunnel_stats,
+            relay_list_changes: VecDeque::new(),
+            recent_errors: VecDeque::new(),
             access_mode_handler,
             api_runtime,
             api_handle,
@@ -962,6 +1129,17 @@ where
             SettingsChanged => {
                 self.handle_feature_indicator_event();
             }
+            AccountExpiryTimer => {
+                let notification = self.expiry_monitor.update();
+                self.notify_account_expiry(notification);
+            }
+            ScheduleTimer => self.apply_schedule().await,
+            DataUsageTimer => self.update_data_usage().await,
+            RelayListChanged(diff) => self.handle_relay_list_changed(diff),
+            DnsLeakTestEvent(result) => self.handle_dns_leak_test_result(result),
+            DiagnosticsPrepared(prepared) => self.on_diagnostics_prepared(*prepared),
+            #[cfg(not(target_os = "android"))]
+            DnsBlocklistEvent(update) => self.dns_configurator.handle_blocklist_update(update),
             #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             ExcludedPathsEvent(update, tx) => self.handle_new_excluded_paths(update, tx).await,
         }
@@ -972,9 +1150,15 @@ where
         &mut self,
         tunnel_state_transition: TunnelStateTransition,
     ) {
+        // Account for the data of the previous state before the counters are reset
+        self.update_data_usage().await;
         self.reset_rpc_sockets_on_tunnel_state_transition(&tunnel_state_transition);
         self.device_checker
             .handle_state_transition(&tunnel_state_transition);
+        self.update_dns_leak_monitor(matches!(
+            tunnel_state_transition,
+            TunnelStateTransition::Connected(_)
+        ));
 
         let tunnel_state = match tunnel_state_transition {
             TunnelStateTransition::Disconnected { locked_down } => TunnelState::Disconnected {
@@ -997,6 +1181,21 @@ where
             TunnelStateTransition::Error(error_state) => TunnelState::Error(error_state),
         };
 
+        if let TunnelState::Connected { endpoint, .. } = &tunnel_state {
+            if let Some(gateway) = endpoint.tunnel_gateway {
+                self.dns_configurator.set_tunnel_gateway(gateway);
+            }
+        }
+
+        if let TunnelState::Connecting { location, .. } = &tunnel_state {
+            // The data of the previous tunnel has been recorded above
+            self.data_usage.set_relay(
+                location
+                    .as_ref()
+                    .and_then(|location| location.hostname.clone()),
+            );
+        }
+
         if !tunnel_state.is_connected() {
             // Cancel reconnects except when entering the connected state.
             // Exempt the latter because a reconnect scheduled while connecting should not be
@@ -1005,6 +1204,9 @@ where
         }
 
         log::debug!("New tunnel state: {:?}", tunnel_state);
+        logging::set_tunnel_state(&tunnel_state);
+        #[cfg(not(target_os = "android"))]
+        metrics::set_tunnel_state(&tunnel_state);
 
         match tunnel_state {
             TunnelState::Disconnected { .. } => {
@@ -1032,6 +1234,14 @@ where
                     );
                 }
 
+                if self.recent_errors.len() >= MAX_RECENT_ERRORS {
+                    self.recent_errors.pop_front();
+                }
+                self.recent_errors.push_back(RecentError {
+                    time: chrono::Utc::now(),
+                    error_state: error_state.clone(),
+                });
+
                 if let ErrorStateCause::AuthFailed(_) = error_state.cause() {
                     // If time is added outside of the app, no notifications
                     // are received. So we must continually try to reconnect.
@@ -1041,6 +1251,28 @@ where
             _ => {}
         }
 
+        if let TunnelState::Connected {
+            location: Some(location),
+            ..
+        } = &tunnel_state
+        {
+            if let Some(relay) = location.hostname.clone() {
+                let account_manager = self.account_manager.clone();
+                tokio::spawn(async move {
+                    let connection = LastConnection {
+                        relay,
+                        time: chrono::Utc::now(),
+                    };
+                    if let Err(error) = account_manager.record_connection(connection).await {
+                        log::debug!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to record last connection")
+                        );
+                    }
+                });
+            }
+        }
+
         self.tunnel_state = tunnel_state.clone();
         self.event_listener.notify_new_state(tunnel_state);
         self.fetch_am_i_mullvad();
@@ -1177,12 +1409,16 @@ where
             SetTargetState(tx, state) => self.on_set_target_state(tx, state).await,
             Reconnect(tx) => self.on_reconnect(tx),
             GetState(tx) => self.on_get_state(tx),
+            GetRecentErrors(tx) => self.on_get_recent_errors(tx),
             CreateNewAccount(tx) => self.on_create_new_account(tx),
             GetAccountData(tx, account_token) => self.on_get_account_data(tx, account_token),
             GetWwwAuthToken(tx) => self.on_get_www_auth_token(tx).await,
             SubmitVoucher(tx, voucher) => self.on_submit_voucher(tx, voucher),
             GetRelayLocations(tx) => self.on_get_relay_locations(tx),
+            GetRelayListChanges(tx) => self.on_get_relay_list_changes(tx),
             UpdateRelayLocations => self.on_update_relay_locations().await,
+            ExportRelayList(tx) => self.on_export_relay_list(tx),
+            ImportRelayList(tx, data) => self.on_import_relay_list(tx, data).await,
             LoginAccount(tx, account_token) => self.on_login_account(tx, account_token),
             LogoutAccount(tx) => self.on_logout_account(tx),
             GetDevice(tx) => self.on_get_device(tx),
@@ -1193,6 +1429,14 @@ where
             }
             GetAccountHistory(tx) => self.on_get_account_history(tx),
             ClearAccountHistory(tx) => self.on_clear_account_history(tx).await,
+            ListSavedAccounts(tx) => self.on_list_saved_accounts(tx),
+            SaveAccount(tx, account) => self.on_save_account(tx, account).await,
+            RemoveSavedAccount(tx, name) => self.on_remove_saved_account(tx, name).await,
+            SwitchAccount(tx, name) => self.on_switch_account(tx, name).await,
+            GetAccountExpiryState(tx) => self.on_get_account_expiry_state(tx),
+            SetExpiryNotificationHours(tx, hours) => {
+                self.on_set_expiry_notification_hours(tx, hours).await
+            }
             SetRelaySettings(tx, update) => self.on_set_relay_settings(tx, update).await,
             SetAllowLan(tx, allow_lan) => self.on_set_allow_lan(tx, allow_lan).await,
             SetShowBetaReleases(tx, enabled) => self.on_set_show_beta_releases(tx, enabled).await,
@@ -1207,8 +1451,12 @@ where
             }
             SetBridgeState(tx, bridge_state) => self.on_set_bridge_state(tx, bridge_state).await,
             SetEnableIpv6(tx, enable_ipv6) => self.on_set_enable_ipv6(tx, enable_ipv6).await,
-            SetQuantumResistantTunnel(tx, quantum_resistant_state) => {
-                self.on_set_quantum_resistant_tunnel(tx, quantum_resistant_state)
+            SetQuantumResistantTunnel(tx, quantum_resistant_state, algorithms) => {
+                self.on_set_quantum_resistant_tunnel(tx, quantum_resistant_state, algorithms)
+                    .await
+            }
+            SetQuantumResistantRekeyInterval(tx, interval) => {
+                self.on_set_quantum_resistant_rekey_interval(tx, interval)
                     .await
             }
             #[cfg(daita)]
@@ -1220,6 +1468,7 @@ where
                 self.on_set_relay_override(tx, relay_override).await
             }
             ClearAllRelayOverrides(tx) => self.on_clear_all_relay_overrides(tx).await,
+            SetLogSinks(tx, sinks) => self.on_set_log_sinks(tx, sinks).await,
             SetWireguardMtu(tx, mtu) => self.on_set_wireguard_mtu(tx, mtu).await,
             SetWireguardRotationInterval(tx, interval) => {
                 self.on_set_wireguard_rotation_interval(tx, interval).await
@@ -1232,6 +1481,14 @@ where
             DeleteCustomList(tx, id) => self.on_delete_custom_list(tx, id).await,
             UpdateCustomList(tx, update) => self.on_update_custom_list(tx, update).await,
             ClearCustomLists(tx) => self.on_clear_custom_lists(tx).await,
+            AddCustomRelay(tx, custom_relay) => self.on_add_custom_relay(tx, custom_relay).await,
+            RemoveCustomRelay(tx, name) => self.on_remove_custom_relay(tx, name).await,
+            AddScheduleRule(tx, rule) => self.on_add_schedule_rule(tx, rule).await,
+            RemoveScheduleRule(tx, name) => self.on_remove_schedule_rule(tx, name).await,
+            GetDataUsage(tx) => self.on_get_data_usage(tx).await,
+            SetDataCap(tx, data_cap) => self.on_set_data_cap(tx, data_cap).await,
+            #[cfg(not(target_os = "android"))]
+            AllowCaptivePortal(tx, window) => self.on_allow_captive_portal(tx, window),
             GetVersionInfo(tx) => self.on_get_version_info(tx),
             AddApiAccessMethod(tx, name, enabled, access_method) => {
                 self.on_add_access_method(tx, name, enabled, access_method)
@@ -1283,6 +1540,8 @@ where
             ApplyJsonSettings(tx, blob) => self.on_apply_json_settings(tx, blob).await,
             ExportJsonSettings(tx) => self.on_export_json_settings(tx),
             GetFeatureIndicators(tx) => self.on_get_feature_indicators(tx),
+            RunDiagnostics(tx) => self.on_run_diagnostics(tx),
+            TestDnsLeaks(tx) => self.on_test_dns_leaks(tx),
         }
     }
 
@@ -1291,6 +1550,16 @@ where
     }
 
     async fn handle_device_event(&mut self, event: AccountEvent) {
+        #[cfg(not(target_os = "android"))]
+        if let AccountEvent::Device(device_event) = &event {
+            let key_created = match device_event {
+                PrivateDeviceEvent::Login(device)
+                | PrivateDeviceEvent::Updated(device)
+                | PrivateDeviceEvent::RotatedKey(device) => Some(device.device.wg_data.created),
+                PrivateDeviceEvent::Logout | PrivateDeviceEvent::Revoked => None,
+            };
+            metrics::set_key_created(key_created);
+        }
         match &event {
             AccountEvent::Device(PrivateDeviceEvent::Login(device)) => {
                 if let Err(error) = self.account_history.set(device.account_token.clone()).await {
@@ -1303,8 +1572,15 @@ where
                     log::debug!("Initiating tunnel restart because the account token changed");
                     self.reconnect_tunnel();
                 }
+                self.expiry_monitor.set_expiry(None);
+                let account_manager = self.account_manager.clone();
+                tokio::spawn(async move {
+                    // The result is received as an `AccountEvent::Expiry`
+                    let _ = account_manager.check_expiry().await;
+                });
             }
             AccountEvent::Device(PrivateDeviceEvent::Logout) => {
+                self.expiry_monitor.set_expiry(None);
                 log::info!("Disconnecting because account token was cleared");
                 self.set_target_state(TargetState::Unsecured).await;
             }
@@ -1315,7 +1591,20 @@ where
                     self.connect_tunnel();
                 }
             }
-            AccountEvent::Device(PrivateDeviceEvent::RotatedKey(_)) => {
+            AccountEvent::Device(PrivateDeviceEvent::RotatedKey(device)) => {
+                if !self.settings.custom_relays.is_empty() {
+                    log::warn!(
+                        "The WireGuard key was rotated. Custom relays must be updated to accept \
+                         the new public key {}: {}",
+                        device.device.wg_data.private_key.public_key(),
+                        self.settings
+                            .custom_relays
+                            .iter()
+                            .map(|relay| relay.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
                 if self.get_target_tunnel_type() == Some(TunnelType::Wireguard) {
                     self.schedule_reconnect(WG_RECONNECT_DELAY);
                 }
@@ -1337,12 +1626,26 @@ where
             }
             _ => (),
         }
+        if let AccountEvent::Expiry(expiry) = event {
+            let notification = self.expiry_monitor.set_expiry(Some(expiry));
+            self.notify_account_expiry(notification);
+        }
         if let AccountEvent::Device(event) = event {
             self.event_listener
                 .notify_device_event(DeviceEvent::from(event));
         }
     }
 
+    fn notify_account_expiry(&self, notification: Option<ExpiryNotification>) {
+        if let Some(notification) = notification {
+            match notification.threshold_hours {
+                Some(hours) => log::info!("Account expires in less than {hours} hours"),
+                None => log::info!("Account has run out of time"),
+            }
+            self.event_listener.notify_account_expiry(notification);
+        }
+    }
+
     fn handle_access_method_event(
         &mut self,
         event: AccessMethodEvent,
@@ -1463,6 +1766,14 @@ where
         Self::oneshot_send(tx, self.tunnel_state.clone(), "current state");
     }
 
+    fn on_get_recent_errors(&self, tx: oneshot::Sender<Vec<RecentError>>) {
+        Self::oneshot_send(
+            tx,
+            self.recent_errors.iter().cloned().collect(),
+            "recent errors",
+        );
+    }
+
     fn on_is_performing_post_upgrade(&self, tx: oneshot::Sender<bool>) {
         let performing_post_upgrade = !self.migration_complete.is_complete();
         Self::oneshot_send(tx, performing_post_upgrade, "performing post upgrade");
@@ -1547,13 +1858,73 @@ where
     }
 
     fn on_get_relay_locations(&mut self, tx: oneshot::Sender<RelayList>) {
-        Self::oneshot_send(tx, self.relay_selector.get_relays(), "relay locations");
+        let mut relay_list = self.relay_selector.get_relays();
+        relay_list
+            .countries
+            .extend(custom_relay_country(&self.settings.custom_relays));
+        Self::oneshot_send(tx, relay_list, "relay locations");
     }
 
     async fn on_update_relay_locations(&mut self) {
         self.relay_list_updater.update().await;
     }
 
+    fn on_get_relay_list_changes(&self, tx: oneshot::Sender<Vec<RelayListDiff>>) {
+        Self::oneshot_send(
+            tx,
+            self.relay_list_changes.iter().cloned().collect(),
+            "relay list changes",
+        );
+    }
+
+    fn handle_relay_list_changed(&mut self, mut diff: RelayListDiff) {
+        log::info!(
+            "Relay list changed: {} added, {} removed, {} changes",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.changed.len()
+        );
+
+        let pinned = relay_list::pinned_hostnames(&self.settings);
+        diff.removed_pinned = diff
+            .removed
+            .iter()
+            .filter(|hostname| pinned.contains(hostname.as_str()))
+            .cloned()
+            .collect();
+        for hostname in &diff.removed_pinned {
+            log::warn!(
+                "Relay {hostname} was removed from the relay list, but is still referred to by the settings"
+            );
+        }
+
+        if self.relay_list_changes.len() >= relay_list::MAX_RELAY_LIST_CHANGES {
+            self.relay_list_changes.pop_front();
+        }
+        self.relay_list_changes.push_back(diff.clone());
+        self.event_listener.notify_relay_list_changed(diff);
+    }
+
+    fn on_export_relay_list(&mut self, tx: ResponseTx<String, relay_list::Error>) {
+        let result = relay_list::export::export(
+            &self.relay_selector.get_relays(),
+            self.relay_selector.last_updated(),
+        );
+        Self::oneshot_send(tx, result, "export_relay_list response");
+    }
+
+    async fn on_import_relay_list(&mut self, tx: ResponseTx<(), relay_list::Error>, data: String) {
+        let result = match relay_list::export::import(&data, self.relay_selector.last_updated()) {
+            Ok(relay_list) if relay_list.etag == self.relay_selector.etag() => {
+                log::debug!("Imported relay list is already in use");
+                Ok(())
+            }
+            Ok(relay_list) => self.relay_list_updater.import(relay_list).await,
+            Err(error) => Err(error),
+        };
+        Self::oneshot_send(tx, result, "import_relay_list response");
+    }
+
     fn on_login_account(&mut self, tx: ResponseTx<(), Error>, account_token: String) {
         let account_manager = self.account_manager.clone();
         let availability = self.api_runtime.availability_handle();
@@ -1619,16 +1990,30 @@ where
     }
 
     fn on_list_devices(&self, tx: ResponseTx<Vec<Device>, Error>, token: AccountToken) {
-        let service = self.account_manager.device_service.clone();
+        let account_manager = self.account_manager.clone();
         tokio::spawn(async move {
-            Self::oneshot_send(
-                tx,
-                service
-                    .list_devices(token)
-                    .await
-                    .map_err(Error::ListDevicesError),
-                "list_devices response",
-            );
+            let result = account_manager
+                .device_service
+                .list_devices(token)
+                .await
+                .map_err(Error::ListDevicesError);
+            let result = match (result, account_manager.data().await) {
+                (Ok(mut devices), Ok(PrivateDeviceState::LoggedIn(config))) => {
+                    // The API does not know about the key age or the last connection of the
+                    // current device, so fill those in from the local device data.
+                    let current = Device::from(config.device);
+                    if let Some(device) = devices
+                        .iter_mut()
+                        .find(|device| device.id == current.id && device.pubkey == current.pubkey)
+                    {
+                        device.pubkey_created = current.pubkey_created;
+                        device.last_connection = current.last_connection;
+                    }
+                    Ok(devices)
+                }
+                (result, _) => result,
+            };
+            Self::oneshot_send(tx, result, "list_devices response");
         });
     }
 
@@ -1678,6 +2063,139 @@ where
         Self::oneshot_send(tx, result, "clear_account_history response");
     }
 
+    fn on_list_saved_accounts(&self, tx: oneshot::Sender<Vec<SavedAccount>>) {
+        Self::oneshot_send(
+            tx,
+            self.account_history.saved_accounts(),
+            "list_saved_accounts response",
+        );
+    }
+
+    async fn on_save_account(&mut self, tx: ResponseTx<(), Error>, account: SavedAccount) {
+        let result = self
+            .account_history
+            .save_account(account)
+            .await
+            .map_err(Error::AccountHistory);
+        Self::oneshot_send(tx, result, "save_account response");
+    }
+
+    async fn on_remove_saved_account(&mut self, tx: ResponseTx<(), Error>, name: String) {
+        let account = match self.account_history.remove_saved_account(&name).await {
+            Ok(account) => account,
+            Err(error) => {
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::AccountHistory(error)),
+                    "remove_saved_account response",
+                );
+                return;
+            }
+        };
+
+        let still_saved = self
+            .account_history
+            .saved_accounts()
+            .iter()
+            .any(|saved| saved.account_token == account.account_token);
+        let account_manager = self.account_manager.clone();
+        tokio::spawn(async move {
+            let result = async {
+                if !still_saved {
+                    account_manager
+                        .forget_stored_device(account.account_token)
+                        .await
+                        .map_err(Error::RemoveDeviceError)?;
+                }
+                Ok(())
+            };
+            Self::oneshot_send(tx, result.await, "remove_saved_account response");
+        });
+    }
+
+    async fn on_switch_account(&mut self, tx: ResponseTx<(), Error>, name: String) {
+        let account_token = match self.account_history.saved_account(&name) {
+            Ok(account_token) => account_token,
+            Err(error) => {
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::AccountHistory(error)),
+                    "switch_account response",
+                );
+                return;
+            }
+        };
+
+        let account_manager = self.account_manager.clone();
+        let availability = self.api_runtime.availability_handle();
+        let saved_accounts = self.account_history.saved_accounts();
+        tokio::spawn(async move {
+            let result = async {
+                // Only keep the current device if it can be switched back to
+                let current = account_manager
+                    .data()
+                    .await
+                    .ok()
+                    .and_then(|state| state.into_device());
+                let keep_current = current.is_some_and(|current| {
+                    saved_accounts
+                        .iter()
+                        .any(|saved| saved.account_token == current.account_token)
+                });
+                account_manager
+                    .switch(account_token, keep_current)
+                    .await
+                    .map_err(|error| {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Switching account failed")
+                        );
+                        Error::LoginError(error)
+                    })?;
+
+                availability.resume_background();
+
+                Ok(())
+            };
+            Self::oneshot_send(tx, result.await, "switch_account response");
+        });
+    }
+
+    fn on_get_account_expiry_state(&self, tx: oneshot::Sender<Option<ExpiryNotification>>) {
+        Self::oneshot_send(
+            tx,
+            self.expiry_monitor.notification(),
+            "get_account_expiry_state response",
+        );
+    }
+
+    async fn on_set_expiry_notification_hours(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        mut hours: Vec<u32>,
+    ) {
+        hours.sort_unstable_by(|a, b| b.cmp(a));
+        hours.dedup();
+        let new_hours = hours.clone();
+        match self
+            .settings
+            .update(move |settings| settings.expiry_notification_hours = new_hours)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_expiry_notification_hours response");
+                if settings_changed {
+                    let notification = self.expiry_monitor.set_thresholds(hours);
+                    self.notify_account_expiry(notification);
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_expiry_notification_hours response");
+            }
+        }
+    }
+
     fn on_get_version_info(&mut self, tx: oneshot::Sender<Result<AppVersionInfo, Error>>) {
         let mut handle = self.version_updater_handle.clone();
         tokio::spawn(async move {
@@ -1717,7 +2235,15 @@ where
             );
         }
 
-        if let Err(error) = self.account_history.clear().await {
+        if let Err(error) = self.account_manager.forget_all_stored_devices().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to remove stored devices")
+            );
+            last_error = Some("Failed to remove stored devices");
+        }
+
+        if let Err(error) = self.account_history.clear_all().await {
             log::error!(
                 "{}",
                 error.display_chain_with_msg("Failed to clear account history")
@@ -2258,11 +2784,16 @@ where
         &mut self,
         tx: ResponseTx<(), settings::Error>,
         quantum_resistant: QuantumResistantState,
+        algorithms: Option<QuantumResistantAlgorithms>,
     ) {
         match self
             .settings
             .update(|settings| {
-                settings.tunnel_options.wireguard.quantum_resistant = quantum_resistant
+                let wireguard = &mut settings.tunnel_options.wireguard;
+                wireguard.quantum_resistant = quantum_resistant;
+                if let Some(algorithms) = algorithms {
+                    wireguard.quantum_resistant_algorithms = algorithms;
+                }
             })
             .await
         {
@@ -2281,6 +2812,37 @@ where
         }
     }
 
+    async fn on_set_quantum_resistant_rekey_interval(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        interval: Option<Duration>,
+    ) {
+        match self
+            .settings
+            .update(|settings| {
+                settings
+                    .tunnel_options
+                    .wireguard
+                    .quantum_resistant_rekey_interval = interval
+            })
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_quantum_resistant_rekey_interval response");
+                if settings_changed
+                    && self.get_connected_tunnel_type() == Some(TunnelType::Wireguard)
+                {
+                    log::info!("Reconnecting because the PQ rekey interval changed");
+                    self.reconnect_tunnel();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_quantum_resistant_rekey_interval response");
+            }
+        }
+    }
+
     #[cfg(daita)]
     async fn on_set_daita_settings(
         &mut self,
@@ -2320,10 +2882,12 @@ where
             Ok(settings_changed) => {
                 if settings_changed {
                     let settings = self.settings.to_settings();
-                    let resolvers =
-                        dns::addresses_from_options(&settings.tunnel_options.dns_options);
+                    let dns_config = self
+                        .dns_configurator
+                        .resolvers(&settings.tunnel_options.dns_options)
+                        .await;
                     self.send_tunnel_command(TunnelCommand::Dns(
-                        resolvers,
+                        dns_config,
                         oneshot_map(tx, |tx, ()| {
                             Self::oneshot_send(tx, Ok(()), "set_dns_options response");
                         }),
@@ -2381,6 +2945,22 @@ where
         }
     }
 
+    async fn on_set_log_sinks(&mut self, tx: ResponseTx<(), settings::Error>, sinks: LogSinks) {
+        // The sinks are applied by a settings change listener
+        let result = self
+            .settings
+            .update(move |settings| settings.log_sinks = sinks)
+            .await
+            .map(|_| ());
+        if let Err(error) = &result {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to save settings")
+            );
+        }
+        Self::oneshot_send(tx, result, "set_log_sinks response");
+    }
+
     async fn on_set_wireguard_mtu(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2491,6 +3071,52 @@ where
         Self::oneshot_send(tx, result, "clear_custom_lists response");
     }
 
+    async fn on_add_custom_relay(&mut self, tx: ResponseTx<(), Error>, custom_relay: CustomRelay) {
+        let result = self.add_custom_relay(custom_relay).await;
+        Self::oneshot_send(tx, result, "add_custom_relay response");
+    }
+
+    async fn on_remove_custom_relay(&mut self, tx: ResponseTx<(), Error>, name: String) {
+        let result = self.remove_custom_relay(name).await;
+        Self::oneshot_send(tx, result, "remove_custom_relay response");
+    }
+
+    async fn on_add_schedule_rule(&mut self, tx: ResponseTx<(), Error>, rule: ScheduleRule) {
+        let result = self.add_schedule_rule(rule).await;
+        Self::oneshot_send(tx, result, "add_schedule_rule response");
+    }
+
+    async fn on_remove_schedule_rule(&mut self, tx: ResponseTx<(), Error>, name: String) {
+        let result = self.remove_schedule_rule(name).await;
+        Self::oneshot_send(tx, result, "remove_schedule_rule response");
+    }
+
+    async fn on_get_data_usage(&mut self, tx: oneshot::Sender<DataUsage>) {
+        self.update_data_usage().await;
+        let usage = self.data_usage.usage().clone();
+        Self::oneshot_send(tx, usage, "get_data_usage response");
+    }
+
+    async fn on_set_data_cap(&mut self, tx: ResponseTx<(), settings::Error>, data_cap: DataCap) {
+        match self
+            .settings
+            .update(move |settings| settings.data_cap = data_cap)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_data_cap response");
+                if settings_changed {
+                    // The new cap may be reached even if the old one was this month
+                    self.data_usage.reset_cap();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_data_cap response");
+            }
+        }
+    }
+
     async fn on_add_access_method(
         &mut self,
         tx: ResponseTx<mullvad_types::access_method::Id, Error>,
@@ -2843,9 +3469,11 @@ where
             .tunnel_options
             .dns_options
             .default_options
-            .any_blockers_enabled();
+            .any_blockers_enabled()
+            || !settings.tunnel_options.dns_options.blocklists.is_empty();
         let custom_dns = settings.tunnel_options.dns_options.state == DnsState::Custom;
         let server_ip_override = !settings.relay_overrides.is_empty();
+        let dns_leak = self.dns_leak_detected;
 
         let generic_features = [
             (split_tunneling, FeatureIndicator::SplitTunneling),
@@ -2854,6 +3482,7 @@ where
             (dns_content_blockers, FeatureIndicator::DnsContentBlockers),
             (custom_dns, FeatureIndicator::CustomDns),
             (server_ip_override, FeatureIndicator::ServerIpOverride),
+            (dns_leak, FeatureIndicator::DnsLeak),
         ];
 
         // Pick protocol-specific features and whether they are currently enabled.
@@ -2932,6 +3561,7 @@ fn new_selector_config(settings: &Settings) -> SelectorConfig {
         obfuscation_settings: settings.obfuscation_settings.clone(),
         custom_lists: settings.custom_lists.clone(),
         relay_overrides: settings.relay_overrides.clone(),
+        custom_relays: settings.custom_relays.clone(),
     }
 }
 
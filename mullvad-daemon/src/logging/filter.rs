@@ -0,0 +1,202 @@
+//! Filtering of log records by module. The filter starts out with the level that the daemon was
+//! started with and the levels of silenced crates. Levels for individual modules can then be
+//! changed at runtime, optionally reverting after some time, without restarting the daemon.
+
+use chrono::{DateTime, Utc};
+use mullvad_types::log_level::{LogLevel, ModuleLogLevel};
+use once_cell::sync::Lazy;
+use std::{sync::RwLock, time::Duration};
+
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid module path: {0:?}")]
+pub struct InvalidModuleError(String);
+
+struct Override {
+    id: u64,
+    module: String,
+    level: LogLevel,
+    revert_at: Option<DateTime<Utc>>,
+}
+
+struct Filter {
+    default: log::LevelFilter,
+    /// Levels that were set when the logger was initialized.
+    modules: Vec<(String, log::LevelFilter)>,
+    /// Levels that were set at runtime. These take precedence over `modules`.
+    overrides: Vec<Override>,
+    next_id: u64,
+}
+
+impl Filter {
+    fn level(&self, target: &str) -> log::LevelFilter {
+        let overridden = self
+            .overrides
+            .iter()
+            .filter(|entry| matches_module(target, &entry.module))
+            .max_by_key(|entry| entry.module.len())
+            .map(|entry| log::LevelFilter::from(entry.level));
+        overridden
+            .or_else(|| {
+                self.modules
+                    .iter()
+                    .filter(|(module, _)| matches_module(target, module))
+                    .max_by_key(|(module, _)| module.len())
+                    .map(|(_, level)| *level)
+            })
+            .unwrap_or(self.default)
+    }
+
+    /// The most verbose level of any module, which the log crate uses to skip records early.
+    fn max_level(&self) -> log::LevelFilter {
+        self.overrides
+            .iter()
+            .map(|entry| log::LevelFilter::from(entry.level))
+            .chain(self.modules.iter().map(|(_, level)| *level))
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+static FILTER: Lazy<RwLock<Filter>> = Lazy::new(|| {
+    RwLock::new(Filter {
+        default: log::LevelFilter::Info,
+        modules: vec![],
+        overrides: vec![],
+        next_id: 0,
+    })
+});
+
+/// Returns whether `target` is `module` or one of its submodules.
+fn matches_module(target: &str, module: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Set the levels that records are filtered by when there are no overrides.
+pub(super) fn init(default: log::LevelFilter, modules: Vec<(String, log::LevelFilter)>) {
+    let mut filter = FILTER.write().unwrap();
+    filter.default = default;
+    filter.modules = modules;
+}
+
+pub(super) fn update_max_level() {
+    log::set_max_level(FILTER.read().unwrap().max_level());
+}
+
+pub(super) fn enabled(metadata: &log::Metadata<'_>) -> bool {
+    metadata.level() <= FILTER.read().unwrap().level(metadata.target())
+}
+
+/// Log records from `module` and its submodules at `level`, replacing any previous override for
+/// the module. If `revert_after` is given, the override is removed after that time.
+pub fn set_module_level(
+    module: String,
+    level: LogLevel,
+    revert_after: Option<Duration>,
+) -> Result<(), InvalidModuleError> {
+    let is_valid = !module.is_empty()
+        && module
+            .split("::")
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_'));
+    if !is_valid {
+        return Err(InvalidModuleError(module));
+    }
+
+    let revert_at = revert_after
+        .and_then(|revert_after| chrono::Duration::from_std(revert_after).ok())
+        .map(|revert_after| Utc::now() + revert_after);
+    let id = {
+        let mut filter = FILTER.write().unwrap();
+        let id = filter.next_id;
+        filter.next_id += 1;
+        filter.overrides.retain(|entry| entry.module != module);
+        filter.overrides.push(Override {
+            id,
+            module: module.clone(),
+            level,
+            revert_at,
+        });
+        log::set_max_level(filter.max_level());
+        id
+    };
+
+    // The lock must not be held while logging
+    match revert_after {
+        Some(revert_after) => {
+            log::info!(
+                "Logging {module} at level {level} for {} seconds",
+                revert_after.as_secs()
+            );
+            tokio::spawn(async move {
+                tokio::time::sleep(revert_after).await;
+                if remove_override(id) {
+                    log::info!("Reverted log level of {module}");
+                }
+            });
+        }
+        None => log::info!("Logging {module} at level {level}"),
+    }
+    Ok(())
+}
+
+/// Remove all overrides.
+pub fn reset_module_levels() {
+    {
+        let mut filter = FILTER.write().unwrap();
+        filter.overrides.clear();
+        log::set_max_level(filter.max_level());
+    }
+    log::info!("Reverted all log level overrides");
+}
+
+/// Return all overrides.
+pub fn module_levels() -> Vec<ModuleLogLevel> {
+    FILTER
+        .read()
+        .unwrap()
+        .overrides
+        .iter()
+        .map(|entry| ModuleLogLevel {
+            module: entry.module.clone(),
+            level: entry.level,
+            revert_at: entry.revert_at,
+        })
+        .collect()
+}
+
+/// Remove the override with the given ID, unless it has been replaced or removed already.
+fn remove_override(id: u64) -> bool {
+    let mut filter = FILTER.write().unwrap();
+    let count = filter.overrides.len();
+    filter.overrides.retain(|entry| entry.id != id);
+    log::set_max_level(filter.max_level());
+    filter.overrides.len() != count
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_override_precedence() {
+        let filter = Filter {
+            default: log::LevelFilter::Info,
+            modules: vec![("hyper".to_owned(), log::LevelFilter::Warn)],
+            overrides: vec![Override {
+                id: 0,
+                module: "talpid_wireguard".to_owned(),
+                level: LogLevel::Trace,
+                revert_at: None,
+            }],
+            next_id: 1,
+        };
+
+        assert_eq!(filter.level("hyper::client"), log::LevelFilter::Warn);
+        assert_eq!(filter.level("hyperlocal"), log::LevelFilter::Info);
+        assert_eq!(
+            filter.level("talpid_wireguard::wireguard_go"),
+            log::LevelFilter::Trace
+        );
+        assert_eq!(filter.max_level(), log::LevelFilter::Trace);
+    }
+}
@@ -2,6 +2,7 @@ use fern::{
     colors::{Color, ColoredLevelConfig},
     Output,
 };
+use mullvad_types::settings::LogSinks;
 use std::{
     fmt, io,
     path::PathBuf,
@@ -9,6 +10,12 @@ use std::{
 };
 use talpid_core::logging::rotate_log;
 
+mod filter;
+mod sinks;
+
+pub use filter::{module_levels, reset_module_levels, set_module_level, InvalidModuleError};
+pub use sinks::{set_sinks, set_tunnel_state};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     /// Unable to open log file for writing
@@ -79,17 +86,24 @@ pub fn init_logger(
     log_level: log::LevelFilter,
     log_file: Option<&PathBuf>,
     output_timestamp: bool,
+    sinks: LogSinks,
 ) -> Result<(), Error> {
-    let mut top_dispatcher = fern::Dispatch::new().level(log_level);
+    let mut module_levels = vec![];
     for silenced_crate in WARNING_SILENCED_CRATES {
-        top_dispatcher = top_dispatcher.level_for(*silenced_crate, log::LevelFilter::Error);
+        module_levels.push((silenced_crate.to_string(), log::LevelFilter::Error));
     }
     for silenced_crate in SILENCED_CRATES {
-        top_dispatcher = top_dispatcher.level_for(*silenced_crate, log::LevelFilter::Warn);
+        module_levels.push((silenced_crate.to_string(), log::LevelFilter::Warn));
     }
     for silenced_crate in SLIGHTLY_SILENCED_CRATES {
-        top_dispatcher = top_dispatcher.level_for(*silenced_crate, one_level_quieter(log_level));
+        module_levels.push((silenced_crate.to_string(), one_level_quieter(log_level)));
     }
+    filter::init(log_level, module_levels);
+
+    // Levels are checked by the filter, since they may change at runtime
+    let mut top_dispatcher = fern::Dispatch::new()
+        .level(log::LevelFilter::Trace)
+        .filter(filter::enabled);
 
     let stdout_formatter = Formatter {
         output_timestamp,
@@ -123,10 +137,19 @@ pub fn init_logger(
         ));
         top_dispatcher = top_dispatcher.chain(logger);
     }
+    let sink_logger: Box<dyn log::Log> = Box::new(sinks::SinkLogger);
+    top_dispatcher = top_dispatcher.chain(sink_logger);
     top_dispatcher.apply().map_err(Error::SetLoggerError)?;
+    // `apply` sets the max level to that of the dispatcher
+    filter::update_max_level();
 
     LOG_ENABLED.store(true, Ordering::SeqCst);
 
+    sinks::init(
+        sinks,
+        log_file.map(|log_file| log_file.with_extension(sinks::JSON_LOG_EXTENSION)),
+    );
+
     Ok(())
 }
 
@@ -0,0 +1,417 @@
+//! Log destinations for log shipping and other machine consumers. Unlike stdout and the log file,
+//! these sinks can be enabled and disabled at runtime, and every entry carries structured fields:
+//! the subsystem that logged it, the current tunnel state and the relay in use.
+
+use mullvad_types::{settings::LogSinks, states::TunnelState};
+use once_cell::sync::Lazy;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+};
+
+/// Name that the daemon identifies itself with in journald and syslog.
+const IDENTIFIER: &str = "mullvad-daemon";
+
+/// Extension of the JSON log, which is stored next to the daemon log file.
+pub const JSON_LOG_EXTENSION: &str = "jsonl";
+
+#[cfg(target_os = "linux")]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+#[cfg(target_os = "macos")]
+const SYSLOG_SOCKET: &str = "/var/run/syslog";
+#[cfg(all(unix, not(target_os = "macos")))]
+const SYSLOG_SOCKET: &str = "/dev/log";
+#[cfg(not(unix))]
+const SYSLOG_ADDRESS: &str = "127.0.0.1:514";
+
+/// Syslog facility for system daemons.
+const SYSLOG_FACILITY_DAEMON: u8 = 3;
+
+/// SD-ID of the structured data element in syslog messages. 32473 is the enterprise number
+/// reserved for documentation, since the fields are not registered.
+const SYSLOG_SD_ID: &str = "mullvad@32473";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SinkKind {
+    Journald,
+    Syslog,
+    Json,
+}
+
+impl SinkKind {
+    fn enabled(sinks: LogSinks) -> Vec<SinkKind> {
+        [
+            (sinks.journald, SinkKind::Journald),
+            (sinks.syslog, SinkKind::Syslog),
+            (sinks.json, SinkKind::Json),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, kind)| enabled.then_some(kind))
+        .collect()
+    }
+}
+
+struct State {
+    /// Sinks enabled on the command line. These are always enabled.
+    cli_sinks: LogSinks,
+    json_path: Option<PathBuf>,
+    sinks: BTreeMap<SinkKind, Box<dyn Sink>>,
+    tunnel_state: &'static str,
+    relay: Option<String>,
+}
+
+/// Whether any sink is enabled. This is checked before taking the lock, since sinks are usually
+/// disabled.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Log records only take the read lock, so they do not contend with each other.
+static STATE: Lazy<RwLock<State>> = Lazy::new(|| {
+    RwLock::new(State {
+        cli_sinks: LogSinks::default(),
+        json_path: None,
+        sinks: BTreeMap::new(),
+        tunnel_state: "disconnected",
+        relay: None,
+    })
+});
+
+/// Enable `cli_sinks`. The JSON log is written to `json_path`, or not at all if it is `None`.
+pub(super) fn init(cli_sinks: LogSinks, json_path: Option<PathBuf>) {
+    {
+        let mut state = STATE.write().unwrap();
+        state.cli_sinks = cli_sinks;
+        state.json_path = json_path;
+    }
+    set_sinks(LogSinks::default());
+}
+
+/// Enable the sinks in `sinks` as well as the ones enabled on the command line, and disable all
+/// others.
+pub fn set_sinks(sinks: LogSinks) {
+    if !super::is_enabled() {
+        return;
+    }
+
+    // Sinks are opened without holding the lock, since opening them may log.
+    let (wanted, missing, json_path) = {
+        let mut state = STATE.write().unwrap();
+        let wanted = SinkKind::enabled(sinks.union(state.cli_sinks));
+        state.sinks.retain(|kind, _| wanted.contains(kind));
+        let missing: Vec<_> = wanted
+            .iter()
+            .copied()
+            .filter(|kind| !state.sinks.contains_key(kind))
+            .collect();
+        ENABLED.store(!state.sinks.is_empty(), Ordering::Release);
+        (wanted, missing, state.json_path.clone())
+    };
+
+    let mut opened = vec![];
+    for kind in missing {
+        match open_sink(kind, json_path.as_ref()) {
+            Ok(sink) => opened.push((kind, sink)),
+            Err(error) => log::error!("Failed to open {kind:?} log sink: {error}"),
+        }
+    }
+
+    let mut state = STATE.write().unwrap();
+    for (kind, sink) in opened {
+        if wanted.contains(&kind) {
+            state.sinks.insert(kind, sink);
+        }
+    }
+    ENABLED.store(!state.sinks.is_empty(), Ordering::Release);
+}
+
+/// Update the tunnel state and relay that are attached to log entries.
+pub fn set_tunnel_state(tunnel_state: &TunnelState) {
+    let (name, location) = match tunnel_state {
+        TunnelState::Disconnected { .. } => ("disconnected", None),
+        TunnelState::Connecting { location, .. } => ("connecting", location.as_ref()),
+        TunnelState::Connected { location, .. } => ("connected", location.as_ref()),
+        TunnelState::Disconnecting(_) => ("disconnecting", None),
+        TunnelState::Error(_) => ("error", None),
+    };
+    let relay = location.and_then(|location| location.hostname.clone());
+
+    let mut state = STATE.write().unwrap();
+    state.tunnel_state = name;
+    if name != "disconnecting" {
+        state.relay = relay;
+    }
+}
+
+fn open_sink(kind: SinkKind, json_path: Option<&PathBuf>) -> io::Result<Box<dyn Sink>> {
+    match kind {
+        #[cfg(target_os = "linux")]
+        SinkKind::Journald => Ok(Box::new(Journald::new()?)),
+        #[cfg(not(target_os = "linux"))]
+        SinkKind::Journald => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "journald is only available on Linux",
+        )),
+        SinkKind::Syslog => Ok(Box::new(Syslog::new()?)),
+        SinkKind::Json => match json_path {
+            Some(path) => Ok(Box::new(JsonLines::new(path)?)),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "logging to file is disabled",
+            )),
+        },
+    }
+}
+
+/// A log record with the structured fields that are attached to it.
+struct Entry<'a> {
+    record: &'a log::Record<'a>,
+    message: String,
+    subsystem: &'a str,
+    tunnel_state: &'static str,
+    relay: Option<&'a str>,
+}
+
+trait Sink: Send + Sync {
+    /// Write `entry` to the sink. Errors are ignored, since there is nowhere to report them.
+    /// Entries may be written concurrently, so each one must be written in a single call.
+    fn write(&self, entry: &Entry<'_>);
+}
+
+/// Forwards log records to the enabled sinks.
+pub(super) struct SinkLogger;
+
+impl log::Log for SinkLogger {
+    fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !ENABLED.load(Ordering::Acquire) {
+            return;
+        }
+        let state = STATE.read().unwrap();
+        let entry = Entry {
+            record,
+            message: record.args().to_string(),
+            subsystem: subsystem(record.target()),
+            tunnel_state: state.tunnel_state,
+            relay: state.relay.as_deref(),
+        };
+        for sink in state.sinks.values() {
+            sink.write(&entry);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Return the module below the crate root of `target`, e.g. `firewall` for
+/// `talpid_core::firewall::linux`, or the crate itself for top level modules.
+fn subsystem(target: &str) -> &str {
+    let mut components = target.split("::");
+    let crate_name = components.next().unwrap_or(target);
+    components.next().unwrap_or(crate_name)
+}
+
+/// Severity as defined by syslog, which journald also uses.
+fn severity(level: log::Level) -> u8 {
+    match level {
+        log::Level::Error => 3,
+        log::Level::Warn => 4,
+        log::Level::Info => 6,
+        log::Level::Debug | log::Level::Trace => 7,
+    }
+}
+
+/// Sends entries to systemd-journald using its native protocol.
+#[cfg(target_os = "linux")]
+struct Journald {
+    socket: std::os::unix::net::UnixDatagram,
+}
+
+#[cfg(target_os = "linux")]
+impl Journald {
+    fn new() -> io::Result<Self> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        socket.connect(JOURNALD_SOCKET)?;
+        Ok(Self { socket })
+    }
+
+    fn append_field(datagram: &mut Vec<u8>, name: &str, value: &str) {
+        datagram.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            // Values with newlines are prefixed with their length instead
+            datagram.push(b'\n');
+            datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            datagram.push(b'=');
+        }
+        datagram.extend_from_slice(value.as_bytes());
+        datagram.push(b'\n');
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Sink for Journald {
+    fn write(&self, entry: &Entry<'_>) {
+        let mut datagram = Vec::with_capacity(256 + entry.message.len());
+        let priority = severity(entry.record.level()).to_string();
+        Self::append_field(&mut datagram, "MESSAGE", &entry.message);
+        Self::append_field(&mut datagram, "PRIORITY", &priority);
+        Self::append_field(&mut datagram, "SYSLOG_IDENTIFIER", IDENTIFIER);
+        Self::append_field(&mut datagram, "MULLVAD_SUBSYSTEM", entry.subsystem);
+        Self::append_field(&mut datagram, "MULLVAD_TARGET", entry.record.target());
+        Self::append_field(&mut datagram, "MULLVAD_TUNNEL_STATE", entry.tunnel_state);
+        if let Some(relay) = entry.relay {
+            Self::append_field(&mut datagram, "MULLVAD_RELAY", relay);
+        }
+        if let Some(file) = entry.record.file() {
+            Self::append_field(&mut datagram, "CODE_FILE", file);
+        }
+        if let Some(line) = entry.record.line() {
+            Self::append_field(&mut datagram, "CODE_LINE", &line.to_string());
+        }
+        let _ = self.socket.send(&datagram);
+    }
+}
+
+/// Sends entries to the local syslog daemon, formatted according to RFC 5424.
+struct Syslog {
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+    #[cfg(not(unix))]
+    socket: std::net::UdpSocket,
+}
+
+impl Syslog {
+    fn new() -> io::Result<Self> {
+        #[cfg(unix)]
+        let socket = {
+            let socket = std::os::unix::net::UnixDatagram::unbound()?;
+            socket.connect(SYSLOG_SOCKET)?;
+            socket
+        };
+        #[cfg(not(unix))]
+        let socket = {
+            let socket = std::net::UdpSocket::bind("127.0.0.1:0")?;
+            socket.connect(SYSLOG_ADDRESS)?;
+            socket
+        };
+        Ok(Self { socket })
+    }
+
+    /// Escape a structured data parameter value.
+    fn escape(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace(']', "\\]")
+    }
+}
+
+impl Sink for Syslog {
+    fn write(&self, entry: &Entry<'_>) {
+        let priority = SYSLOG_FACILITY_DAEMON * 8 + severity(entry.record.level());
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+        let mut structured_data = format!(
+            "[{SYSLOG_SD_ID} subsystem=\"{}\" tunnel_state=\"{}\"",
+            Self::escape(entry.subsystem),
+            entry.tunnel_state
+        );
+        if let Some(relay) = entry.relay {
+            let _ = write!(structured_data, " relay=\"{}\"", Self::escape(relay));
+        }
+        structured_data.push(']');
+
+        // HOSTNAME and MSGID are left out, as allowed by the RFC
+        let message = format!(
+            "<{priority}>1 {timestamp} - {IDENTIFIER} {} - {structured_data} {}",
+            std::process::id(),
+            entry.message
+        );
+        let _ = self.socket.send(message.as_bytes());
+    }
+}
+
+/// Writes entries to a file as one JSON object per line.
+struct JsonLines {
+    file: File,
+}
+
+impl JsonLines {
+    fn new(path: &PathBuf) -> io::Result<Self> {
+        // Keep the previous log, like the text log file
+        let backup = path.with_extension(format!("old.{JSON_LOG_EXTENSION}"));
+        if let Err(error) = fs::rename(path, backup) {
+            if error.kind() != io::ErrorKind::NotFound {
+                log::warn!("Failed to rotate JSON log file: {error}");
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+}
+
+impl Sink for JsonLines {
+    fn write(&self, entry: &Entry<'_>) {
+        let line = serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            "level": entry.record.level().as_str(),
+            "target": entry.record.target(),
+            "subsystem": entry.subsystem,
+            "tunnel_state": entry.tunnel_state,
+            "relay": entry.relay,
+            "message": entry.message,
+        });
+        // The file is opened in append mode, so a single write is not interleaved with others
+        let _ = (&self.file).write_all(format!("{line}\n").as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_subsystem() {
+        assert_eq!(subsystem("talpid_core::firewall::linux"), "firewall");
+        assert_eq!(
+            subsystem("mullvad_daemon::management_interface"),
+            "management_interface"
+        );
+        assert_eq!(subsystem("mullvad_daemon"), "mullvad_daemon");
+        assert_eq!(subsystem(""), "");
+    }
+
+    #[test]
+    fn test_syslog_escape() {
+        assert_eq!(Syslog::escape("se-got-wg-001"), "se-got-wg-001");
+        assert_eq!(Syslog::escape(r#"a"b"#), r#"a\"b"#);
+        assert_eq!(Syslog::escape(r"a\b"), r"a\\b");
+        assert_eq!(Syslog::escape("a]b"), r"a\]b");
+        // Backslashes must be escaped before the escape sequences are added
+        assert_eq!(Syslog::escape(r#"\""#), r#"\\\""#);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_journald_append_field() {
+        let mut datagram = vec![];
+        Journald::append_field(&mut datagram, "PRIORITY", "6");
+        assert_eq!(datagram, b"PRIORITY=6\n");
+
+        let mut datagram = vec![];
+        Journald::append_field(&mut datagram, "MESSAGE", "a\nb");
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"a\nb\n");
+        assert_eq!(datagram, expected);
+    }
+}
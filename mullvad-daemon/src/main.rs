@@ -1,6 +1,7 @@
 #[cfg(not(windows))]
 use mullvad_daemon::cleanup_old_rpc_socket;
 use mullvad_daemon::{
+    access_policy::AccessPolicy,
     logging,
     management_interface::{ManagementInterfaceEventBroadcaster, ManagementInterfaceServer},
     rpc_uniqueness_check, runtime, version, Daemon, DaemonCommandChannel, DaemonCommandSender,
@@ -147,6 +148,7 @@ fn init_logger(config: &cli::Config, log_file: Option<PathBuf>) -> Result<(), St
         config.log_level,
         log_file.as_ref(),
         config.log_stdout_timestamps,
+        config.log_sinks,
     )
     .map_err(|e| e.display_chain_with_msg("Unable to initialize logger"))?;
     log_panics::init();
@@ -207,7 +209,34 @@ async fn create_daemon(
         .map_err(|e| e.display_chain_with_msg("Unable to get cache dir"))?;
 
     let command_channel = DaemonCommandChannel::new();
-    let event_listener = spawn_management_interface(command_channel.sender(), rpc_socket_path)?;
+    let access_policy = AccessPolicy::load(&settings_dir);
+    let event_listener = spawn_management_interface(
+        command_channel.sender(),
+        rpc_socket_path,
+        access_policy.clone(),
+    )?;
+
+    #[cfg(not(target_os = "android"))]
+    if let Some(address) = cli::get_config().http_gateway {
+        if let Err(error) =
+            mullvad_daemon::http_gateway::spawn(address, &settings_dir, access_policy).await
+        {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to start HTTP gateway")
+            );
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    if let Some(address) = cli::get_config().metrics {
+        if let Err(error) = mullvad_daemon::metrics::spawn(address).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to start metrics endpoint")
+            );
+        }
+    }
 
     Daemon::start(
         log_dir,
@@ -224,11 +253,12 @@ async fn create_daemon(
 fn spawn_management_interface(
     command_sender: DaemonCommandSender,
     rpc_socket_path: impl AsRef<Path>,
+    access_policy: AccessPolicy,
 ) -> Result<ManagementInterfaceEventBroadcaster, String> {
-    let event_broadcaster = ManagementInterfaceServer::start(command_sender, &rpc_socket_path)
-        .map_err(|error| {
-            error.display_chain_with_msg("Unable to start management interface server")
-        })?;
+    let event_broadcaster =
+        ManagementInterfaceServer::start(command_sender, &rpc_socket_path, access_policy).map_err(
+            |error| error.display_chain_with_msg("Unable to start management interface server"),
+        )?;
 
     log::info!(
         "Management interface listening on {}",
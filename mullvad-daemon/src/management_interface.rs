@@ -1,5 +1,6 @@
 use crate::{
-    account_history, device, version_check, DaemonCommand, DaemonCommandSender, EventListener,
+    access_policy::AccessPolicy, account_history, device, version_check, DaemonCommand,
+    DaemonCommandSender, EventListener,
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -7,19 +8,20 @@ use futures::{
 };
 use mullvad_api::{rest::Error as RestError, StatusCode};
 use mullvad_management_interface::{
+    access::{AccessControl, PeerCredentials, RpcCategory},
     types::{self, daemon_event, management_service_server::ManagementService},
     Code, Request, Response, Status,
 };
 use mullvad_types::{
-    account::AccountToken,
+    account::{AccountToken, SavedAccount},
     relay_constraints::{
         BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
     },
     relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    settings::{DnsOptions, LogSinks, Settings},
     states::{TargetState, TunnelState},
     version,
-    wireguard::{RotationInterval, RotationIntervalError},
+    wireguard::{RotationInterval, RotationIntervalError, MIN_QUANTUM_RESISTANT_REKEY_INTERVAL},
 };
 use std::{
     path::Path,
@@ -40,6 +42,7 @@ pub enum Error {
 struct ManagementServiceImpl {
     daemon_tx: DaemonCommandSender,
     subscriptions: Arc<Mutex<Vec<EventsListenerSender>>>,
+    access_policy: AccessPolicy,
 }
 
 pub type ServiceResult<T> = std::result::Result<Response<T>, Status>;
@@ -91,6 +94,16 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(types::TunnelState::from(state)))
     }
 
+    async fn get_recent_errors(&self, _: Request<()>) -> ServiceResult<types::RecentErrors> {
+        log::debug!("get_recent_errors");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetRecentErrors(tx))?;
+        let errors = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::RecentErrors {
+            errors: errors.into_iter().map(types::RecentError::from).collect(),
+        }))
+    }
+
     // Control the daemon and receive events
     //
 
@@ -170,6 +183,38 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn get_relay_list_changes(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::RelayListChanges> {
+        log::debug!("get_relay_list_changes");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetRelayListChanges(tx))?;
+        let changes = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::RelayListChanges {
+            changes: changes
+                .into_iter()
+                .map(types::RelayListDiff::from)
+                .collect(),
+        }))
+    }
+
+    async fn export_relay_list(&self, _: Request<()>) -> ServiceResult<String> {
+        log::debug!("export_relay_list");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ExportRelayList(tx))?;
+        let blob = self.wait_for_result(rx).await??;
+        Ok(Response::new(blob))
+    }
+
+    async fn import_relay_list(&self, request: Request<String>) -> ServiceResult<()> {
+        log::debug!("import_relay_list");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ImportRelayList(tx, request.into_inner()))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     async fn set_relay_settings(
         &self,
         request: Request<types::RelaySettings>,
@@ -330,12 +375,43 @@ impl ManagementService for ManagementServiceImpl {
         &self,
         request: Request<types::QuantumResistantState>,
     ) -> ServiceResult<()> {
-        let state = mullvad_types::wireguard::QuantumResistantState::try_from(request.into_inner())
+        let request = request.into_inner();
+        let algorithms = types::wireguard::try_quantum_resistant_algorithms(&request)
+            .map_err(map_protobuf_type_err)?;
+        let state = mullvad_types::wireguard::QuantumResistantState::try_from(request)
             .map_err(map_protobuf_type_err)?;
 
-        log::debug!("set_quantum_resistant_tunnel({state:?})");
+        log::debug!("set_quantum_resistant_tunnel({state:?}, {algorithms:?})");
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetQuantumResistantTunnel(tx, state))?;
+        self.send_command_to_daemon(DaemonCommand::SetQuantumResistantTunnel(
+            tx, state, algorithms,
+        ))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn set_quantum_resistant_rekey_interval(
+        &self,
+        request: Request<types::Duration>,
+    ) -> ServiceResult<()> {
+        let interval = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative rekey interval"))?;
+        let interval = if interval.is_zero() {
+            None
+        } else if interval < MIN_QUANTUM_RESISTANT_REKEY_INTERVAL {
+            return Err(Status::invalid_argument(format!(
+                "Rekey interval must be at least {} minutes",
+                MIN_QUANTUM_RESISTANT_REKEY_INTERVAL.as_secs() / 60
+            )));
+        } else {
+            Some(interval)
+        };
+
+        log::debug!("set_quantum_resistant_rekey_interval({interval:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetQuantumResistantRekeyInterval(
+            tx, interval,
+        ))?;
         self.wait_for_result(rx).await??;
         Ok(Response::new(()))
     }
@@ -390,6 +466,15 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_log_sinks(&self, request: Request<types::LogSinks>) -> ServiceResult<()> {
+        let sinks = LogSinks::from(request.into_inner());
+        log::debug!("set_log_sinks({sinks:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetLogSinks(tx, sinks))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     // Account management
     //
 
@@ -463,6 +548,79 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn list_saved_accounts(&self, _: Request<()>) -> ServiceResult<types::SavedAccounts> {
+        log::debug!("list_saved_accounts");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ListSavedAccounts(tx))?;
+        self.wait_for_result(rx).await.map(|accounts| {
+            Response::new(types::SavedAccounts {
+                accounts: accounts
+                    .into_iter()
+                    .map(types::SavedAccount::from)
+                    .collect(),
+            })
+        })
+    }
+
+    async fn save_account(&self, request: Request<types::SavedAccount>) -> ServiceResult<()> {
+        let account = SavedAccount::from(request.into_inner());
+        log::debug!("save_account({})", account.name);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SaveAccount(tx, account))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn remove_saved_account(&self, request: Request<String>) -> ServiceResult<()> {
+        let name = request.into_inner();
+        log::debug!("remove_saved_account({})", name);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveSavedAccount(tx, name))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn switch_account(&self, request: Request<String>) -> ServiceResult<()> {
+        let name = request.into_inner();
+        log::debug!("switch_account({})", name);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SwitchAccount(tx, name))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn get_account_expiry_state(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::AccountExpiryState> {
+        log::debug!("get_account_expiry_state");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetAccountExpiryState(tx))?;
+        self.wait_for_result(rx).await.map(|notification| {
+            Response::new(types::AccountExpiryState {
+                notification: notification.map(types::ExpiryNotification::from),
+            })
+        })
+    }
+
+    async fn set_expiry_notification_hours(
+        &self,
+        request: Request<types::ExpiryNotificationHours>,
+    ) -> ServiceResult<()> {
+        let hours = request.into_inner().hours;
+        log::debug!("set_expiry_notification_hours({:?})", hours);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetExpiryNotificationHours(tx, hours))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     async fn get_www_auth_token(&self, _: Request<()>) -> ServiceResult<String> {
         log::debug!("get_www_auth_token");
         let (tx, rx) = oneshot::channel();
@@ -636,6 +794,103 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    // Custom relays
+
+    async fn add_custom_relay(&self, request: Request<types::CustomRelay>) -> ServiceResult<()> {
+        log::debug!("add_custom_relay");
+        let custom_relay =
+            mullvad_types::custom_relay::CustomRelay::try_from(request.into_inner())?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddCustomRelay(tx, custom_relay))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn remove_custom_relay(&self, request: Request<String>) -> ServiceResult<()> {
+        log::debug!("remove_custom_relay");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveCustomRelay(tx, request.into_inner()))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    // Schedule
+
+    async fn add_schedule_rule(&self, request: Request<types::ScheduleRule>) -> ServiceResult<()> {
+        log::debug!("add_schedule_rule");
+        let rule = mullvad_types::schedule::ScheduleRule::try_from(request.into_inner())?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddScheduleRule(tx, rule))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn remove_schedule_rule(&self, request: Request<String>) -> ServiceResult<()> {
+        log::debug!("remove_schedule_rule");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveScheduleRule(tx, request.into_inner()))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    // Data usage
+
+    async fn get_data_usage(&self, _: Request<()>) -> ServiceResult<types::DataUsage> {
+        log::debug!("get_data_usage");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetDataUsage(tx))?;
+        let usage = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::DataUsage::from(usage)))
+    }
+
+    async fn set_data_cap(&self, request: Request<types::DataCap>) -> ServiceResult<()> {
+        let data_cap = mullvad_types::data_usage::DataCap::from(request.into_inner());
+        log::debug!("set_data_cap({:?})", data_cap);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetDataCap(tx, data_cap))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    // Captive portal
+
+    #[cfg(not(target_os = "android"))]
+    async fn allow_captive_portal(
+        &self,
+        request: Request<types::Duration>,
+    ) -> ServiceResult<String> {
+        let window = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative time window"))?;
+        log::debug!("allow_captive_portal({:?})", window);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AllowCaptivePortal(tx, window))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(|error| match error {
+                crate::captive_portal::Error::LockedByPolicy => Status::with_details(
+                    Code::PermissionDenied,
+                    error.to_string(),
+                    mullvad_management_interface::SETTING_LOCKED_DETAILS.into(),
+                ),
+                error => Status::failed_precondition(error.display_chain()),
+            })
+    }
+    #[cfg(target_os = "android")]
+    async fn allow_captive_portal(&self, _: Request<types::Duration>) -> ServiceResult<String> {
+        Err(Status::unimplemented(
+            "Captive portal access is not supported on Android",
+        ))
+    }
+
     // Access Methods
 
     async fn add_api_access_method(
@@ -1039,6 +1294,70 @@ impl ManagementService for ManagementServiceImpl {
 
         Ok(Response::new(feature_indicators))
     }
+
+    async fn run_diagnostics(&self, _: Request<()>) -> ServiceResult<types::DiagnosticsReport> {
+        log::debug!("run_diagnostics");
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RunDiagnostics(tx))?;
+
+        let report = self
+            .wait_for_result(rx)
+            .await
+            .map(types::DiagnosticsReport::from)?;
+
+        Ok(Response::new(report))
+    }
+
+    async fn test_dns_leaks(&self, _: Request<()>) -> ServiceResult<types::DnsLeakTestResult> {
+        log::debug!("test_dns_leaks");
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::TestDnsLeaks(tx))?;
+
+        let result = self.wait_for_result(rx).await?.ok_or_else(|| {
+            Status::failed_precondition("DNS leaks can only be tested while connected")
+        })?;
+
+        Ok(Response::new(types::DnsLeakTestResult::from(result)))
+    }
+
+    // Logging
+    //
+
+    async fn set_log_level(&self, request: Request<types::SetModuleLogLevel>) -> ServiceResult<()> {
+        let request = request.into_inner();
+        let level = types::log_level::try_log_level_from_i32(request.level)?;
+        let revert_after = request
+            .revert_after
+            .map(Duration::try_from)
+            .transpose()
+            .map_err(|_| Status::invalid_argument("unexpected negative revert duration"))?;
+        log::debug!(
+            "set_log_level({}, {level}, {revert_after:?})",
+            request.module
+        );
+
+        // The log filter is global, so the daemon does not have to be involved
+        crate::logging::set_module_level(request.module, level, revert_after)
+            .map_err(|error| Status::invalid_argument(error.to_string()))?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_log_levels(&self, _: Request<()>) -> ServiceResult<types::ModuleLogLevels> {
+        log::debug!("get_log_levels");
+        let levels = crate::logging::module_levels()
+            .into_iter()
+            .map(types::ModuleLogLevel::from)
+            .collect();
+        Ok(Response::new(types::ModuleLogLevels { levels }))
+    }
+
+    async fn reset_log_levels(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("reset_log_levels");
+        crate::logging::reset_module_levels();
+        Ok(Response::new(()))
+    }
 }
 
 impl ManagementServiceImpl {
@@ -1054,12 +1373,29 @@ impl ManagementServiceImpl {
     }
 }
 
+impl AccessControl for ManagementServiceImpl {
+    fn authorize(
+        &self,
+        peer: Option<&PeerCredentials>,
+        category: RpcCategory,
+    ) -> Result<(), Status> {
+        if self.access_policy.allows(peer, category) {
+            Ok(())
+        } else {
+            Err(Status::permission_denied(format!(
+                "The access policy does not allow this user to use '{category}' commands"
+            )))
+        }
+    }
+}
+
 pub struct ManagementInterfaceServer(());
 
 impl ManagementInterfaceServer {
     pub fn start(
         tunnel_tx: DaemonCommandSender,
         rpc_socket_path: impl AsRef<Path>,
+        access_policy: AccessPolicy,
     ) -> Result<ManagementInterfaceEventBroadcaster, Error> {
         let subscriptions = Arc::<Mutex<Vec<EventsListenerSender>>>::default();
 
@@ -1067,6 +1403,7 @@ impl ManagementInterfaceServer {
         let server = ManagementServiceImpl {
             daemon_tx: tunnel_tx,
             subscriptions: subscriptions.clone(),
+            access_policy,
         };
         let join_handle = mullvad_management_interface::spawn_rpc_server(
             server,
@@ -1166,6 +1503,33 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
             )),
         })
     }
+
+    fn notify_account_expiry(&self, notification: mullvad_types::account::ExpiryNotification) {
+        log::debug!("Broadcasting account expiry notification");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::AccountExpiry(
+                types::ExpiryNotification::from(notification),
+            )),
+        })
+    }
+
+    fn notify_relay_list_changed(&self, diff: mullvad_types::relay_list::RelayListDiff) {
+        log::debug!("Broadcasting relay list changes");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::RelayListChanged(
+                types::RelayListDiff::from(diff),
+            )),
+        })
+    }
+
+    fn notify_data_cap_reached(&self, event: mullvad_types::data_usage::DataCapReached) {
+        log::debug!("Broadcasting data cap notification");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::DataCapReached(
+                types::DataCapReached::from(event),
+            )),
+        })
+    }
 }
 
 impl ManagementInterfaceEventBroadcaster {
@@ -1265,6 +1629,11 @@ fn map_account_history_error(error: account_history::Error) -> Status {
         account_history::Error::Serialize(..) | account_history::Error::WriteCancelled(..) => {
             Status::new(Code::Internal, error.to_string())
         }
+        account_history::Error::UnknownAccountName(..) => Status::with_details(
+            Code::NotFound,
+            error.to_string(),
+            mullvad_management_interface::SAVED_ACCOUNT_NOT_FOUND_DETAILS.into(),
+        ),
     }
 }
 
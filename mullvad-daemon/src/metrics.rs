@@ -0,0 +1,463 @@
+//! Optional Prometheus metrics endpoint.
+//!
+//! The endpoint is only started if the daemon is given `--metrics <ADDRESS>`, and only listens on
+//! loopback addresses, since the metrics are not authenticated. `GET /metrics` returns the metrics
+//! in the Prometheus text exposition format.
+
+use chrono::{DateTime, Utc};
+use hyper::{
+    header::{self, HeaderValue},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use mullvad_api::rest::{RequestStats, RequestStatsHandle, LATENCY_BUCKETS};
+use mullvad_relay_selector::RelaySelector;
+use mullvad_types::states::TunnelState;
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt::Write,
+    net::SocketAddr,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+use talpid_types::{net::wireguard::PublicKey, tunnel::ErrorStateCause, ErrorExt};
+use talpid_wireguard::stats::StatsHandle;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const TUNNEL_STATES: [&str; 5] = [
+    "disconnected",
+    "connecting",
+    "connected",
+    "disconnecting",
+    "error",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Metrics may only be served on a loopback address, not {0}")]
+    NotLoopback(SocketAddr),
+
+    #[error("Failed to listen on {0}")]
+    Bind(SocketAddr, #[source] hyper::Error),
+}
+
+/// State that is updated by the daemon, as opposed to read when the metrics are requested.
+struct State {
+    tunnel_state: &'static str,
+    connect_attempts: u64,
+    connect_failures: BTreeMap<&'static str, u64>,
+    key_created: Option<DateTime<Utc>>,
+    relay_selector: Option<RelaySelector>,
+    tunnel_stats: Option<StatsHandle>,
+    request_stats: Option<RequestStatsHandle>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State::new());
+
+impl State {
+    const fn new() -> Self {
+        State {
+            tunnel_state: "disconnected",
+            connect_attempts: 0,
+            connect_failures: BTreeMap::new(),
+            key_created: None,
+            relay_selector: None,
+            tunnel_stats: None,
+            request_stats: None,
+        }
+    }
+
+    fn set_tunnel_state(&mut self, tunnel_state: &TunnelState) {
+        let name = match tunnel_state {
+            TunnelState::Disconnected { .. } => "disconnected",
+            TunnelState::Connecting { .. } => {
+                self.connect_attempts += 1;
+                "connecting"
+            }
+            TunnelState::Connected { .. } => "connected",
+            TunnelState::Disconnecting(_) => "disconnecting",
+            TunnelState::Error(error_state) => {
+                // Only count attempts that failed. Being offline is not a failure to connect,
+                // and neither is blocking from any state other than connecting.
+                let cause = error_state.cause();
+                if self.tunnel_state == "connecting" && !matches!(cause, ErrorStateCause::IsOffline)
+                {
+                    *self.connect_failures.entry(cause_label(cause)).or_default() += 1;
+                }
+                "error"
+            }
+        };
+        self.tunnel_state = name;
+    }
+}
+
+/// Count connection attempts and failures, and track the current tunnel state.
+pub fn set_tunnel_state(tunnel_state: &TunnelState) {
+    STATE.lock().unwrap().set_tunnel_state(tunnel_state);
+}
+
+/// Set when the current WireGuard key was created, or `None` if there is no device.
+pub fn set_key_created(created: Option<DateTime<Utc>>) {
+    STATE.lock().unwrap().key_created = created;
+}
+
+/// Set the relay selector to read the age of the relay list from.
+pub fn set_relay_selector(relay_selector: RelaySelector) {
+    STATE.lock().unwrap().relay_selector = Some(relay_selector);
+}
+
+/// Set the handle to read the stats of WireGuard tunnels from.
+pub fn set_tunnel_stats(tunnel_stats: StatsHandle) {
+    STATE.lock().unwrap().tunnel_stats = Some(tunnel_stats);
+}
+
+/// Set the handle to read the latencies and errors of API requests from.
+pub fn set_request_stats(request_stats: RequestStatsHandle) {
+    STATE.lock().unwrap().request_stats = Some(request_stats);
+}
+
+fn cause_label(cause: &ErrorStateCause) -> &'static str {
+    match cause {
+        ErrorStateCause::AuthFailed(_) => "auth_failed",
+        ErrorStateCause::Ipv6Unavailable => "ipv6_unavailable",
+        ErrorStateCause::SetFirewallPolicyError(_) => "set_firewall_policy_error",
+        ErrorStateCause::SetDnsError => "set_dns_error",
+        #[cfg(target_os = "windows")]
+        ErrorStateCause::CreateTunnelDevice { .. } => "create_tunnel_device",
+        ErrorStateCause::StartTunnelError => "start_tunnel_error",
+        ErrorStateCause::TunnelParameterError(_) => "tunnel_parameter_error",
+        ErrorStateCause::IsOffline => "is_offline",
+        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        ErrorStateCause::SplitTunnelError => "split_tunnel_error",
+        #[cfg(target_os = "macos")]
+        ErrorStateCause::NeedFullDiskPermissions => "need_full_disk_permissions",
+    }
+}
+
+/// Start serving metrics on `address`.
+pub async fn spawn(address: SocketAddr) -> Result<(), Error> {
+    if !address.ip().is_loopback() {
+        return Err(Error::NotLoopback(address));
+    }
+
+    let make_service =
+        make_service_fn(|_connection| async { Ok::<_, Infallible>(service_fn(handle_request)) });
+    let server = Server::try_bind(&address)
+        .map_err(|error| Error::Bind(address, error))?
+        .serve(make_service);
+
+    log::info!("Serving metrics on http://{address}/metrics");
+    tokio::spawn(async move {
+        if let Err(error) = server.await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Metrics endpoint failed")
+            );
+        }
+    });
+    Ok(())
+}
+
+async fn handle_request(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if request.uri().path() != "/metrics" {
+        return Ok(status_response(StatusCode::NOT_FOUND));
+    }
+    if request.method() != Method::GET {
+        return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let mut response = Response::new(Body::from(encode()));
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+    Ok(response)
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::from(status.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Encode all metrics in the Prometheus text format.
+fn encode() -> String {
+    let mut out = Encoder::default();
+    let now = SystemTime::now();
+
+    let (tunnel_stats, request_stats) = {
+        let state = STATE.lock().unwrap();
+
+        out.header(
+            "mullvad_tunnel_state",
+            "gauge",
+            "Whether the tunnel is in the given state",
+        );
+        for tunnel_state in TUNNEL_STATES {
+            let value = u8::from(tunnel_state == state.tunnel_state);
+            out.sample("mullvad_tunnel_state", &[("state", tunnel_state)], value);
+        }
+
+        out.header(
+            "mullvad_connect_attempts_total",
+            "counter",
+            "Number of times the tunnel has started connecting",
+        );
+        out.sample(
+            "mullvad_connect_attempts_total",
+            &[],
+            state.connect_attempts,
+        );
+
+        out.header(
+            "mullvad_connect_failures_total",
+            "counter",
+            "Number of times the tunnel has entered the error state, by cause",
+        );
+        for (cause, count) in &state.connect_failures {
+            out.sample(
+                "mullvad_connect_failures_total",
+                &[("cause", *cause)],
+                count,
+            );
+        }
+
+        if let Some(created) = state.key_created {
+            out.header(
+                "mullvad_wireguard_key_age_seconds",
+                "gauge",
+                "Time since the WireGuard key of this device was created",
+            );
+            let age = (Utc::now() - created).num_seconds().max(0);
+            out.sample("mullvad_wireguard_key_age_seconds", &[], age);
+        }
+
+        if let Some(relay_selector) = &state.relay_selector {
+            out.header(
+                "mullvad_relay_list_age_seconds",
+                "gauge",
+                "Time since the relay list was last updated",
+            );
+            let age = age(now, relay_selector.last_updated());
+            out.sample("mullvad_relay_list_age_seconds", &[], age.as_secs());
+        }
+
+        (state.tunnel_stats.clone(), state.request_stats.clone())
+    };
+
+    if let Some(stats) = tunnel_stats.and_then(|tunnel_stats| tunnel_stats.current()) {
+        let peers: Vec<_> = stats
+            .into_iter()
+            .map(|(peer, stats)| (PublicKey::from(peer).to_base64(), stats))
+            .collect();
+
+        out.header(
+            "mullvad_wireguard_receive_bytes_total",
+            "counter",
+            "Bytes received from the peer since the tunnel was created",
+        );
+        for (peer, stats) in &peers {
+            let labels = [("peer", peer.as_str())];
+            out.sample(
+                "mullvad_wireguard_receive_bytes_total",
+                &labels,
+                stats.rx_bytes,
+            );
+        }
+        out.header(
+            "mullvad_wireguard_transmit_bytes_total",
+            "counter",
+            "Bytes sent to the peer since the tunnel was created",
+        );
+        for (peer, stats) in &peers {
+            let labels = [("peer", peer.as_str())];
+            out.sample(
+                "mullvad_wireguard_transmit_bytes_total",
+                &labels,
+                stats.tx_bytes,
+            );
+        }
+        out.header(
+            "mullvad_wireguard_last_handshake_age_seconds",
+            "gauge",
+            "Time since the most recent handshake with the peer",
+        );
+        for (peer, stats) in &peers {
+            if let Some(last_handshake) = stats.last_handshake {
+                let labels = [("peer", peer.as_str())];
+                let age = age(now, last_handshake).as_secs();
+                out.sample("mullvad_wireguard_last_handshake_age_seconds", &labels, age);
+            }
+        }
+    }
+
+    if let Some(request_stats) = request_stats {
+        encode_request_stats(&mut out, &request_stats.get());
+    }
+
+    out.0
+}
+
+fn encode_request_stats(out: &mut Encoder, requests: &RequestStats) {
+    out.header(
+        "mullvad_api_request_duration_seconds",
+        "histogram",
+        "Time until a response to an API request was received",
+    );
+    for (bucket, count) in LATENCY_BUCKETS.iter().zip(requests.latency_buckets) {
+        let le = bucket.as_secs_f64().to_string();
+        out.sample(
+            "mullvad_api_request_duration_seconds_bucket",
+            &[("le", le.as_str())],
+            count,
+        );
+    }
+    out.sample(
+        "mullvad_api_request_duration_seconds_bucket",
+        &[("le", "+Inf")],
+        requests.count,
+    );
+    out.sample(
+        "mullvad_api_request_duration_seconds_sum",
+        &[],
+        requests.latency_sum.as_secs_f64(),
+    );
+    out.sample(
+        "mullvad_api_request_duration_seconds_count",
+        &[],
+        requests.count,
+    );
+
+    out.header(
+        "mullvad_api_request_errors_total",
+        "counter",
+        "Number of failed API requests, by kind of error",
+    );
+    for (kind, count) in &requests.errors {
+        out.sample(
+            "mullvad_api_request_errors_total",
+            &[("kind", *kind)],
+            count,
+        );
+    }
+}
+
+fn age(now: SystemTime, time: SystemTime) -> Duration {
+    now.duration_since(time).unwrap_or_default()
+}
+
+#[derive(Default)]
+struct Encoder(String);
+
+impl Encoder {
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_label(value)))
+                .collect();
+            let _ = write!(self.0, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.0, " {value}");
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::tunnel::ErrorState;
+
+    fn error_state(cause: ErrorStateCause) -> TunnelState {
+        TunnelState::Error(ErrorState::new(cause, None))
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("se-got-wg-001"), "se-got-wg-001");
+        assert_eq!(escape_label(r#"a"b"#), r#"a\"b"#);
+        assert_eq!(escape_label(r"a\b"), r"a\\b");
+        assert_eq!(escape_label("a\nb"), r"a\nb");
+    }
+
+    #[test]
+    fn test_encoder() {
+        let mut out = Encoder::default();
+        out.header("mullvad_test", "gauge", "A test metric");
+        out.sample("mullvad_test", &[], 1);
+        out.sample("mullvad_test", &[("a", "x"), ("b", "\"y\"")], 2.5);
+        assert_eq!(
+            out.0,
+            "# HELP mullvad_test A test metric\n\
+             # TYPE mullvad_test gauge\n\
+             mullvad_test 1\n\
+             mullvad_test{a=\"x\",b=\"\\\"y\\\"\"} 2.5\n"
+        );
+    }
+
+    #[test]
+    fn test_request_histogram() {
+        let requests = RequestStats {
+            count: 3,
+            latency_sum: Duration::from_millis(3200),
+            latency_buckets: [1, 1, 1, 2, 2, 2, 3],
+            errors: BTreeMap::from([("timeout", 1)]),
+        };
+
+        let mut out = Encoder::default();
+        encode_request_stats(&mut out, &requests);
+        let lines: Vec<_> = out
+            .0
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                r#"mullvad_api_request_duration_seconds_bucket{le="0.1"} 1"#,
+                r#"mullvad_api_request_duration_seconds_bucket{le="0.25"} 1"#,
+                r#"mullvad_api_request_duration_seconds_bucket{le="0.5"} 1"#,
+                r#"mullvad_api_request_duration_seconds_bucket{le="1"} 2"#,
+                r#"mullvad_api_request_duration_seconds_bucket{le="2.5"} 2"#,
+                r#"mullvad_api_request_duration_seconds_bucket{le="5"} 2"#,
+                r#"mullvad_api_request_duration_seconds_bucket{le="10"} 3"#,
+                r#"mullvad_api_request_duration_seconds_bucket{le="+Inf"} 3"#,
+                "mullvad_api_request_duration_seconds_sum 3.2",
+                "mullvad_api_request_duration_seconds_count 3",
+                r#"mullvad_api_request_errors_total{kind="timeout"} 1"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_connect_failures() {
+        let mut state = State::new();
+
+        // Blocking without attempting to connect is not a failure
+        state.set_tunnel_state(&error_state(ErrorStateCause::StartTunnelError));
+        // Neither is being offline while connecting
+        state.tunnel_state = "connecting";
+        state.set_tunnel_state(&error_state(ErrorStateCause::IsOffline));
+        assert!(state.connect_failures.is_empty());
+
+        state.tunnel_state = "connecting";
+        state.set_tunnel_state(&error_state(ErrorStateCause::StartTunnelError));
+        assert_eq!(
+            state.connect_failures,
+            BTreeMap::from([("start_tunnel_error", 1)])
+        );
+    }
+}
@@ -59,7 +59,7 @@ pub async fn migrate_formats(settings_dir: &Path, settings: &mut serde_json::Val
         .await
         .map_err(Error::ReadHistory)?;
 
-    if is_format_v3(&bytes) {
+    if is_format_v3(&bytes) || is_format_v4(&bytes) {
         return Ok(());
     }
     write_format_v3(file, migrate_formats_inner(&bytes, settings)?).await
@@ -90,6 +90,19 @@ fn is_format_v3(bytes: &[u8]) -> bool {
     }
 }
 
+/// The last used account along with any saved accounts. This is converted from v3 by the
+/// account history itself.
+fn is_format_v4(bytes: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Format {
+        #[allow(dead_code)]
+        token: Option<AccountToken>,
+        #[allow(dead_code)]
+        saved: Vec<serde_json::Value>,
+    }
+    serde_json::from_slice::<'_, Format>(bytes).is_ok()
+}
+
 async fn write_format_v3(mut file: File, token: Option<AccountToken>) -> Result<()> {
     file.set_len(0).await.map_err(Error::WriteHistory)?;
     file.seek(io::SeekFrom::Start(0))
@@ -168,6 +181,8 @@ mod test {
 ]"#;
     pub const ACCOUNT_HISTORY_V2_EMPTY: &str = r#"[]"#;
     pub const ACCOUNT_HISTORY_V3: &str = r#"123456"#;
+    pub const ACCOUNT_HISTORY_V4: &str =
+        r#"{"token":"123456","saved":[{"name":"test","account_token":"4567"}]}"#;
 
     pub const OLD_SETTINGS: &str = r#"
 {
@@ -333,6 +348,14 @@ mod test {
         assert!(super::is_format_v3(ACCOUNT_HISTORY_V3.as_bytes()));
     }
 
+    #[test]
+    fn test_v4() {
+        assert!(!super::is_format_v4(ACCOUNT_HISTORY_V1.as_bytes()));
+        assert!(!super::is_format_v4(ACCOUNT_HISTORY_V2.as_bytes()));
+        assert!(!super::is_format_v4(ACCOUNT_HISTORY_V3.as_bytes()));
+        assert!(super::is_format_v4(ACCOUNT_HISTORY_V4.as_bytes()));
+    }
+
     #[test]
     fn test_v2() {
         assert!(super::try_format_v2(ACCOUNT_HISTORY_V1.as_bytes()).is_err());
@@ -243,10 +243,11 @@ mod windows {
     type SECURITY_INFORMATION = u32;
 
     const MIGRATION_DIRNAME: &str = "windows.old";
-    const MIGRATE_FILES: [(&str, bool); 3] = [
+    const MIGRATE_FILES: [(&str, bool); 4] = [
         ("settings.json", true),
         ("device.json", true),
         ("account-history.json", false),
+        ("saved-accounts.json", false),
     ];
 
     #[derive(thiserror::Error, Debug)]
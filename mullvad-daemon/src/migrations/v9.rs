@@ -41,6 +41,9 @@ const SPLIT_TUNNELING_STATE: &str = "split-tunnelling-enabled.txt";
 /// apps which has been stored by the Android client and writes them to the daemon's settings,
 /// adding the 'split_tunnel' key to the settings object in the process.
 ///
+/// On all platforms, this migration also adds an empty 'schedule' key, which holds the rules that
+/// connect or disconnect the tunnel at certain times.
+///
 /// # Note
 /// This `migrate` function needs to get passed a `settings_dir` to work on Android. This is
 /// because the Android client will pass the settings directory when initializing the daemon,
@@ -73,6 +76,11 @@ pub fn migrate(
         }
     }
 
+    // TODO: Remove the check when closing the migration, like above.
+    if !json_blob.contains_key("schedule") {
+        add_schedule(json_blob);
+    }
+
     // TODO: Uncomment this when closing the migration:
     // json_blob["settings_version"] = serde_json::json!(SettingsVersion::V10);
 
@@ -86,6 +94,11 @@ fn version_matches(settings: &serde_json::Value) -> bool {
         .unwrap_or(false)
 }
 
+/// Add an empty "schedule" key to the settings object.
+fn add_schedule(settings: &mut JsonSettings) {
+    settings.insert("schedule".to_string(), serde_json::json!({ "rules": [] }));
+}
+
 /// Represent the settings blob for what it is: A JSON-object.
 fn to_settings_object(settings: &mut serde_json::Value) -> Result<&mut JsonSettings> {
     settings
@@ -195,12 +208,38 @@ mod android {
     }
 }
 
-#[cfg(target_os = "android")]
 #[cfg(test)]
 mod test {
+    #[cfg(target_os = "android")]
     use crate::migrations::v9::test::constants::V9_SETTINGS;
 
+    /// Assert that an empty schedule is added, and that existing rules are left alone.
+    #[test]
+    fn test_v9_add_schedule() {
+        use super::migrate;
+
+        let mut settings = serde_json::json!({ "settings_version": 9 });
+        migrate(
+            &mut settings,
+            #[cfg(target_os = "android")]
+            None,
+        )
+        .unwrap();
+        assert_eq!(settings["schedule"], serde_json::json!({ "rules": [] }));
+
+        let rules = serde_json::json!({ "rules": [{ "name": "night" }] });
+        let mut settings = serde_json::json!({ "settings_version": 9, "schedule": rules.clone() });
+        migrate(
+            &mut settings,
+            #[cfg(target_os = "android")]
+            None,
+        )
+        .unwrap();
+        assert_eq!(settings["schedule"], rules);
+    }
+
     /// Assert that split-tunneling settings has been added to the android settings post-migration.
+    #[cfg(target_os = "android")]
     #[test]
     fn test_v9_to_v10_migration() {
         use crate::migrations::v9::{
@@ -219,6 +258,7 @@ mod test {
         assert_eq!(settings, new_settings);
     }
 
+    #[cfg(target_os = "android")]
     mod constants {
         /// This settings blob does not contain the "split_tunnel" option.
         pub const V9_ANDROID_SETTINGS: &str = r#"
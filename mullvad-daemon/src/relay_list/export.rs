@@ -0,0 +1,210 @@
+//! Import and export of relay lists, for machines that cannot reach the API.
+
+use super::Error;
+use chrono::{DateTime, Utc};
+use mullvad_types::relay_list::RelayList;
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+
+/// Version of the export format. Bump this if the format changes in an incompatible way.
+const FORMAT_VERSION: u32 = 2;
+
+/// A relay list as written by `mullvad relay list export`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ExportedRelayList {
+    format_version: u32,
+    exported: DateTime<Utc>,
+    /// When `relay_list` was received from the API, or when it was imported.
+    fetched: DateTime<Utc>,
+    /// The etag of `relay_list`, as received from the API.
+    etag: String,
+    /// Hex-encoded SHA-256 digest of the serialized `relay_list`. Since it is not keyed, it only
+    /// detects accidental corruption, such as a truncated file.
+    sha256: String,
+    relay_list: RelayList,
+}
+
+/// Serialize a relay list that was fetched at `fetched` into the export format.
+pub fn export(relay_list: &RelayList, fetched: SystemTime) -> Result<String, Error> {
+    let etag = relay_list.etag.clone().ok_or(Error::MissingEtag)?;
+    let exported = ExportedRelayList {
+        format_version: FORMAT_VERSION,
+        exported: Utc::now(),
+        fetched: fetched.into(),
+        etag,
+        sha256: digest(relay_list)?,
+        relay_list: relay_list.clone(),
+    };
+    serde_json::to_string_pretty(&exported).map_err(Error::SerializeExport)
+}
+
+/// Parse and validate an exported relay list. The list must not have been corrupted, it must have
+/// been fetched from the API, and it must be newer than the current list, which was fetched at
+/// `current_fetched`.
+///
+/// Anyone can produce a list that passes these checks, since the API does not sign relay lists.
+/// Importing therefore requires the same access as logging in or resetting the daemon.
+pub fn import(data: &str, current_fetched: SystemTime) -> Result<RelayList, Error> {
+    let exported: ExportedRelayList = serde_json::from_str(data).map_err(Error::ParseExport)?;
+
+    if exported.format_version != FORMAT_VERSION {
+        return Err(Error::UnsupportedFormat(exported.format_version));
+    }
+    if digest(&exported.relay_list)? != exported.sha256.to_ascii_lowercase() {
+        return Err(Error::ChecksumMismatch);
+    }
+    if exported.relay_list.etag.as_deref() != Some(exported.etag.as_str()) {
+        return Err(Error::EtagMismatch);
+    }
+    if exported.fetched > exported.exported || exported.exported > Utc::now() {
+        return Err(Error::InvalidTimestamp);
+    }
+    if exported.fetched <= DateTime::<Utc>::from(current_fetched) {
+        return Err(Error::StaleRelayList);
+    }
+    if exported.relay_list.relays().next().is_none() {
+        return Err(Error::NoRelays);
+    }
+
+    Ok(exported.relay_list)
+}
+
+fn digest(relay_list: &RelayList) -> Result<String, Error> {
+    let bytes = serde_json::to_vec(relay_list).map_err(Error::SerializeExport)?;
+    Ok(hex::encode(Sha256::digest(bytes)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::relay_list::{Relay, RelayEndpointData, RelayListCity, RelayListCountry};
+    use std::{net::Ipv4Addr, time::Duration};
+
+    fn relay_list() -> RelayList {
+        let mut relay_list = RelayList::empty();
+        relay_list.etag = Some("\"1234\"".to_owned());
+        relay_list.countries.push(RelayListCountry {
+            name: "Sweden".to_owned(),
+            code: "se".to_owned(),
+            cities: vec![RelayListCity {
+                name: "Gothenburg".to_owned(),
+                code: "got".to_owned(),
+                latitude: 57.70887,
+                longitude: 11.97456,
+                relays: vec![Relay {
+                    hostname: "se-got-ovpn-001".to_owned(),
+                    ipv4_addr_in: Ipv4Addr::new(185, 213, 154, 131),
+                    ipv6_addr_in: None,
+                    include_in_country: true,
+                    active: true,
+                    owned: true,
+                    provider: "31173".to_owned(),
+                    weight: 1,
+                    custom: false,
+                    endpoint_data: RelayEndpointData::Openvpn,
+                    location: None,
+                }],
+            }],
+        });
+        relay_list
+    }
+
+    fn exported(fetched: SystemTime) -> ExportedRelayList {
+        serde_json::from_str(&export(&relay_list(), fetched).unwrap()).unwrap()
+    }
+
+    fn hours_ago(hours: u64) -> SystemTime {
+        SystemTime::now() - Duration::from_secs(hours * 60 * 60)
+    }
+
+    #[test]
+    fn test_import_newer_list() {
+        let data = serde_json::to_string(&exported(hours_ago(1))).unwrap();
+        let imported = import(&data, hours_ago(2)).unwrap();
+        assert_eq!(imported.etag, relay_list().etag);
+    }
+
+    #[test]
+    fn test_tampered_list_is_rejected() {
+        // Changing the list without updating the checksum
+        let mut exported = exported(hours_ago(1));
+        exported.relay_list.etag = Some("\"5678\"".to_owned());
+        let data = serde_json::to_string(&exported).unwrap();
+        assert!(matches!(
+            import(&data, hours_ago(2)),
+            Err(Error::ChecksumMismatch)
+        ));
+
+        // Changing the etag outside of the list
+        let mut exported = self::exported(hours_ago(1));
+        exported.etag = "\"5678\"".to_owned();
+        let data = serde_json::to_string(&exported).unwrap();
+        assert!(matches!(
+            import(&data, hours_ago(2)),
+            Err(Error::EtagMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_stale_list_is_rejected() {
+        let data = serde_json::to_string(&exported(hours_ago(2))).unwrap();
+        assert!(matches!(
+            import(&data, hours_ago(1)),
+            Err(Error::StaleRelayList)
+        ));
+
+        // A list exported now that was fetched long ago is still stale
+        let mut exported = exported(hours_ago(2));
+        exported.exported = Utc::now();
+        let data = serde_json::to_string(&exported).unwrap();
+        assert!(matches!(
+            import(&data, hours_ago(1)),
+            Err(Error::StaleRelayList)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_timestamps_are_rejected() {
+        let mut exported = exported(hours_ago(1));
+        exported.fetched = Utc::now() + chrono::Duration::hours(1);
+        exported.exported = exported.fetched;
+        let data = serde_json::to_string(&exported).unwrap();
+        assert!(matches!(
+            import(&data, hours_ago(2)),
+            Err(Error::InvalidTimestamp)
+        ));
+
+        let mut exported = self::exported(hours_ago(1));
+        exported.fetched = exported.exported + chrono::Duration::seconds(1);
+        let data = serde_json::to_string(&exported).unwrap();
+        assert!(matches!(
+            import(&data, hours_ago(2)),
+            Err(Error::InvalidTimestamp)
+        ));
+    }
+
+    #[test]
+    fn test_malformed_list_is_rejected() {
+        assert!(matches!(
+            import("", hours_ago(2)),
+            Err(Error::ParseExport(_))
+        ));
+        assert!(matches!(
+            import("{\"format_version\": 2}", hours_ago(2)),
+            Err(Error::ParseExport(_))
+        ));
+
+        let mut exported = exported(hours_ago(1));
+        exported.format_version = 1;
+        let data = serde_json::to_string(&exported).unwrap();
+        assert!(matches!(
+            import(&data, hours_ago(2)),
+            Err(Error::UnsupportedFormat(1))
+        ));
+
+        let mut empty = RelayList::empty();
+        empty.etag = Some("\"1234\"".to_owned());
+        let data = export(&empty, hours_ago(1)).unwrap();
+        assert!(matches!(import(&data, hours_ago(2)), Err(Error::NoRelays)));
+    }
+}
@@ -1,11 +1,12 @@
 //! Relay list updater
 
 use futures::{
-    channel::mpsc,
+    channel::{mpsc, oneshot},
     future::{Fuse, FusedFuture},
     Future, FutureExt, SinkExt, StreamExt,
 };
 use std::{
+    collections::BTreeSet,
     path::{Path, PathBuf},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
@@ -13,10 +14,17 @@ use tokio::fs::File;
 
 use mullvad_api::{availability::ApiAvailabilityHandle, rest::MullvadRestHandle, RelayListProxy};
 use mullvad_relay_selector::RelaySelector;
-use mullvad_types::relay_list::RelayList;
+use mullvad_types::{
+    constraints::Constraint,
+    relay_constraints::{GeographicLocationConstraint, LocationConstraint, RelaySettings},
+    relay_list::{RelayList, RelayListDiff},
+    settings::Settings,
+};
 use talpid_future::retry::{retry_future, ExponentialBackoff, Jittered};
 use talpid_types::ErrorExt;
 
+pub mod export;
+
 /// How often the updater should wake up to check the cache of the in-memory cache of relays.
 /// This check is very cheap. The only reason to not have it very often is because if downloading
 /// constantly fails it will try very often and fill the logs etc.
@@ -32,6 +40,9 @@ const DOWNLOAD_RETRY_STRATEGY: Jittered<ExponentialBackoff> = Jittered::jitter(
 /// Where the relay list is cached on disk.
 pub(crate) const RELAYS_FILENAME: &str = "relays.json";
 
+/// How many relay list changes to remember.
+pub(crate) const MAX_RELAY_LIST_CHANGES: usize = 10;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Downloader already shut down")]
@@ -39,18 +50,73 @@ pub enum Error {
 
     #[error("Mullvad relay selector error")]
     RelaySelector(#[from] mullvad_relay_selector::Error),
+
+    #[error("The relay list has no etag")]
+    MissingEtag,
+
+    #[error("Failed to serialize relay list")]
+    SerializeExport(#[source] serde_json::Error),
+
+    #[error("Failed to parse exported relay list")]
+    ParseExport(#[source] serde_json::Error),
+
+    #[error("Unsupported relay list export format: {0}")]
+    UnsupportedFormat(u32),
+
+    #[error("The relay list checksum does not match its contents")]
+    ChecksumMismatch,
+
+    #[error("The relay list etag does not match its contents")]
+    EtagMismatch,
+
+    #[error("The relay list contains no relays")]
+    NoRelays,
+
+    #[error("The relay list has invalid timestamps")]
+    InvalidTimestamp,
+
+    #[error("The relay list is not newer than the current relay list")]
+    StaleRelayList,
+}
+
+/// Converts an [Error] to a management interface status
+impl From<Error> for mullvad_management_interface::Status {
+    fn from(error: Error) -> mullvad_management_interface::Status {
+        use mullvad_management_interface::Status;
+
+        match error {
+            Error::MissingEtag => Status::failed_precondition(error.to_string()),
+            Error::ParseExport(_)
+            | Error::UnsupportedFormat(_)
+            | Error::ChecksumMismatch
+            | Error::EtagMismatch
+            | Error::NoRelays
+            | Error::InvalidTimestamp => Status::invalid_argument(error.to_string()),
+            Error::StaleRelayList => Status::failed_precondition(error.to_string()),
+            Error::DownloaderShutdown | Error::RelaySelector(_) | Error::SerializeExport(_) => {
+                Status::internal(error.to_string())
+            }
+        }
+    }
+}
+
+enum UpdaterCommand {
+    /// Check for a new relay list.
+    Update,
+    /// Replace the relay list with one that was obtained some other way.
+    Import(RelayList, oneshot::Sender<Result<(), Error>>),
 }
 
 #[derive(Clone)]
 pub struct RelayListUpdaterHandle {
-    tx: mpsc::Sender<()>,
+    tx: mpsc::Sender<UpdaterCommand>,
 }
 
 impl RelayListUpdaterHandle {
     pub async fn update(&mut self) {
         if let Err(error) = self
             .tx
-            .send(())
+            .send(UpdaterCommand::Update)
             .await
             .map_err(|_| Error::DownloaderShutdown)
         {
@@ -60,13 +126,24 @@ impl RelayListUpdaterHandle {
             );
         }
     }
+
+    /// Replace the current relay list, as if it had been downloaded. The list is expected to
+    /// have been validated already.
+    pub async fn import(&mut self, relay_list: RelayList) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(UpdaterCommand::Import(relay_list, tx))
+            .await
+            .map_err(|_| Error::DownloaderShutdown)?;
+        rx.await.map_err(|_| Error::DownloaderShutdown)?
+    }
 }
 
 pub struct RelayListUpdater {
     api_client: RelayListProxy,
     cache_path: PathBuf,
     relay_selector: RelaySelector,
-    on_update: Box<dyn Fn(&RelayList) + Send + 'static>,
+    on_update: Box<dyn Fn(&RelayList, RelayListDiff) + Send + 'static>,
     last_check: SystemTime,
     api_availability: ApiAvailabilityHandle,
 }
@@ -76,7 +153,7 @@ impl RelayListUpdater {
         selector: RelaySelector,
         api_handle: MullvadRestHandle,
         cache_dir: &Path,
-        on_update: impl Fn(&RelayList) + Send + 'static,
+        on_update: impl Fn(&RelayList, RelayListDiff) + Send + 'static,
     ) -> RelayListUpdaterHandle {
         let (tx, cmd_rx) = mpsc::channel(1);
         let api_availability = api_handle.availability.clone();
@@ -95,7 +172,7 @@ impl RelayListUpdater {
         RelayListUpdaterHandle { tx }
     }
 
-    async fn run(mut self, mut cmd_rx: mpsc::Receiver<()>) {
+    async fn run(mut self, mut cmd_rx: mpsc::Receiver<UpdaterCommand>) {
         let mut download_future = Box::pin(Fuse::terminated());
         loop {
             let next_check = tokio::time::sleep(UPDATE_CHECK_INTERVAL).fuse();
@@ -116,11 +193,24 @@ impl RelayListUpdater {
 
                 cmd = cmd_rx.next() => {
                     match cmd {
-                        Some(()) => {
+                        Some(UpdaterCommand::Update) => {
                             let tag = self.relay_selector.etag();
                             download_future = Box::pin(Self::download_relay_list(self.api_availability.clone(), self.api_client.clone(), tag).fuse());
                             self.last_check = SystemTime::now();
                         },
+                        Some(UpdaterCommand::Import(relay_list, tx)) => {
+                            log::info!("Importing relay list with etag {:?}", relay_list.etag);
+                            let result = self.update_cache(relay_list).await;
+                            if let Err(ref error) = result {
+                                log::error!(
+                                    "{}",
+                                    error.display_chain_with_msg(
+                                        "Failed to update relay list cache"
+                                    )
+                                );
+                            }
+                            let _ = tx.send(result);
+                        },
                         None => {
                             log::trace!("Relay list updater shutting down");
                             return;
@@ -138,8 +228,11 @@ impl RelayListUpdater {
     ) {
         match result {
             Ok(Some(relay_list)) => {
-                if let Err(err) = self.update_cache(relay_list).await {
-                    log::error!("Failed to update relay list cache: {}", err);
+                if let Err(error) = self.update_cache(relay_list).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to update relay list cache")
+                    );
                 }
             }
             Ok(None) => log::debug!("Relay list is up-to-date"),
@@ -183,17 +276,15 @@ impl RelayListUpdater {
         )
     }
 
+    /// Start using `new_relay_list`, and write it to the cache file. The new list is used even if
+    /// it cannot be written to disk, but the error is returned.
     async fn update_cache(&mut self, new_relay_list: RelayList) -> Result<(), Error> {
-        if let Err(error) = Self::cache_relays(&self.cache_path, &new_relay_list).await {
-            log::error!(
-                "{}",
-                error.display_chain_with_msg("Failed to update relay cache on disk")
-            );
-        }
+        let cache_result = Self::cache_relays(&self.cache_path, &new_relay_list).await;
 
+        let diff = RelayListDiff::new(&self.relay_selector.get_relays(), &new_relay_list);
         self.relay_selector.set_relays(new_relay_list.clone());
-        (self.on_update)(&new_relay_list);
-        Ok(())
+        (self.on_update)(&new_relay_list, diff);
+        cache_result
     }
 
     /// Write a `RelayList` to the cache file.
@@ -211,3 +302,40 @@ impl RelayListUpdater {
         Ok(())
     }
 }
+
+/// Return the hostnames of all relays that are referred to by the settings, i.e. by custom lists,
+/// relay overrides or the relay constraints.
+pub fn pinned_hostnames(settings: &Settings) -> BTreeSet<&str> {
+    let mut hostnames = BTreeSet::new();
+
+    let locations = settings
+        .custom_lists
+        .iter()
+        .flat_map(|list| list.locations.iter());
+    for location in locations {
+        if let GeographicLocationConstraint::Hostname(_, _, hostname) = location {
+            hostnames.insert(hostname.as_str());
+        }
+    }
+
+    for relay_override in &settings.relay_overrides {
+        hostnames.insert(relay_override.hostname.as_str());
+    }
+
+    if let RelaySettings::Normal(constraints) = &settings.relay_settings {
+        let locations = [
+            &constraints.location,
+            &constraints.wireguard_constraints.entry_location,
+        ];
+        for location in locations {
+            if let Constraint::Only(LocationConstraint::Location(
+                GeographicLocationConstraint::Hostname(_, _, hostname),
+            )) = location
+            {
+                hostnames.insert(hostname.as_str());
+            }
+        }
+    }
+
+    hostnames
+}
@@ -0,0 +1,347 @@
+//! Applies the time-based rules in the settings. See [`mullvad_types::schedule`].
+//!
+//! Rules do not connect the tunnel once the monthly data cap has disconnected it, neither when a
+//! rule becomes active nor when the state saved before the first rule is restored. This lasts
+//! until the next month or until the data cap is changed. The user may still connect the tunnel.
+
+use crate::{Daemon, DaemonEventSender, Error, EventListener, InternalDaemonEvent};
+use chrono::{DateTime, Local, Timelike, Utc};
+use futures::future::{abortable, AbortHandle};
+use mullvad_types::{
+    constraints::Constraint,
+    relay_constraints::{LocationConstraint, RelaySettings},
+    schedule::{Schedule, ScheduleAction, ScheduleRule},
+    settings::LockedSetting,
+    states::TargetState,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use talpid_core::mpsc::Sender;
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+/// Stores the [`SavedState`], since a rule may change the relay location in the settings and
+/// still be active when the daemon is stopped.
+const SAVED_STATE_FILE: &str = "schedule-saved-state.json";
+
+/// Sent at the start of every minute, since a different rule may apply then.
+pub struct ScheduleTimerEvent;
+
+impl From<ScheduleTimerEvent> for InternalDaemonEvent {
+    fn from(_: ScheduleTimerEvent) -> Self {
+        InternalDaemonEvent::ScheduleTimer
+    }
+}
+
+/// Settings that were in effect before the first rule became active.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SavedState {
+    target_state: Option<TargetState>,
+    /// Only set if a rule has changed the relay location.
+    location: Option<Constraint<LocationConstraint>>,
+}
+
+impl SavedState {
+    fn is_empty(&self) -> bool {
+        self.target_state.is_none() && self.location.is_none()
+    }
+}
+
+pub enum Transition {
+    /// A rule became active, possibly replacing another rule.
+    Enter(ScheduleRule),
+    /// No rule is active anymore.
+    Leave(SavedState),
+}
+
+/// Keeps track of which rule is active, and what to return to once none is.
+pub struct Scheduler {
+    active_rule: Option<ScheduleRule>,
+    saved_state: SavedState,
+    saved_state_path: PathBuf,
+    timer: AbortHandle,
+}
+
+impl Scheduler {
+    /// Create a scheduler, restoring any state saved before the daemon was last stopped.
+    pub async fn new(event_tx: &DaemonEventSender, settings_dir: &Path) -> Self {
+        let saved_state_path = settings_dir.join(SAVED_STATE_FILE);
+        let saved_state = read_saved_state(&saved_state_path).await;
+
+        let (timer, timer_handle) = abortable(tick_every_minute(event_tx.to_specialized_sender()));
+        tokio::spawn(timer);
+
+        Scheduler {
+            active_rule: None,
+            saved_state,
+            saved_state_path,
+            timer: timer_handle,
+        }
+    }
+
+    /// Return the transition to make at `now`, if the active rule has changed.
+    ///
+    /// A state saved by a rule that ended while the daemon was stopped is restored as well.
+    pub async fn update(&mut self, schedule: &Schedule, now: DateTime<Utc>) -> Option<Transition> {
+        let rule = schedule.active_rule(now);
+        if rule == self.active_rule.as_ref() && (rule.is_some() || self.saved_state.is_empty()) {
+            return None;
+        }
+        self.active_rule = rule.cloned();
+        match rule {
+            Some(rule) => Some(Transition::Enter(rule.clone())),
+            None => {
+                let saved_state = std::mem::take(&mut self.saved_state);
+                self.remove_saved_state().await;
+                Some(Transition::Leave(saved_state))
+            }
+        }
+    }
+
+    /// Remember the target state to return to, unless one is already saved.
+    pub async fn save_target_state(&mut self, target_state: TargetState) {
+        if self.saved_state.target_state.is_none() {
+            self.saved_state.target_state = Some(target_state);
+            self.save().await;
+        }
+    }
+
+    /// Remember the relay location to return to, unless one is already saved.
+    async fn save_location(&mut self, location: Constraint<LocationConstraint>) {
+        if self.saved_state.location.is_none() {
+            self.saved_state.location = Some(location);
+            self.save().await;
+        }
+    }
+
+    /// Take the relay location to return to, if a rule has changed it.
+    async fn take_saved_location(&mut self) -> Option<Constraint<LocationConstraint>> {
+        let location = self.saved_state.location.take()?;
+        self.save().await;
+        Some(location)
+    }
+
+    async fn save(&self) {
+        match serde_json::to_vec(&self.saved_state) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.saved_state_path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write saved schedule state")
+                    );
+                }
+            }
+            Err(error) => log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to serialize saved schedule state")
+            ),
+        }
+    }
+
+    async fn remove_saved_state(&self) {
+        match fs::remove_file(&self.saved_state_path).await {
+            Ok(()) => (),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to remove saved schedule state")
+            ),
+        }
+    }
+}
+
+async fn read_saved_state(path: &Path) -> SavedState {
+    match fs::read(path).await {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|error| {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to parse saved schedule state")
+            );
+            SavedState::default()
+        }),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => SavedState::default(),
+        Err(error) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to read saved schedule state")
+            );
+            SavedState::default()
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.timer.abort();
+    }
+}
+
+async fn tick_every_minute(event_tx: DaemonEventSender<ScheduleTimerEvent>) {
+    while event_tx.send(ScheduleTimerEvent).is_ok() {
+        talpid_time::sleep(until_next_minute(Utc::now())).await;
+    }
+}
+
+fn until_next_minute(now: DateTime<Utc>) -> Duration {
+    let elapsed = Duration::new(u64::from(now.second()), now.nanosecond());
+    Duration::from_secs(60).saturating_sub(elapsed)
+}
+
+impl<L> Daemon<L>
+where
+    L: EventListener,
+{
+    /// Add a rule with lower precedence than all existing rules.
+    ///
+    /// Returns an error if the name is empty, too long, or used by another rule.
+    pub async fn add_schedule_rule(&mut self, rule: ScheduleRule) -> Result<(), Error> {
+        self.settings
+            .try_update(|settings| settings.schedule.add(rule))
+            .await
+            .map_err(Error::SettingsError)?;
+        self.apply_schedule().await;
+        Ok(())
+    }
+
+    /// Remove a schedule rule.
+    ///
+    /// Returns an error if there is no rule with the given name.
+    pub async fn remove_schedule_rule(&mut self, name: String) -> Result<(), Error> {
+        self.settings
+            .try_update(|settings| settings.schedule.remove(&name))
+            .await
+            .map_err(Error::SettingsError)?;
+        self.apply_schedule().await;
+        Ok(())
+    }
+
+    /// Apply the schedule rule that is active now, if a different one was active before.
+    pub(crate) async fn apply_schedule(&mut self) {
+        match self
+            .scheduler
+            .update(&self.settings.schedule, Utc::now())
+            .await
+        {
+            Some(Transition::Enter(rule)) => self.enter_schedule_rule(rule).await,
+            Some(Transition::Leave(saved_state)) => self.leave_schedule(saved_state).await,
+            None => (),
+        }
+    }
+
+    async fn enter_schedule_rule(&mut self, rule: ScheduleRule) {
+        log::info!("Schedule rule \"{}\" is now active", rule.name);
+        self.scheduler.save_target_state(*self.target_state).await;
+
+        match rule.action {
+            ScheduleAction::Connect {
+                location: Some(ref location),
+            } => {
+                if let RelaySettings::Normal(ref constraints) = self.settings.relay_settings {
+                    self.scheduler
+                        .save_location(constraints.location.clone())
+                        .await;
+                }
+                self.set_scheduled_location(Constraint::Only(location.clone()))
+                    .await;
+            }
+            // A previous rule may have changed the location
+            _ => {
+                if let Some(location) = self.scheduler.take_saved_location().await {
+                    self.set_scheduled_location(location).await;
+                }
+            }
+        }
+        self.set_scheduled_target_state(rule.action.target_state())
+            .await;
+    }
+
+    async fn leave_schedule(&mut self, saved_state: SavedState) {
+        log::info!("No schedule rule is active anymore");
+        if let Some(location) = saved_state.location {
+            self.set_scheduled_location(location).await;
+        }
+        if let Some(target_state) = saved_state.target_state {
+            self.set_scheduled_target_state(target_state).await;
+        }
+    }
+
+    async fn set_scheduled_target_state(&mut self, target_state: TargetState) {
+        if target_state == TargetState::Secured
+            && self
+                .data_usage
+                .is_disconnected_by_cap(&self.settings.data_cap, Local::now().date_naive())
+        {
+            log::info!("Not connecting since the monthly data cap has been reached");
+            return;
+        }
+        self.set_target_state(target_state).await;
+    }
+
+    async fn set_scheduled_location(&mut self, location: Constraint<LocationConstraint>) {
+        if self
+            .settings
+            .locked_settings
+            .contains(&LockedSetting::RelayLocation)
+        {
+            log::warn!("Not changing the relay location since it is locked by policy");
+            return;
+        }
+        if !matches!(self.settings.relay_settings, RelaySettings::Normal(_)) {
+            log::warn!("Not changing the relay location since a custom tunnel endpoint is used");
+            return;
+        }
+
+        let result = self
+            .settings
+            .update(move |settings| {
+                if let RelaySettings::Normal(ref mut constraints) = settings.relay_settings {
+                    constraints.location = location;
+                }
+            })
+            .await;
+        match result {
+            Ok(true) => {
+                log::info!("Initiating tunnel restart because the relay location changed");
+                self.reconnect_tunnel();
+            }
+            Ok(false) => (),
+            Err(error) => log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to change the scheduled relay location")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_until_next_minute() {
+        let now = DateTime::parse_from_rfc3339("2024-06-03T12:34:45.5Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(until_next_minute(now), Duration::from_millis(14_500));
+    }
+
+    #[tokio::test]
+    async fn test_saved_state_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SAVED_STATE_FILE);
+
+        assert_eq!(read_saved_state(&path).await, SavedState::default());
+
+        let state = SavedState {
+            target_state: Some(TargetState::Unsecured),
+            location: Some(Constraint::Any),
+        };
+        fs::write(&path, serde_json::to_vec(&state).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(read_saved_state(&path).await, state);
+    }
+}
@@ -1,8 +1,10 @@
 use futures::TryFutureExt;
 use mullvad_types::{
     custom_list::Error as CustomListError,
+    custom_relay::Error as CustomRelayError,
     relay_constraints::{RelayConstraints, RelaySettings, WireguardConstraints},
-    settings::{DnsState, Settings},
+    schedule::Error as ScheduleError,
+    settings::{DnsState, LockedSetting, Settings},
 };
 use std::{
     fmt::{self, Display},
@@ -17,6 +19,7 @@ use tokio::{
 };
 
 pub mod patch;
+pub mod policy;
 
 const SETTINGS_FILE: &str = "settings.json";
 
@@ -39,6 +42,9 @@ pub enum Error {
 
     #[error("Failed to apply settings update")]
     UpdateFailed(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("The setting '{0}' is locked by an administrator policy")]
+    SettingLocked(LockedSetting),
 }
 
 /// Converts an [Error] to a management interface status
@@ -57,9 +63,30 @@ impl From<Error> for mullvad_management_interface::Status {
                 let custom_list_err = *err.downcast::<CustomListError>().unwrap();
                 handle_custom_list_error(custom_list_err)
             }
+            Error::UpdateFailed(err)
+                if err
+                    .downcast_ref::<mullvad_types::custom_relay::Error>()
+                    .is_some() =>
+            {
+                let custom_relay_err = *err.downcast::<CustomRelayError>().unwrap();
+                handle_custom_relay_error(custom_relay_err)
+            }
+            Error::UpdateFailed(err)
+                if err
+                    .downcast_ref::<mullvad_types::schedule::Error>()
+                    .is_some() =>
+            {
+                let schedule_err = *err.downcast::<ScheduleError>().unwrap();
+                handle_schedule_error(schedule_err)
+            }
             Error::SerializeError(..) | Error::ParseError(..) | Error::UpdateFailed(..) => {
                 Status::new(Code::Internal, error.to_string())
             }
+            Error::SettingLocked(..) => Status::with_details(
+                Code::PermissionDenied,
+                error.to_string(),
+                mullvad_management_interface::SETTING_LOCKED_DETAILS.into(),
+            ),
         }
     }
 }
@@ -89,9 +116,34 @@ fn handle_custom_list_error(
     }
 }
 
+fn handle_custom_relay_error(
+    custom_relay_err: CustomRelayError,
+) -> mullvad_management_interface::Status {
+    use mullvad_management_interface::{Code, Status};
+    let code = match custom_relay_err {
+        CustomRelayError::DuplicateName => Code::AlreadyExists,
+        CustomRelayError::NotFound => Code::NotFound,
+        CustomRelayError::InvalidName
+        | CustomRelayError::NoAddresses
+        | CustomRelayError::NoAllowedIps => Code::InvalidArgument,
+    };
+    Status::new(code, custom_relay_err.to_string())
+}
+
+fn handle_schedule_error(schedule_err: ScheduleError) -> mullvad_management_interface::Status {
+    use mullvad_management_interface::{Code, Status};
+    let code = match schedule_err {
+        ScheduleError::DuplicateName => Code::AlreadyExists,
+        ScheduleError::NotFound => Code::NotFound,
+        ScheduleError::InvalidName => Code::InvalidArgument,
+    };
+    Status::new(code, schedule_err.to_string())
+}
+
 pub struct SettingsPersister {
     settings: Settings,
     path: PathBuf,
+    policy: policy::SettingsPolicy,
     #[allow(clippy::type_complexity)]
     on_change_listeners: Vec<Box<dyn Fn(&Settings) + Send + Sync>>,
 }
@@ -99,7 +151,8 @@ pub struct SettingsPersister {
 pub type MadeChanges = bool;
 
 impl SettingsPersister {
-    /// Loads user settings from file. If it fails, it returns the defaults.
+    /// Loads user settings from file. If it fails, it returns the defaults. Settings locked by
+    /// the administrator policy in `settings_dir` are overwritten with their pinned values.
     pub async fn load(settings_dir: &Path) -> Self {
         let path = settings_dir.join(SETTINGS_FILE);
         let LoadSettingsResult {
@@ -117,9 +170,14 @@ impl SettingsPersister {
             settings.show_beta_releases = true;
         }
 
+        let policy = policy::SettingsPolicy::load(settings_dir).await;
+        should_save |= policy.check(&settings).is_err();
+        policy.apply(&mut settings);
+
         let mut persister = SettingsPersister {
             settings,
             path,
+            policy,
             on_change_listeners: vec![],
         };
 
@@ -221,6 +279,7 @@ impl SettingsPersister {
     /// Resets default settings
     pub async fn reset(&mut self) -> Result<(), Error> {
         self.settings = Self::default_settings();
+        self.policy.apply(&mut self.settings);
         let path = self.path.clone();
         self.save()
             .or_else(|e| async move {
@@ -288,12 +347,16 @@ impl SettingsPersister {
     ///
     /// # On failure
     ///
-    /// `try_update` may fail in two scenarios
+    /// `try_update` may fail in three scenarios
     ///
     /// ## The settings could not be written to disk
     ///
     /// In this case, all changes are rolled back and an error is returned.
     ///
+    /// ## A setting locked by the administrator policy was changed
+    ///
+    /// All changes are rolled back and [`Error::SettingLocked`] is returned.
+    ///
     /// ## `update_fn` failed
     ///
     /// If `update_fn` were to fail the error will be propagated through the
@@ -331,6 +394,14 @@ impl SettingsPersister {
         update_fn(&mut new_settings)
             .map_err(Box::from)
             .map_err(Error::UpdateFailed)?;
+        self.policy
+            .check(&new_settings)
+            .map_err(Error::SettingLocked)?;
+        // Not part of the persisted settings, so it may have been lost if the settings were
+        // round-tripped through JSON
+        new_settings
+            .locked_settings
+            .clone_from(&self.settings.locked_settings);
 
         if self.settings == new_settings {
             return Ok(false);
@@ -458,6 +529,17 @@ impl Display for SettingsSummary<'_> {
                 }
                 write!(f, "{}", content.join(" "))?;
             }
+            DnsState::Custom
+                if !self
+                    .settings
+                    .tunnel_options
+                    .dns_options
+                    .custom_options
+                    .encrypted_servers
+                    .is_empty() =>
+            {
+                f.write_str("custom, encrypted")?
+            }
             DnsState::Custom => {
                 // NOTE: Technically inaccurate, as the gateway IP is a local IP but isn't treated
                 // as one.
@@ -486,6 +568,10 @@ impl Display for SettingsSummary<'_> {
                 }
             }
         }
+        let blocklists = self.settings.tunnel_options.dns_options.blocklists.len();
+        if blocklists > 0 {
+            write!(f, ", {blocklists} blocklists")?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,199 @@
+//! Administrator-managed policy that pins selected settings to fixed values.
+//!
+//! The policy is read from [`POLICY_FILE`] in the settings directory when the daemon starts.
+//! Pinned settings are applied on top of the user's settings, and any attempt to change them is
+//! rejected with [`Error::SettingLocked`](super::Error::SettingLocked). Example policy:
+//!
+//! ```json
+//! {
+//!     "block_when_disconnected": true,
+//!     "allow_lan": false,
+//!     "relay_location": { "only": { "location": { "country": "se" } } }
+//! }
+//! ```
+//!
+//! On Unix, the policy is ignored unless it is owned by root and not writable by other users.
+
+use mullvad_types::{
+    constraints::Constraint,
+    relay_constraints::{LocationConstraint, RelayConstraints, RelaySettings},
+    settings::{DnsOptions, LockedSetting, Settings},
+};
+use serde::Deserialize;
+use std::path::Path;
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+pub const POLICY_FILE: &str = "policy.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Unable to read policy file")]
+    Read(#[source] io::Error),
+
+    #[error("Unable to parse policy file")]
+    Parse(#[source] serde_json::Error),
+
+    #[cfg(unix)]
+    #[error("The policy file must be owned by root and only be writable by root")]
+    InsecurePermissions,
+}
+
+/// Values of the settings that are locked. Settings that are `None` may be changed freely.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsPolicy {
+    allow_lan: Option<bool>,
+    block_when_disconnected: Option<bool>,
+    auto_connect: Option<bool>,
+    dns_options: Option<DnsOptions>,
+    relay_location: Option<Constraint<LocationConstraint>>,
+}
+
+impl SettingsPolicy {
+    /// Load the policy from `settings_dir`. If there is no usable policy, nothing is locked.
+    pub async fn load(settings_dir: &Path) -> Self {
+        match Self::load_from_file(&settings_dir.join(POLICY_FILE)).await {
+            Ok(policy) => {
+                let locked = policy.locked_settings();
+                if !locked.is_empty() {
+                    log::info!("Settings locked by policy: {locked:?}");
+                }
+                policy
+            }
+            Err(Error::Read(error)) if error.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Ignoring administrator settings policy")
+                );
+                Self::default()
+            }
+        }
+    }
+
+    async fn load_from_file(path: &Path) -> Result<Self, Error> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let metadata = fs::metadata(path).await.map_err(Error::Read)?;
+            if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+                return Err(Error::InsecurePermissions);
+            }
+        }
+        let contents = fs::read(path).await.map_err(Error::Read)?;
+        serde_json::from_slice(&contents).map_err(Error::Parse)
+    }
+
+    /// Return the settings that the policy locks.
+    pub fn locked_settings(&self) -> Vec<LockedSetting> {
+        let SettingsPolicy {
+            allow_lan,
+            block_when_disconnected,
+            auto_connect,
+            dns_options,
+            relay_location,
+        } = self;
+
+        [
+            (allow_lan.is_some(), LockedSetting::AllowLan),
+            (
+                block_when_disconnected.is_some(),
+                LockedSetting::BlockWhenDisconnected,
+            ),
+            (auto_connect.is_some(), LockedSetting::AutoConnect),
+            (dns_options.is_some(), LockedSetting::DnsOptions),
+            (relay_location.is_some(), LockedSetting::RelayLocation),
+        ]
+        .into_iter()
+        .filter_map(|(locked, setting)| locked.then_some(setting))
+        .collect()
+    }
+
+    /// Overwrite locked settings in `settings` with the values from the policy.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(allow_lan) = self.allow_lan {
+            settings.allow_lan = allow_lan;
+        }
+        if let Some(block_when_disconnected) = self.block_when_disconnected {
+            settings.block_when_disconnected = block_when_disconnected;
+        }
+        if let Some(auto_connect) = self.auto_connect {
+            settings.auto_connect = auto_connect;
+        }
+        if let Some(ref dns_options) = self.dns_options {
+            settings.tunnel_options.dns_options = dns_options.clone();
+        }
+        if let Some(ref location) = self.relay_location {
+            match settings.relay_settings {
+                RelaySettings::Normal(ref mut constraints) => {
+                    constraints.location = location.clone();
+                }
+                RelaySettings::CustomTunnelEndpoint(_) => {
+                    settings.relay_settings = RelaySettings::Normal(RelayConstraints {
+                        location: location.clone(),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        settings.locked_settings = self.locked_settings();
+    }
+
+    /// Return the first locked setting whose value in `settings` differs from the policy.
+    pub fn check(&self, settings: &Settings) -> Result<(), LockedSetting> {
+        fn violates<T: PartialEq>(locked: &Option<T>, value: &T) -> bool {
+            locked.as_ref().is_some_and(|locked| locked != value)
+        }
+
+        if violates(&self.allow_lan, &settings.allow_lan) {
+            return Err(LockedSetting::AllowLan);
+        }
+        if violates(
+            &self.block_when_disconnected,
+            &settings.block_when_disconnected,
+        ) {
+            return Err(LockedSetting::BlockWhenDisconnected);
+        }
+        if violates(&self.auto_connect, &settings.auto_connect) {
+            return Err(LockedSetting::AutoConnect);
+        }
+        if violates(&self.dns_options, &settings.tunnel_options.dns_options) {
+            return Err(LockedSetting::DnsOptions);
+        }
+        if let Some(ref location) = self.relay_location {
+            match settings.relay_settings {
+                RelaySettings::Normal(ref constraints) if constraints.location == *location => (),
+                _ => return Err(LockedSetting::RelayLocation),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_check() {
+        let policy: SettingsPolicy =
+            serde_json::from_str(r#"{ "allow_lan": false, "auto_connect": true }"#).unwrap();
+        let mut settings = Settings {
+            allow_lan: true,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.check(&settings), Err(LockedSetting::AllowLan));
+        policy.apply(&mut settings);
+        assert_eq!(policy.check(&settings), Ok(()));
+        assert_eq!(
+            settings.locked_settings,
+            [LockedSetting::AllowLan, LockedSetting::AutoConnect]
+        );
+
+        settings.block_when_disconnected = !settings.block_when_disconnected;
+        assert_eq!(policy.check(&settings), Ok(()));
+    }
+}
@@ -10,8 +10,12 @@ use tokio::sync::Mutex;
 
 use mullvad_relay_selector::{GetRelay, RelaySelector, RuntimeParameters, WireguardConfig};
 use mullvad_types::{
-    endpoint::MullvadWireguardEndpoint, location::GeoIpLocation, relay_list::Relay,
+    custom_relay::{is_custom_relay, CustomRelay},
+    endpoint::MullvadWireguardEndpoint,
+    location::GeoIpLocation,
+    relay_list::Relay,
     settings::TunnelOptions,
+    wireguard::QuantumResistantState,
 };
 use once_cell::sync::Lazy;
 use talpid_core::tunnel_state_machine::TunnelParametersGenerator;
@@ -49,6 +53,9 @@ pub enum Error {
 
     #[error("Failed to resolve hostname for custom relay")]
     ResolveCustomHostname,
+
+    #[error("Quantum-resistant tunnels are not supported by the custom relay {0}")]
+    QuantumResistanceUnsupported(String),
 }
 
 #[derive(Clone)]
@@ -127,7 +134,7 @@ impl ParametersGenerator {
             city: Some(location.city),
             latitude: location.latitude,
             longitude: location.longitude,
-            mullvad_exit_ip: true,
+            mullvad_exit_ip: !relays.exits_via_custom_relay(),
             hostname: Some(hostname),
             bridge_hostname,
             entry_hostname,
@@ -176,13 +183,24 @@ impl InnerParametersGenerator {
                     WireguardConfig::Singlehop { exit } => (None, exit),
                     WireguardConfig::Multihop { exit, entry } => (Some(entry), exit),
                 };
+                let custom_exit = self.relay_selector.custom_relay(&wg_exit.hostname);
+                let custom_entry = wg_entry
+                    .as_ref()
+                    .filter(|entry| is_custom_relay(entry))
+                    .and_then(|entry| self.relay_selector.custom_relay(&entry.hostname));
                 self.last_generated_relays = Some(LastSelectedRelays::WireGuard {
                     wg_entry,
                     wg_exit,
                     obfuscator: obfuscator_relay,
                 });
 
-                Ok(self.create_wireguard_tunnel_parameters(endpoint, data, obfuscator_config))
+                self.create_wireguard_tunnel_parameters(
+                    endpoint,
+                    data,
+                    obfuscator_config,
+                    custom_exit,
+                    custom_entry,
+                )
             }
             GetRelay::Custom(custom_relay) => {
                 self.last_generated_relays = None;
@@ -220,12 +238,19 @@ impl InnerParametersGenerator {
         endpoint: MullvadWireguardEndpoint,
         data: PrivateAccountAndDevice,
         obfuscator_config: Option<ObfuscatorConfig>,
-    ) -> TunnelParameters {
+        custom_exit: Option<CustomRelay>,
+        custom_entry: Option<CustomRelay>,
+    ) -> Result<TunnelParameters, Error> {
         let tunnel_ipv4 = data.device.wg_data.addresses.ipv4_address.ip();
         let tunnel_ipv6 = data.device.wg_data.addresses.ipv6_address.ip();
+        // A custom exit assigns its own tunnel addresses to the device
+        let addresses = match &custom_exit {
+            Some(custom_exit) => custom_exit.addresses.clone(),
+            None => vec![IpAddr::from(tunnel_ipv4), IpAddr::from(tunnel_ipv6)],
+        };
         let tunnel = wireguard::TunnelConfig {
             private_key: data.device.wg_data.private_key,
-            addresses: vec![IpAddr::from(tunnel_ipv4), IpAddr::from(tunnel_ipv6)],
+            addresses,
         };
         // FIXME: Used for debugging purposes during the migration to same IP. Remove when
         // the migration is over.
@@ -235,25 +260,50 @@ impl InnerParametersGenerator {
             log::debug!("Same IP is NOT being used");
         }
 
-        wireguard::TunnelParameters {
+        let mut options = self
+            .tunnel_options
+            .wireguard
+            .clone()
+            .into_talpid_tunnel_options();
+        // Custom relays cannot negotiate a PSK, and a custom entry does not accept the ephemeral
+        // key that the exit negotiation replaces the device key with. If quantum resistance was
+        // explicitly enabled, refuse to connect unless the relay allows going without it.
+        if options.quantum_resistant {
+            if let Some(custom_relay) = custom_exit.as_ref().or(custom_entry.as_ref()) {
+                let required =
+                    self.tunnel_options.wireguard.quantum_resistant == QuantumResistantState::On;
+                if required && !custom_relay.allow_without_quantum_resistance {
+                    return Err(Error::QuantumResistanceUnsupported(
+                        custom_relay.name.clone(),
+                    ));
+                }
+                log::warn!(
+                    "Quantum-resistant tunnels are not supported by custom relays. Connecting \
+                     through {} without one",
+                    custom_relay.name
+                );
+                options.quantum_resistant = false;
+            }
+        }
+
+        Ok(wireguard::TunnelParameters {
             connection: wireguard::ConnectionConfig {
                 tunnel,
                 peer: endpoint.peer,
                 exit_peer: endpoint.exit_peer,
                 ipv4_gateway: endpoint.ipv4_gateway,
-                ipv6_gateway: Some(endpoint.ipv6_gateway),
+                ipv6_gateway: match &custom_exit {
+                    Some(custom_exit) => custom_exit.ipv6_gateway,
+                    None => Some(endpoint.ipv6_gateway),
+                },
                 #[cfg(target_os = "linux")]
                 fwmark: Some(mullvad_types::TUNNEL_FWMARK),
             },
-            options: self
-                .tunnel_options
-                .wireguard
-                .clone()
-                .into_talpid_tunnel_options(),
+            options,
             generic_options: self.tunnel_options.generic.clone(),
             obfuscation: obfuscator_config,
         }
-        .into()
+        .into())
     }
 
     async fn device(&self) -> Result<PrivateAccountAndDevice, Error> {
@@ -286,6 +336,10 @@ impl TunnelParametersGenerator for ParametersGenerator {
                     Error::ResolveCustomHostname => {
                         ParameterGenerationError::CustomTunnelHostResultionError
                     }
+                    error @ Error::QuantumResistanceUnsupported(_) => {
+                        log::error!("{}", error.display_chain());
+                        ParameterGenerationError::QuantumResistanceUnsupported
+                    }
                     error => {
                         log::error!(
                             "{}",
@@ -316,3 +370,15 @@ enum LastSelectedRelays {
     #[cfg(not(target_os = "android"))]
     OpenVpn { relay: Relay, bridge: Option<Relay> },
 }
+
+impl LastSelectedRelays {
+    /// Returns whether traffic leaves through a [custom relay][`CustomRelay`] rather than a
+    /// Mullvad relay.
+    fn exits_via_custom_relay(&self) -> bool {
+        match self {
+            LastSelectedRelays::WireGuard { wg_exit, .. } => is_custom_relay(wg_exit),
+            #[cfg(not(target_os = "android"))]
+            LastSelectedRelays::OpenVpn { .. } => false,
+        }
+    }
+}
@@ -8,7 +8,7 @@ use std::{
     sync::{Arc, Mutex},
 };
 use talpid_tunnel_config_client::{request_ephemeral_peer_with, Error, RelayConfigService};
-use talpid_types::net::wireguard::{PrivateKey, PublicKey};
+use talpid_types::net::wireguard::{PrivateKey, PublicKey, QuantumResistantAlgorithms};
 use tokio::runtime::Handle as TokioHandle;
 use tonic::transport::channel::Endpoint;
 use tower::util::service_fn;
@@ -140,7 +140,7 @@ impl IOSRuntime {
                 async_provider,
                 PublicKey::from(self.pub_key),
                 ephemeral_pub_key,
-                true,
+                Some(QuantumResistantAlgorithms::default()),
                 false,
             ) =>  {
                 shutdown_handle.shutdown();
@@ -13,10 +13,11 @@ use jnix::{
     FromJava, JnixEnv,
 };
 use mullvad_daemon::{
-    cleanup_old_rpc_socket, exception_logging, logging,
+    access_policy::AccessPolicy, cleanup_old_rpc_socket, exception_logging, logging,
     management_interface::ManagementInterfaceServer, runtime::new_multi_thread, version, Daemon,
     DaemonCommandChannel, DaemonCommandSender,
 };
+use mullvad_types::settings::LogSinks;
 use std::{
     io,
     path::{Path, PathBuf},
@@ -189,8 +190,13 @@ async fn spawn_daemon_inner(
 ) -> Result<tokio::task::JoinHandle<()>, Error> {
     cleanup_old_rpc_socket(&rpc_socket).await;
 
-    let event_listener = ManagementInterfaceServer::start(command_channel.sender(), &rpc_socket)
-        .map_err(Error::SpawnManagementInterface)?;
+    // Only the app itself can reach the socket, so there is no need to restrict access
+    let event_listener = ManagementInterfaceServer::start(
+        command_channel.sender(),
+        &rpc_socket,
+        AccessPolicy::allow_all(),
+    )
+    .map_err(Error::SpawnManagementInterface)?;
 
     log::info!("Management interface listening on {}", rpc_socket.display());
 
@@ -229,7 +235,12 @@ fn start_logging(log_dir: &Path) -> Result<(), String> {
 fn start_logging_inner(log_dir: &Path) -> Result<(), logging::Error> {
     let log_file = log_dir.join(LOG_FILENAME);
 
-    logging::init_logger(log::LevelFilter::Debug, Some(&log_file), true)?;
+    logging::init_logger(
+        log::LevelFilter::Debug,
+        Some(&log_file),
+        true,
+        LogSinks::default(),
+    )?;
     exception_logging::enable();
     log_panics::init();
 
@@ -6,6 +6,7 @@ use jnix::{
     },
     FromJava, JnixEnv,
 };
+use mullvad_problem_report::redact::RedactionLevel;
 use std::path::Path;
 use talpid_types::ErrorExt;
 
@@ -23,7 +24,13 @@ pub extern "system" fn Java_net_mullvad_mullvadvpn_dataproxy_MullvadProblemRepor
     let output_path_string = String::from_java(&env, outputPath);
     let output_path = Path::new(&output_path_string);
 
-    match mullvad_problem_report::collect_report::<&str>(&[], output_path, Vec::new(), log_dir) {
+    match mullvad_problem_report::collect_report::<&str>(
+        &[],
+        output_path,
+        Vec::new(),
+        RedactionLevel::default(),
+        log_dir,
+    ) {
         Ok(()) => JNI_TRUE,
         Err(error) => {
             log::error!(
@@ -0,0 +1,235 @@
+//! Access control for the management interface. Every RPC belongs to an [`RpcCategory`], and the
+//! server asks an [`AccessControl`] implementation whether the connected client may use RPCs in
+//! that category before the call is dispatched.
+
+use std::{
+    convert::Infallible,
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tonic::{
+    body::BoxBody,
+    codegen::{http, BoxFuture, Service},
+    server::NamedService,
+    transport::Body,
+    Status,
+};
+
+/// Credentials of the process on the other end of the management interface socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
+/// Groups of RPCs that access can be granted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RpcCategory {
+    /// Reading the tunnel state, settings and other non-sensitive information.
+    Status,
+    /// Connecting, disconnecting and reconnecting.
+    Tunnel,
+    /// Changing settings, including lockdown mode.
+    Settings,
+    /// Logging in or out, managing devices and anything else that reveals or changes the account.
+    Account,
+}
+
+impl RpcCategory {
+    pub const ALL: [RpcCategory; 4] = [
+        RpcCategory::Status,
+        RpcCategory::Tunnel,
+        RpcCategory::Settings,
+        RpcCategory::Account,
+    ];
+
+    /// Return the category of the RPC named `method`, e.g. `ConnectTunnel`.
+    pub fn from_method(method: &str) -> Self {
+        match method {
+            "GetTunnelState"
+            | "GetRecentErrors"
+            | "EventsListen"
+            | "GetCurrentVersion"
+            | "GetVersionInfo"
+            | "IsPerformingPostUpgrade"
+            | "UpdateRelayLocations"
+            | "GetRelayLocations"
+            | "ExportRelayList"
+            | "GetRelayListChanges"
+            | "GetSettings"
+            | "GetWireguardKey"
+            | "GetCurrentApiAccessMethod"
+            | "GetSplitTunnelProcesses"
+            | "GetExcludedProcesses"
+            | "CheckVolumes"
+            | "ExportJsonSettings"
+            | "GetFeatureIndicators"
+            | "GetAccountExpiryState"
+            | "RunDiagnostics"
+            | "TestDnsLeaks"
+            | "GetLogLevels"
+            | "GetDataUsage" => RpcCategory::Status,
+
+            "ConnectTunnel" | "DisconnectTunnel" | "ReconnectTunnel" => RpcCategory::Tunnel,
+
+            "PrepareRestart"
+            | "PrepareRestartV2"
+            | "SetRelaySettings"
+            | "SetBridgeSettings"
+            | "SetBridgeState"
+            | "SetObfuscationSettings"
+            | "ResetSettings"
+            | "SetAllowLan"
+            | "SetShowBetaReleases"
+            | "SetExpiryNotificationHours"
+            | "SetBlockWhenDisconnected"
+            | "AllowCaptivePortal"
+            | "SetAutoConnect"
+            | "SetOpenvpnMssfix"
+            | "SetWireguardMtu"
+            | "SetEnableIpv6"
+            | "SetQuantumResistantTunnel"
+            | "SetQuantumResistantRekeyInterval"
+            | "SetDaitaSettings"
+            | "SetDnsOptions"
+            | "SetRelayOverride"
+            | "ClearAllRelayOverrides"
+            | "SetLogSinks"
+            | "SetLogLevel"
+            | "ResetLogLevels"
+            | "SetWireguardRotationInterval"
+            | "ResetWireguardRotationInterval"
+            | "RotateWireguardKey"
+            | "CreateCustomList"
+            | "DeleteCustomList"
+            | "UpdateCustomList"
+            | "ClearCustomLists"
+            | "AddCustomRelay"
+            | "RemoveCustomRelay"
+            | "AddScheduleRule"
+            | "RemoveScheduleRule"
+            | "SetDataCap"
+            | "AddApiAccessMethod"
+            | "RemoveApiAccessMethod"
+            | "SetApiAccessMethod"
+            | "UpdateApiAccessMethod"
+            | "ClearCustomApiAccessMethods"
+            | "TestCustomApiAccessMethod"
+            | "TestApiAccessMethodById"
+            | "AddSplitTunnelProcess"
+            | "RemoveSplitTunnelProcess"
+            | "ClearSplitTunnelProcesses"
+            | "AddSplitTunnelApp"
+            | "RemoveSplitTunnelApp"
+            | "SetSplitTunnelState"
+            | "ClearSplitTunnelApps"
+            | "ApplyJsonSettings" => RpcCategory::Settings,
+
+            // Everything else, including `FactoryReset`, `LoginAccount` and RPCs that this list
+            // has not been updated with, requires the most sensitive category. This includes
+            // `ImportRelayList`, since imported relay lists cannot be authenticated.
+            _ => RpcCategory::Account,
+        }
+    }
+}
+
+impl fmt::Display for RpcCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RpcCategory::Status => "status",
+            RpcCategory::Tunnel => "tunnel",
+            RpcCategory::Settings => "settings",
+            RpcCategory::Account => "account",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Unknown RPC category: {0}")]
+pub struct RpcCategoryParseError(String);
+
+impl FromStr for RpcCategory {
+    type Err = RpcCategoryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RpcCategory::ALL
+            .into_iter()
+            .find(|category| category.to_string() == s)
+            .ok_or_else(|| RpcCategoryParseError(s.to_owned()))
+    }
+}
+
+/// Decides which clients may use which RPCs.
+pub trait AccessControl: Send + Sync + 'static {
+    /// Return an error if a client with the credentials `peer` may not use RPCs in `category`.
+    /// `peer` is `None` if the credentials of the client could not be determined.
+    fn authorize(
+        &self,
+        peer: Option<&PeerCredentials>,
+        category: RpcCategory,
+    ) -> Result<(), Status>;
+}
+
+/// Wraps a gRPC service, and rejects requests that the [`AccessControl`] does not authorize.
+pub(crate) struct AccessControlled<S, A> {
+    inner: S,
+    access_control: Arc<A>,
+}
+
+impl<S, A> AccessControlled<S, A> {
+    pub fn new(inner: S, access_control: Arc<A>) -> Self {
+        AccessControlled {
+            inner,
+            access_control,
+        }
+    }
+}
+
+impl<S: Clone, A> Clone for AccessControlled<S, A> {
+    fn clone(&self) -> Self {
+        AccessControlled {
+            inner: self.inner.clone(),
+            access_control: self.access_control.clone(),
+        }
+    }
+}
+
+impl<S, A> Service<http::Request<Body>> for AccessControlled<S, A>
+where
+    S: Service<http::Request<Body>, Response = http::Response<BoxBody>, Error = Infallible>,
+    S::Future: Send + 'static,
+    A: AccessControl,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let method = request.uri().path().rsplit('/').next().unwrap_or_default();
+        let category = RpcCategory::from_method(method);
+        // Inserted by tonic from `Connected::connect_info`
+        let peer = request
+            .extensions()
+            .get::<Option<PeerCredentials>>()
+            .copied()
+            .flatten();
+
+        if let Err(status) = self.access_control.authorize(peer.as_ref(), category) {
+            log::warn!("Denied {method} to client {peer:?}: {}", status.message());
+            return Box::pin(async move { Ok(status.to_http()) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+impl<S: NamedService, A> NamedService for AccessControlled<S, A> {
+    const NAME: &'static str = S::NAME;
+}
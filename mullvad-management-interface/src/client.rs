@@ -7,28 +7,35 @@ use futures::{Stream, StreamExt};
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
     access_method::AccessMethodSetting,
+    account::ExpiryNotification,
     device::{DeviceEvent, RemoveDeviceEvent},
-    relay_list::RelayList,
-    settings::Settings,
-    states::TunnelState,
+    relay_list::{RelayList, RelayListDiff},
+    settings::{LogSinks, Settings},
+    states::{RecentError, TunnelState},
     version::AppVersionInfo,
 };
 
 #[cfg(not(target_os = "android"))]
 use mullvad_types::{
     access_method::{self, AccessMethod},
-    account::{AccountData, AccountToken, VoucherSubmission},
+    account::{AccountData, AccountToken, SavedAccount, VoucherSubmission},
     custom_list::{CustomList, Id},
+    custom_relay::CustomRelay,
+    data_usage::{DataCap, DataCapReached, DataUsage},
     device::{Device, DeviceId, DeviceState},
+    diagnostics::DiagnosticsReport,
     features::FeatureIndicators,
+    log_level::{LogLevel, ModuleLogLevel},
     relay_constraints::{
         BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
     },
+    schedule::ScheduleRule,
     settings::DnsOptions,
-    wireguard::{PublicKey, QuantumResistantState, RotationInterval},
+    wireguard::{PublicKey, QuantumResistantAlgorithms, QuantumResistantState, RotationInterval},
 };
 #[cfg(not(target_os = "android"))]
 use std::{path::Path, str::FromStr};
+use talpid_types::net::dns::DnsLeakTestResult;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 #[cfg(not(target_os = "android"))]
@@ -51,6 +58,9 @@ pub enum DaemonEvent {
     Device(DeviceEvent),
     RemoveDevice(RemoveDeviceEvent),
     NewAccessMethod(AccessMethodSetting),
+    AccountExpiry(ExpiryNotification),
+    RelayListChanged(RelayListDiff),
+    DataCapReached(DataCapReached),
 }
 
 impl TryFrom<types::daemon_event::Event> for DaemonEvent {
@@ -81,6 +91,17 @@ impl TryFrom<types::daemon_event::Event> for DaemonEvent {
                     .map(DaemonEvent::NewAccessMethod)
                     .map_err(Error::InvalidResponse)
             }
+            types::daemon_event::Event::AccountExpiry(notification) => {
+                ExpiryNotification::try_from(notification)
+                    .map(DaemonEvent::AccountExpiry)
+                    .map_err(Error::InvalidResponse)
+            }
+            types::daemon_event::Event::RelayListChanged(diff) => RelayListDiff::try_from(diff)
+                .map(DaemonEvent::RelayListChanged)
+                .map_err(Error::InvalidResponse),
+            types::daemon_event::Event::DataCapReached(event) => {
+                Ok(DaemonEvent::DataCapReached(DataCapReached::from(event)))
+            }
         }
     }
 }
@@ -133,6 +154,19 @@ impl MullvadProxyClient {
         TunnelState::try_from(state).map_err(Error::InvalidResponse)
     }
 
+    /// Return the error states that the tunnel has recently entered, oldest first.
+    pub async fn get_recent_errors(&mut self) -> Result<Vec<RecentError>> {
+        self.0
+            .get_recent_errors(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner()
+            .errors
+            .into_iter()
+            .map(|error| RecentError::try_from(error).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
     pub async fn events_listen(&mut self) -> Result<impl Stream<Item = Result<DaemonEvent>>> {
         let listener = self
             .0
@@ -271,12 +305,38 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    /// Return recent changes to the relay list, oldest first.
+    pub async fn get_relay_list_changes(&mut self) -> Result<Vec<RelayListDiff>> {
+        self.0
+            .get_relay_list_changes(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner()
+            .changes
+            .into_iter()
+            .map(|diff| RelayListDiff::try_from(diff).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
+    /// Return the current relay list in a format that can be passed to
+    /// [`Self::import_relay_list`], e.g. on a machine that cannot reach the API.
+    pub async fn export_relay_list(&mut self) -> Result<String> {
+        let blob = self.0.export_relay_list(()).await.map_err(Error::Rpc)?;
+        Ok(blob.into_inner())
+    }
+
+    /// Replace the relay list with one that was returned by [`Self::export_relay_list`].
+    pub async fn import_relay_list(&mut self, blob: String) -> Result<()> {
+        self.0.import_relay_list(blob).await.map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn set_relay_settings(&mut self, update: RelaySettings) -> Result<()> {
         let update = types::RelaySettings::from(update);
         self.0
             .set_relay_settings(update)
             .await
-            .map_err(Error::Rpc)?;
+            .map_err(map_settings_error)?;
         Ok(())
     }
 
@@ -315,12 +375,18 @@ impl MullvadProxyClient {
     }
 
     pub async fn reset_settings(&mut self) -> Result<()> {
-        self.0.reset_settings(()).await.map_err(Error::Rpc)?;
+        self.0
+            .reset_settings(())
+            .await
+            .map_err(map_settings_error)?;
         Ok(())
     }
 
     pub async fn set_allow_lan(&mut self, state: bool) -> Result<()> {
-        self.0.set_allow_lan(state).await.map_err(Error::Rpc)?;
+        self.0
+            .set_allow_lan(state)
+            .await
+            .map_err(map_settings_error)?;
         Ok(())
     }
 
@@ -336,12 +402,15 @@ impl MullvadProxyClient {
         self.0
             .set_block_when_disconnected(state)
             .await
-            .map_err(Error::Rpc)?;
+            .map_err(map_settings_error)?;
         Ok(())
     }
 
     pub async fn set_auto_connect(&mut self, state: bool) -> Result<()> {
-        self.0.set_auto_connect(state).await.map_err(Error::Rpc)?;
+        self.0
+            .set_auto_connect(state)
+            .await
+            .map_err(map_settings_error)?;
         Ok(())
     }
 
@@ -369,8 +438,14 @@ impl MullvadProxyClient {
     pub async fn set_quantum_resistant_tunnel(
         &mut self,
         state: QuantumResistantState,
+        algorithms: Option<QuantumResistantAlgorithms>,
     ) -> Result<()> {
-        let state = types::QuantumResistantState::from(state);
+        let state = types::QuantumResistantState {
+            algorithms: algorithms.map(|algorithms| {
+                i32::from(types::quantum_resistant_state::Algorithms::from(algorithms))
+            }),
+            ..types::QuantumResistantState::from(state)
+        };
         self.0
             .set_quantum_resistant_tunnel(state)
             .await
@@ -378,6 +453,19 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_quantum_resistant_rekey_interval(
+        &mut self,
+        interval: Option<std::time::Duration>,
+    ) -> Result<()> {
+        let duration = types::Duration::try_from(interval.unwrap_or_default())
+            .map_err(|_| Error::DurationTooLarge)?;
+        self.0
+            .set_quantum_resistant_rekey_interval(duration)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     #[cfg(daita)]
     pub async fn set_daita_settings(&mut self, settings: DaitaSettings) -> Result<()> {
         let settings = types::DaitaSettings::from(settings);
@@ -390,7 +478,10 @@ impl MullvadProxyClient {
 
     pub async fn set_dns_options(&mut self, options: DnsOptions) -> Result<()> {
         let options = types::DnsOptions::from(&options);
-        self.0.set_dns_options(options).await.map_err(Error::Rpc)?;
+        self.0
+            .set_dns_options(options)
+            .await
+            .map_err(map_settings_error)?;
         Ok(())
     }
 
@@ -411,6 +502,14 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_log_sinks(&mut self, sinks: LogSinks) -> Result<()> {
+        self.0
+            .set_log_sinks(types::LogSinks::from(sinks))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn create_new_account(&mut self) -> Result<AccountToken> {
         Ok(self
             .0
@@ -458,6 +557,63 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn list_saved_accounts(&mut self) -> Result<Vec<SavedAccount>> {
+        let accounts = self
+            .0
+            .list_saved_accounts(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner()
+            .accounts;
+        Ok(accounts.into_iter().map(SavedAccount::from).collect())
+    }
+
+    pub async fn save_account(&mut self, account: SavedAccount) -> Result<()> {
+        self.0
+            .save_account(types::SavedAccount::from(account))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn remove_saved_account(&mut self, name: String) -> Result<()> {
+        self.0
+            .remove_saved_account(name)
+            .await
+            .map_err(map_saved_account_error)?;
+        Ok(())
+    }
+
+    pub async fn switch_account(&mut self, name: String) -> Result<()> {
+        self.0
+            .switch_account(name)
+            .await
+            .map_err(map_saved_account_error)?;
+        Ok(())
+    }
+
+    pub async fn get_account_expiry_state(&mut self) -> Result<Option<ExpiryNotification>> {
+        let state = self
+            .0
+            .get_account_expiry_state(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        state
+            .notification
+            .map(ExpiryNotification::try_from)
+            .transpose()
+            .map_err(Error::InvalidResponse)
+    }
+
+    pub async fn set_expiry_notification_hours(&mut self, hours: Vec<u32>) -> Result<()> {
+        self.0
+            .set_expiry_notification_hours(types::ExpiryNotificationHours { hours })
+            .await
+            .map_err(map_settings_error)?;
+        Ok(())
+    }
+
     // get_www_auth_token
 
     pub async fn submit_voucher(&mut self, voucher: String) -> Result<VoucherSubmission> {
@@ -588,6 +744,65 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn add_custom_relay(&mut self, custom_relay: CustomRelay) -> Result<()> {
+        self.0
+            .add_custom_relay(types::CustomRelay::from(custom_relay))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn remove_custom_relay(&mut self, name: String) -> Result<()> {
+        self.0.remove_custom_relay(name).await.map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn add_schedule_rule(&mut self, rule: ScheduleRule) -> Result<()> {
+        self.0
+            .add_schedule_rule(types::ScheduleRule::from(rule))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn remove_schedule_rule(&mut self, name: String) -> Result<()> {
+        self.0
+            .remove_schedule_rule(name)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_data_usage(&mut self) -> Result<DataUsage> {
+        let usage = self
+            .0
+            .get_data_usage(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        DataUsage::try_from(usage).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn set_data_cap(&mut self, data_cap: DataCap) -> Result<()> {
+        self.0
+            .set_data_cap(types::DataCap::from(data_cap))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Probe for a captive portal and allow traffic to it for `window`. Returns the URL of the
+    /// portal.
+    pub async fn allow_captive_portal(&mut self, window: std::time::Duration) -> Result<String> {
+        let window = types::Duration::try_from(window).map_err(|_| Error::DurationTooLarge)?;
+        Ok(self
+            .0
+            .allow_captive_portal(window)
+            .await
+            .map_err(map_settings_error)?
+            .into_inner())
+    }
+
     pub async fn add_access_method(
         &mut self,
         name: String,
@@ -734,7 +949,10 @@ impl MullvadProxyClient {
     // check_volumes
 
     pub async fn apply_json_settings(&mut self, blob: String) -> Result<()> {
-        self.0.apply_json_settings(blob).await.map_err(Error::Rpc)?;
+        self.0
+            .apply_json_settings(blob)
+            .await
+            .map_err(map_settings_error)?;
         Ok(())
     }
 
@@ -751,6 +969,64 @@ impl MullvadProxyClient {
             .map(|response| response.into_inner())
             .map(FeatureIndicators::from)
     }
+
+    pub async fn run_diagnostics(&mut self) -> Result<DiagnosticsReport> {
+        let report = self
+            .0
+            .run_diagnostics(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        DiagnosticsReport::try_from(report).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn test_dns_leaks(&mut self) -> Result<DnsLeakTestResult> {
+        let result = self
+            .0
+            .test_dns_leaks(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        DnsLeakTestResult::try_from(result).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn set_log_level(
+        &mut self,
+        module: String,
+        level: LogLevel,
+        revert_after: Option<std::time::Duration>,
+    ) -> Result<()> {
+        let revert_after = revert_after
+            .map(types::Duration::try_from)
+            .transpose()
+            .map_err(|_| Error::DurationTooLarge)?;
+        self.0
+            .set_log_level(types::SetModuleLogLevel {
+                module,
+                level: i32::from(types::LogLevel::from(level)),
+                revert_after,
+            })
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_log_levels(&mut self) -> Result<Vec<ModuleLogLevel>> {
+        self.0
+            .get_log_levels(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner()
+            .levels
+            .into_iter()
+            .map(|level| ModuleLogLevel::try_from(level).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
+    pub async fn reset_log_levels(&mut self) -> Result<()> {
+        self.0.reset_log_levels(()).await.map_err(Error::Rpc)?;
+        Ok(())
+    }
 }
 
 #[cfg(not(target_os = "android"))]
@@ -764,6 +1040,26 @@ fn map_device_error(status: Status) -> Error {
     }
 }
 
+#[cfg(not(target_os = "android"))]
+fn map_saved_account_error(status: Status) -> Error {
+    if status.code() == Code::NotFound && status.details() == crate::SAVED_ACCOUNT_NOT_FOUND_DETAILS
+    {
+        Error::SavedAccountNotFound
+    } else {
+        map_device_error(status)
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+fn map_settings_error(status: Status) -> Error {
+    if status.code() == Code::PermissionDenied && status.details() == crate::SETTING_LOCKED_DETAILS
+    {
+        Error::SettingLocked(status.message().to_owned())
+    } else {
+        Error::Rpc(status)
+    }
+}
+
 #[cfg(not(target_os = "android"))]
 fn map_custom_list_error(status: Status) -> Error {
     match status.code() {
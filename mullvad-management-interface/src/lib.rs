@@ -1,6 +1,9 @@
+pub mod access;
 pub mod client;
 pub mod types;
 
+use access::{AccessControl, AccessControlled, PeerCredentials};
+#[cfg(not(target_os = "android"))]
 use parity_tokio_ipc::Endpoint as IpcEndpoint;
 #[cfg(unix)]
 use std::{env, fs, os::unix::fs::PermissionsExt};
@@ -8,6 +11,7 @@ use std::{
     future::Future,
     io,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
@@ -32,6 +36,8 @@ static MULLVAD_MANAGEMENT_SOCKET_GROUP: Lazy<Option<String>> =
 pub const CUSTOM_LIST_LIST_NOT_FOUND_DETAILS: &[u8] = b"custom_list_list_not_found";
 pub const CUSTOM_LIST_LIST_EXISTS_DETAILS: &[u8] = b"custom_list_list_exists";
 pub const CUSTOM_LIST_LIST_NAME_TOO_LONG_DETAILS: &[u8] = b"custom_list_list_name_too_long";
+pub const SETTING_LOCKED_DETAILS: &[u8] = b"setting_locked";
+pub const SAVED_ACCOUNT_NOT_FOUND_DETAILS: &[u8] = b"saved_account_not_found";
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -92,6 +98,9 @@ pub enum Error {
     #[error("There is no such device")]
     DeviceNotFound,
 
+    #[error("There is no saved account with that name")]
+    SavedAccountNotFound,
+
     #[error("Location data is unavailable")]
     NoLocationData,
 
@@ -112,6 +121,9 @@ pub enum Error {
 
     #[error("An access method with that id does not exist")]
     ApiAccessMethodNotFound,
+
+    #[error("{0}")]
+    SettingLocked(String),
 }
 
 #[cfg(not(target_os = "android"))]
@@ -135,22 +147,41 @@ pub use client::MullvadProxyClient;
 
 pub type ServerJoinHandle = tokio::task::JoinHandle<Result<(), Error>>;
 
-pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 'static>(
+/// Serve `service` on `rpc_socket_path`. Every request is authorized by `service` using the
+/// credentials of the connected client before it is dispatched.
+pub fn spawn_rpc_server<
+    T: ManagementService + AccessControl,
+    F: Future<Output = ()> + Send + 'static,
+>(
     service: T,
     abort_rx: F,
     rpc_socket_path: impl AsRef<std::path::Path>,
 ) -> std::result::Result<ServerJoinHandle, Error> {
     use futures::stream::TryStreamExt;
-    use parity_tokio_ipc::SecurityAttributes;
 
-    let mut endpoint = IpcEndpoint::new(rpc_socket_path.as_ref().to_string_lossy().to_string());
-    endpoint.set_security_attributes(
-        SecurityAttributes::allow_everyone_create()
-            .map_err(Error::SecurityAttributes)?
-            .set_mode(0o766)
-            .map_err(Error::SecurityAttributes)?,
-    );
-    let incoming = endpoint.incoming().map_err(Error::StartServerError)?;
+    // Unix sockets are set up without `parity_tokio_ipc`, since it does not expose the
+    // credentials of the peer.
+    #[cfg(unix)]
+    let incoming = {
+        let listener = tokio::net::UnixListener::bind(rpc_socket_path.as_ref())
+            .map_err(Error::StartServerError)?;
+        fs::set_permissions(rpc_socket_path.as_ref(), PermissionsExt::from_mode(0o766))
+            .map_err(Error::PermissionsError)?;
+        futures::stream::unfold(listener, |listener| async move {
+            let stream = listener.accept().await.map(|(stream, _addr)| stream);
+            Some((stream, listener))
+        })
+    };
+    #[cfg(windows)]
+    let incoming = {
+        use parity_tokio_ipc::SecurityAttributes;
+
+        let mut endpoint = IpcEndpoint::new(rpc_socket_path.as_ref().to_string_lossy().to_string());
+        endpoint.set_security_attributes(
+            SecurityAttributes::allow_everyone_create().map_err(Error::SecurityAttributes)?,
+        );
+        endpoint.incoming().map_err(Error::StartServerError)?
+    };
 
     #[cfg(unix)]
     if let Some(group_name) = &*MULLVAD_MANAGEMENT_SOCKET_GROUP {
@@ -163,9 +194,12 @@ pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 's
             .map_err(Error::PermissionsError)?;
     }
 
+    let service = Arc::new(service);
+    let server = AccessControlled::new(ManagementServiceServer::from_arc(service.clone()), service);
+
     Ok(tokio::spawn(async move {
         Server::builder()
-            .add_service(ManagementServiceServer::new(service))
+            .add_service(server)
             .serve_with_incoming_shutdown(incoming.map_ok(StreamBox), abort_rx)
             .await
             .map_err(Error::GrpcTransportError)
@@ -174,8 +208,27 @@ pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 's
 
 #[derive(Debug)]
 struct StreamBox<T: AsyncRead + AsyncWrite>(pub T);
+#[cfg(unix)]
+impl Connected for StreamBox<tokio::net::UnixStream> {
+    type ConnectInfo = Option<PeerCredentials>;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        match self.0.peer_cred() {
+            Ok(credentials) => Some(PeerCredentials {
+                uid: credentials.uid(),
+                gid: credentials.gid(),
+                pid: credentials.pid(),
+            }),
+            Err(error) => {
+                log::error!("Failed to obtain credentials of management interface client: {error}");
+                None
+            }
+        }
+    }
+}
+#[cfg(windows)]
 impl<T: AsyncRead + AsyncWrite> Connected for StreamBox<T> {
-    type ConnectInfo = Option<()>;
+    type ConnectInfo = Option<PeerCredentials>;
 
     fn connect_info(&self) -> Self::ConnectInfo {
         None
@@ -1,6 +1,6 @@
 use crate::types;
 use chrono::DateTime;
-use mullvad_types::account::{AccountData, VoucherSubmission};
+use mullvad_types::account::{AccountData, ExpiryNotification, SavedAccount, VoucherSubmission};
 #[cfg(target_os = "android")]
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
 
@@ -66,6 +66,54 @@ impl TryFrom<types::AccountData> for AccountData {
     }
 }
 
+impl From<SavedAccount> for types::SavedAccount {
+    fn from(account: SavedAccount) -> Self {
+        types::SavedAccount {
+            name: account.name,
+            account_token: account.account_token,
+        }
+    }
+}
+
+impl From<types::SavedAccount> for SavedAccount {
+    fn from(account: types::SavedAccount) -> Self {
+        SavedAccount {
+            name: account.name,
+            account_token: account.account_token,
+        }
+    }
+}
+
+impl From<ExpiryNotification> for types::ExpiryNotification {
+    fn from(notification: ExpiryNotification) -> Self {
+        types::ExpiryNotification {
+            expiry: Some(types::Timestamp {
+                seconds: notification.expiry.timestamp(),
+                nanos: 0,
+            }),
+            threshold_hours: notification.threshold_hours,
+        }
+    }
+}
+
+impl TryFrom<types::ExpiryNotification> for ExpiryNotification {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(notification: types::ExpiryNotification) -> Result<Self, FromProtobufTypeError> {
+        let expiry = notification
+            .expiry
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing expiry"))?;
+
+        let expiry = DateTime::from_timestamp(expiry.seconds, expiry.nanos as u32)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
+
+        Ok(ExpiryNotification {
+            expiry,
+            threshold_hours: notification.threshold_hours,
+        })
+    }
+}
+
 #[cfg(target_os = "android")]
 impl TryFrom<types::PlayPurchase> for PlayPurchase {
     type Error = FromProtobufTypeError;
@@ -0,0 +1,69 @@
+use crate::types::{
+    conversions::{arg_from_str, bytes_to_pubkey, bytes_to_wg_key},
+    proto, FromProtobufTypeError,
+};
+use mullvad_types::custom_relay::CustomRelay;
+use talpid_types::net::wireguard::PresharedKey;
+
+impl From<CustomRelay> for proto::CustomRelay {
+    fn from(relay: CustomRelay) -> Self {
+        Self {
+            name: relay.name,
+            endpoint: relay.endpoint.to_string(),
+            public_key: relay.public_key.as_bytes().to_vec(),
+            allowed_ips: relay
+                .allowed_ips
+                .iter()
+                .map(|network| network.to_string())
+                .collect(),
+            addresses: relay
+                .addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+            ipv4_gateway: relay.ipv4_gateway.to_string(),
+            ipv6_gateway: relay.ipv6_gateway.map(|address| address.to_string()),
+            psk: relay.psk.map(|psk| psk.as_bytes().to_vec()),
+            allow_without_quantum_resistance: relay.allow_without_quantum_resistance,
+        }
+    }
+}
+
+impl TryFrom<proto::CustomRelay> for CustomRelay {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(relay: proto::CustomRelay) -> Result<Self, Self::Error> {
+        let allowed_ips = relay
+            .allowed_ips
+            .iter()
+            .map(|network| arg_from_str(network, "invalid allowed IP"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let addresses = relay
+            .addresses
+            .iter()
+            .map(|address| arg_from_str(address, "invalid tunnel address"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ipv6_gateway = relay
+            .ipv6_gateway
+            .map(|address| arg_from_str(&address, "invalid IPv6 gateway"))
+            .transpose()?;
+        let psk = relay
+            .psk
+            .map(|psk| {
+                bytes_to_wg_key(&psk, "invalid preshared key").map(|key| PresharedKey::from(*key))
+            })
+            .transpose()?;
+
+        Ok(CustomRelay {
+            name: relay.name,
+            endpoint: arg_from_str(&relay.endpoint, "invalid endpoint")?,
+            public_key: bytes_to_pubkey(&relay.public_key)?,
+            allowed_ips,
+            addresses,
+            ipv4_gateway: arg_from_str(&relay.ipv4_gateway, "invalid IPv4 gateway")?,
+            ipv6_gateway,
+            psk,
+            allow_without_quantum_resistance: relay.allow_without_quantum_resistance,
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use crate::types::{conversions::arg_from_str, proto, FromProtobufTypeError};
+use mullvad_types::data_usage::{ByteCount, DataCap, DataCapReached, DataUsage};
+
+impl From<DataUsage> for proto::DataUsage {
+    fn from(usage: DataUsage) -> Self {
+        let mut entries = vec![];
+        for (date, relays) in usage.days {
+            for (relay, count) in relays {
+                entries.push(proto::DataUsageEntry {
+                    date: date.to_string(),
+                    relay,
+                    rx_bytes: count.rx_bytes,
+                    tx_bytes: count.tx_bytes,
+                });
+            }
+        }
+        Self { entries }
+    }
+}
+
+impl TryFrom<proto::DataUsage> for DataUsage {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(usage: proto::DataUsage) -> Result<Self, Self::Error> {
+        let mut data_usage = DataUsage::default();
+        for entry in usage.entries {
+            let date = arg_from_str(&entry.date, "invalid data usage date")?;
+            let count = ByteCount {
+                rx_bytes: entry.rx_bytes,
+                tx_bytes: entry.tx_bytes,
+            };
+            data_usage.add(date, &entry.relay, count);
+        }
+        Ok(data_usage)
+    }
+}
+
+impl From<DataCap> for proto::DataCap {
+    fn from(cap: DataCap) -> Self {
+        Self {
+            monthly_quota: cap.monthly_quota,
+            disconnect: cap.disconnect,
+        }
+    }
+}
+
+impl From<proto::DataCap> for DataCap {
+    fn from(cap: proto::DataCap) -> Self {
+        Self {
+            monthly_quota: cap.monthly_quota,
+            disconnect: cap.disconnect,
+        }
+    }
+}
+
+impl From<DataCapReached> for proto::DataCapReached {
+    fn from(event: DataCapReached) -> Self {
+        Self {
+            usage: event.usage,
+            quota: event.quota,
+            disconnected: event.disconnected,
+        }
+    }
+}
+
+impl From<proto::DataCapReached> for DataCapReached {
+    fn from(event: proto::DataCapReached) -> Self {
+        Self {
+            usage: event.usage,
+            quota: event.quota,
+            disconnected: event.disconnected,
+        }
+    }
+}
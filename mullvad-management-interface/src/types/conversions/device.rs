@@ -16,16 +16,64 @@ impl TryFrom<proto::Device> for mullvad_types::device::Device {
         let created = DateTime::from_timestamp(created_seconds, 0)
             .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
 
+        let pubkey_created = device
+            .pubkey_created
+            .map(|created| {
+                DateTime::from_timestamp(created.seconds, 0)
+                    .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+            })
+            .transpose()?;
+
+        let last_connection = device
+            .last_connection
+            .map(mullvad_types::device::LastConnection::try_from)
+            .transpose()?;
+
         Ok(mullvad_types::device::Device {
             id: device.id,
             name: device.name,
             pubkey: bytes_to_pubkey(&device.pubkey)?,
             hijack_dns: device.hijack_dns,
             created,
+            pubkey_created,
+            last_connection,
         })
     }
 }
 
+impl TryFrom<proto::LastConnection> for mullvad_types::device::LastConnection {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(connection: proto::LastConnection) -> Result<Self, Self::Error> {
+        let time_seconds = connection
+            .time
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing 'time' field",
+            ))?
+            .seconds;
+
+        let time = DateTime::from_timestamp(time_seconds, 0)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
+
+        Ok(mullvad_types::device::LastConnection {
+            relay: connection.relay,
+            time,
+        })
+    }
+}
+
+impl From<mullvad_types::device::LastConnection> for proto::LastConnection {
+    fn from(connection: mullvad_types::device::LastConnection) -> Self {
+        proto::LastConnection {
+            relay: connection.relay,
+            time: Some(Timestamp {
+                seconds: connection.time.timestamp(),
+                nanos: 0,
+            }),
+        }
+    }
+}
+
 impl From<mullvad_types::device::Device> for proto::Device {
     fn from(device: mullvad_types::device::Device) -> Self {
         proto::Device {
@@ -37,6 +85,11 @@ impl From<mullvad_types::device::Device> for proto::Device {
                 seconds: device.created.timestamp(),
                 nanos: 0,
             }),
+            pubkey_created: device.pubkey_created.map(|created| Timestamp {
+                seconds: created.timestamp(),
+                nanos: 0,
+            }),
+            last_connection: device.last_connection.map(proto::LastConnection::from),
         }
     }
 }
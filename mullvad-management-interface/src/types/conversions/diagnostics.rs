@@ -0,0 +1,145 @@
+use crate::types::{conversions::arg_from_str, proto, FromProtobufTypeError};
+use chrono::DateTime;
+use mullvad_types::diagnostics::{CheckCategory, CheckStatus, DiagnosticCheck, DiagnosticsReport};
+use prost_types::Timestamp;
+use std::net::IpAddr;
+use talpid_types::net::dns::DnsLeakTestResult;
+
+impl From<DiagnosticsReport> for proto::DiagnosticsReport {
+    fn from(report: DiagnosticsReport) -> Self {
+        proto::DiagnosticsReport {
+            created: Some(Timestamp {
+                seconds: report.created.timestamp(),
+                nanos: 0,
+            }),
+            checks: report
+                .checks
+                .into_iter()
+                .map(proto::DiagnosticCheck::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::DiagnosticsReport> for DiagnosticsReport {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(report: proto::DiagnosticsReport) -> Result<Self, Self::Error> {
+        let created_seconds = report
+            .created
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing 'created' field",
+            ))?
+            .seconds;
+        let created = DateTime::from_timestamp(created_seconds, 0)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
+
+        Ok(DiagnosticsReport {
+            created,
+            checks: report
+                .checks
+                .into_iter()
+                .map(DiagnosticCheck::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<DiagnosticCheck> for proto::DiagnosticCheck {
+    fn from(check: DiagnosticCheck) -> Self {
+        use proto::diagnostic_check::{Category, Status};
+
+        let category = match check.category {
+            CheckCategory::ApiAccess => Category::ApiAccess,
+            CheckCategory::RelayReachability => Category::RelayReachability,
+            CheckCategory::DnsLeak => Category::DnsLeak,
+            CheckCategory::Firewall => Category::Firewall,
+            CheckCategory::Routing => Category::Routing,
+            CheckCategory::Clock => Category::Clock,
+        };
+        let status = match check.status {
+            CheckStatus::Pass => Status::Pass,
+            CheckStatus::Warn => Status::Warn,
+            CheckStatus::Fail => Status::Fail,
+            CheckStatus::Skipped => Status::Skipped,
+        };
+
+        proto::DiagnosticCheck {
+            category: i32::from(category),
+            name: check.name,
+            status: i32::from(status),
+            details: check.details,
+        }
+    }
+}
+
+impl TryFrom<proto::DiagnosticCheck> for DiagnosticCheck {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(check: proto::DiagnosticCheck) -> Result<Self, Self::Error> {
+        use proto::diagnostic_check::{Category, Status};
+
+        let category = match Category::try_from(check.category) {
+            Ok(Category::ApiAccess) => CheckCategory::ApiAccess,
+            Ok(Category::RelayReachability) => CheckCategory::RelayReachability,
+            Ok(Category::DnsLeak) => CheckCategory::DnsLeak,
+            Ok(Category::Firewall) => CheckCategory::Firewall,
+            Ok(Category::Routing) => CheckCategory::Routing,
+            Ok(Category::Clock) => CheckCategory::Clock,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid diagnostic check category",
+                ))
+            }
+        };
+        let status = match Status::try_from(check.status) {
+            Ok(Status::Pass) => CheckStatus::Pass,
+            Ok(Status::Warn) => CheckStatus::Warn,
+            Ok(Status::Fail) => CheckStatus::Fail,
+            Ok(Status::Skipped) => CheckStatus::Skipped,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid diagnostic check status",
+                ))
+            }
+        };
+
+        Ok(DiagnosticCheck {
+            category,
+            name: check.name,
+            status,
+            details: check.details,
+        })
+    }
+}
+
+impl From<DnsLeakTestResult> for proto::DnsLeakTestResult {
+    fn from(result: DnsLeakTestResult) -> Self {
+        let to_strings = |addresses: Vec<IpAddr>| addresses.iter().map(IpAddr::to_string).collect();
+        proto::DnsLeakTestResult {
+            expected_resolvers: to_strings(result.expected_resolvers),
+            system_resolvers: to_strings(result.system_resolvers),
+            leaking_resolvers: to_strings(result.leaking_resolvers),
+            answered_by_unexpected_resolver: result.answered_by_unexpected_resolver,
+        }
+    }
+}
+
+impl TryFrom<proto::DnsLeakTestResult> for DnsLeakTestResult {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(result: proto::DnsLeakTestResult) -> Result<Self, Self::Error> {
+        let from_strings = |addresses: Vec<String>| {
+            addresses
+                .iter()
+                .map(|address| arg_from_str(address, "invalid resolver address"))
+                .collect::<Result<Vec<IpAddr>, _>>()
+        };
+        Ok(DnsLeakTestResult {
+            expected_resolvers: from_strings(result.expected_resolvers)?,
+            system_resolvers: from_strings(result.system_resolvers)?,
+            leaking_resolvers: from_strings(result.leaking_resolvers)?,
+            answered_by_unexpected_resolver: result.answered_by_unexpected_resolver,
+        })
+    }
+}
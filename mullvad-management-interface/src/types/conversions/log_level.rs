@@ -0,0 +1,69 @@
+use crate::types::{proto, FromProtobufTypeError};
+use chrono::DateTime;
+use mullvad_types::log_level::{LogLevel, ModuleLogLevel};
+use prost_types::Timestamp;
+
+impl From<LogLevel> for proto::LogLevel {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => proto::LogLevel::Off,
+            LogLevel::Error => proto::LogLevel::Error,
+            LogLevel::Warn => proto::LogLevel::Warn,
+            LogLevel::Info => proto::LogLevel::Info,
+            LogLevel::Debug => proto::LogLevel::Debug,
+            LogLevel::Trace => proto::LogLevel::Trace,
+        }
+    }
+}
+
+impl From<proto::LogLevel> for LogLevel {
+    fn from(level: proto::LogLevel) -> Self {
+        match level {
+            proto::LogLevel::Off => LogLevel::Off,
+            proto::LogLevel::Error => LogLevel::Error,
+            proto::LogLevel::Warn => LogLevel::Warn,
+            proto::LogLevel::Info => LogLevel::Info,
+            proto::LogLevel::Debug => LogLevel::Debug,
+            proto::LogLevel::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+pub fn try_log_level_from_i32(level: i32) -> Result<LogLevel, FromProtobufTypeError> {
+    proto::LogLevel::try_from(level)
+        .map(LogLevel::from)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid log level"))
+}
+
+impl From<ModuleLogLevel> for proto::ModuleLogLevel {
+    fn from(level: ModuleLogLevel) -> Self {
+        proto::ModuleLogLevel {
+            module: level.module,
+            level: i32::from(proto::LogLevel::from(level.level)),
+            revert_at: level.revert_at.map(|revert_at| Timestamp {
+                seconds: revert_at.timestamp(),
+                nanos: 0,
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::ModuleLogLevel> for ModuleLogLevel {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(level: proto::ModuleLogLevel) -> Result<Self, Self::Error> {
+        let revert_at = level
+            .revert_at
+            .map(|revert_at| {
+                DateTime::from_timestamp(revert_at.seconds, 0)
+                    .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+            })
+            .transpose()?;
+
+        Ok(ModuleLogLevel {
+            module: level.module,
+            level: try_log_level_from_i32(level.level)?,
+            revert_at,
+        })
+    }
+}
@@ -3,19 +3,24 @@ use std::str::FromStr;
 mod access_method;
 mod account;
 mod custom_list;
+mod custom_relay;
 mod custom_tunnel;
+mod data_usage;
 mod device;
+mod diagnostics;
 mod features;
 mod location;
+pub mod log_level;
 mod net;
 pub mod relay_constraints;
 mod relay_list;
+mod schedule;
 mod settings;
 #[cfg(target_os = "windows")]
 mod split_tunnel;
 mod states;
 mod version;
-mod wireguard;
+pub mod wireguard;
 
 #[derive(thiserror::Error, Debug)]
 pub enum FromProtobufTypeError {
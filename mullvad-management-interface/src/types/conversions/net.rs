@@ -127,6 +127,7 @@ impl TryFrom<proto::TunnelEndpoint> for talpid_types::net::TunnelEndpoint {
             tunnel_interface: endpoint
                 .tunnel_metadata
                 .map(|tunnel_metadata| tunnel_metadata.tunnel_interface),
+            tunnel_gateway: None,
             #[cfg(daita)]
             daita: endpoint.daita,
         })
@@ -112,6 +112,7 @@ impl From<mullvad_types::relay_list::Relay> for proto::Relay {
             owned: relay.owned,
             provider: relay.provider,
             weight: relay.weight,
+            custom: relay.custom,
             endpoint_type: match &relay.endpoint_data {
                 MullvadEndpointData::Openvpn => proto::relay::RelayType::Openvpn as i32,
                 MullvadEndpointData::Bridge => proto::relay::RelayType::Bridge as i32,
@@ -268,6 +269,7 @@ impl TryFrom<proto::Relay> for mullvad_types::relay_list::Relay {
             owned: relay.owned,
             provider: relay.provider,
             weight: relay.weight,
+            custom: relay.custom,
             endpoint_data,
             location: relay.location.map(|location| MullvadLocation {
                 country: location.country,
@@ -377,3 +379,93 @@ impl TryFrom<proto::WireguardEndpointData> for mullvad_types::relay_list::Wiregu
         })
     }
 }
+
+impl From<mullvad_types::relay_list::RelayListDiff> for proto::RelayListDiff {
+    fn from(diff: mullvad_types::relay_list::RelayListDiff) -> Self {
+        proto::RelayListDiff {
+            time: Some(crate::types::Timestamp {
+                seconds: diff.time.timestamp(),
+                nanos: 0,
+            }),
+            added: diff.added,
+            removed: diff.removed,
+            changed: diff
+                .changed
+                .into_iter()
+                .map(proto::RelayChange::from)
+                .collect(),
+            removed_pinned: diff.removed_pinned,
+        }
+    }
+}
+
+impl From<mullvad_types::relay_list::RelayChange> for proto::RelayChange {
+    fn from(change: mullvad_types::relay_list::RelayChange) -> Self {
+        use mullvad_types::relay_list::RelayChangeKind;
+
+        let kind = match change.kind {
+            RelayChangeKind::Ipv4Address => proto::relay_change::Kind::Ipv4Address,
+            RelayChangeKind::Ipv6Address => proto::relay_change::Kind::Ipv6Address,
+            RelayChangeKind::PublicKey => proto::relay_change::Kind::PublicKey,
+            RelayChangeKind::Daita => proto::relay_change::Kind::Daita,
+            RelayChangeKind::Active => proto::relay_change::Kind::Active,
+        };
+        proto::RelayChange {
+            hostname: change.hostname,
+            kind: kind as i32,
+            old: change.old,
+            new: change.new,
+        }
+    }
+}
+
+impl TryFrom<proto::RelayListDiff> for mullvad_types::relay_list::RelayListDiff {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(diff: proto::RelayListDiff) -> Result<Self, FromProtobufTypeError> {
+        let time = diff
+            .time
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing time"))?;
+        let time = chrono::DateTime::from_timestamp(time.seconds, time.nanos as u32)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
+
+        Ok(mullvad_types::relay_list::RelayListDiff {
+            time,
+            added: diff.added,
+            removed: diff.removed,
+            changed: diff
+                .changed
+                .into_iter()
+                .map(mullvad_types::relay_list::RelayChange::try_from)
+                .collect::<Result<_, _>>()?,
+            removed_pinned: diff.removed_pinned,
+        })
+    }
+}
+
+impl TryFrom<proto::RelayChange> for mullvad_types::relay_list::RelayChange {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(change: proto::RelayChange) -> Result<Self, FromProtobufTypeError> {
+        use mullvad_types::relay_list::RelayChangeKind;
+
+        let kind = match proto::relay_change::Kind::try_from(change.kind) {
+            Ok(proto::relay_change::Kind::Ipv4Address) => RelayChangeKind::Ipv4Address,
+            Ok(proto::relay_change::Kind::Ipv6Address) => RelayChangeKind::Ipv6Address,
+            Ok(proto::relay_change::Kind::PublicKey) => RelayChangeKind::PublicKey,
+            Ok(proto::relay_change::Kind::Daita) => RelayChangeKind::Daita,
+            Ok(proto::relay_change::Kind::Active) => RelayChangeKind::Active,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid relay change kind",
+                ))
+            }
+        };
+        Ok(mullvad_types::relay_list::RelayChange {
+            hostname: change.hostname,
+            kind,
+            old: change.old,
+            new: change.new,
+        })
+    }
+}
@@ -0,0 +1,81 @@
+use crate::types::{conversions::arg_from_str, proto, FromProtobufTypeError};
+use mullvad_types::{
+    constraints::Constraint,
+    schedule::{Schedule, ScheduleAction, ScheduleRule},
+};
+
+impl From<Schedule> for proto::Schedule {
+    fn from(schedule: Schedule) -> Self {
+        Self {
+            rules: schedule
+                .rules
+                .into_iter()
+                .map(proto::ScheduleRule::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::Schedule> for Schedule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(schedule: proto::Schedule) -> Result<Self, Self::Error> {
+        let rules = schedule
+            .rules
+            .into_iter()
+            .map(ScheduleRule::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Schedule { rules })
+    }
+}
+
+impl From<ScheduleRule> for proto::ScheduleRule {
+    fn from(rule: ScheduleRule) -> Self {
+        let (action, location) = match rule.action {
+            ScheduleAction::Connect { location } => (
+                proto::schedule_rule::Action::Connect,
+                location.map(proto::LocationConstraint::from),
+            ),
+            ScheduleAction::Disconnect => (proto::schedule_rule::Action::Disconnect, None),
+        };
+        Self {
+            name: rule.name,
+            window: rule.window.to_string(),
+            timezone: rule.timezone.map(|timezone| timezone.name().to_owned()),
+            action: i32::from(action),
+            location,
+        }
+    }
+}
+
+impl TryFrom<proto::ScheduleRule> for ScheduleRule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rule: proto::ScheduleRule) -> Result<Self, Self::Error> {
+        let action = match proto::schedule_rule::Action::try_from(rule.action) {
+            Ok(proto::schedule_rule::Action::Connect) => {
+                let location = match rule.location.map(Constraint::try_from).transpose()? {
+                    Some(Constraint::Only(location)) => Some(location),
+                    Some(Constraint::Any) | None => None,
+                };
+                ScheduleAction::Connect { location }
+            }
+            Ok(proto::schedule_rule::Action::Disconnect) => ScheduleAction::Disconnect,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid schedule action",
+                ))
+            }
+        };
+
+        Ok(ScheduleRule {
+            name: rule.name,
+            window: arg_from_str(&rule.window, "invalid schedule window")?,
+            timezone: rule
+                .timezone
+                .map(|timezone| arg_from_str(&timezone, "invalid time zone"))
+                .transpose()?,
+            action,
+        })
+    }
+}
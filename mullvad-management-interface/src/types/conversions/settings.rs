@@ -1,6 +1,9 @@
-use crate::types::{proto, FromProtobufTypeError};
+use crate::types::{conversions::arg_from_str, proto, FromProtobufTypeError};
 use mullvad_types::settings::CURRENT_SETTINGS_VERSION;
-use talpid_types::ErrorExt;
+use talpid_types::{
+    net::dns::{EncryptedDnsProtocol, EncryptedDnsServer},
+    ErrorExt,
+};
 
 impl From<&mullvad_types::settings::Settings> for proto::Settings {
     fn from(settings: &mullvad_types::settings::Settings) -> Self {
@@ -38,6 +41,7 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
             auto_connect: settings.auto_connect,
             tunnel_options: Some(proto::TunnelOptions::from(&settings.tunnel_options)),
             show_beta_releases: settings.show_beta_releases,
+            expiry_notification_hours: settings.expiry_notification_hours.clone(),
             obfuscation_settings: Some(proto::ObfuscationSettings::from(
                 &settings.obfuscation_settings,
             )),
@@ -54,6 +58,20 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
                 .cloned()
                 .map(proto::RelayOverride::from)
                 .collect(),
+            custom_relays: settings
+                .custom_relays
+                .iter()
+                .cloned()
+                .map(proto::CustomRelay::from)
+                .collect(),
+            log_sinks: Some(proto::LogSinks::from(settings.log_sinks)),
+            schedule: Some(proto::Schedule::from(settings.schedule.clone())),
+            data_cap: Some(proto::DataCap::from(settings.data_cap)),
+            locked_settings: settings
+                .locked_settings
+                .iter()
+                .map(|&setting| i32::from(proto::LockedSetting::from(setting)))
+                .collect(),
         }
     }
 }
@@ -82,7 +100,20 @@ impl From<&mullvad_types::settings::DnsOptions> for proto::DnsOptions {
                     .iter()
                     .map(|addr| addr.to_string())
                     .collect(),
+                encrypted_servers: options
+                    .custom_options
+                    .encrypted_servers
+                    .iter()
+                    .cloned()
+                    .map(proto::EncryptedDnsServer::from)
+                    .collect(),
             }),
+            blocklists: options
+                .blocklists
+                .iter()
+                .cloned()
+                .map(proto::DnsBlocklist::from)
+                .collect(),
         }
     }
 }
@@ -99,7 +130,16 @@ impl From<&mullvad_types::settings::TunnelOptions> for proto::TunnelOptions {
                     prost_types::Duration::try_from(std::time::Duration::from(ivl))
                         .expect("Failed to convert std::time::Duration to prost_types::Duration for tunnel_options.wireguard.rotation_interval")
                 }),
-                quantum_resistant: Some(proto::QuantumResistantState::from(options.wireguard.quantum_resistant)),
+                quantum_resistant: Some(proto::QuantumResistantState {
+                    algorithms: Some(i32::from(proto::quantum_resistant_state::Algorithms::from(
+                        options.wireguard.quantum_resistant_algorithms,
+                    ))),
+                    ..proto::QuantumResistantState::from(options.wireguard.quantum_resistant)
+                }),
+                quantum_resistant_rekey_interval: options.wireguard.quantum_resistant_rekey_interval.map(|interval| {
+                    prost_types::Duration::try_from(interval)
+                        .expect("Failed to convert std::time::Duration to prost_types::Duration for tunnel_options.wireguard.quantum_resistant_rekey_interval")
+                }),
                 #[cfg(daita)]
                 daita: Some(proto::DaitaSettings::from(options.wireguard.daita.clone())),
                 #[cfg(not(daita))]
@@ -183,9 +223,28 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .into_iter()
                 .map(mullvad_types::relay_constraints::RelayOverride::try_from)
                 .collect::<Result<Vec<_>, _>>()?,
+            custom_relays: settings
+                .custom_relays
+                .into_iter()
+                .map(mullvad_types::custom_relay::CustomRelay::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
             show_beta_releases: settings.show_beta_releases,
+            expiry_notification_hours: settings.expiry_notification_hours,
             #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             split_tunnel: mullvad_types::settings::SplitTunnelSettings::from(split_tunnel),
+            log_sinks: settings
+                .log_sinks
+                .map(mullvad_types::settings::LogSinks::from)
+                .unwrap_or_default(),
+            schedule: settings
+                .schedule
+                .map(mullvad_types::schedule::Schedule::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            data_cap: settings
+                .data_cap
+                .map(mullvad_types::data_usage::DataCap::from)
+                .unwrap_or_default(),
             obfuscation_settings: mullvad_types::relay_constraints::ObfuscationSettings::try_from(
                 obfuscation_settings,
             )?,
@@ -198,10 +257,69 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
             api_access_methods: mullvad_types::access_method::Settings::try_from(
                 api_access_methods_settings,
             )?,
+            locked_settings: settings
+                .locked_settings
+                .into_iter()
+                .map(|setting| {
+                    proto::LockedSetting::try_from(setting)
+                        .map(mullvad_types::settings::LockedSetting::from)
+                        .map_err(|_| {
+                            FromProtobufTypeError::InvalidArgument("invalid locked setting")
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
         })
     }
 }
 
+impl From<mullvad_types::settings::LogSinks> for proto::LogSinks {
+    fn from(sinks: mullvad_types::settings::LogSinks) -> Self {
+        proto::LogSinks {
+            journald: sinks.journald,
+            syslog: sinks.syslog,
+            json: sinks.json,
+        }
+    }
+}
+
+impl From<proto::LogSinks> for mullvad_types::settings::LogSinks {
+    fn from(sinks: proto::LogSinks) -> Self {
+        mullvad_types::settings::LogSinks {
+            journald: sinks.journald,
+            syslog: sinks.syslog,
+            json: sinks.json,
+        }
+    }
+}
+
+impl From<mullvad_types::settings::LockedSetting> for proto::LockedSetting {
+    fn from(setting: mullvad_types::settings::LockedSetting) -> Self {
+        use mullvad_types::settings::LockedSetting;
+
+        match setting {
+            LockedSetting::AllowLan => proto::LockedSetting::AllowLan,
+            LockedSetting::BlockWhenDisconnected => proto::LockedSetting::BlockWhenDisconnected,
+            LockedSetting::AutoConnect => proto::LockedSetting::AutoConnect,
+            LockedSetting::DnsOptions => proto::LockedSetting::DnsOptions,
+            LockedSetting::RelayLocation => proto::LockedSetting::RelayLocation,
+        }
+    }
+}
+
+impl From<proto::LockedSetting> for mullvad_types::settings::LockedSetting {
+    fn from(setting: proto::LockedSetting) -> Self {
+        use mullvad_types::settings::LockedSetting;
+
+        match setting {
+            proto::LockedSetting::AllowLan => LockedSetting::AllowLan,
+            proto::LockedSetting::BlockWhenDisconnected => LockedSetting::BlockWhenDisconnected,
+            proto::LockedSetting::AutoConnect => LockedSetting::AutoConnect,
+            proto::LockedSetting::DnsOptions => LockedSetting::DnsOptions,
+            proto::LockedSetting::RelayLocation => LockedSetting::RelayLocation,
+        }
+    }
+}
+
 pub fn try_bridge_state_from_i32(
     bridge_state: i32,
 ) -> Result<mullvad_types::relay_constraints::BridgeState, FromProtobufTypeError> {
@@ -256,6 +374,13 @@ impl TryFrom<proto::TunnelOptions> for mullvad_types::settings::TunnelOptions {
             .ok_or(FromProtobufTypeError::InvalidArgument(
                 "missing tunnel DNS options",
             ))?;
+        let quantum_resistant_algorithms = wireguard_options
+            .quantum_resistant
+            .as_ref()
+            .map(super::wireguard::try_quantum_resistant_algorithms)
+            .transpose()?
+            .flatten()
+            .unwrap_or_default();
 
         Ok(Self {
             openvpn: net::openvpn::TunnelOptions {
@@ -283,6 +408,12 @@ impl TryFrom<proto::TunnelOptions> for mullvad_types::settings::TunnelOptions {
                     .ok_or(FromProtobufTypeError::InvalidArgument(
                         "missing quantum resistant state",
                     ))??,
+                quantum_resistant_algorithms,
+                quantum_resistant_rekey_interval: wireguard_options
+                    .quantum_resistant_rekey_interval
+                    .map(std::time::Duration::try_from)
+                    .transpose()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))?,
                 #[cfg(daita)]
                 daita: wireguard_options
                     .daita
@@ -305,8 +436,8 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
     fn try_from(options: proto::DnsOptions) -> Result<Self, Self::Error> {
         use mullvad_types::settings::{
             CustomDnsOptions as MullvadCustomDnsOptions,
-            DefaultDnsOptions as MullvadDefaultDnsOptions, DnsOptions as MullvadDnsOptions,
-            DnsState as MullvadDnsState,
+            DefaultDnsOptions as MullvadDefaultDnsOptions, DnsBlocklist as MullvadDnsBlocklist,
+            DnsOptions as MullvadDnsOptions, DnsState as MullvadDnsState,
         };
 
         let state = match proto::dns_options::DnsState::try_from(options.state) {
@@ -352,7 +483,91 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
                         })
                     })
                     .collect::<Result<Vec<_>, _>>()?,
+                encrypted_servers: custom_options
+                    .encrypted_servers
+                    .into_iter()
+                    .map(EncryptedDnsServer::try_from)
+                    .collect::<Result<Vec<_>, _>>()?,
             },
+            blocklists: options
+                .blocklists
+                .into_iter()
+                .map(MullvadDnsBlocklist::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
         })
     }
 }
+
+impl From<EncryptedDnsServer> for proto::EncryptedDnsServer {
+    fn from(server: EncryptedDnsServer) -> Self {
+        use proto::encrypted_dns_server::Protocol;
+
+        let protocol = match server.protocol {
+            EncryptedDnsProtocol::Https => Protocol::Https,
+            EncryptedDnsProtocol::Tls => Protocol::Tls,
+        };
+        proto::EncryptedDnsServer {
+            protocol: i32::from(protocol),
+            address: server.address.to_string(),
+            port: u32::from(server.port),
+            server_name: server.server_name,
+        }
+    }
+}
+
+impl TryFrom<proto::EncryptedDnsServer> for EncryptedDnsServer {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(server: proto::EncryptedDnsServer) -> Result<Self, Self::Error> {
+        use proto::encrypted_dns_server::Protocol;
+
+        let protocol = match Protocol::try_from(server.protocol) {
+            Ok(Protocol::Https) => EncryptedDnsProtocol::Https,
+            Ok(Protocol::Tls) => EncryptedDnsProtocol::Tls,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid encrypted DNS protocol",
+                ))
+            }
+        };
+        Ok(EncryptedDnsServer {
+            protocol,
+            address: arg_from_str(&server.address, "invalid IP address")?,
+            port: u16::try_from(server.port)
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid port"))?,
+            server_name: server.server_name,
+        })
+    }
+}
+
+impl From<mullvad_types::settings::DnsBlocklist> for proto::DnsBlocklist {
+    fn from(blocklist: mullvad_types::settings::DnsBlocklist) -> Self {
+        use mullvad_types::settings::DnsBlocklist;
+        use proto::dns_blocklist::Source;
+
+        let source = match blocklist {
+            DnsBlocklist::File(path) => Source::Path(path.to_string_lossy().into_owned()),
+            DnsBlocklist::Url(url) => Source::Url(url),
+        };
+        proto::DnsBlocklist {
+            source: Some(source),
+        }
+    }
+}
+
+impl TryFrom<proto::DnsBlocklist> for mullvad_types::settings::DnsBlocklist {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(blocklist: proto::DnsBlocklist) -> Result<Self, Self::Error> {
+        use mullvad_types::settings::DnsBlocklist;
+        use proto::dns_blocklist::Source;
+
+        match blocklist.source {
+            Some(Source::Path(path)) => Ok(DnsBlocklist::File(path.into())),
+            Some(Source::Url(url)) => Ok(DnsBlocklist::Url(url)),
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "missing DNS blocklist source",
+            )),
+        }
+    }
+}
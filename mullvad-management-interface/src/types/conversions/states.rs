@@ -152,6 +152,9 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
                             talpid_tunnel::ParameterGenerationError::CustomTunnelHostResultionError => {
                                 i32::from(GenerationError::CustomTunnelHostResolutionError)
                             }
+                            talpid_tunnel::ParameterGenerationError::QuantumResistanceUnsupported => {
+                                i32::from(GenerationError::QuantumResistanceUnsupported)
+                            }
                         }
                             } else {
                                 0
@@ -173,6 +176,10 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
                             }
                             _ => None,
                         },
+                        diagnostics: error_state
+                            .diagnostics()
+                            .cloned()
+                            .map(proto::error_state::Diagnostics::from),
                     }),
                 })
             }
@@ -182,6 +189,53 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
     }
 }
 
+impl From<mullvad_types::states::RecentError> for proto::RecentError {
+    fn from(error: mullvad_types::states::RecentError) -> Self {
+        // Reuse the error state conversion of the tunnel state
+        let tunnel_state =
+            proto::TunnelState::from(mullvad_types::states::TunnelState::Error(error.error_state));
+        let error_state = match tunnel_state.state {
+            Some(proto::tunnel_state::State::Error(error)) => error.error_state,
+            _ => None,
+        };
+        proto::RecentError {
+            time: Some(crate::types::Timestamp {
+                seconds: error.time.timestamp(),
+                nanos: error.time.timestamp_subsec_nanos() as i32,
+            }),
+            error_state,
+        }
+    }
+}
+
+impl TryFrom<proto::RecentError> for mullvad_types::states::RecentError {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(error: proto::RecentError) -> Result<Self, FromProtobufTypeError> {
+        let time = error
+            .time
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing time"))?;
+        let time = chrono::DateTime::from_timestamp(time.seconds, time.nanos as u32)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
+
+        let tunnel_state = proto::TunnelState {
+            state: Some(proto::tunnel_state::State::Error(
+                proto::tunnel_state::Error {
+                    error_state: error.error_state,
+                },
+            )),
+        };
+        match mullvad_types::states::TunnelState::try_from(tunnel_state)? {
+            mullvad_types::states::TunnelState::Error(error_state) => {
+                Ok(mullvad_types::states::RecentError { time, error_state })
+            }
+            _ => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid error state",
+            )),
+        }
+    }
+}
+
 impl From<mullvad_types::auth_failed::AuthFailed> for proto::error_state::AuthFailedError {
     fn from(auth_failed: mullvad_types::auth_failed::AuthFailed) -> Self {
         use mullvad_types::auth_failed::AuthFailed;
@@ -298,6 +352,7 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                         parameter_error,
                         policy_error,
                         create_tunnel_error,
+                        diagnostics,
                     }),
             })) => {
                 #[cfg(not(target_os = "windows"))]
@@ -345,6 +400,7 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                             Ok(proto::error_state::GenerationError::NoMatchingBridgeRelay) => talpid_tunnel::ParameterGenerationError::NoMatchingBridgeRelay,
                             Ok(proto::error_state::GenerationError::NoMatchingRelay) => talpid_tunnel::ParameterGenerationError::NoMatchingRelay,
                             Ok(proto::error_state::GenerationError::NoWireguardKey) => talpid_tunnel::ParameterGenerationError::NoWireguardKey,
+                            Ok(proto::error_state::GenerationError::QuantumResistanceUnsupported) => talpid_tunnel::ParameterGenerationError::QuantumResistanceUnsupported,
                             _ => return Err(FromProtobufTypeError::InvalidArgument(
                                 "invalid parameter error",
                             )),
@@ -380,7 +436,13 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                     })
                     .transpose()?;
 
-                MullvadState::Error(talpid_tunnel::ErrorState::new(cause, block_failure))
+                let mut error_state = talpid_tunnel::ErrorState::new(cause, block_failure);
+                if let Some(diagnostics) = diagnostics {
+                    error_state = error_state
+                        .with_diagnostics(talpid_tunnel::ErrorDiagnostics::try_from(diagnostics)?);
+                }
+
+                MullvadState::Error(error_state)
             }
             _ => {
                 return Err(FromProtobufTypeError::InvalidArgument(
@@ -418,3 +480,97 @@ fn try_firewall_policy_error_from_i32(
         )),
     }
 }
+
+impl From<talpid_types::tunnel::ErrorDiagnostics> for proto::error_state::Diagnostics {
+    fn from(diagnostics: talpid_types::tunnel::ErrorDiagnostics) -> Self {
+        use proto::error_state::diagnostics::{Remediation, Subsystem};
+        use talpid_types::tunnel::{ErrorSubsystem, RemediationCode};
+
+        let subsystem = match diagnostics.subsystem {
+            ErrorSubsystem::Unknown => Subsystem::UnknownSubsystem,
+            ErrorSubsystem::Firewall => Subsystem::Firewall,
+            ErrorSubsystem::Dns => Subsystem::Dns,
+            ErrorSubsystem::TunnelDevice => Subsystem::TunnelDevice,
+            ErrorSubsystem::Tunnel => Subsystem::Tunnel,
+            ErrorSubsystem::RelaySelection => Subsystem::RelaySelection,
+            ErrorSubsystem::Authentication => Subsystem::Authentication,
+            ErrorSubsystem::Network => Subsystem::Network,
+            ErrorSubsystem::SplitTunnel => Subsystem::SplitTunnel,
+            ErrorSubsystem::Permissions => Subsystem::Permissions,
+        };
+        let remediation = match diagnostics.remediation {
+            RemediationCode::None => Remediation::NoRemediation,
+            RemediationCode::CheckAccount => Remediation::CheckAccount,
+            RemediationCode::EnableIpv6 => Remediation::EnableIpv6,
+            RemediationCode::ResolveFirewallConflict => Remediation::ResolveFirewallConflict,
+            RemediationCode::CheckDnsConfiguration => Remediation::CheckDnsConfiguration,
+            RemediationCode::LoadKernelModule => Remediation::LoadKernelModule,
+            RemediationCode::ReinstallTunnelDriver => Remediation::ReinstallTunnelDriver,
+            RemediationCode::ChangeRelayConstraints => Remediation::ChangeRelayConstraints,
+            RemediationCode::RotateWireguardKey => Remediation::RotateWireguardKey,
+            RemediationCode::CheckConnectivity => Remediation::CheckConnectivity,
+            RemediationCode::GrantPermission => Remediation::GrantPermission,
+            RemediationCode::Restart => Remediation::Restart,
+        };
+
+        Self {
+            subsystem: i32::from(subsystem),
+            error_chain: diagnostics.error_chain,
+            environment: diagnostics.environment.into_iter().collect(),
+            remediation: i32::from(remediation),
+        }
+    }
+}
+
+impl TryFrom<proto::error_state::Diagnostics> for talpid_types::tunnel::ErrorDiagnostics {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(diagnostics: proto::error_state::Diagnostics) -> Result<Self, Self::Error> {
+        use proto::error_state::diagnostics::{Remediation, Subsystem};
+        use talpid_types::tunnel::{ErrorSubsystem, RemediationCode};
+
+        let subsystem = match Subsystem::try_from(diagnostics.subsystem) {
+            Ok(Subsystem::UnknownSubsystem) => ErrorSubsystem::Unknown,
+            Ok(Subsystem::Firewall) => ErrorSubsystem::Firewall,
+            Ok(Subsystem::Dns) => ErrorSubsystem::Dns,
+            Ok(Subsystem::TunnelDevice) => ErrorSubsystem::TunnelDevice,
+            Ok(Subsystem::Tunnel) => ErrorSubsystem::Tunnel,
+            Ok(Subsystem::RelaySelection) => ErrorSubsystem::RelaySelection,
+            Ok(Subsystem::Authentication) => ErrorSubsystem::Authentication,
+            Ok(Subsystem::Network) => ErrorSubsystem::Network,
+            Ok(Subsystem::SplitTunnel) => ErrorSubsystem::SplitTunnel,
+            Ok(Subsystem::Permissions) => ErrorSubsystem::Permissions,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid error subsystem",
+                ))
+            }
+        };
+        let remediation = match Remediation::try_from(diagnostics.remediation) {
+            Ok(Remediation::NoRemediation) => RemediationCode::None,
+            Ok(Remediation::CheckAccount) => RemediationCode::CheckAccount,
+            Ok(Remediation::EnableIpv6) => RemediationCode::EnableIpv6,
+            Ok(Remediation::ResolveFirewallConflict) => RemediationCode::ResolveFirewallConflict,
+            Ok(Remediation::CheckDnsConfiguration) => RemediationCode::CheckDnsConfiguration,
+            Ok(Remediation::LoadKernelModule) => RemediationCode::LoadKernelModule,
+            Ok(Remediation::ReinstallTunnelDriver) => RemediationCode::ReinstallTunnelDriver,
+            Ok(Remediation::ChangeRelayConstraints) => RemediationCode::ChangeRelayConstraints,
+            Ok(Remediation::RotateWireguardKey) => RemediationCode::RotateWireguardKey,
+            Ok(Remediation::CheckConnectivity) => RemediationCode::CheckConnectivity,
+            Ok(Remediation::GrantPermission) => RemediationCode::GrantPermission,
+            Ok(Remediation::Restart) => RemediationCode::Restart,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid remediation code",
+                ))
+            }
+        };
+
+        Ok(Self {
+            subsystem,
+            error_chain: diagnostics.error_chain,
+            environment: diagnostics.environment.into_iter().collect(),
+            remediation,
+        })
+    }
+}
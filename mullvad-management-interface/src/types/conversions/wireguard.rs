@@ -41,17 +41,57 @@ impl From<mullvad_types::wireguard::QuantumResistantState> for proto::QuantumRes
         match state {
             mullvad_types::wireguard::QuantumResistantState::Auto => proto::QuantumResistantState {
                 state: i32::from(proto::quantum_resistant_state::State::Auto),
+                algorithms: None,
             },
             mullvad_types::wireguard::QuantumResistantState::On => proto::QuantumResistantState {
                 state: i32::from(proto::quantum_resistant_state::State::On),
+                algorithms: None,
             },
             mullvad_types::wireguard::QuantumResistantState::Off => proto::QuantumResistantState {
                 state: i32::from(proto::quantum_resistant_state::State::Off),
+                algorithms: None,
             },
         }
     }
 }
 
+impl From<mullvad_types::wireguard::QuantumResistantAlgorithms>
+    for proto::quantum_resistant_state::Algorithms
+{
+    fn from(algorithms: mullvad_types::wireguard::QuantumResistantAlgorithms) -> Self {
+        use mullvad_types::wireguard::QuantumResistantAlgorithms;
+        match algorithms {
+            QuantumResistantAlgorithms::PreferMlKem => Self::PreferMlKem,
+            QuantumResistantAlgorithms::RequireMlKem => Self::RequireMlKem,
+            QuantumResistantAlgorithms::Kyber => Self::Kyber,
+        }
+    }
+}
+
+/// Return the algorithm policy of `state`, if it is set.
+pub fn try_quantum_resistant_algorithms(
+    state: &proto::QuantumResistantState,
+) -> Result<Option<mullvad_types::wireguard::QuantumResistantAlgorithms>, FromProtobufTypeError> {
+    use mullvad_types::wireguard::QuantumResistantAlgorithms;
+    let Some(algorithms) = state.algorithms else {
+        return Ok(None);
+    };
+    match proto::quantum_resistant_state::Algorithms::try_from(algorithms) {
+        Ok(proto::quantum_resistant_state::Algorithms::PreferMlKem) => {
+            Ok(Some(QuantumResistantAlgorithms::PreferMlKem))
+        }
+        Ok(proto::quantum_resistant_state::Algorithms::RequireMlKem) => {
+            Ok(Some(QuantumResistantAlgorithms::RequireMlKem))
+        }
+        Ok(proto::quantum_resistant_state::Algorithms::Kyber) => {
+            Ok(Some(QuantumResistantAlgorithms::Kyber))
+        }
+        Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+            "invalid quantum resistant algorithms",
+        )),
+    }
+}
+
 impl TryFrom<proto::QuantumResistantState> for mullvad_types::wireguard::QuantumResistantState {
     type Error = FromProtobufTypeError;
 
@@ -0,0 +1,268 @@
+//! Problem report archives. An archive contains the same logs as a text report, but keeps every
+//! part in a separate file, next to a sanitized export of the daemon settings and snapshots of
+//! the routing and firewall state. A `manifest.json` describes every file and counts what was
+//! redacted.
+//!
+//! Archives are only meant to be stored locally, or reviewed and shared by the user. They are
+//! never sent by this tool.
+
+use crate::{
+    redact::{self, RedactionKind, RedactionLevel},
+    ProblemReport,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+    process::Command,
+    time::SystemTime,
+};
+use talpid_types::ErrorExt;
+
+const FORMAT_VERSION: u32 = 1;
+
+/// Offset and value of the magic field in a tar header.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// Name of the daemon settings file in the settings directory.
+#[cfg(not(target_os = "android"))]
+const SETTINGS_FILE: &str = "settings.json";
+
+/// A command whose output describes the routing or firewall state.
+struct Snapshot {
+    file: &'static str,
+    program: &'static str,
+    args: &'static [&'static str],
+}
+
+const SNAPSHOTS: &[Snapshot] = &[
+    #[cfg(target_os = "linux")]
+    Snapshot {
+        file: "snapshots/routes.txt",
+        program: "ip",
+        args: &["route", "show", "table", "all"],
+    },
+    #[cfg(target_os = "linux")]
+    Snapshot {
+        file: "snapshots/routes6.txt",
+        program: "ip",
+        args: &["-6", "route", "show", "table", "all"],
+    },
+    #[cfg(target_os = "linux")]
+    Snapshot {
+        file: "snapshots/rules.txt",
+        program: "ip",
+        args: &["rule", "show"],
+    },
+    #[cfg(target_os = "linux")]
+    Snapshot {
+        file: "snapshots/firewall.txt",
+        program: "nft",
+        args: &["list", "ruleset"],
+    },
+    #[cfg(target_os = "macos")]
+    Snapshot {
+        file: "snapshots/routes.txt",
+        program: "netstat",
+        args: &["-rn"],
+    },
+    #[cfg(target_os = "macos")]
+    Snapshot {
+        file: "snapshots/firewall.txt",
+        program: "pfctl",
+        args: &["-a", "mullvad", "-s", "rules"],
+    },
+    #[cfg(target_os = "windows")]
+    Snapshot {
+        file: "snapshots/routes.txt",
+        program: "route",
+        args: &["print"],
+    },
+    #[cfg(target_os = "windows")]
+    Snapshot {
+        file: "snapshots/firewall.xml",
+        program: "netsh",
+        args: &["wfp", "show", "filters", "file=-"],
+    },
+];
+
+#[derive(Serialize)]
+struct Manifest {
+    format_version: u32,
+    created: String,
+    redaction_level: RedactionLevel,
+    files: Vec<ManifestFile>,
+    redactions: BTreeMap<RedactionKind, usize>,
+}
+
+#[derive(Serialize)]
+struct ManifestFile {
+    path: String,
+    description: String,
+}
+
+/// Write `report` to a tar archive at `path`, along with the settings export and snapshots.
+pub fn write_archive(path: &Path, report: &mut ProblemReport) -> io::Result<()> {
+    let mut files = vec![(
+        "metadata.json".to_owned(),
+        "System information".to_owned(),
+        serde_json::to_string_pretty(&report.metadata)?,
+    )];
+    for (index, (label, content)) in report.logs.iter().enumerate() {
+        files.push((
+            format!("logs/{index:02}-{}", log_file_name(label)),
+            label.clone(),
+            content.clone(),
+        ));
+    }
+    for (name, content) in &report.daemon_state {
+        files.push((
+            format!("daemon/{name}.json"),
+            format!("Daemon state: {name}"),
+            content.clone(),
+        ));
+    }
+    #[cfg(not(target_os = "android"))]
+    files.push((
+        SETTINGS_FILE.to_owned(),
+        "Daemon settings, with secrets removed".to_owned(),
+        export_settings(report),
+    ));
+    for snapshot in SNAPSHOTS {
+        let command = format!("{} {}", snapshot.program, snapshot.args.join(" "));
+        let output = run_snapshot(snapshot);
+        let output =
+            report
+                .redactor
+                .redact_recorded(snapshot.file, &output, &mut report.redactions);
+        files.push((
+            snapshot.file.to_owned(),
+            format!("Output of '{command}'"),
+            output,
+        ));
+    }
+
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        created: chrono::Utc::now().to_rfc3339(),
+        redaction_level: report.redactor.level(),
+        files: files
+            .iter()
+            .map(|(path, description, _)| ManifestFile {
+                path: path.clone(),
+                description: description.clone(),
+            })
+            .collect(),
+        redactions: redact::summarize(&report.redactions),
+    };
+
+    let file = File::create(path)?;
+    let mut permissions = file.metadata()?.permissions();
+    permissions.set_readonly(true);
+    file.set_permissions(permissions)?;
+
+    let mtime = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let mut archive = tar::Builder::new(BufWriter::new(file));
+    append(
+        &mut archive,
+        "manifest.json",
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+        mtime,
+    )?;
+    for (path, _, content) in &files {
+        append(&mut archive, path, content.as_bytes(), mtime)?;
+    }
+    archive.into_inner()?.flush()
+}
+
+/// Returns whether the file at `path` is a tar archive.
+pub fn is_archive(path: &Path) -> bool {
+    let mut header = [0u8; TAR_MAGIC_OFFSET + TAR_MAGIC.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map(|()| &header[TAR_MAGIC_OFFSET..] == TAR_MAGIC)
+        .unwrap_or(false)
+}
+
+fn append<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    content: &[u8],
+    mtime: u64,
+) -> io::Result<()> {
+    let mut header = tar::Header::new_ustar();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    archive.append_data(&mut header, path, content)
+}
+
+/// Turn a log label, which is either a path or an error message, into a file name.
+fn log_file_name(label: &str) -> String {
+    let name: String = label
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(label)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.ends_with(".log") || name.ends_with(".txt") {
+        name
+    } else {
+        format!("{name}.txt")
+    }
+}
+
+/// Read the daemon settings, replace all secrets and redact the rest like a log.
+#[cfg(not(target_os = "android"))]
+fn export_settings(report: &mut ProblemReport) -> String {
+    let settings = mullvad_paths::settings_dir()
+        .map_err(|error| error.display_chain_with_msg("Unable to get settings directory"))
+        .and_then(|dir| {
+            std::fs::read_to_string(dir.join(SETTINGS_FILE))
+                .map_err(|error| error.display_chain_with_msg("Failed to read settings"))
+        })
+        .and_then(|content| {
+            serde_json::from_str::<serde_json::Value>(&content)
+                .map_err(|error| error.display_chain_with_msg("Failed to parse settings"))
+        });
+    let content = match settings {
+        Ok(mut settings) => {
+            mullvad_types::secrets::remove_secrets(&mut settings);
+            serde_json::to_string_pretty(&settings).unwrap_or_else(|error| error.display_chain())
+        }
+        Err(error) => error,
+    };
+    report
+        .redactor
+        .redact_recorded(SETTINGS_FILE, &content, &mut report.redactions)
+}
+
+fn run_snapshot(snapshot: &Snapshot) -> String {
+    match Command::new(snapshot.program).args(snapshot.args).output() {
+        Ok(output) => {
+            let mut content = String::from_utf8_lossy(&output.stdout).into_owned();
+            content.push_str(&String::from_utf8_lossy(&output.stderr));
+            if !output.status.success() {
+                content.push_str(&format!(
+                    "\n{} exited with {}",
+                    snapshot.program, output.status
+                ));
+            }
+            content
+        }
+        Err(error) => error.display_chain_with_msg(&format!("Failed to run {}", snapshot.program)),
+    }
+}
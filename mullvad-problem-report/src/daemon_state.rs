@@ -0,0 +1,225 @@
+//! State of the running daemon, queried over the management interface. This answers the
+//! questions that support would otherwise have to ask: what the tunnel is doing, how the app is
+//! configured, how old the relay list is and how the API is reached.
+
+use chrono::{DateTime, Utc};
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::states::RecentError;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+use talpid_types::{
+    tunnel::{ErrorDiagnostics, ErrorSubsystem, RemediationCode},
+    ErrorExt,
+};
+
+/// Give up on the daemon if it has not answered all queries within this time.
+const DAEMON_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Name of the cached relay list in the cache directory.
+const RELAYS_FILE: &str = "relays.json";
+
+/// Number of error states to include.
+const MAX_RECENT_ERRORS: usize = 10;
+
+/// Why the tunnel entered the error state, flattened for support.
+#[derive(Debug, Serialize)]
+struct ErrorSummary {
+    time: DateTime<Utc>,
+    cause: String,
+    blocking: bool,
+    subsystem: ErrorSubsystem,
+    error_chain: Vec<String>,
+    environment: BTreeMap<String, String>,
+    remediation: RemediationCode,
+}
+
+#[derive(Serialize)]
+struct RelayListInfo {
+    etag: Option<String>,
+    /// Time since the cached relay list was last written, if it exists.
+    age_seconds: Option<u64>,
+}
+
+/// Query the daemon and return named sections of JSON. A section that could not be queried
+/// contains the error instead.
+pub fn collect() -> Vec<(&'static str, String)> {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime.block_on(async {
+            tokio::time::timeout(DAEMON_TIMEOUT, query_daemon())
+                .await
+                .unwrap_or_else(|_| vec![("daemon", "Timed out querying the daemon".to_owned())])
+        }),
+        Err(error) => vec![(
+            "daemon",
+            error.display_chain_with_msg("Unable to spawn Tokio runtime"),
+        )],
+    }
+}
+
+async fn query_daemon() -> Vec<(&'static str, String)> {
+    let mut rpc = match MullvadProxyClient::new().await {
+        Ok(rpc) => rpc,
+        Err(error) => {
+            return vec![(
+                "daemon",
+                error.display_chain_with_msg("Unable to connect to the daemon"),
+            )]
+        }
+    };
+
+    let tunnel_state = to_json(rpc.get_tunnel_state().await);
+    let settings = to_json(rpc.get_settings().await);
+    let relay_list = to_json(
+        rpc.get_relay_locations()
+            .await
+            .map(|relay_list| RelayListInfo {
+                etag: relay_list.etag,
+                age_seconds: relay_list_age(),
+            }),
+    );
+    let feature_indicators = to_json(rpc.get_feature_indicators().await);
+    let access_method = to_json(rpc.get_current_api_access_method().await);
+    let recent_errors = to_json(rpc.get_recent_errors().await.map(summarize_errors));
+
+    vec![
+        ("tunnel_state", tunnel_state),
+        ("settings", settings),
+        ("relay_list", relay_list),
+        ("feature_indicators", feature_indicators),
+        ("access_method", access_method),
+        ("recent_errors", recent_errors),
+    ]
+}
+
+/// Serialize `result` with all secrets removed, or return its error chain.
+fn to_json<T: Serialize>(result: Result<T, mullvad_management_interface::Error>) -> String {
+    let value = match result {
+        Ok(value) => serde_json::to_value(value),
+        Err(error) => return error.display_chain(),
+    };
+    match value {
+        Ok(mut value) => {
+            mullvad_types::secrets::remove_secrets(&mut value);
+            serde_json::to_string_pretty(&value).unwrap_or_else(|error| error.display_chain())
+        }
+        Err(error) => error.display_chain(),
+    }
+}
+
+fn relay_list_age() -> Option<u64> {
+    let modified = mullvad_paths::get_cache_dir()
+        .ok()?
+        .join(RELAYS_FILE)
+        .metadata()
+        .ok()?
+        .modified()
+        .ok()?;
+    SystemTime::now()
+        .duration_since(modified)
+        .ok()
+        .map(|age| age.as_secs())
+}
+
+/// Summarize the most recent error states, newest first. Error states that lack diagnostics
+/// fall back to what can be derived from the cause.
+fn summarize_errors(errors: Vec<RecentError>) -> Vec<ErrorSummary> {
+    errors
+        .into_iter()
+        .rev()
+        .take(MAX_RECENT_ERRORS)
+        .map(|error| {
+            let state = error.error_state;
+            let diagnostics = state
+                .diagnostics()
+                .cloned()
+                .unwrap_or_else(|| ErrorDiagnostics::from_cause(state.cause()));
+            ErrorSummary {
+                time: error.time,
+                cause: state.cause().to_string(),
+                blocking: state.is_blocking(),
+                subsystem: diagnostics.subsystem,
+                error_chain: diagnostics.error_chain,
+                environment: diagnostics.environment,
+                remediation: diagnostics.remediation,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::tunnel::{ErrorState, ErrorStateCause, FirewallPolicyError};
+
+    fn recent_error(seconds: i64, error_state: ErrorState) -> RecentError {
+        RecentError {
+            time: DateTime::from_timestamp(seconds, 0).unwrap(),
+            error_state,
+        }
+    }
+
+    #[test]
+    fn test_summarize_errors_uses_diagnostics() {
+        let diagnostics = ErrorDiagnostics {
+            subsystem: ErrorSubsystem::Dns,
+            error_chain: vec![
+                "Failed to set system DNS settings".to_owned(),
+                "resolvconf exited with status 1".to_owned(),
+            ],
+            environment: BTreeMap::from([("dns_manager".to_owned(), "resolvconf".to_owned())]),
+            remediation: RemediationCode::CheckDnsConfiguration,
+        };
+        let errors = vec![recent_error(
+            1,
+            ErrorState::new(ErrorStateCause::SetDnsError, None)
+                .with_diagnostics(diagnostics.clone()),
+        )];
+
+        let summary = summarize_errors(errors);
+        assert_eq!(summary.len(), 1);
+        assert!(summary[0].blocking);
+        assert_eq!(summary[0].subsystem, diagnostics.subsystem);
+        assert_eq!(summary[0].error_chain, diagnostics.error_chain);
+        assert_eq!(summary[0].environment, diagnostics.environment);
+        assert_eq!(summary[0].remediation, diagnostics.remediation);
+    }
+
+    #[test]
+    fn test_summarize_errors_without_diagnostics() {
+        let cause = ErrorStateCause::Ipv6Unavailable;
+        let errors = vec![recent_error(
+            1,
+            ErrorState::new(cause.clone(), Some(FirewallPolicyError::Generic)),
+        )];
+
+        let summary = summarize_errors(errors);
+        assert!(!summary[0].blocking);
+        assert_eq!(summary[0].cause, cause.to_string());
+        assert_eq!(summary[0].subsystem, cause.subsystem());
+        assert_eq!(summary[0].remediation, cause.remediation());
+        assert!(summary[0].error_chain.is_empty());
+    }
+
+    #[test]
+    fn test_summarize_errors_newest_first() {
+        let errors: Vec<_> = (0..MAX_RECENT_ERRORS as i64 + 5)
+            .map(|seconds| {
+                recent_error(
+                    seconds,
+                    ErrorState::new(ErrorStateCause::StartTunnelError, None),
+                )
+            })
+            .collect();
+
+        let summary = summarize_errors(errors);
+        let times: Vec<_> = summary.iter().map(|error| error.time.timestamp()).collect();
+        let expected: Vec<_> = (5..MAX_RECENT_ERRORS as i64 + 5).rev().collect();
+        assert_eq!(times, expected);
+    }
+}
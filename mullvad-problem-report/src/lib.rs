@@ -1,8 +1,6 @@
 use mullvad_api::proxy::ApiConnectionMode;
-use once_cell::sync::Lazy;
-use regex::Regex;
+use redact::{Redaction, RedactionLevel, Redactor};
 use std::{
-    borrow::Cow,
     cmp::min,
     collections::{BTreeMap, HashSet},
     ffi::OsStr,
@@ -12,7 +10,11 @@ use std::{
 };
 use talpid_types::ErrorExt;
 
+mod archive;
+#[cfg(not(target_os = "android"))]
+mod daemon_state;
 pub mod metadata;
+pub mod redact;
 
 /// Maximum number of bytes to read from each log file
 const LOG_MAX_READ_BYTES: usize = 128 * 1024;
@@ -69,6 +71,9 @@ pub enum Error {
     #[error("Failed to send problem report {} times", MAX_SEND_ATTEMPTS)]
     SendFailedTooManyTimes,
 
+    #[error("Problem report archives are for local use only. Collect a text report to send it")]
+    CannotSendArchive,
+
     #[error("Unable to spawn Tokio runtime")]
     CreateRuntime(#[source] io::Error),
 
@@ -105,13 +110,90 @@ pub enum LogError {
     NoLocalAppDataDir,
 }
 
+/// Collect a problem report into a single text file that can be sent with
+/// [`send_problem_report`].
 pub fn collect_report<P: AsRef<Path>>(
     extra_logs: &[P],
     output_path: &Path,
     redact_custom_strings: Vec<String>,
+    redaction_level: RedactionLevel,
+    #[cfg(not(target_os = "android"))] include_daemon_state: bool,
     #[cfg(target_os = "android")] android_log_dir: &Path,
 ) -> Result<(), Error> {
-    let mut problem_report = ProblemReport::new(redact_custom_strings);
+    let problem_report = gather_report(
+        extra_logs,
+        redact_custom_strings,
+        redaction_level,
+        #[cfg(not(target_os = "android"))]
+        include_daemon_state,
+        #[cfg(target_os = "android")]
+        android_log_dir,
+    );
+
+    write_problem_report(output_path, &problem_report).map_err(|source| Error::WriteReportError {
+        path: output_path.display().to_string(),
+        source,
+    })
+}
+
+/// Collect a problem report into a tar archive with a manifest, one file per log, the daemon
+/// settings and snapshots of the routing and firewall state. The archive is meant to be kept
+/// locally or reviewed by the user, and cannot be sent with [`send_problem_report`].
+pub fn collect_archive<P: AsRef<Path>>(
+    extra_logs: &[P],
+    output_path: &Path,
+    redact_custom_strings: Vec<String>,
+    redaction_level: RedactionLevel,
+    #[cfg(not(target_os = "android"))] include_daemon_state: bool,
+    #[cfg(target_os = "android")] android_log_dir: &Path,
+) -> Result<(), Error> {
+    let mut problem_report = gather_report(
+        extra_logs,
+        redact_custom_strings,
+        redaction_level,
+        #[cfg(not(target_os = "android"))]
+        include_daemon_state,
+        #[cfg(target_os = "android")]
+        android_log_dir,
+    );
+
+    archive::write_archive(output_path, &mut problem_report).map_err(|source| {
+        Error::WriteReportError {
+            path: output_path.display().to_string(),
+            source,
+        }
+    })
+}
+
+/// Collect a problem report without writing it anywhere, and return everything that would be
+/// redacted from it.
+pub fn preview<P: AsRef<Path>>(
+    extra_logs: &[P],
+    redact_custom_strings: Vec<String>,
+    redaction_level: RedactionLevel,
+    #[cfg(not(target_os = "android"))] include_daemon_state: bool,
+    #[cfg(target_os = "android")] android_log_dir: &Path,
+) -> Vec<Redaction> {
+    gather_report(
+        extra_logs,
+        redact_custom_strings,
+        redaction_level,
+        #[cfg(not(target_os = "android"))]
+        include_daemon_state,
+        #[cfg(target_os = "android")]
+        android_log_dir,
+    )
+    .redactions
+}
+
+fn gather_report<P: AsRef<Path>>(
+    extra_logs: &[P],
+    redact_custom_strings: Vec<String>,
+    redaction_level: RedactionLevel,
+    #[cfg(not(target_os = "android"))] include_daemon_state: bool,
+    #[cfg(target_os = "android")] android_log_dir: &Path,
+) -> ProblemReport {
+    let mut problem_report = ProblemReport::new(redact_custom_strings, redaction_level);
 
     let daemon_logs_dir = {
         #[cfg(target_os = "android")]
@@ -169,11 +251,11 @@ pub fn collect_report<P: AsRef<Path>>(
     }
 
     problem_report.add_logs(extra_logs);
-
-    write_problem_report(output_path, &problem_report).map_err(|source| Error::WriteReportError {
-        path: output_path.display().to_string(),
-        source,
-    })
+    #[cfg(not(target_os = "android"))]
+    if include_daemon_state {
+        problem_report.add_daemon_state();
+    }
+    problem_report
 }
 
 /// Returns an iterator over all files in the given directory that has the `.log` extension.
@@ -262,6 +344,10 @@ pub fn send_problem_report(
     report_path: &Path,
     cache_dir: &Path,
 ) -> Result<(), Error> {
+    if archive::is_archive(report_path) {
+        return Err(Error::CannotSendArchive);
+    }
+
     let report_content = normalize_newlines(
         read_file_lossy(report_path, REPORT_MAX_SIZE).map_err(|source| {
             Error::ReadProblemReportError {
@@ -343,20 +429,23 @@ struct ProblemReport {
     metadata: BTreeMap<String, String>,
     logs: Vec<(String, String)>,
     log_paths: HashSet<PathBuf>,
-    redact_custom_strings: Vec<String>,
+    daemon_state: Vec<(&'static str, String)>,
+    redactor: Redactor,
+    redactions: Vec<Redaction>,
 }
 
 impl ProblemReport {
     /// Creates a new problem report with system information. Logs can be added with `add_log`.
-    /// Logs will have all strings in `redact_custom_strings` removed from them.
-    pub fn new(mut redact_custom_strings: Vec<String>) -> Self {
-        redact_custom_strings.retain(|redact| !redact.is_empty());
-
+    /// Logs will have everything included in `redaction_level` and all strings in
+    /// `redact_custom_strings` removed from them.
+    pub fn new(redact_custom_strings: Vec<String>, redaction_level: RedactionLevel) -> Self {
         ProblemReport {
             metadata: metadata::collect(),
             logs: Vec::new(),
             log_paths: HashSet::new(),
-            redact_custom_strings,
+            daemon_state: Vec::new(),
+            redactor: Redactor::new(redaction_level, redact_custom_strings),
+            redactions: Vec::new(),
         }
     }
 
@@ -377,15 +466,20 @@ impl ProblemReport {
     pub fn add_log(&mut self, path: &Path) {
         let expanded_path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
         if self.log_paths.insert(expanded_path.clone()) {
-            let redacted_path = self.redact(&expanded_path.to_string_lossy());
-            let content = self.redact(&read_file_lossy(path, LOG_MAX_READ_BYTES).unwrap_or_else(
-                |error| {
-                    error.display_chain_with_msg(&format!(
-                        "Error reading the contents of log file: {}",
-                        expanded_path.display()
-                    ))
-                },
-            ));
+            let redacted_path = self.redactor.redact_recorded(
+                "log paths",
+                &expanded_path.to_string_lossy(),
+                &mut self.redactions,
+            );
+            let content = read_file_lossy(path, LOG_MAX_READ_BYTES).unwrap_or_else(|error| {
+                error.display_chain_with_msg(&format!(
+                    "Error reading the contents of log file: {}",
+                    expanded_path.display()
+                ))
+            });
+            let content =
+                self.redactor
+                    .redact_recorded(&redacted_path, &content, &mut self.redactions);
             self.logs.push((redacted_path, content));
             log::info!("Adding {}", expanded_path.display());
         }
@@ -393,57 +487,28 @@ impl ProblemReport {
 
     /// Attach an error to the report.
     pub fn add_error(&mut self, message: &'static str, error: &impl ErrorExt) {
-        let redacted_error = self.redact(&error.display_chain());
+        let redacted_error =
+            self.redactor
+                .redact_recorded(message, &error.display_chain(), &mut self.redactions);
         self.logs.push((message.to_string(), redacted_error));
     }
 
-    fn redact(&self, input: &str) -> String {
-        let out1 = Self::redact_account_number(input);
-        let out2 = Self::redact_home_dir(&out1);
-        let out3 = Self::redact_network_info(&out2);
-        let out4 = Self::redact_guids(&out3);
-        self.redact_custom_strings(&out4).to_string()
-    }
-
-    fn redact_account_number(input: &str) -> Cow<'_, str> {
-        static RE: Lazy<Regex> = Lazy::new(|| Regex::new("\\d{16}").unwrap());
-        RE.replace_all(input, "[REDACTED ACCOUNT NUMBER]")
-    }
-
-    fn redact_home_dir(input: &str) -> Cow<'_, str> {
-        redact_home_dir_inner(input, dirs::home_dir())
-    }
-
-    fn redact_network_info(input: &str) -> Cow<'_, str> {
-        static RE: Lazy<Regex> = Lazy::new(|| {
-            let boundary = "[^0-9a-zA-Z.:]";
-            let combined_pattern = format!(
-                "(?P<start>^|{})(?:{}|{}|{})",
-                boundary,
-                build_ipv4_regex(),
-                build_ipv6_regex(),
-                build_mac_regex(),
-            );
-            Regex::new(&combined_pattern).unwrap()
-        });
-        RE.replace_all(input, "$start[REDACTED]")
-    }
-
-    fn redact_guids(input: &str) -> Cow<'_, str> {
-        static RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"(?i)\{?[A-F0-9]{8}-[A-F0-9]{4}-[A-F0-9]{4}-[A-F0-9]{4}-[A-F0-9]{12}\}?")
-                .unwrap()
-        });
-        RE.replace_all(input, "[REDACTED]")
+    /// Attach the state of the running daemon. Sections that cannot be queried contain the error
+    /// instead.
+    #[cfg(not(target_os = "android"))]
+    pub fn add_daemon_state(&mut self) {
+        for (name, content) in daemon_state::collect() {
+            let source = format!("Daemon state: {name}");
+            let content = self
+                .redactor
+                .redact_recorded(&source, &content, &mut self.redactions);
+            self.daemon_state.push((name, content));
+        }
     }
 
-    fn redact_custom_strings<'a>(&self, input: &'a str) -> Cow<'a, str> {
-        // Can probably me made a lot faster with aho-corasick if optimization is ever needed.
-        let mut out = Cow::from(input);
-        for redact in &self.redact_custom_strings {
-            out = out.replace(redact, "[REDACTED]").into()
-        }
-        out
+    #[cfg(test)]
+    fn redact(&self, input: &str) -> String {
+        self.redactor.redact(input)
     }
 
     fn write_to<W: Write>(&self, mut output: W) -> io::Result<()> {
@@ -454,6 +519,13 @@ impl ProblemReport {
         }
         // Write empty line to separate metadata from first log
         write_line!(output)?;
+        for (name, content) in &self.daemon_state {
+            write_line!(output, "{}", LOG_DELIMITER)?;
+            write_line!(output, "Daemon state: {}", name)?;
+            write_line!(output, "{}", LOG_DELIMITER)?;
+            output.write_all(content.as_bytes())?;
+            write_line!(output)?;
+        }
         for (label, content) in &self.logs {
             write_line!(output, "{}", LOG_DELIMITER)?;
             write_line!(output, "Log: {}", label)?;
@@ -489,87 +561,6 @@ impl ProblemReport {
     }
 }
 
-fn redact_home_dir_inner(input: &str, home_dir: Option<PathBuf>) -> Cow<'_, str> {
-    match home_dir {
-        Some(home) => {
-            let out = input.replace(home.to_string_lossy().as_ref(), "~");
-
-            // On Windows, redact the prefix of any path that contains \Users\{user}.
-            #[cfg(target_os = "windows")]
-            {
-                let mut home = home;
-                let prefix = home.components().next();
-                if let Some(prefix @ std::path::Component::Prefix(_)) = prefix.as_ref() {
-                    home = home.strip_prefix(prefix).unwrap().to_path_buf();
-                }
-                let expr = format!(r"[\w\\]+{}", regex::escape(&home.display().to_string()));
-                let regex = Regex::new(&expr).unwrap();
-
-                Cow::Owned(regex.replace_all(&out, "~").to_string())
-            }
-
-            #[cfg(not(target_os = "windows"))]
-            Cow::from(out)
-        }
-        None => Cow::from(input),
-    }
-}
-
-fn build_mac_regex() -> String {
-    let octet = "[[:xdigit:]]{2}"; // 0 - ff
-
-    // five pairs of two hexadecimal chars followed by colon or dash
-    // followed by a pair of hexadecimal chars
-    format!("(?:{octet}[:-]){{5}}({octet})")
-}
-
-fn build_ipv4_regex() -> String {
-    // regex adapted from  https://www.regular-expressions.info/ip.html
-
-    let above_250 = "25[0-5]";
-    let above_200 = "2[0-4][0-9]";
-    let above_100 = "1[0-9][0-9]";
-
-    // 100-119 | 120-126 | 128-129 | 130 - 199
-    let above_100_not_127 = "1(?:[01][0-9]|2[0-6]|2[89]|[3-9][0-9])";
-
-    let above_0 = "0?[0-9][0-9]?";
-
-    // matches 0-255, except 127
-    let first_octet = format!("(?:{above_250}|{above_200}|{above_100_not_127}|{above_0})");
-
-    // matches 0-255
-    let ip_octet = format!("(?:{above_250}|{above_200}|{above_100}|{above_0})");
-
-    format!("(?:{first_octet}\\.{ip_octet}\\.{ip_octet}\\.{ip_octet})")
-}
-
-fn build_ipv6_regex() -> String {
-    // Regular expression obtained from:
-    // https://stackoverflow.com/a/17871737
-    let ipv4_segment = "(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])";
-    let ipv4_address = format!("({ipv4_segment}\\.){{3,3}}{ipv4_segment}");
-
-    let ipv6_segment = "[0-9a-fA-F]{1,4}";
-
-    let long = format!("({ipv6_segment}:){{7,7}}{ipv6_segment}");
-    let compressed_1 = format!("({ipv6_segment}:){{1,7}}:");
-    let compressed_2 = format!("({ipv6_segment}:){{1,6}}:{ipv6_segment}");
-    let compressed_3 = format!("({ipv6_segment}:){{1,5}}(:{ipv6_segment}){{1,2}}");
-    let compressed_4 = format!("({ipv6_segment}:){{1,4}}(:{ipv6_segment}){{1,3}}");
-    let compressed_5 = format!("({ipv6_segment}:){{1,3}}(:{ipv6_segment}){{1,4}}");
-    let compressed_6 = format!("({ipv6_segment}:){{1,2}}(:{ipv6_segment}){{1,5}}");
-    let compressed_7 = format!("{ipv6_segment}:((:{ipv6_segment}){{1,6}})");
-    let compressed_8 = format!(":((:{ipv6_segment}){{1,7}}|:)");
-    let link_local = "[Ff][Ee]80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}";
-    let ipv4_mapped = format!("::([fF]{{4}}(:0{{1,4}}){{0,1}}:){{0,1}}{ipv4_address}");
-    let ipv4_embedded = format!("({ipv6_segment}:){{1,4}}:{ipv4_address}");
-
-    format!(
-        "{long}|{link_local}|{ipv4_mapped}|{ipv4_embedded}|{compressed_8}|{compressed_7}|{compressed_6}|{compressed_5}|{compressed_4}|{compressed_3}|{compressed_2}|{compressed_1}",
-    )
-}
-
 /// Helper to lossily read a file to a `String`. If the file size exceeds the given `max_bytes`,
 /// only the last `max_bytes` bytes of the file are read.
 fn read_file_lossy(path: &Path, max_bytes: usize) -> io::Result<String> {
@@ -645,21 +636,6 @@ mod tests {
         assert_redacts("{123123ab-12ab-89cd-45ef-012345678901}");
     }
 
-    #[test]
-    #[cfg(windows)]
-    fn redacts_home_dir() {
-        let assert_redacts_home_dir = |home_dir, test_str| {
-            let input = format!(r"pre {}\remaining\path post", test_str);
-            let actual = redact_home_dir_inner(&input, Some(PathBuf::from(home_dir)));
-            assert_eq!(r"pre ~\remaining\path post", actual);
-        };
-
-        let home_dir = r"C:\Users\user";
-
-        assert_redacts_home_dir(home_dir, r"\Device\HarddiskVolume1\Users\user");
-        assert_redacts_home_dir(home_dir, r"C:\Users\user");
-    }
-
     #[test]
     fn doesnt_redact_not_guid() {
         assert_does_not_redact("23123ab-12ab-89cd-45ef-012345678901");
@@ -672,20 +648,20 @@ mod tests {
     }
 
     fn assert_redacts(input: &str) {
-        let report = ProblemReport::new(vec![]);
+        let report = ProblemReport::new(vec![], RedactionLevel::Standard);
         let actual = report.redact(&format!("pre {input} post"));
         assert_eq!("pre [REDACTED] post", actual);
     }
 
     fn assert_does_not_redact(input: &str) {
-        let report = ProblemReport::new(vec![]);
+        let report = ProblemReport::new(vec![], RedactionLevel::Standard);
         let res = report.redact(input);
         assert_eq!(input, res);
     }
 
     #[test]
     fn parse_metadata() {
-        let report = ProblemReport::new(Vec::new());
+        let report = ProblemReport::new(Vec::new(), RedactionLevel::Standard);
         let mut report_data = Vec::new();
         report
             .write_to(&mut report_data)
@@ -1,5 +1,9 @@
-use clap::Parser;
-use mullvad_problem_report::{collect_report, Error};
+use clap::{Parser, ValueEnum};
+use mullvad_problem_report::{
+    collect_archive, collect_report,
+    redact::{self, RedactionLevel},
+    Error,
+};
 use std::{
     env,
     path::{Path, PathBuf},
@@ -35,6 +39,32 @@ enum Cli {
         /// List of strings to remove from the report
         #[arg(long)]
         redact: Vec<String>,
+        /// How much personal information to remove from the report
+        #[arg(long, value_enum, default_value_t = RedactionLevel::default())]
+        redaction_level: RedactionLevel,
+        /// Include the current state of the running daemon, such as the tunnel state and
+        /// settings
+        #[arg(long)]
+        daemon_state: bool,
+        /// Whether to write a text report that can be sent, or an archive for local use
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+
+    /// Show everything that would be removed from a report, without writing it anywhere
+    Preview {
+        /// Paths to additional log files to be included
+        extra_logs: Vec<PathBuf>,
+        /// List of strings to remove from the report
+        #[arg(long)]
+        redact: Vec<String>,
+        /// How much personal information to remove from the report
+        #[arg(long, value_enum, default_value_t = RedactionLevel::default())]
+        redaction_level: RedactionLevel,
+        /// Include the current state of the running daemon, such as the tunnel state and
+        /// settings
+        #[arg(long)]
+        daemon_state: bool,
     },
 
     /// Send collected problem report
@@ -51,6 +81,15 @@ enum Cli {
     },
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    /// A single text file
+    Text,
+    /// A tar archive with a manifest, the settings and routing and firewall snapshots. It
+    /// cannot be sent with the send subcommand
+    Archive,
+}
+
 fn run() -> Result<(), Error> {
     env_logger::init();
 
@@ -59,13 +98,47 @@ fn run() -> Result<(), Error> {
             output,
             extra_logs,
             redact,
-        } => {
-            collect_report(&extra_logs, &output, redact)?;
+            redaction_level,
+            daemon_state,
+            format,
+        } => match format {
+            ReportFormat::Text => {
+                collect_report(&extra_logs, &output, redact, redaction_level, daemon_state)?;
 
-            println!("Problem report written to {}", output.display());
+                println!("Problem report written to {}", output.display());
+                println!();
+                println!("Send the problem report to support via the send subcommand. See:");
+                println!(" $ {} send --help", env::args().next().unwrap());
+            }
+            ReportFormat::Archive => {
+                collect_archive(&extra_logs, &output, redact, redaction_level, daemon_state)?;
+
+                println!("Problem report archive written to {}", output.display());
+                println!("See manifest.json in the archive for a description of its contents");
+            }
+        },
+        Cli::Preview {
+            extra_logs,
+            redact,
+            redaction_level,
+            daemon_state,
+        } => {
+            let redactions =
+                mullvad_problem_report::preview(&extra_logs, redact, redaction_level, daemon_state);
+            for redaction in &redactions {
+                println!(
+                    "{}:{}: {}: {}",
+                    redaction.source, redaction.line, redaction.kind, redaction.original
+                );
+            }
             println!();
-            println!("Send the problem report to support via the send subcommand. See:");
-            println!(" $ {} send --help", env::args().next().unwrap());
+            println!("Redaction level: {redaction_level}");
+            if redactions.is_empty() {
+                println!("Nothing would be redacted");
+            }
+            for (kind, count) in redact::summarize(&redactions) {
+                println!("{kind}: {count} redacted");
+            }
         }
         Cli::Send {
             report,
@@ -0,0 +1,390 @@
+//! Removal of personal information from problem reports.
+//!
+//! Every piece of text that is removed is recorded as a [`Redaction`], so that the user can
+//! review exactly what is removed, and what is left, before a report leaves the machine.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+/// What kind of information a redaction removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionKind {
+    AccountNumber,
+    HomeDir,
+    NetworkInfo,
+    Guid,
+    Email,
+    WireguardKey,
+    Custom,
+}
+
+impl fmt::Display for RedactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            RedactionKind::AccountNumber => "account number",
+            RedactionKind::HomeDir => "home directory",
+            RedactionKind::NetworkInfo => "network info",
+            RedactionKind::Guid => "GUID",
+            RedactionKind::Email => "email address",
+            RedactionKind::WireguardKey => "WireGuard key",
+            RedactionKind::Custom => "custom string",
+        };
+        f.write_str(kind)
+    }
+}
+
+/// How much information to remove from a report.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(not(target_os = "android"), derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionLevel {
+    /// Only remove account numbers and custom strings. Network information is kept.
+    Minimal,
+    /// Also remove home directories, IP and MAC addresses, and GUIDs.
+    #[default]
+    Standard,
+    /// Also remove email addresses and WireGuard keys.
+    Strict,
+}
+
+impl RedactionLevel {
+    /// Returns whether information of the given kind is removed at this level.
+    pub fn includes(self, kind: RedactionKind) -> bool {
+        match kind {
+            RedactionKind::AccountNumber | RedactionKind::Custom => true,
+            RedactionKind::HomeDir | RedactionKind::NetworkInfo | RedactionKind::Guid => {
+                self != RedactionLevel::Minimal
+            }
+            RedactionKind::Email | RedactionKind::WireguardKey => self == RedactionLevel::Strict,
+        }
+    }
+}
+
+impl fmt::Display for RedactionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            RedactionLevel::Minimal => "minimal",
+            RedactionLevel::Standard => "standard",
+            RedactionLevel::Strict => "strict",
+        };
+        f.write_str(level)
+    }
+}
+
+/// A piece of text that was removed from a report.
+#[derive(Debug, Clone, Serialize)]
+pub struct Redaction {
+    /// The log or file that the text was removed from.
+    pub source: String,
+    /// The line in `source` that the text was found on, starting at 1.
+    pub line: usize,
+    pub kind: RedactionKind,
+    /// The text that was removed.
+    pub original: String,
+}
+
+/// Count redactions per kind.
+pub fn summarize(redactions: &[Redaction]) -> BTreeMap<RedactionKind, usize> {
+    let mut summary = BTreeMap::new();
+    for redaction in redactions {
+        *summary.entry(redaction.kind).or_default() += 1;
+    }
+    summary
+}
+
+/// A pattern to remove. If the regex has a capture group named `start`, that part of the match
+/// is kept.
+struct Pattern {
+    kind: RedactionKind,
+    regex: Regex,
+    replacement: &'static str,
+}
+
+#[derive(Debug)]
+pub struct Redactor {
+    level: RedactionLevel,
+    home_dir: Option<PathBuf>,
+    custom_strings: Vec<String>,
+}
+
+impl Redactor {
+    /// Create a redactor that removes everything included in `level`, as well as all strings in
+    /// `custom_strings`.
+    pub fn new(level: RedactionLevel, custom_strings: Vec<String>) -> Self {
+        Self::with_home_dir(level, custom_strings, dirs::home_dir())
+    }
+
+    fn with_home_dir(
+        level: RedactionLevel,
+        mut custom_strings: Vec<String>,
+        home_dir: Option<PathBuf>,
+    ) -> Self {
+        custom_strings.retain(|redact| !redact.is_empty());
+        Redactor {
+            level,
+            home_dir,
+            custom_strings,
+        }
+    }
+
+    pub fn level(&self) -> RedactionLevel {
+        self.level
+    }
+
+    /// Remove personal information from `input`.
+    pub fn redact(&self, input: &str) -> String {
+        self.redact_recorded("", input, &mut vec![])
+    }
+
+    /// Remove personal information from `input`, and append what was removed to `redactions`.
+    /// `source` names the log or file that `input` was read from.
+    pub fn redact_recorded(
+        &self,
+        source: &str,
+        input: &str,
+        redactions: &mut Vec<Redaction>,
+    ) -> String {
+        let mut output = input.to_owned();
+        for pattern in self.patterns() {
+            if self.level.includes(pattern.kind) {
+                output = apply(&pattern, source, &output, redactions);
+            }
+        }
+        output
+    }
+
+    fn patterns(&self) -> Vec<Pattern> {
+        let mut patterns = vec![Pattern {
+            kind: RedactionKind::AccountNumber,
+            regex: ACCOUNT_NUMBER_REGEX.clone(),
+            replacement: "[REDACTED ACCOUNT NUMBER]",
+        }];
+        if let Some(home_dir) = &self.home_dir {
+            patterns.push(Pattern {
+                kind: RedactionKind::HomeDir,
+                regex: home_dir_regex(home_dir),
+                replacement: "~",
+            });
+        }
+        patterns.extend([
+            Pattern {
+                kind: RedactionKind::NetworkInfo,
+                regex: NETWORK_INFO_REGEX.clone(),
+                replacement: "[REDACTED]",
+            },
+            Pattern {
+                kind: RedactionKind::Guid,
+                regex: GUID_REGEX.clone(),
+                replacement: "[REDACTED]",
+            },
+            Pattern {
+                kind: RedactionKind::Email,
+                regex: EMAIL_REGEX.clone(),
+                replacement: "[REDACTED EMAIL]",
+            },
+            Pattern {
+                kind: RedactionKind::WireguardKey,
+                regex: WIREGUARD_KEY_REGEX.clone(),
+                replacement: "[REDACTED KEY]",
+            },
+        ]);
+        if !self.custom_strings.is_empty() {
+            let alternatives: Vec<_> = self
+                .custom_strings
+                .iter()
+                .map(|redact| regex::escape(redact))
+                .collect();
+            patterns.push(Pattern {
+                kind: RedactionKind::Custom,
+                regex: Regex::new(&alternatives.join("|")).unwrap(),
+                replacement: "[REDACTED]",
+            });
+        }
+        patterns
+    }
+}
+
+/// Replace all matches of `pattern` in `input`, and record each match.
+fn apply(pattern: &Pattern, source: &str, input: &str, redactions: &mut Vec<Redaction>) -> String {
+    // Replacements never contain newlines, so line numbers are the same in the original input.
+    let mut line = 1;
+    let mut counted_up_to = 0;
+    pattern
+        .regex
+        .replace_all(input, |captures: &Captures<'_>| {
+            let whole = captures.get(0).unwrap();
+            let start = captures.name("start").map_or("", |start| start.as_str());
+            let redacted_start = whole.start() + start.len();
+
+            line += input[counted_up_to..redacted_start].matches('\n').count();
+            counted_up_to = redacted_start;
+            redactions.push(Redaction {
+                source: source.to_owned(),
+                line,
+                kind: pattern.kind,
+                original: input[redacted_start..whole.end()].to_owned(),
+            });
+
+            format!("{start}{}", pattern.replacement)
+        })
+        .into_owned()
+}
+
+static ACCOUNT_NUMBER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("\\d{16}").unwrap());
+
+static NETWORK_INFO_REGEX: Lazy<Regex> = Lazy::new(|| {
+    let boundary = "[^0-9a-zA-Z.:]";
+    let combined_pattern = format!(
+        "(?P<start>^|{})(?:{}|{}|{})",
+        boundary,
+        build_ipv4_regex(),
+        build_ipv6_regex(),
+        build_mac_regex(),
+    );
+    Regex::new(&combined_pattern).unwrap()
+});
+
+static GUID_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\{?[A-F0-9]{8}-[A-F0-9]{4}-[A-F0-9]{4}-[A-F0-9]{4}-[A-F0-9]{12}\}?").unwrap()
+});
+
+static EMAIL_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}").unwrap()
+});
+
+/// Matches base64 encoded 32 byte keys.
+static WIREGUARD_KEY_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?P<start>^|[^A-Za-z0-9+/=])[A-Za-z0-9+/]{42}[AEIMQUYcgkosw048]=").unwrap()
+});
+
+fn home_dir_regex(home: &std::path::Path) -> Regex {
+    let home_pattern = regex::escape(&home.to_string_lossy());
+
+    // On Windows, also redact the prefix of any path that contains \Users\{user}.
+    #[cfg(target_os = "windows")]
+    let home_pattern = {
+        let mut home = home.to_path_buf();
+        let prefix = home.components().next();
+        if let Some(prefix @ std::path::Component::Prefix(_)) = prefix.as_ref() {
+            home = home.strip_prefix(prefix).unwrap().to_path_buf();
+        }
+        format!(
+            r"{home_pattern}|[\w\\]+{}",
+            regex::escape(&home.display().to_string())
+        )
+    };
+
+    Regex::new(&home_pattern).unwrap()
+}
+
+fn build_mac_regex() -> String {
+    let octet = "[[:xdigit:]]{2}"; // 0 - ff
+
+    // five pairs of two hexadecimal chars followed by colon or dash
+    // followed by a pair of hexadecimal chars
+    format!("(?:{octet}[:-]){{5}}({octet})")
+}
+
+fn build_ipv4_regex() -> String {
+    // regex adapted from  https://www.regular-expressions.info/ip.html
+
+    let above_250 = "25[0-5]";
+    let above_200 = "2[0-4][0-9]";
+    let above_100 = "1[0-9][0-9]";
+
+    // 100-119 | 120-126 | 128-129 | 130 - 199
+    let above_100_not_127 = "1(?:[01][0-9]|2[0-6]|2[89]|[3-9][0-9])";
+
+    let above_0 = "0?[0-9][0-9]?";
+
+    // matches 0-255, except 127
+    let first_octet = format!("(?:{above_250}|{above_200}|{above_100_not_127}|{above_0})");
+
+    // matches 0-255
+    let ip_octet = format!("(?:{above_250}|{above_200}|{above_100}|{above_0})");
+
+    format!("(?:{first_octet}\\.{ip_octet}\\.{ip_octet}\\.{ip_octet})")
+}
+
+fn build_ipv6_regex() -> String {
+    // Regular expression obtained from:
+    // https://stackoverflow.com/a/17871737
+    let ipv4_segment = "(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])";
+    let ipv4_address = format!("({ipv4_segment}\\.){{3,3}}{ipv4_segment}");
+
+    let ipv6_segment = "[0-9a-fA-F]{1,4}";
+
+    let long = format!("({ipv6_segment}:){{7,7}}{ipv6_segment}");
+    let compressed_1 = format!("({ipv6_segment}:){{1,7}}:");
+    let compressed_2 = format!("({ipv6_segment}:){{1,6}}:{ipv6_segment}");
+    let compressed_3 = format!("({ipv6_segment}:){{1,5}}(:{ipv6_segment}){{1,2}}");
+    let compressed_4 = format!("({ipv6_segment}:){{1,4}}(:{ipv6_segment}){{1,3}}");
+    let compressed_5 = format!("({ipv6_segment}:){{1,3}}(:{ipv6_segment}){{1,4}}");
+    let compressed_6 = format!("({ipv6_segment}:){{1,2}}(:{ipv6_segment}){{1,5}}");
+    let compressed_7 = format!("{ipv6_segment}:((:{ipv6_segment}){{1,6}})");
+    let compressed_8 = format!(":((:{ipv6_segment}){{1,7}}|:)");
+    let link_local = "[Ff][Ee]80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}";
+    let ipv4_mapped = format!("::([fF]{{4}}(:0{{1,4}}){{0,1}}:){{0,1}}{ipv4_address}");
+    let ipv4_embedded = format!("({ipv6_segment}:){{1,4}}:{ipv4_address}");
+
+    format!(
+        "{long}|{link_local}|{ipv4_mapped}|{ipv4_embedded}|{compressed_8}|{compressed_7}|{compressed_6}|{compressed_5}|{compressed_4}|{compressed_3}|{compressed_2}|{compressed_1}",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(windows)]
+    fn redacts_home_dir() {
+        let assert_redacts_home_dir = |home_dir, test_str| {
+            let redactor = Redactor::with_home_dir(
+                RedactionLevel::Standard,
+                vec![],
+                Some(PathBuf::from(home_dir)),
+            );
+            let input = format!(r"pre {}\remaining\path post", test_str);
+            assert_eq!(r"pre ~\remaining\path post", redactor.redact(&input));
+        };
+
+        let home_dir = r"C:\Users\user";
+
+        assert_redacts_home_dir(home_dir, r"\Device\HarddiskVolume1\Users\user");
+        assert_redacts_home_dir(home_dir, r"C:\Users\user");
+    }
+
+    #[test]
+    fn test_redaction_levels_and_records() {
+        let input = "account 1234123412341234\nrelay 185.213.154.68 key \
+            BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4= user@example.com";
+
+        let minimal = Redactor::with_home_dir(RedactionLevel::Minimal, vec![], None);
+        let mut redactions = vec![];
+        let output = minimal.redact_recorded("test.log", input, &mut redactions);
+        assert!(output.contains("185.213.154.68"));
+        assert_eq!(redactions.len(), 1);
+        assert_eq!(redactions[0].kind, RedactionKind::AccountNumber);
+        assert_eq!(redactions[0].line, 1);
+
+        let strict = Redactor::with_home_dir(RedactionLevel::Strict, vec![], None);
+        let mut redactions = vec![];
+        let output = strict.redact_recorded("test.log", input, &mut redactions);
+        assert_eq!(
+            output,
+            "account [REDACTED ACCOUNT NUMBER]\nrelay [REDACTED] key [REDACTED KEY] \
+                [REDACTED EMAIL]"
+        );
+        let network_info = redactions
+            .iter()
+            .find(|redaction| redaction.kind == RedactionKind::NetworkInfo)
+            .unwrap();
+        assert_eq!(network_info.line, 2);
+        assert_eq!(network_info.original, "185.213.154.68");
+        assert_eq!(summarize(&redactions).len(), 4);
+    }
+}
@@ -12,6 +12,7 @@ use std::net::{IpAddr, SocketAddr};
 use ipnetwork::IpNetwork;
 use mullvad_types::{
     constraints::Constraint,
+    custom_relay::CustomRelay,
     endpoint::MullvadWireguardEndpoint,
     relay_constraints::TransportPort,
     relay_list::{
@@ -156,6 +157,27 @@ fn wireguard_multihop_endpoint(
     })
 }
 
+/// Point the peer that connects to a [custom relay][`CustomRelay`] at the server configured by
+/// the user. Mullvad port ranges and gateways do not apply to custom relays.
+///
+/// `is_exit` should be true if the custom relay is the exit of a single-hop connection, and false
+/// if it is the entry of a multihop circuit.
+pub fn apply_custom_relay(
+    endpoint: &mut MullvadWireguardEndpoint,
+    custom_relay: &CustomRelay,
+    is_exit: bool,
+) {
+    endpoint.peer.endpoint = custom_relay.endpoint;
+    endpoint.peer.psk = custom_relay.psk.clone();
+    if is_exit {
+        endpoint.peer.allowed_ips = custom_relay.allowed_ips.clone();
+        endpoint.ipv4_gateway = custom_relay.ipv4_gateway;
+        if let Some(ipv6_gateway) = custom_relay.ipv6_gateway {
+            endpoint.ipv6_gateway = ipv6_gateway;
+        }
+    }
+}
+
 /// Get the correct IP address for the given relay.
 fn get_address_for_wireguard_relay(
     query: &WireguardRelayQuery,
@@ -4,6 +4,7 @@ use std::collections::HashSet;
 use mullvad_types::{
     constraints::{Constraint, Match},
     custom_list::CustomListsSettings,
+    custom_relay::is_custom_relay,
     relay_constraints::{
         GeographicLocationConstraint, InternalBridgeConstraints, LocationConstraint, Ownership,
         Providers,
@@ -29,10 +30,12 @@ pub fn filter_matching_relay_list<'a, R: Iterator<Item = &'a Relay> + Clone>(
             .filter(|relay| filter_on_active(relay))
             // Filter by location
             .filter(|relay| filter_on_location(&locations, relay))
+            // Filter out custom relays that were not explicitly selected
+            .filter(|relay| filter_custom_relay(&locations, relay))
             // Filter by ownership
-            .filter(|relay| filter_on_ownership(&query.ownership, relay))
+            .filter(|relay| is_custom_relay(relay) || filter_on_ownership(&query.ownership, relay))
             // Filter by providers
-            .filter(|relay| filter_on_providers(&query.providers, relay))
+            .filter(|relay| is_custom_relay(relay) || filter_on_providers(&query.providers, relay))
             // Filter by DAITA support
             .filter(|relay| filter_on_daita(&query.wireguard_constraints.daita, relay));
 
@@ -100,6 +103,16 @@ pub fn filter_on_location(
     filter.matches(relay)
 }
 
+/// Returns whether `relay` may be selected given the location constraint `filter`. Custom relays
+/// are only selected if the location refers to them, i.e. never when the location is `any`.
+/// Ownership and provider constraints do not apply to them.
+pub fn filter_custom_relay(
+    filter: &Constraint<ResolvedLocationConstraint<'_>>,
+    relay: &Relay,
+) -> bool {
+    !is_custom_relay(relay) || filter.is_only()
+}
+
 /// Returns whether `relay` satisfy the ownership constraint posed by `filter`.
 pub fn filter_on_ownership(filter: &Constraint<Ownership>, relay: &Relay) -> bool {
     filter.matches(relay)
@@ -20,6 +20,7 @@ use std::{
 use mullvad_types::{
     constraints::Constraint,
     custom_list::CustomListsSettings,
+    custom_relay::{is_custom_relay, CustomRelay},
     endpoint::MullvadWireguardEndpoint,
     location::{Coordinates, Location},
     relay_constraints::{
@@ -41,7 +42,7 @@ use talpid_types::{
 use crate::error::{EndpointErrorDetails, Error};
 
 use self::{
-    detailer::{openvpn_endpoint, wireguard_endpoint},
+    detailer::{apply_custom_relay, openvpn_endpoint, wireguard_endpoint},
     matcher::{filter_matching_bridges, filter_matching_relay_list},
     parsed_relays::ParsedRelays,
     query::{BridgeQuery, OpenVpnRelayQuery, RelayQuery, WireguardRelayQuery},
@@ -103,6 +104,7 @@ pub struct SelectorConfig {
     pub additional_constraints: AdditionalRelayConstraints,
     pub custom_lists: CustomListsSettings,
     pub relay_overrides: Vec<RelayOverride>,
+    pub custom_relays: Vec<CustomRelay>,
     // Wireguard specific data
     pub obfuscation_settings: ObfuscationSettings,
     // OpenVPN specific data
@@ -298,6 +300,7 @@ impl Default for SelectorConfig {
             bridge_state: default_settings.bridge_state,
             custom_lists: default_settings.custom_lists,
             relay_overrides: default_settings.relay_overrides,
+            custom_relays: default_settings.custom_relays,
         }
     }
 }
@@ -400,15 +403,19 @@ impl RelaySelector {
         cache_path: impl AsRef<Path>,
     ) -> Self {
         const DATE_TIME_FORMAT_STR: &str = "%Y-%m-%d %H:%M:%S%.3f";
-        let unsynchronized_parsed_relays =
-            ParsedRelays::from_file(&cache_path, &resource_path, &config.relay_overrides)
-                .unwrap_or_else(|error| {
-                    log::error!(
-                        "{}",
-                        error.display_chain_with_msg("Unable to load cached and bundled relays")
-                    );
-                    ParsedRelays::empty()
-                });
+        let unsynchronized_parsed_relays = ParsedRelays::from_file(
+            &cache_path,
+            &resource_path,
+            &config.relay_overrides,
+            &config.custom_relays,
+        )
+        .unwrap_or_else(|error| {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to load cached and bundled relays")
+            );
+            ParsedRelays::empty()
+        });
         log::info!(
             "Initialized with {} cached relays from {}",
             unsynchronized_parsed_relays.relays().count(),
@@ -428,13 +435,14 @@ impl RelaySelector {
                 relay_list,
                 SystemTime::now(),
                 &config.relay_overrides,
+                &config.custom_relays,
             ))),
             config: Arc::new(Mutex::new(config)),
         }
     }
 
     pub fn set_config(&mut self, config: SelectorConfig) {
-        self.set_overrides(&config.relay_overrides);
+        self.set_overrides(&config.relay_overrides, &config.custom_relays);
         let mut config_mutex = self.config.lock().unwrap();
         *config_mutex = config;
     }
@@ -444,9 +452,9 @@ impl RelaySelector {
         parsed_relays.update(relays);
     }
 
-    fn set_overrides(&mut self, relay_overrides: &[RelayOverride]) {
+    fn set_overrides(&mut self, relay_overrides: &[RelayOverride], custom_relays: &[CustomRelay]) {
         let mut parsed_relays = self.parsed_relays.lock().unwrap();
-        parsed_relays.set_overrides(relay_overrides);
+        parsed_relays.set_overrides(relay_overrides, custom_relays);
     }
 
     /// Returns all countries and cities. The cities in the object returned does not have any
@@ -460,6 +468,15 @@ impl RelaySelector {
         self.parsed_relays.lock().unwrap().etag()
     }
 
+    /// Returns the [custom relay][`CustomRelay`] with the given hostname, if any.
+    pub fn custom_relay(&self, hostname: &str) -> Option<CustomRelay> {
+        self.parsed_relays
+            .lock()
+            .unwrap()
+            .custom_relay(hostname)
+            .cloned()
+    }
+
     pub fn last_updated(&self) -> SystemTime {
         self.parsed_relays.lock().unwrap().last_updated()
     }
@@ -735,8 +752,13 @@ impl RelaySelector {
         let mut exit_relay_query = query.clone();
         // DAITA should only be enabled for the entry relay
         exit_relay_query.wireguard_constraints.daita = Constraint::Only(false);
-        let exit_candidates =
-            filter_matching_relay_list(&exit_relay_query, parsed_relays.relays(), custom_lists);
+        // Custom relays are not Mullvad relays, so traffic cannot be forwarded to them from a
+        // Mullvad entry relay. They can only be used as the entry.
+        let exit_candidates: Vec<_> =
+            filter_matching_relay_list(&exit_relay_query, parsed_relays.relays(), custom_lists)
+                .into_iter()
+                .filter(|relay| !is_custom_relay(relay))
+                .collect();
         let entry_candidates =
             filter_matching_relay_list(&entry_relay_query, parsed_relays.relays(), custom_lists);
 
@@ -773,7 +795,7 @@ impl RelaySelector {
         parsed_relays: &ParsedRelays,
         relay: &WireguardConfig,
     ) -> Result<MullvadWireguardEndpoint, Error> {
-        wireguard_endpoint(
+        let mut endpoint = wireguard_endpoint(
             &query.wireguard_constraints,
             &parsed_relays.parsed_list().wireguard,
             relay,
@@ -781,7 +803,17 @@ impl RelaySelector {
         .map_err(|internal| Error::NoEndpoint {
             internal,
             relay: EndpointErrorDetails::from_wireguard(relay.clone()),
-        })
+        })?;
+
+        let (entry, is_exit) = match relay {
+            WireguardConfig::Singlehop { exit } => (exit, true),
+            WireguardConfig::Multihop { entry, .. } => (entry, false),
+        };
+        if let Some(custom_relay) = parsed_relays.custom_relay(&entry.hostname) {
+            apply_custom_relay(&mut endpoint, custom_relay, is_exit);
+        }
+
+        Ok(endpoint)
     }
 
     fn get_wireguard_obfuscator(
@@ -15,6 +15,7 @@ use std::{
 };
 
 use mullvad_types::{
+    custom_relay::{custom_relay_country, CustomRelay},
     location::Location,
     relay_constraints::RelayOverride,
     relay_list::{Relay, RelayList},
@@ -30,6 +31,8 @@ pub(crate) struct ParsedRelays {
     /// The original list of relays, as returned by the Mullvad relays API.
     original_list: RelayList,
     overrides: Vec<RelayOverride>,
+    /// Self-hosted servers that are listed alongside the relays in `parsed_list`.
+    custom_relays: Vec<CustomRelay>,
 }
 
 impl ParsedRelays {
@@ -41,7 +44,12 @@ impl ParsedRelays {
     /// Replace `self` with a new [`ParsedRelays`] based on [new_relays][`ParsedRelays`],
     /// bumping `self.last_updated` to the current system time.
     pub fn update(&mut self, new_relays: RelayList) {
-        *self = Self::from_relay_list(new_relays, SystemTime::now(), &self.overrides);
+        *self = Self::from_relay_list(
+            new_relays,
+            SystemTime::now(),
+            &self.overrides,
+            &self.custom_relays,
+        );
 
         log::info!(
             "Updated relay inventory has {} relays",
@@ -65,16 +73,30 @@ impl ParsedRelays {
         &self.original_list
     }
 
-    /// The current list of relays, after applying [overrides][`RelayOverride`].
+    /// The current list of relays, after applying [overrides][`RelayOverride`] and adding
+    /// [custom relays][`CustomRelay`].
     pub const fn parsed_list(&self) -> &RelayList {
         &self.parsed_list
     }
 
-    /// Replace the previous set of [overrides][`RelayOverride`] with `new_overrides`.
-    /// This will update `self.parsed_list` as a side-effect.
-    pub(crate) fn set_overrides(&mut self, new_overrides: &[RelayOverride]) {
-        self.parsed_list = Self::parse_relay_list(&self.original_list, new_overrides);
+    /// Return the [custom relay][`CustomRelay`] with the given hostname, if any.
+    pub fn custom_relay(&self, hostname: &str) -> Option<&CustomRelay> {
+        self.custom_relays
+            .iter()
+            .find(|custom_relay| custom_relay.name == hostname)
+    }
+
+    /// Replace the previous set of [overrides][`RelayOverride`] and
+    /// [custom relays][`CustomRelay`]. This will update `self.parsed_list` as a side-effect.
+    pub(crate) fn set_overrides(
+        &mut self,
+        new_overrides: &[RelayOverride],
+        new_custom_relays: &[CustomRelay],
+    ) {
+        self.parsed_list =
+            Self::parse_relay_list(&self.original_list, new_overrides, new_custom_relays);
         self.overrides = new_overrides.to_vec();
+        self.custom_relays = new_custom_relays.to_vec();
     }
 
     pub(crate) fn empty() -> Self {
@@ -83,6 +105,7 @@ impl ParsedRelays {
             parsed_list: RelayList::empty(),
             original_list: RelayList::empty(),
             overrides: vec![],
+            custom_relays: vec![],
         }
     }
 
@@ -91,11 +114,12 @@ impl ParsedRelays {
         cache_path: impl AsRef<Path>,
         resource_path: impl AsRef<Path>,
         overrides: &[RelayOverride],
+        custom_relays: &[CustomRelay],
     ) -> Result<Self, Error> {
         // prefer the resource path's relay list if the cached one doesn't exist or was modified
         // before the resource one was created.
-        let cached_relays = Self::from_file_inner(cache_path, overrides);
-        let bundled_relays = match Self::from_file_inner(resource_path, overrides) {
+        let cached_relays = Self::from_file_inner(cache_path, overrides, custom_relays);
+        let bundled_relays = match Self::from_file_inner(resource_path, overrides, custom_relays) {
             Ok(bundled_relays) => bundled_relays,
             Err(e) => {
                 log::error!("Failed to load bundled relays: {}", e);
@@ -114,13 +138,22 @@ impl ParsedRelays {
         }
     }
 
-    fn from_file_inner(path: impl AsRef<Path>, overrides: &[RelayOverride]) -> Result<Self, Error> {
+    fn from_file_inner(
+        path: impl AsRef<Path>,
+        overrides: &[RelayOverride],
+        custom_relays: &[CustomRelay],
+    ) -> Result<Self, Error> {
         log::debug!("Reading relays from {}", path.as_ref().display());
         let (last_modified, file) =
             Self::open_file(path.as_ref()).map_err(Error::OpenRelayCache)?;
         let relay_list = serde_json::from_reader(BufReader::new(file)).map_err(Error::Serialize)?;
 
-        Ok(Self::from_relay_list(relay_list, last_modified, overrides))
+        Ok(Self::from_relay_list(
+            relay_list,
+            last_modified,
+            overrides,
+            custom_relays,
+        ))
     }
 
     fn open_file(path: &Path) -> io::Result<(SystemTime, std::fs::File)> {
@@ -129,25 +162,32 @@ impl ParsedRelays {
         Ok((last_modified, file))
     }
 
-    /// Create a new [`ParsedRelays`] from [relay_list][`RelayList`] and
-    /// [overrides][`RelayOverride`]. This will apply `overrides` to `relay_list` and store the
-    /// result in `self.parsed_list`.
+    /// Create a new [`ParsedRelays`] from [relay_list][`RelayList`],
+    /// [overrides][`RelayOverride`] and [custom relays][`CustomRelay`]. This will apply
+    /// `overrides` to `relay_list`, add `custom_relays` and store the result in
+    /// `self.parsed_list`.
     pub(crate) fn from_relay_list(
         relay_list: RelayList,
         last_updated: SystemTime,
         overrides: &[RelayOverride],
+        custom_relays: &[CustomRelay],
     ) -> Self {
         ParsedRelays {
             last_updated,
-            parsed_list: Self::parse_relay_list(&relay_list, overrides),
+            parsed_list: Self::parse_relay_list(&relay_list, overrides, custom_relays),
             original_list: relay_list,
             overrides: overrides.to_vec(),
+            custom_relays: custom_relays.to_vec(),
         }
     }
 
-    /// Apply [overrides][`RelayOverride`] to [relay_list][`RelayList`], yielding an updated relay
-    /// list.
-    fn parse_relay_list(relay_list: &RelayList, overrides: &[RelayOverride]) -> RelayList {
+    /// Apply [overrides][`RelayOverride`] to [relay_list][`RelayList`] and add
+    /// [custom relays][`CustomRelay`], yielding an updated relay list.
+    fn parse_relay_list(
+        relay_list: &RelayList,
+        overrides: &[RelayOverride],
+        custom_relays: &[CustomRelay],
+    ) -> RelayList {
         let mut remaining_overrides = HashMap::new();
         for relay_override in overrides {
             remaining_overrides.insert(
@@ -185,6 +225,11 @@ impl ParsedRelays {
             }
         }
 
+        // Custom relays already carry their location, and cannot be overridden
+        parsed_list
+            .countries
+            .extend(custom_relay_country(custom_relays));
+
         parsed_list
     }
 }
@@ -17,6 +17,7 @@ use mullvad_relay_selector::{
 };
 use mullvad_types::{
     constraints::Constraint,
+    custom_relay::{CustomRelay, CUSTOM_RELAY_CITY_CODE, CUSTOM_RELAY_COUNTRY_CODE},
     endpoint::MullvadEndpoint,
     relay_constraints::{
         BridgeConstraints, BridgeState, GeographicLocationConstraint, Ownership, Providers,
@@ -49,6 +50,7 @@ static RELAYS: Lazy<RelayList> = Lazy::new(|| RelayList {
                     owned: true,
                     provider: "provider0".to_string(),
                     weight: 1,
+                    custom: false,
                     endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
                         public_key: PublicKey::from_base64(
                             "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -67,6 +69,7 @@ static RELAYS: Lazy<RelayList> = Lazy::new(|| RelayList {
                     owned: false,
                     provider: "provider1".to_string(),
                     weight: 1,
+                    custom: false,
                     endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
                         public_key: PublicKey::from_base64(
                             "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -85,6 +88,7 @@ static RELAYS: Lazy<RelayList> = Lazy::new(|| RelayList {
                     owned: true,
                     provider: "provider2".to_string(),
                     weight: 1,
+                    custom: false,
                     endpoint_data: RelayEndpointData::Openvpn,
                     location: None,
                 },
@@ -97,6 +101,7 @@ static RELAYS: Lazy<RelayList> = Lazy::new(|| RelayList {
                     owned: true,
                     provider: "provider0".to_string(),
                     weight: 1,
+                    custom: false,
                     endpoint_data: RelayEndpointData::Openvpn,
                     location: None,
                 },
@@ -109,6 +114,7 @@ static RELAYS: Lazy<RelayList> = Lazy::new(|| RelayList {
                     owned: true,
                     provider: "provider3".to_string(),
                     weight: 1,
+                    custom: false,
                     endpoint_data: RelayEndpointData::Bridge,
                     location: None,
                 },
@@ -431,6 +437,7 @@ fn test_wireguard_entry() {
                         owned: true,
                         provider: "provider0".to_string(),
                         weight: 1,
+                        custom: false,
                         endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
                             public_key: PublicKey::from_base64(
                                 "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -449,6 +456,7 @@ fn test_wireguard_entry() {
                         owned: false,
                         provider: "provider1".to_string(),
                         weight: 1,
+                        custom: false,
                         endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
                             public_key: PublicKey::from_base64(
                                 "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -949,6 +957,7 @@ fn test_include_in_country() {
                         owned: true,
                         provider: "31173".to_string(),
                         weight: 1,
+                        custom: false,
                         endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
                             public_key: PublicKey::from_base64(
                                 "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -967,6 +976,7 @@ fn test_include_in_country() {
                         owned: false,
                         provider: "31173".to_string(),
                         weight: 1,
+                        custom: false,
                         endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
                             public_key: PublicKey::from_base64(
                                 "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -1167,6 +1177,7 @@ fn test_daita() {
                         owned: true,
                         provider: "31173".to_string(),
                         weight: 1,
+                        custom: false,
                         endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
                             public_key: PublicKey::from_base64(
                                 "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -1185,6 +1196,7 @@ fn test_daita() {
                         owned: false,
                         provider: "31173".to_string(),
                         weight: 1,
+                        custom: false,
                         endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
                             public_key: PublicKey::from_base64(
                                 "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -1321,3 +1333,51 @@ fn valid_user_setting_should_yield_relay() {
         }
     }
 }
+
+/// Custom relays should only be selected when the location refers to them, and the peer should
+/// point at the configured server rather than at a Mullvad port.
+#[test]
+fn test_custom_relay_selection() {
+    let custom_relay = CustomRelay {
+        name: "home-server".to_string(),
+        endpoint: "203.0.113.1:51000".parse().unwrap(),
+        public_key: PublicKey::from_base64("BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=").unwrap(),
+        allowed_ips: vec!["10.0.0.0/8".parse().unwrap()],
+        addresses: vec!["10.1.0.2".parse().unwrap()],
+        ipv4_gateway: "10.1.0.1".parse().unwrap(),
+        ipv6_gateway: None,
+        psk: None,
+        allow_without_quantum_resistance: false,
+    };
+    let config = SelectorConfig {
+        custom_relays: vec![custom_relay.clone()],
+        ..SelectorConfig::default()
+    };
+    let relay_selector = RelaySelector::from_list(config, RELAYS.clone());
+
+    let query = RelayQueryBuilder::new().wireguard().build();
+    for _ in 0..100 {
+        let relay = unwrap_relay(relay_selector.get_relay_by_query(query.clone()).unwrap());
+        assert_ne!(relay.hostname, custom_relay.name);
+    }
+
+    let location = GeographicLocationConstraint::hostname(
+        CUSTOM_RELAY_COUNTRY_CODE,
+        CUSTOM_RELAY_CITY_CODE,
+        &custom_relay.name,
+    );
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .location(location)
+        .build();
+    match relay_selector.get_relay_by_query(query).unwrap() {
+        GetRelay::Wireguard { endpoint, .. } => {
+            assert_eq!(endpoint.peer.endpoint, custom_relay.endpoint);
+            assert_eq!(endpoint.peer.allowed_ips, custom_relay.allowed_ips);
+            assert_eq!(endpoint.ipv4_gateway, custom_relay.ipv4_gateway);
+        }
+        wrong_relay => panic!(
+            "Relay selector should have picked a WireGuard relay, instead chose {wrong_relay:?}"
+        ),
+    }
+}
@@ -29,6 +29,25 @@ impl AccountData {
     }
 }
 
+/// An account that has been given a name so that it can be switched to quickly.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SavedAccount {
+    pub name: String,
+    pub account_token: AccountToken,
+}
+
+/// Hours before the account expires at which clients are notified by default.
+pub const DEFAULT_EXPIRY_NOTIFICATION_HOURS: [u32; 3] = [7 * 24, 3 * 24, 24];
+
+/// Warning that the account is about to run out of time, or already has.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ExpiryNotification {
+    pub expiry: DateTime<Utc>,
+    /// The notification threshold that has been passed, in hours before `expiry`. `None` if the
+    /// account has run out of time.
+    pub threshold_hours: Option<u32>,
+}
+
 /// Data structure that's returned from successful invocation of the mullvad API's
 /// `/v1/submit-voucher` RPC.
 #[derive(Deserialize, Serialize, Debug)]
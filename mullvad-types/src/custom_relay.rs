@@ -0,0 +1,145 @@
+//! Self-hosted WireGuard servers that can be selected in the same way as Mullvad relays.
+//!
+//! Custom relays are listed under a country and city of their own, so they can be selected by
+//! location or hostname, added to custom lists and used as a multihop entry. They are never
+//! picked unless the location constraint explicitly refers to them.
+
+use crate::{
+    location::Location,
+    relay_list::{
+        Relay, RelayEndpointData, RelayListCity, RelayListCountry, WireguardRelayEndpointData,
+    },
+};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use talpid_types::net::wireguard::{PresharedKey, PublicKey};
+
+/// Country code that custom relays are listed under. `xx` is a user-assigned code in
+/// ISO 3166, so it never clashes with a Mullvad location.
+pub const CUSTOM_RELAY_COUNTRY_CODE: &str = "xx";
+const CUSTOM_RELAY_COUNTRY_NAME: &str = "Custom relays";
+/// City code that custom relays are listed under.
+pub const CUSTOM_RELAY_CITY_CODE: &str = "cst";
+const CUSTOM_RELAY_CITY_NAME: &str = "Self-hosted";
+/// Provider reported for custom relays.
+pub const CUSTOM_RELAY_PROVIDER: &str = "custom";
+
+const CUSTOM_RELAY_NAME_MAX_SIZE: usize = 30;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Custom relay name must be between 1 and {CUSTOM_RELAY_NAME_MAX_SIZE} characters")]
+    InvalidName,
+    #[error("A relay with this name already exists")]
+    DuplicateName,
+    #[error("Custom relay not found")]
+    NotFound,
+    #[error("Custom relay must have at least one tunnel address")]
+    NoAddresses,
+    #[error("Custom relay must have at least one allowed IP")]
+    NoAllowedIps,
+}
+
+/// A WireGuard server that is owned and operated by the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomRelay {
+    /// Unique name of the relay. This is used as its hostname.
+    pub name: String,
+    /// Address and port that the server listens on.
+    pub endpoint: SocketAddr,
+    /// Public key of the server.
+    pub public_key: PublicKey,
+    /// Networks to route through the server when it is used as the exit. When it is used as a
+    /// multihop entry, only the exit relay is routed through it.
+    pub allowed_ips: Vec<IpNetwork>,
+    /// Tunnel addresses that the server has assigned to this device.
+    pub addresses: Vec<IpAddr>,
+    /// In-tunnel IPv4 address of the server. Used for DNS and connectivity checks.
+    pub ipv4_gateway: Ipv4Addr,
+    /// In-tunnel IPv6 address of the server, if any.
+    #[serde(default)]
+    pub ipv6_gateway: Option<Ipv6Addr>,
+    /// Preshared key shared with the server, if any.
+    #[serde(default)]
+    pub psk: Option<PresharedKey>,
+    /// Connect through this server without a quantum-resistant tunnel when one is enabled.
+    /// Custom relays cannot negotiate one, so connecting fails unless this is set.
+    #[serde(default)]
+    pub allow_without_quantum_resistance: bool,
+}
+
+impl CustomRelay {
+    /// Check that the relay is complete enough to connect to.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.name.is_empty() || self.name.chars().count() > CUSTOM_RELAY_NAME_MAX_SIZE {
+            return Err(Error::InvalidName);
+        }
+        if self.addresses.is_empty() {
+            return Err(Error::NoAddresses);
+        }
+        if self.allowed_ips.is_empty() {
+            return Err(Error::NoAllowedIps);
+        }
+        Ok(())
+    }
+
+    /// Return the location that custom relays are listed under.
+    pub fn location() -> Location {
+        Location {
+            country: CUSTOM_RELAY_COUNTRY_NAME.to_owned(),
+            country_code: CUSTOM_RELAY_COUNTRY_CODE.to_owned(),
+            city: CUSTOM_RELAY_CITY_NAME.to_owned(),
+            city_code: CUSTOM_RELAY_CITY_CODE.to_owned(),
+            latitude: 0.0,
+            longitude: 0.0,
+        }
+    }
+
+    /// Return a WireGuard [`Relay`] that represents this server in the relay list.
+    pub fn to_relay(&self) -> Relay {
+        let (ipv4_addr_in, ipv6_addr_in) = match self.endpoint.ip() {
+            IpAddr::V4(addr) => (addr, None),
+            IpAddr::V6(addr) => (Ipv4Addr::UNSPECIFIED, Some(addr)),
+        };
+        Relay {
+            hostname: self.name.clone(),
+            ipv4_addr_in,
+            ipv6_addr_in,
+            include_in_country: true,
+            active: true,
+            owned: false,
+            provider: CUSTOM_RELAY_PROVIDER.to_owned(),
+            weight: 1,
+            custom: true,
+            endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
+                public_key: self.public_key.clone(),
+                daita: false,
+            }),
+            location: Some(Self::location()),
+        }
+    }
+}
+
+/// Returns whether `relay` is a [`CustomRelay`] rather than a Mullvad relay.
+pub fn is_custom_relay(relay: &Relay) -> bool {
+    relay.custom
+}
+
+/// Return the relay list country that contains `custom_relays`, or `None` if there are none.
+pub fn custom_relay_country(custom_relays: &[CustomRelay]) -> Option<RelayListCountry> {
+    if custom_relays.is_empty() {
+        return None;
+    }
+    Some(RelayListCountry {
+        name: CUSTOM_RELAY_COUNTRY_NAME.to_owned(),
+        code: CUSTOM_RELAY_COUNTRY_CODE.to_owned(),
+        cities: vec![RelayListCity {
+            name: CUSTOM_RELAY_CITY_NAME.to_owned(),
+            code: CUSTOM_RELAY_CITY_CODE.to_owned(),
+            latitude: 0.0,
+            longitude: 0.0,
+            relays: custom_relays.iter().map(CustomRelay::to_relay).collect(),
+        }],
+    })
+}
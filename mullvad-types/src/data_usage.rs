@@ -0,0 +1,123 @@
+//! Accounting of the data that is transferred through the tunnel, and an optional monthly cap.
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, ops::AddAssign};
+
+/// Number of bytes transferred through the tunnel.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteCount {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl ByteCount {
+    /// Return the number of bytes transferred in either direction.
+    pub fn total(&self) -> u64 {
+        self.rx_bytes.saturating_add(self.tx_bytes)
+    }
+}
+
+impl AddAssign for ByteCount {
+    fn add_assign(&mut self, other: Self) {
+        self.rx_bytes = self.rx_bytes.saturating_add(other.rx_bytes);
+        self.tx_bytes = self.tx_bytes.saturating_add(other.tx_bytes);
+    }
+}
+
+/// Data transferred through the tunnel, per day in local time and relay hostname.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataUsage {
+    pub days: BTreeMap<NaiveDate, BTreeMap<String, ByteCount>>,
+}
+
+impl DataUsage {
+    /// Add `bytes` transferred through `relay` on `date`.
+    pub fn add(&mut self, date: NaiveDate, relay: &str, bytes: ByteCount) {
+        let relays = self.days.entry(date).or_default();
+        match relays.get_mut(relay) {
+            Some(count) => *count += bytes,
+            None => {
+                relays.insert(relay.to_owned(), bytes);
+            }
+        }
+    }
+
+    /// Return the total usage of the month that `date` is in.
+    pub fn month_total(&self, date: NaiveDate) -> ByteCount {
+        let mut total = ByteCount::default();
+        for (_, relays) in self
+            .days
+            .range(first_day_of_month(date)..)
+            .take_while(|(day, _)| day.year() == date.year() && day.month() == date.month())
+        {
+            for count in relays.values() {
+                total += *count;
+            }
+        }
+        total
+    }
+
+    /// Forget the usage of all days before `oldest`.
+    pub fn prune(&mut self, oldest: NaiveDate) {
+        self.days = self.days.split_off(&oldest);
+    }
+}
+
+/// Return the first day of the month that `date` is in.
+pub fn first_day_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("every month has a first day")
+}
+
+/// A soft limit on the data transferred through the tunnel each calendar month.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataCap {
+    /// Bytes that may be transferred each month, in both directions combined. There is no limit
+    /// if this is `None`.
+    pub monthly_quota: Option<u64>,
+    /// Whether to disconnect the tunnel once the quota is reached. Clients are notified either
+    /// way. The tunnel may be connected again afterwards.
+    pub disconnect: bool,
+}
+
+/// Sent when the monthly quota of the [`DataCap`] has been reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataCapReached {
+    /// Bytes transferred so far this month.
+    pub usage: u64,
+    pub quota: u64,
+    /// Whether the tunnel was disconnected because of it.
+    pub disconnected: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_month_total() {
+        let bytes = ByteCount {
+            rx_bytes: 100,
+            tx_bytes: 10,
+        };
+        let mut usage = DataUsage::default();
+        usage.add(date(2024, 5, 31), "se-got-wg-001", bytes);
+        usage.add(date(2024, 6, 1), "se-got-wg-001", bytes);
+        usage.add(date(2024, 6, 1), "se-got-wg-001", bytes);
+        usage.add(date(2024, 6, 30), "de-fra-wg-001", bytes);
+        usage.add(date(2024, 7, 1), "de-fra-wg-001", bytes);
+
+        assert_eq!(usage.month_total(date(2024, 6, 15)).total(), 330);
+        assert_eq!(usage.days[&date(2024, 6, 1)]["se-got-wg-001"].rx_bytes, 200);
+
+        usage.prune(date(2024, 6, 30));
+        assert_eq!(usage.month_total(date(2024, 6, 15)).total(), 110);
+        assert_eq!(usage.days.len(), 2);
+    }
+}
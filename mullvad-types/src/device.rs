@@ -17,6 +17,19 @@ pub struct Device {
     pub pubkey: PublicKey,
     pub hijack_dns: bool,
     pub created: DateTime<Utc>,
+    /// When the current WireGuard key was created. Only known for devices used on this computer.
+    #[serde(default)]
+    pub pubkey_created: Option<DateTime<Utc>>,
+    /// The last connection made by the device. Only known for devices used on this computer.
+    #[serde(default)]
+    pub last_connection: Option<LastConnection>,
+}
+
+/// A relay that a device was connected to, and when.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LastConnection {
+    pub relay: String,
+    pub time: DateTime<Utc>,
 }
 
 impl Device {
@@ -86,7 +99,7 @@ impl AccountAndDevice {
 }
 
 /// Reason why a [DeviceEvent] was emitted.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum DeviceEventCause {
     /// Logged in on a new device.
     LoggedIn,
@@ -101,7 +114,7 @@ pub enum DeviceEventCause {
 }
 
 /// Emitted when logging in or out of an account, or when the device changes.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct DeviceEvent {
     pub cause: DeviceEventCause,
     pub new_state: DeviceState,
@@ -109,7 +122,7 @@ pub struct DeviceEvent {
 
 /// Emitted when a device is removed using the `RemoveDevice` RPC.
 /// This is not sent by a normal logout or when it is revoked remotely.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct RemoveDeviceEvent {
     pub account_token: AccountToken,
     pub new_devices: Vec<Device>,
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Result of running the connection troubleshooter in the daemon.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    /// When the checks were run.
+    pub created: DateTime<Utc>,
+    /// Outcome of every check, in the order they were run.
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+impl DiagnosticsReport {
+    /// Returns true if no check failed.
+    pub fn is_healthy(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Fail)
+    }
+}
+
+impl fmt::Display for DiagnosticsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Mullvad VPN diagnostics report")?;
+        writeln!(f, "Created: {}", self.created.to_rfc3339())?;
+        for check in &self.checks {
+            writeln!(f)?;
+            write!(f, "{check}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single check performed by the connection troubleshooter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticCheck {
+    pub category: CheckCategory,
+    /// Short description of what was checked.
+    pub name: String,
+    pub status: CheckStatus,
+    /// Human-readable explanation of the outcome.
+    pub details: String,
+}
+
+impl DiagnosticCheck {
+    pub fn new(
+        category: CheckCategory,
+        name: impl Into<String>,
+        status: CheckStatus,
+        details: impl Into<String>,
+    ) -> Self {
+        Self {
+            category,
+            name: name.into(),
+            status,
+            details: details.into(),
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[{}] {}: {}", self.status, self.category, self.name)?;
+        if !self.details.is_empty() {
+            writeln!(f, "    {}", self.details)?;
+        }
+        Ok(())
+    }
+}
+
+/// Area of the system that a [`DiagnosticCheck`] concerns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckCategory {
+    ApiAccess,
+    RelayReachability,
+    DnsLeak,
+    Firewall,
+    Routing,
+    Clock,
+}
+
+impl fmt::Display for CheckCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CheckCategory::ApiAccess => "API access",
+            CheckCategory::RelayReachability => "Relay reachability",
+            CheckCategory::DnsLeak => "DNS leak",
+            CheckCategory::Firewall => "Firewall",
+            CheckCategory::Routing => "Routing",
+            CheckCategory::Clock => "Clock",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Outcome of a [`DiagnosticCheck`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+    /// The check could not be run in the current state or on this platform.
+    Skipped,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skipped => "SKIP",
+        };
+        f.write_str(name)
+    }
+}
@@ -38,6 +38,8 @@ pub enum FeatureIndicator {
     CustomMtu,
     CustomMssFix,
     Daita,
+    /// DNS requests were answered by resolvers other than the ones used by the tunnel.
+    DnsLeak,
 }
 
 impl std::fmt::Display for FeatureIndicator {
@@ -56,6 +58,7 @@ impl std::fmt::Display for FeatureIndicator {
             FeatureIndicator::CustomMtu => "Custom MTU",
             FeatureIndicator::CustomMssFix => "Custom MSS",
             FeatureIndicator::Daita => "DAITA",
+            FeatureIndicator::DnsLeak => "DNS Leak Detected",
         };
         write!(f, "{feature}")
     }
@@ -3,12 +3,18 @@ pub mod account;
 pub mod auth_failed;
 pub mod constraints;
 pub mod custom_list;
+pub mod custom_relay;
+pub mod data_usage;
 pub mod device;
+pub mod diagnostics;
 pub mod endpoint;
 pub mod features;
 pub mod location;
+pub mod log_level;
 pub mod relay_constraints;
 pub mod relay_list;
+pub mod schedule;
+pub mod secrets;
 pub mod settings;
 pub mod states;
 pub mod version;
@@ -1,6 +1,10 @@
 use crate::location::{CityCode, CountryCode, Location};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
 use talpid_types::net::{
     proxy::{CustomProxy, Shadowsocks},
     wireguard, TransportProtocol,
@@ -82,6 +86,10 @@ pub struct Relay {
     pub owned: bool,
     pub provider: String,
     pub weight: u64,
+    /// Whether this is a [`CustomRelay`](crate::custom_relay::CustomRelay) rather than a Mullvad
+    /// relay. This is never read from a relay list, so that the API cannot mark relays as custom.
+    #[serde(skip_deserializing)]
+    pub custom: bool,
     pub endpoint_data: RelayEndpointData,
     pub location: Option<Location>,
 }
@@ -105,6 +113,7 @@ impl PartialEq for Relay {
     ///     # owned: true,
     ///     # provider: "provider0".to_string(),
     ///     # weight: 1,
+    ///     # custom: false,
     ///     # endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
     ///     #   public_key: PublicKey::from_base64(
     ///     #       "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
@@ -219,3 +228,214 @@ impl ShadowsocksEndpointData {
         })
     }
 }
+
+/// Describes how a relay list differs from the one it replaced.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RelayListDiff {
+    /// When the new relay list was received.
+    pub time: DateTime<Utc>,
+    /// Hostnames of relays that were added.
+    pub added: Vec<String>,
+    /// Hostnames of relays that were removed.
+    pub removed: Vec<String>,
+    /// Changes to relays that exist in both lists.
+    pub changed: Vec<RelayChange>,
+    /// Hostnames of removed relays that were referenced by the settings, e.g. by a custom list
+    /// or a relay override. This is filled in by the daemon.
+    pub removed_pinned: Vec<String>,
+}
+
+/// A change to a single property of a relay.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RelayChange {
+    pub hostname: String,
+    pub kind: RelayChangeKind,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayChangeKind {
+    Ipv4Address,
+    Ipv6Address,
+    PublicKey,
+    Daita,
+    Active,
+}
+
+impl RelayListDiff {
+    /// Compute the differences between `old` and `new`.
+    pub fn new(old: &RelayList, new: &RelayList) -> Self {
+        let old_relays: HashMap<&str, &Relay> = old
+            .relays()
+            .map(|relay| (relay.hostname.as_str(), relay))
+            .collect();
+        let new_relays: HashMap<&str, &Relay> = new
+            .relays()
+            .map(|relay| (relay.hostname.as_str(), relay))
+            .collect();
+
+        let mut added: Vec<String> = new_relays
+            .keys()
+            .filter(|hostname| !old_relays.contains_key(*hostname))
+            .map(|hostname| hostname.to_string())
+            .collect();
+        let mut removed: Vec<String> = old_relays
+            .keys()
+            .filter(|hostname| !new_relays.contains_key(*hostname))
+            .map(|hostname| hostname.to_string())
+            .collect();
+        added.sort();
+        removed.sort();
+
+        let mut changed = vec![];
+        for relay in new.relays() {
+            if let Some(old_relay) = old_relays.get(relay.hostname.as_str()) {
+                changed.extend(Self::relay_changes(old_relay, relay));
+            }
+        }
+        changed.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+
+        RelayListDiff {
+            time: Utc::now(),
+            added,
+            removed,
+            changed,
+            removed_pinned: vec![],
+        }
+    }
+
+    /// Returns true if the relay lists contain the same relays.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    fn relay_changes(old: &Relay, new: &Relay) -> Vec<RelayChange> {
+        let mut changes = vec![];
+        let mut compare = |kind, old_value: String, new_value: String| {
+            if old_value != new_value {
+                changes.push(RelayChange {
+                    hostname: new.hostname.clone(),
+                    kind,
+                    old: old_value,
+                    new: new_value,
+                });
+            }
+        };
+        let format_ipv6 = |relay: &Relay| {
+            relay
+                .ipv6_addr_in
+                .map(|addr| addr.to_string())
+                .unwrap_or_default()
+        };
+
+        compare(
+            RelayChangeKind::Ipv4Address,
+            old.ipv4_addr_in.to_string(),
+            new.ipv4_addr_in.to_string(),
+        );
+        compare(
+            RelayChangeKind::Ipv6Address,
+            format_ipv6(old),
+            format_ipv6(new),
+        );
+        compare(
+            RelayChangeKind::Active,
+            old.active.to_string(),
+            new.active.to_string(),
+        );
+        if let (RelayEndpointData::Wireguard(old_data), RelayEndpointData::Wireguard(new_data)) =
+            (&old.endpoint_data, &new.endpoint_data)
+        {
+            compare(
+                RelayChangeKind::PublicKey,
+                old_data.public_key.to_base64(),
+                new_data.public_key.to_base64(),
+            );
+            compare(
+                RelayChangeKind::Daita,
+                old_data.daita.to_string(),
+                new_data.daita.to_string(),
+            );
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn relay(hostname: &str, ipv4: &str, daita: bool) -> Relay {
+        Relay {
+            hostname: hostname.to_owned(),
+            ipv4_addr_in: ipv4.parse().unwrap(),
+            ipv6_addr_in: None,
+            include_in_country: true,
+            active: true,
+            owned: true,
+            provider: "provider0".to_owned(),
+            weight: 1,
+            custom: false,
+            endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
+                public_key: wireguard::PublicKey::from_base64(
+                    "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
+                )
+                .unwrap(),
+                daita,
+            }),
+            location: None,
+        }
+    }
+
+    fn relay_list(relays: Vec<Relay>) -> RelayList {
+        RelayList {
+            countries: vec![RelayListCountry {
+                name: "Sweden".to_owned(),
+                code: "se".to_owned(),
+                cities: vec![RelayListCity {
+                    name: "Gothenburg".to_owned(),
+                    code: "got".to_owned(),
+                    latitude: 57.70887,
+                    longitude: 11.97456,
+                    relays,
+                }],
+            }],
+            ..RelayList::empty()
+        }
+    }
+
+    #[test]
+    fn test_relay_list_diff() {
+        let old = relay_list(vec![
+            relay("se-got-wg-001", "10.0.0.1", false),
+            relay("se-got-wg-002", "10.0.0.2", false),
+        ]);
+        let new = relay_list(vec![
+            relay("se-got-wg-002", "10.0.0.22", true),
+            relay("se-got-wg-003", "10.0.0.3", false),
+        ]);
+
+        let diff = RelayListDiff::new(&old, &new);
+        assert_eq!(diff.added, vec!["se-got-wg-003".to_owned()]);
+        assert_eq!(diff.removed, vec!["se-got-wg-001".to_owned()]);
+        let kinds: Vec<_> = diff.changed.iter().map(|change| change.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![RelayChangeKind::Ipv4Address, RelayChangeKind::Daita]
+        );
+
+        assert!(RelayListDiff::new(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_custom_flag_is_not_deserialized() {
+        let mut custom = relay("se-got-wg-001", "10.0.0.1", false);
+        custom.custom = true;
+        let json = serde_json::to_string(&custom).unwrap();
+        let relay: Relay = serde_json::from_str(&json).unwrap();
+        assert!(!relay.custom);
+    }
+}
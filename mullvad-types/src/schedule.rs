@@ -0,0 +1,405 @@
+//! Rules that connect or disconnect the tunnel during recurring time windows.
+//!
+//! A rule is active during every minute that matches its [`CronExpression`]. While a rule is
+//! active, the daemon applies its [`ScheduleAction`]. When no rule is active anymore, the daemon
+//! returns to the state it was in before the first rule became active. Rules never connect the
+//! tunnel while the monthly data cap keeps it disconnected.
+
+use crate::{relay_constraints::LocationConstraint, states::TargetState};
+use chrono::{DateTime, Datelike, Local, Timelike, Utc};
+pub use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+const SCHEDULE_RULE_NAME_MAX_SIZE: usize = 30;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Schedule rule name must be between 1 and {SCHEDULE_RULE_NAME_MAX_SIZE} characters")]
+    InvalidName,
+    #[error("A schedule rule with this name already exists")]
+    DuplicateName,
+    #[error("Schedule rule not found")]
+    NotFound,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ParseCronError {
+    #[error("Expected 5 fields (minute, hour, day of month, month, day of week), found {0}")]
+    FieldCount(usize),
+    #[error("Invalid {field} field: '{value}'")]
+    InvalidField { field: &'static str, value: String },
+    #[error("The {field} field must be a single range of values, found '{value}'")]
+    NonContiguousField { field: &'static str, value: String },
+}
+
+/// Time-based rules, in order of precedence.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    pub rules: Vec<ScheduleRule>,
+}
+
+impl Schedule {
+    /// Add a rule with lower precedence than all existing rules.
+    pub fn add(&mut self, rule: ScheduleRule) -> Result<(), Error> {
+        if rule.name.is_empty() || rule.name.chars().count() > SCHEDULE_RULE_NAME_MAX_SIZE {
+            return Err(Error::InvalidName);
+        }
+        if self.rules.iter().any(|existing| existing.name == rule.name) {
+            return Err(Error::DuplicateName);
+        }
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), Error> {
+        let index = self
+            .rules
+            .iter()
+            .position(|rule| rule.name == name)
+            .ok_or(Error::NotFound)?;
+        self.rules.remove(index);
+        Ok(())
+    }
+
+    /// Return the first rule that is active at `now`, if any.
+    pub fn active_rule(&self, now: DateTime<Utc>) -> Option<&ScheduleRule> {
+        self.rules.iter().find(|rule| rule.is_active(now))
+    }
+}
+
+/// An action that is applied during a recurring time window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    /// Unique name of the rule.
+    pub name: String,
+    /// The minutes during which the rule is active.
+    pub window: CronExpression,
+    /// Time zone that `window` is evaluated in. If this is `None`, the system time zone is used.
+    #[serde(default)]
+    pub timezone: Option<Tz>,
+    pub action: ScheduleAction,
+}
+
+impl ScheduleRule {
+    /// Return whether `now` falls within the time window of the rule.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        match self.timezone {
+            Some(timezone) => self.window.matches(&now.with_timezone(&timezone)),
+            None => self.window.matches(&now.with_timezone(&Local)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleAction {
+    /// Connect the tunnel. If `location` is set, the relay location is changed as well.
+    Connect {
+        location: Option<LocationConstraint>,
+    },
+    /// Disconnect the tunnel.
+    Disconnect,
+}
+
+impl ScheduleAction {
+    /// Return the target state that the action results in.
+    pub fn target_state(&self) -> TargetState {
+        match self {
+            ScheduleAction::Connect { .. } => TargetState::Secured,
+            ScheduleAction::Disconnect => TargetState::Unsecured,
+        }
+    }
+}
+
+/// A cron-like expression of the form `MINUTE HOUR DAY-OF-MONTH MONTH DAY-OF-WEEK`.
+///
+/// Each field is `*`, a value, a range `a-b`, or a comma-separated list of these. Any of them may
+/// be followed by a step, e.g. `*/15` or `9-17/2`. Months and weekdays may be given by their
+/// three-letter English names, and Sunday is either `0` or `7`. As in cron, a day matches if
+/// either the day of month or the day of week matches, unless one of them is `*`.
+///
+/// The minute and hour fields must each match a single range of values, which may wrap around,
+/// such as `22-23,0-5`. Otherwise, a rule using the expression would be entered and left over
+/// and over, e.g. every minute for `*/15`.
+///
+/// For example, `* 9-16 * * mon-fri` matches every minute from 9:00 to 16:59 on weekdays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpression {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronExpression {
+    /// Return whether the minute that `time` falls within matches the expression.
+    pub fn matches<T: Datelike + Timelike>(&self, time: &T) -> bool {
+        let is_set = |field: u64, value: u32| field & (1 << value) != 0;
+
+        let day_of_month = is_set(self.days_of_month, time.day());
+        let day_of_week = is_set(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = if self.any_day_of_month || self.any_day_of_week {
+            day_of_month && day_of_week
+        } else {
+            day_of_month || day_of_week
+        };
+
+        day && is_set(self.minutes, time.minute())
+            && is_set(self.hours, time.hour())
+            && is_set(self.months, time.month())
+    }
+}
+
+impl FromStr for CronExpression {
+    type Err = ParseCronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(ParseCronError::FieldCount(fields.len()));
+        };
+
+        // Sunday may be given as both 0 and 7
+        let mut days_of_week_set = parse_field(days_of_week, "day of week", 0, 7, &WEEKDAY_NAMES)?;
+        if days_of_week_set & (1 << 7) != 0 {
+            days_of_week_set = (days_of_week_set | 1) & !(1 << 7);
+        }
+
+        let minutes_set = parse_field(minutes, "minute", 0, 59, &[])?;
+        if !is_contiguous(minutes_set, 0, 59) {
+            return Err(ParseCronError::NonContiguousField {
+                field: "minute",
+                value: minutes.to_owned(),
+            });
+        }
+        let hours_set = parse_field(hours, "hour", 0, 23, &[])?;
+        if !is_contiguous(hours_set, 0, 23) {
+            return Err(ParseCronError::NonContiguousField {
+                field: "hour",
+                value: hours.to_owned(),
+            });
+        }
+
+        Ok(CronExpression {
+            expression: fields.join(" "),
+            minutes: minutes_set,
+            hours: hours_set,
+            days_of_month: parse_field(days_of_month, "day of month", 1, 31, &[])?,
+            months: parse_field(months, "month", 1, 12, &MONTH_NAMES)?,
+            days_of_week: days_of_week_set,
+            any_day_of_month: days_of_month.starts_with('*'),
+            any_day_of_week: days_of_week.starts_with('*'),
+        })
+    }
+}
+
+/// Parse a field into a set of values, where bit `n` is set if `n` is included. `names` are
+/// alternative names for the values starting at `min`.
+fn parse_field(
+    field: &str,
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+) -> Result<u64, ParseCronError> {
+    let invalid = || ParseCronError::InvalidField {
+        field: name,
+        value: field.to_owned(),
+    };
+    let parse_value = |value: &str| -> Result<u32, ParseCronError> {
+        let value = match names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(value))
+        {
+            Some(index) => min + index as u32,
+            None => value.parse().map_err(|_| invalid())?,
+        };
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(invalid())
+        }
+    };
+
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u32>().map_err(|_| invalid())?)),
+            None => (part, None),
+        };
+        if step == Some(0) {
+            return Err(invalid());
+        }
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+            // `a/n` means every n:th value from `a`
+            None if step.is_some() => (parse_value(range)?, max),
+            None => {
+                let value = parse_value(range)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+/// Return whether the values in `set` form a single range within `min..=max`, where a range may
+/// wrap around from `max` to `min`.
+fn is_contiguous(set: u64, min: u32, max: u32) -> bool {
+    let is_set = |value: u32| set & (1 << value) != 0;
+    // Count the values that start a range, i.e. whose predecessor is not in the set
+    let range_starts = (min..=max)
+        .filter(|&value| {
+            let previous = if value == min { max } else { value - 1 };
+            is_set(value) && !is_set(previous)
+        })
+        .count();
+    // All values being set is a single range without a start
+    range_starts <= 1
+}
+
+impl fmt::Display for CronExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl Serialize for CronExpression {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.expression)
+    }
+}
+
+impl<'de> Deserialize<'de> for CronExpression {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let expression = String::deserialize(deserializer)?;
+        expression.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    fn time(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_cron_expression() {
+        let expression: CronExpression = "0-14 9-17 * * mon-fri".parse().unwrap();
+        assert_eq!(expression.to_string(), "0-14 9-17 * * mon-fri");
+        assert_eq!(expression.minutes, (1 << 15) - 1);
+        assert_eq!(expression.days_of_week, 0b0111110);
+
+        // Ranges may wrap around
+        let expression: CronExpression = "* 22-23,0-5 * * *".parse().unwrap();
+        assert_eq!(expression.hours, 0b111111 | 1 << 22 | 1 << 23);
+
+        let expression: CronExpression = "0 0 1,15 jan-mar/2 7".parse().unwrap();
+        assert_eq!(expression.days_of_month, 1 << 1 | 1 << 15);
+        assert_eq!(expression.months, 1 << 1 | 1 << 3);
+        assert_eq!(expression.days_of_week, 1);
+
+        assert_eq!(
+            "* * * *".parse::<CronExpression>(),
+            Err(ParseCronError::FieldCount(4))
+        );
+        for invalid in [
+            "60 * * * *",
+            "* 5-2 * * *",
+            "*/0 * * * *",
+            "* * 0 * *",
+            "* * * * x",
+        ] {
+            assert!(invalid.parse::<CronExpression>().is_err(), "{invalid}");
+        }
+
+        // Windows with gaps would be entered and left repeatedly
+        assert_eq!(
+            "*/15 9-17 * * mon-fri".parse::<CronExpression>(),
+            Err(ParseCronError::NonContiguousField {
+                field: "minute",
+                value: "*/15".to_owned()
+            })
+        );
+        assert_eq!(
+            "* 9-11,13-17 * * *".parse::<CronExpression>(),
+            Err(ParseCronError::NonContiguousField {
+                field: "hour",
+                value: "9-11,13-17".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_cron_expression_matches() {
+        // 2024-06-03 is a Monday
+        let work_hours: CronExpression = "* 9-16 * * mon-fri".parse().unwrap();
+        assert!(work_hours.matches(&time(2024, 6, 3, 9, 0)));
+        assert!(work_hours.matches(&time(2024, 6, 3, 16, 59)));
+        assert!(!work_hours.matches(&time(2024, 6, 3, 17, 0)));
+        assert!(!work_hours.matches(&time(2024, 6, 2, 12, 0)));
+
+        // The day matches if either the day of month or the day of week does
+        let either_day: CronExpression = "* * 1 * sun".parse().unwrap();
+        assert!(either_day.matches(&time(2024, 6, 1, 0, 0)));
+        assert!(either_day.matches(&time(2024, 6, 2, 0, 0)));
+        assert!(!either_day.matches(&time(2024, 6, 3, 0, 0)));
+    }
+
+    #[test]
+    fn test_active_rule() {
+        let rule = |name: &str, window: &str, action| ScheduleRule {
+            name: name.to_owned(),
+            window: window.parse().unwrap(),
+            timezone: Some(chrono_tz::Europe::Stockholm),
+            action,
+        };
+        let mut schedule = Schedule::default();
+        schedule
+            .add(rule("backup", "* 2-3 * * *", ScheduleAction::Disconnect))
+            .unwrap();
+        schedule
+            .add(rule(
+                "night",
+                "* 0-5 * * *",
+                ScheduleAction::Connect { location: None },
+            ))
+            .unwrap();
+        assert!(matches!(
+            schedule.add(rule("night", "* * * * *", ScheduleAction::Disconnect)),
+            Err(Error::DuplicateName)
+        ));
+
+        // 02:30 in Stockholm, during summer time
+        let now = Utc.from_utc_datetime(&time(2024, 6, 3, 0, 30));
+        assert_eq!(schedule.active_rule(now).unwrap().name, "backup");
+        let now = Utc.from_utc_datetime(&time(2024, 6, 3, 2, 30));
+        assert_eq!(schedule.active_rule(now).unwrap().name, "night");
+        let now = Utc.from_utc_datetime(&time(2024, 6, 3, 4, 0));
+        assert!(schedule.active_rule(now).is_none());
+    }
+}
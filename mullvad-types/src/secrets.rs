@@ -0,0 +1,73 @@
+//! Removal of credentials from serialized values, such as the settings, before they are printed
+//! or included in problem reports.
+
+/// Object keys whose values are credentials: WireGuard keys, pre-shared keys of custom relays,
+/// and the credentials of API access methods and custom proxies.
+pub const SECRET_FIELDS: &[&str] = &["private_key", "psk", "password", "username"];
+
+/// Value that secrets are replaced with.
+pub const REMOVED: &str = "[REMOVED]";
+
+/// Replace the value of every secret field in `value`, at any depth.
+pub fn remove_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_FIELDS.contains(&key.as_str()) && !value.is_null() {
+                    *value = serde_json::Value::from(REMOVED);
+                } else {
+                    remove_secrets(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(remove_secrets),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_remove_secrets() {
+        let mut value = json!({
+            "custom_relays": [
+                { "name": "home", "psk": "c2VjcmV0", "public_key": "cHVibGlj" },
+                { "name": "office", "psk": null },
+            ],
+            "api_access_methods": {
+                "custom": [{
+                    "socks5": { "username": "user", "password": "hunter2", "port": 1080 }
+                }]
+            },
+            "wireguard": { "private_key": "a2V5" },
+        });
+        remove_secrets(&mut value);
+
+        assert_eq!(
+            value,
+            json!({
+                "custom_relays": [
+                    { "name": "home", "psk": REMOVED, "public_key": "cHVibGlj" },
+                    { "name": "office", "psk": null },
+                ],
+                "api_access_methods": {
+                    "custom": [{
+                        "socks5": { "username": REMOVED, "password": REMOVED, "port": 1080 }
+                    }]
+                },
+                "wireguard": { "private_key": REMOVED },
+            })
+        );
+    }
+
+    #[test]
+    fn test_secret_containers_are_replaced() {
+        // A secret that is itself an object is replaced as a whole
+        let mut value = json!({ "password": { "value": "hunter2" } });
+        remove_secrets(&mut value);
+        assert_eq!(value, json!({ "password": REMOVED }));
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use std::net::IpAddr;
+use std::{fmt, net::IpAddr, path::PathBuf, str::FromStr};
+use talpid_types::net::dns::EncryptedDnsServer;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -16,6 +17,9 @@ pub struct DnsOptions {
     pub state: DnsState,
     pub default_options: DefaultDnsOptions,
     pub custom_options: CustomDnsOptions,
+    /// User-supplied lists of blocked domains. These apply on top of the DNS content blockers and
+    /// custom resolvers.
+    pub blocklists: Vec<DnsBlocklist>,
 }
 
 /// Default DNS config
@@ -34,6 +38,10 @@ pub struct DefaultDnsOptions {
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct CustomDnsOptions {
     pub addresses: Vec<IpAddr>,
+    /// DNS-over-HTTPS and DNS-over-TLS resolvers. If any are set, queries are sent to a local
+    /// forwarder that relays them to these resolvers, and `addresses` is ignored.
+    #[serde(default)]
+    pub encrypted_servers: Vec<EncryptedDnsServer>,
 }
 
 impl DefaultDnsOptions {
@@ -56,3 +64,35 @@ impl DefaultDnsOptions {
             || block_social_media
     }
 }
+
+/// Source of a list of blocked domains, in hosts file or AdBlock format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsBlocklist {
+    /// A local file.
+    File(PathBuf),
+    /// An HTTP(S) URL. The list is downloaded periodically.
+    Url(String),
+}
+
+impl FromStr for DnsBlocklist {
+    type Err = std::convert::Infallible;
+
+    /// Parse a URL if the string starts with `http://` or `https://`, and a file path otherwise.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(DnsBlocklist::Url(s.to_owned()))
+        } else {
+            Ok(DnsBlocklist::File(PathBuf::from(s)))
+        }
+    }
+}
+
+impl fmt::Display for DnsBlocklist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsBlocklist::File(path) => write!(f, "{}", path.display()),
+            DnsBlocklist::Url(url) => write!(f, "{url}"),
+        }
+    }
+}
@@ -1,17 +1,22 @@
 use crate::{
     access_method,
+    account::DEFAULT_EXPIRY_NOTIFICATION_HOURS,
     constraints::Constraint,
     custom_list::CustomListsSettings,
+    custom_relay::CustomRelay,
+    data_usage::DataCap,
     relay_constraints::{
         BridgeSettings, BridgeState, GeographicLocationConstraint, LocationConstraint,
         ObfuscationSettings, RelayConstraints, RelayOverride, RelaySettings,
         RelaySettingsFormatter, SelectedObfuscation, WireguardConstraints,
     },
+    schedule::Schedule,
     wireguard,
 };
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 #[cfg(any(windows, target_os = "android", target_os = "macos"))]
 use std::collections::HashSet;
+use std::fmt;
 use talpid_types::net::{openvpn, GenericTunnelOptions};
 
 mod dns;
@@ -77,6 +82,8 @@ pub struct Settings {
     pub bridge_state: BridgeState,
     /// All of the custom relay lists
     pub custom_lists: CustomListsSettings,
+    /// Self-hosted WireGuard servers that can be selected like relays
+    pub custom_relays: Vec<CustomRelay>,
     /// API access methods
     pub api_access_methods: access_method::Settings,
     /// If the daemon should allow communication with private (LAN) networks.
@@ -93,11 +100,70 @@ pub struct Settings {
     pub relay_overrides: Vec<RelayOverride>,
     /// Whether to notify users of beta updates.
     pub show_beta_releases: bool,
+    /// Hours before the account expires at which clients are notified.
+    pub expiry_notification_hours: Vec<u32>,
     /// Split tunneling settings
     #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     pub split_tunnel: SplitTunnelSettings,
+    /// Additional destinations for daemon logs
+    pub log_sinks: LogSinks,
+    /// Rules that connect or disconnect the tunnel at certain times
+    pub schedule: Schedule,
+    /// Monthly limit on the data transferred through the tunnel
+    pub data_cap: DataCap,
     /// Specifies settings schema version
     pub settings_version: SettingsVersion,
+    /// Settings that are pinned by an administrator policy and cannot be changed. These are
+    /// determined by the daemon when it starts and are never saved.
+    #[serde(skip)]
+    pub locked_settings: Vec<LockedSetting>,
+}
+
+/// A setting that can be pinned by an administrator policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockedSetting {
+    AllowLan,
+    BlockWhenDisconnected,
+    AutoConnect,
+    DnsOptions,
+    RelayLocation,
+}
+
+impl fmt::Display for LockedSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LockedSetting::AllowLan => "allow_lan",
+            LockedSetting::BlockWhenDisconnected => "block_when_disconnected",
+            LockedSetting::AutoConnect => "auto_connect",
+            LockedSetting::DnsOptions => "dns_options",
+            LockedSetting::RelayLocation => "relay_location",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Destinations that daemon logs are written to, in addition to stdout and the log file.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LogSinks {
+    /// Send logs to systemd-journald, with structured fields. Only supported on Linux.
+    pub journald: bool,
+    /// Send logs to the local syslog daemon, formatted according to RFC 5424.
+    pub syslog: bool,
+    /// Write logs to a file with one JSON object per line.
+    pub json: bool,
+}
+
+impl LogSinks {
+    /// Returns the sinks that are enabled in either `self` or `other`.
+    pub fn union(self, other: LogSinks) -> LogSinks {
+        LogSinks {
+            journald: self.journald || other.journald,
+            syslog: self.syslog || other.syslog,
+            json: self.json || other.json,
+        }
+    }
 }
 
 #[cfg(any(windows, target_os = "android", target_os = "macos"))]
@@ -198,6 +264,7 @@ impl Default for Settings {
             },
             bridge_state: BridgeState::Auto,
             custom_lists: CustomListsSettings::default(),
+            custom_relays: vec![],
             api_access_methods: access_method::Settings::default(),
             allow_lan: false,
             block_when_disconnected: false,
@@ -205,9 +272,14 @@ impl Default for Settings {
             tunnel_options: TunnelOptions::default(),
             relay_overrides: vec![],
             show_beta_releases: false,
+            expiry_notification_hours: DEFAULT_EXPIRY_NOTIFICATION_HOURS.to_vec(),
             #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             split_tunnel: SplitTunnelSettings::default(),
+            log_sinks: LogSinks::default(),
+            schedule: Schedule::default(),
+            data_cap: DataCap::default(),
             settings_version: CURRENT_SETTINGS_VERSION,
+            locked_settings: vec![],
         }
     }
 }
@@ -272,7 +344,7 @@ pub struct TunnelOptions {
     pub dns_options: DnsOptions,
 }
 
-pub use dns::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
+pub use dns::{CustomDnsOptions, DefaultDnsOptions, DnsBlocklist, DnsOptions, DnsState};
 
 impl Default for TunnelOptions {
     fn default() -> Self {
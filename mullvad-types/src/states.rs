@@ -1,4 +1,5 @@
 use crate::{features::FeatureIndicators, location::GeoIpLocation};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use talpid_types::{
@@ -113,3 +114,11 @@ impl TunnelState {
         }
     }
 }
+
+/// An error state that the tunnel state machine entered at some point.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentError {
+    /// When the error state was entered.
+    pub time: DateTime<Utc>,
+    pub error_state: ErrorState,
+}
@@ -4,10 +4,15 @@ use serde::{Deserialize, Deserializer, Serialize};
 use std::{fmt, str::FromStr, time::Duration};
 use talpid_types::net::wireguard;
 
+pub use talpid_types::net::wireguard::QuantumResistantAlgorithms;
+
 pub const MIN_ROTATION_INTERVAL: Duration = Duration::from_secs(1 * 24 * 60 * 60);
 pub const MAX_ROTATION_INTERVAL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
 pub const DEFAULT_ROTATION_INTERVAL: Duration = MAX_ROTATION_INTERVAL;
 
+/// Shortest allowed interval between renegotiations of the PQ-safe PSK of a tunnel.
+pub const MIN_QUANTUM_RESISTANT_REKEY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
 /// Whether to enable or disable quantum resistant tunnels when the setting is set to
 /// `QuantumResistantState::Auto`. It is currently enabled by default on Linux and macOS,
 /// but disabled on all other platforms.
@@ -194,6 +199,10 @@ pub struct TunnelOptions {
     pub mtu: Option<u16>,
     /// Obtain a PSK using the relay config client.
     pub quantum_resistant: QuantumResistantState,
+    /// Which post-quantum algorithms may be used to obtain the PSK.
+    pub quantum_resistant_algorithms: QuantumResistantAlgorithms,
+    /// Renegotiate the PSK this often while connected, or never if `None`.
+    pub quantum_resistant_rekey_interval: Option<Duration>,
     /// Configure DAITA
     #[cfg(daita)]
     pub daita: DaitaSettings,
@@ -207,6 +216,8 @@ impl Default for TunnelOptions {
         TunnelOptions {
             mtu: None,
             quantum_resistant: QuantumResistantState::Auto,
+            quantum_resistant_algorithms: QuantumResistantAlgorithms::default(),
+            quantum_resistant_rekey_interval: None,
             #[cfg(daita)]
             daita: DaitaSettings::default(),
             rotation_interval: None,
@@ -223,6 +234,10 @@ impl TunnelOptions {
                 QuantumResistantState::On => true,
                 QuantumResistantState::Off => false,
             },
+            quantum_resistant_algorithms: self.quantum_resistant_algorithms,
+            quantum_resistant_rekey_interval: self
+                .quantum_resistant_rekey_interval
+                .map(|interval| interval.max(MIN_QUANTUM_RESISTANT_REKEY_INTERVAL)),
             #[cfg(daita)]
             daita: self.daita.enabled,
         }
@@ -0,0 +1,153 @@
+//! Domain blocklists enforced by the DNS [forwarder](super::forwarder).
+
+use std::{collections::HashSet, net::IpAddr};
+
+/// Host names that are commonly found in hosts files but should never be blocked.
+const IGNORED_HOSTS: &[&str] = &[
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+];
+
+/// A set of blocked domains. Subdomains of a blocked domain are blocked as well.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Blocklist {
+    domains: HashSet<String>,
+}
+
+impl Blocklist {
+    /// Parse a blocklist in hosts file format, AdBlock format (`||example.com^`), or a plain list
+    /// of domains. Lines that cannot be parsed are ignored.
+    pub fn parse(contents: &str) -> Self {
+        let mut domains = HashSet::new();
+        for line in contents.lines() {
+            parse_line(line, &mut domains);
+        }
+        Blocklist { domains }
+    }
+
+    /// Add all domains in `other` to this blocklist.
+    pub fn extend(&mut self, other: &Blocklist) {
+        self.domains.extend(other.domains.iter().cloned());
+    }
+
+    /// Returns the number of blocked domains.
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    /// Returns whether `domain`, or any domain that it is a subdomain of, is blocked.
+    pub fn is_blocked(&self, domain: &str) -> bool {
+        if self.domains.is_empty() {
+            return false;
+        }
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+        let mut candidate = domain.as_str();
+        loop {
+            if self.domains.contains(candidate) {
+                return true;
+            }
+            match candidate.split_once('.') {
+                Some((_label, parent)) => candidate = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+fn parse_line(line: &str, domains: &mut HashSet<String>) {
+    let line = line.trim();
+    // Comments, AdBlock headers and AdBlock exception rules
+    if line.is_empty() || line.starts_with(['#', '!', '[']) || line.starts_with("@@") {
+        return;
+    }
+
+    if let Some(rule) = line.strip_prefix("||") {
+        // AdBlock rules. Only unconditional rules that block a whole domain are supported.
+        if let Some(domain) = rule.strip_suffix('^') {
+            insert_domain(domain, domains);
+        }
+        return;
+    }
+
+    let line = line.split('#').next().unwrap_or_default();
+    let mut fields = line.split_whitespace();
+    let Some(first) = fields.next() else {
+        return;
+    };
+    if first.parse::<IpAddr>().is_ok() {
+        // Hosts file entry
+        for host in fields {
+            insert_domain(host, domains);
+        }
+    } else if fields.next().is_none() {
+        // Plain list of domains
+        insert_domain(first, domains);
+    }
+}
+
+fn insert_domain(domain: &str, domains: &mut HashSet<String>) {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let is_valid = !domain.is_empty()
+        && domain.len() <= 253
+        && domain
+            .split('.')
+            .all(|label| !label.is_empty() && label.len() <= 63)
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_'));
+    if is_valid && !IGNORED_HOSTS.contains(&domain.as_str()) {
+        domains.insert(domain);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Blocklist;
+
+    #[test]
+    fn test_parse_hosts_file() {
+        let blocklist = Blocklist::parse(
+            "# comment\n\
+             127.0.0.1 localhost\n\
+             0.0.0.0 ads.example.com tracker.example.net # trailing comment\n\
+             ::1 ip6-localhost\n",
+        );
+        assert_eq!(blocklist.len(), 2);
+        assert!(blocklist.is_blocked("ads.example.com"));
+        assert!(blocklist.is_blocked("tracker.example.net."));
+        assert!(!blocklist.is_blocked("localhost"));
+    }
+
+    #[test]
+    fn test_parse_adblock_list() {
+        let blocklist = Blocklist::parse(
+            "[Adblock Plus 2.0]\n\
+             ! comment\n\
+             ||ads.example.com^\n\
+             ||tracker.example.net^$third-party\n\
+             @@||allowed.example.com^\n\
+             ||example.org/path\n",
+        );
+        assert_eq!(blocklist.len(), 1);
+        assert!(blocklist.is_blocked("ads.example.com"));
+        assert!(!blocklist.is_blocked("tracker.example.net"));
+        assert!(!blocklist.is_blocked("allowed.example.com"));
+    }
+
+    #[test]
+    fn test_subdomains_are_blocked() {
+        let blocklist = Blocklist::parse("Example.com\n");
+        assert!(blocklist.is_blocked("example.com"));
+        assert!(blocklist.is_blocked("sub.EXAMPLE.com."));
+        assert!(!blocklist.is_blocked("notexample.com"));
+        assert!(!blocklist.is_blocked("com"));
+    }
+}
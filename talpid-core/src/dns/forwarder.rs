@@ -0,0 +1,405 @@
+//! Local DNS stub resolver that forwards queries to upstream resolvers, optionally over an
+//! encrypted transport, and answers queries for blocked domains itself.
+//!
+//! The stub listens on a loopback address, which is handed to the tunnel state machine as the
+//! only DNS server. The firewall therefore only allows plain DNS to the stub, while the upstream
+//! traffic is routed through the tunnel like any other traffic.
+
+use super::blocklist::Blocklist;
+
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::ResolveErrorKind,
+    TokioAsyncResolver,
+};
+use hickory_server::{
+    authority::MessageResponseBuilder,
+    proto::op::{Header, MessageType, OpCode, ResponseCode},
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+    ServerFuture,
+};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use talpid_types::{
+    net::dns::{EncryptedDnsProtocol, EncryptedDnsServer},
+    ErrorExt,
+};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// Address that the forwarder listens on. A less common loopback address is used to avoid
+/// conflicts with other local resolvers, including the filtering resolver on macOS, which listen on
+/// `127.0.0.1`.
+pub const LISTEN_ADDR: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 66);
+
+/// How long to keep idle TCP connections from clients open.
+const TCP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors that can occur when starting the forwarder.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to bind UDP socket
+    #[error("Failed to bind UDP socket")]
+    UdpBindError(#[source] io::Error),
+
+    /// Failed to bind TCP listener
+    #[error("Failed to bind TCP listener")]
+    TcpBindError(#[source] io::Error),
+
+    /// Failed to assign the listening address to the loopback interface
+    #[cfg(target_os = "macos")]
+    #[error("Failed to add loopback alias")]
+    AddLoopbackAlias(#[source] io::Error),
+}
+
+/// Resolvers that the forwarder sends queries to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Upstreams {
+    /// Plain DNS resolvers. These are only protected by the tunnel.
+    Plain(Vec<IpAddr>),
+    /// DNS-over-HTTPS or DNS-over-TLS resolvers.
+    Encrypted(Vec<EncryptedDnsServer>),
+}
+
+/// A running DNS forwarder. The forwarder stops when this is dropped.
+pub struct DnsForwarder {
+    listen_addr: SocketAddr,
+    upstreams: Upstreams,
+    resolver: Arc<RwLock<Arc<TokioAsyncResolver>>>,
+    blocklist: Arc<RwLock<Arc<Blocklist>>>,
+    server_task: tokio::task::JoinHandle<()>,
+}
+
+impl DnsForwarder {
+    /// Start forwarding queries received on [`LISTEN_ADDR`] to `upstreams`. Queries for domains in
+    /// `blocklist` are answered with `NXDOMAIN` instead.
+    pub async fn start(upstreams: Upstreams, blocklist: Arc<Blocklist>) -> Result<Self, Error> {
+        #[cfg(target_os = "macos")]
+        add_loopback_alias()
+            .await
+            .map_err(Error::AddLoopbackAlias)?;
+
+        Self::start_on(
+            SocketAddr::new(LISTEN_ADDR.into(), 53),
+            upstreams,
+            blocklist,
+        )
+        .await
+    }
+
+    async fn start_on(
+        listen_addr: SocketAddr,
+        upstreams: Upstreams,
+        blocklist: Arc<Blocklist>,
+    ) -> Result<Self, Error> {
+        let resolver = Arc::new(RwLock::new(Arc::new(new_resolver(&upstreams))));
+        let blocklist = Arc::new(RwLock::new(blocklist));
+
+        let mut server = ServerFuture::new(ForwardingHandler {
+            resolver: resolver.clone(),
+            blocklist: blocklist.clone(),
+        });
+        let udp_socket = UdpSocket::bind(listen_addr)
+            .await
+            .map_err(Error::UdpBindError)?;
+        // Listen on the same port for TCP if an ephemeral port was requested
+        let listen_addr = udp_socket.local_addr().map_err(Error::UdpBindError)?;
+        server.register_socket(udp_socket);
+        server.register_listener(
+            TcpListener::bind(listen_addr)
+                .await
+                .map_err(Error::TcpBindError)?,
+            TCP_TIMEOUT,
+        );
+
+        let server_task = tokio::spawn(async move {
+            if let Err(error) = server.block_until_done().await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("DNS forwarder stopped unexpectedly")
+                );
+            }
+        });
+
+        log::info!("Started DNS forwarder on {listen_addr}");
+
+        Ok(DnsForwarder {
+            listen_addr,
+            upstreams,
+            resolver,
+            blocklist,
+            server_task,
+        })
+    }
+
+    /// Address and port that the forwarder listens on.
+    pub fn listen_addr(&self) -> SocketAddr {
+        self.listen_addr
+    }
+
+    /// Upstream resolvers that queries are forwarded to.
+    pub fn upstreams(&self) -> &Upstreams {
+        &self.upstreams
+    }
+
+    /// Forward queries to `upstreams` from now on.
+    pub fn set_upstreams(&mut self, upstreams: Upstreams) {
+        if upstreams == self.upstreams {
+            return;
+        }
+        *self.resolver.write().unwrap() = Arc::new(new_resolver(&upstreams));
+        self.upstreams = upstreams;
+    }
+
+    /// Block the domains in `blocklist` from now on.
+    pub fn set_blocklist(&mut self, blocklist: Arc<Blocklist>) {
+        *self.blocklist.write().unwrap() = blocklist;
+    }
+}
+
+impl Drop for DnsForwarder {
+    fn drop(&mut self) {
+        log::info!("Stopping DNS forwarder");
+        self.server_task.abort();
+
+        #[cfg(target_os = "macos")]
+        if self.listen_addr.ip() == IpAddr::V4(LISTEN_ADDR) {
+            if let Err(error) = std::process::Command::new("/sbin/ifconfig")
+                .args(["lo0", "-alias", &LISTEN_ADDR.to_string()])
+                .status()
+            {
+                log::warn!("Failed to remove loopback alias: {error}");
+            }
+        }
+    }
+}
+
+/// Only `127.0.0.1` is assigned to the loopback interface by default on macOS, so [`LISTEN_ADDR`]
+/// must be added as an alias before it can be bound.
+#[cfg(target_os = "macos")]
+async fn add_loopback_alias() -> io::Result<()> {
+    let status = tokio::process::Command::new("/sbin/ifconfig")
+        .args(["lo0", "alias", &LISTEN_ADDR.to_string()])
+        .status()
+        .await?;
+    if !status.success() {
+        return Err(io::Error::other(format!("ifconfig exited with {status}")));
+    }
+    Ok(())
+}
+
+fn new_resolver(upstreams: &Upstreams) -> TokioAsyncResolver {
+    let name_servers = match upstreams {
+        Upstreams::Plain(ips) => NameServerConfigGroup::from_ips_clear(ips, 53, true),
+        Upstreams::Encrypted(servers) => {
+            let mut name_servers = NameServerConfigGroup::new();
+            for upstream in servers {
+                let ips = [upstream.address];
+                let server_name = upstream.server_name.clone();
+                name_servers.merge(match upstream.protocol {
+                    EncryptedDnsProtocol::Https => NameServerConfigGroup::from_ips_https(
+                        &ips,
+                        upstream.port,
+                        server_name,
+                        true,
+                    ),
+                    EncryptedDnsProtocol::Tls => {
+                        NameServerConfigGroup::from_ips_tls(&ips, upstream.port, server_name, true)
+                    }
+                });
+            }
+            name_servers
+        }
+    };
+
+    let mut options = ResolverOpts::default();
+    // The system has already consulted the hosts file before asking us
+    options.use_hosts_file = false;
+
+    TokioAsyncResolver::tokio(
+        ResolverConfig::from_parts(None, vec![], name_servers),
+        options,
+    )
+}
+
+/// An implementation of [hickory_server::server::RequestHandler] that resolves queries using the
+/// current upstream resolvers, unless the queried domain is blocked.
+struct ForwardingHandler {
+    resolver: Arc<RwLock<Arc<TokioAsyncResolver>>>,
+    blocklist: Arc<RwLock<Arc<Blocklist>>>,
+}
+
+impl ForwardingHandler {
+    async fn forward<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handle: R,
+    ) -> io::Result<ResponseInfo> {
+        let resolver = self.resolver.read().unwrap().clone();
+        let query = request.query();
+        let builder = MessageResponseBuilder::from_message_request(request);
+
+        let blocklist = self.blocklist.read().unwrap().clone();
+        if blocklist.is_blocked(&query.name().to_string()) {
+            log::trace!("Blocking DNS query for {}", query.name());
+            let response = builder.error_msg(request.header(), ResponseCode::NXDomain);
+            return response_handle.send_response(response).await;
+        }
+
+        match resolver.lookup(query.name(), query.query_type()).await {
+            Ok(lookup) => {
+                let mut header = Header::response_from_request(request.header());
+                header.set_recursion_available(true);
+                let response = builder.build(
+                    header,
+                    lookup.record_iter(),
+                    std::iter::empty(),
+                    std::iter::empty(),
+                    std::iter::empty(),
+                );
+                response_handle.send_response(response).await
+            }
+            Err(error) => {
+                let response_code = match error.kind() {
+                    ResolveErrorKind::NoRecordsFound { response_code, .. } => *response_code,
+                    _ => {
+                        log::debug!("Failed to forward DNS query: {error}");
+                        ResponseCode::ServFail
+                    }
+                };
+                let response = builder.error_msg(request.header(), response_code);
+                response_handle.send_response(response).await
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for ForwardingHandler {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        if !request.src().ip().is_loopback() {
+            log::error!("Dropping a stray request from outside: {}", request.src());
+            return Header::new().into();
+        }
+        if request.message_type() != MessageType::Query || request.op_code() != OpCode::Query {
+            log::trace!("Dropping non-query request: {:?}", request);
+            return Header::new().into();
+        }
+
+        match self.forward(request, response_handle).await {
+            Ok(info) => info,
+            Err(error) => {
+                log::error!("Failed to send response: {}", error);
+                Header::new().into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hickory_proto::op::Message;
+
+    async fn start_forwarder(upstreams: Upstreams, blocklist: &str) -> DnsForwarder {
+        DnsForwarder::start_on(
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0),
+            upstreams,
+            Arc::new(Blocklist::parse(blocklist)),
+        )
+        .await
+        .expect("failed to start forwarder")
+    }
+
+    async fn query(forwarder: &DnsForwarder, hostname: &str) -> Message {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        socket.connect(forwarder.listen_addr()).await.unwrap();
+        socket
+            .send(&crate::dns::leak_test::build_query(hostname))
+            .await
+            .unwrap();
+        let mut buf = [0u8; 512];
+        let len = tokio::time::timeout(Duration::from_secs(10), socket.recv(&mut buf))
+            .await
+            .expect("no response from forwarder")
+            .unwrap();
+        Message::from_vec(&buf[..len]).unwrap()
+    }
+
+    #[test]
+    fn test_blocked_domain() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let forwarder = start_forwarder(Upstreams::Plain(vec![]), "ads.example.com").await;
+
+            let response = query(&forwarder, "tracker.ads.example.com").await;
+            assert_eq!(response.response_code(), ResponseCode::NXDomain);
+            assert!(response.answers().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_unreachable_upstream() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // Without any upstreams, queries that are not blocked cannot be resolved
+            let forwarder = start_forwarder(Upstreams::Plain(vec![]), "ads.example.com").await;
+
+            let response = query(&forwarder, "example.com").await;
+            assert_eq!(response.response_code(), ResponseCode::ServFail);
+        });
+    }
+
+    #[test]
+    fn test_update_blocklist() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut forwarder = start_forwarder(Upstreams::Plain(vec![]), "").await;
+            assert_eq!(
+                query(&forwarder, "ads.example.com").await.response_code(),
+                ResponseCode::ServFail
+            );
+
+            forwarder.set_blocklist(Arc::new(Blocklist::parse("ads.example.com")));
+            assert_eq!(
+                query(&forwarder, "ads.example.com").await.response_code(),
+                ResponseCode::NXDomain
+            );
+        });
+    }
+
+    #[test]
+    fn test_set_upstreams() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut forwarder = start_forwarder(Upstreams::Plain(vec![]), "").await;
+            let upstreams = Upstreams::Plain(vec![Ipv4Addr::new(10, 64, 0, 1).into()]);
+
+            forwarder.set_upstreams(upstreams.clone());
+            assert_eq!(forwarder.upstreams(), &upstreams);
+        });
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let forwarder = start_forwarder(Upstreams::Plain(vec![]), "").await;
+            let listen_addr = forwarder.listen_addr();
+            drop(forwarder);
+            // Let the aborted server task release its sockets
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            UdpSocket::bind(listen_addr)
+                .await
+                .expect("the forwarder socket should have been closed");
+        });
+    }
+}
@@ -0,0 +1,296 @@
+//! Runtime DNS leak test. Compares the resolvers used by the system against the resolvers that
+//! the tunnel is configured to use, and verifies that no other resolver can be reached.
+//!
+//! A unique hostname is looked up through the system resolver. The resolvers of the tunnel are
+//! then asked for the same hostname without recursion, which they can only answer from their
+//! cache. If none of them has the answer, the lookup was answered by some other resolver.
+
+use rand::{distributions::Alphanumeric, Rng};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use talpid_types::{net::dns::DnsLeakTestResult, ErrorExt};
+use tokio::net::UdpSocket;
+
+/// Domain under which unique subdomains are looked up.
+const LEAK_TEST_DOMAIN: &str = "dnsleak.am.i.mullvad.net";
+/// How long to wait for a resolver to answer a query.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Look up a unique subdomain through the system resolver, and check that it was answered by one
+/// of `expected_resolvers`. Also check whether any resolver other than `expected_resolvers`
+/// answers DNS queries.
+pub async fn run(expected_resolvers: Vec<IpAddr>) -> DnsLeakTestResult {
+    let hostname = unique_hostname();
+
+    let resolved = match tokio::net::lookup_host((hostname.as_str(), 0)).await {
+        Ok(_) => true,
+        Err(error) => {
+            log::debug!("DNS leak test lookup of {hostname} failed: {error}");
+            false
+        }
+    };
+    let answered_by_unexpected_resolver =
+        resolved && !answered_by_any(&expected_resolvers, &hostname).await;
+    if answered_by_unexpected_resolver {
+        log::warn!("A DNS query was not answered by any of the resolvers used by the tunnel");
+    }
+
+    let system_resolvers = match system_resolvers().await {
+        Ok(resolvers) => resolvers,
+        Err(error) => {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to read system DNS resolvers")
+            );
+            vec![]
+        }
+    };
+
+    let mut leaking_resolvers = vec![];
+    for resolver in system_resolvers
+        .iter()
+        .filter(|resolver| !resolver.is_loopback() && !expected_resolvers.contains(resolver))
+    {
+        if query_resolver(*resolver, &hostname, true).await.is_ok() {
+            log::warn!("Resolver {resolver} is not used by the tunnel but answered a DNS query");
+            leaking_resolvers.push(*resolver);
+        }
+    }
+
+    DnsLeakTestResult {
+        expected_resolvers,
+        system_resolvers,
+        leaking_resolvers,
+        answered_by_unexpected_resolver,
+    }
+}
+
+/// Returns whether any of `resolvers` has already resolved `hostname`. If a resolver does not
+/// respond to the query, this cannot be determined, and `true` is returned.
+async fn answered_by_any(resolvers: &[IpAddr], hostname: &str) -> bool {
+    if resolvers.is_empty() {
+        return true;
+    }
+    for resolver in resolvers {
+        match query_resolver(*resolver, hostname, false).await {
+            Ok(response) if has_answer(&response) => return true,
+            Ok(_) => (),
+            Err(error) => {
+                log::debug!("Resolver {resolver} did not respond to a cached query: {error}");
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Build a minimal DNS query for the A record of `hostname`.
+pub fn build_query(hostname: &str) -> Vec<u8> {
+    encode_query(hostname, true)
+}
+
+/// Encode a query for the A record of `hostname`. If `recursion_desired` is false, a resolver
+/// only answers the query if the result is cached.
+fn encode_query(hostname: &str, recursion_desired: bool) -> Vec<u8> {
+    let id: u16 = rand::random();
+    let mut query = Vec::with_capacity(hostname.len() + 18);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Flags: recursion desired, if requested. One question, no other records.
+    let flags = if recursion_desired { 0x01 } else { 0x00 };
+    query.extend_from_slice(&[flags, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in hostname.split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    // QTYPE A, QCLASS IN
+    query.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+    query
+}
+
+/// Returns whether `response` is a successful response with at least one answer record.
+fn has_answer(response: &[u8]) -> bool {
+    const RCODE_NO_ERROR: u8 = 0;
+    if response.len() < 12 {
+        return false;
+    }
+    let rcode = response[3] & 0x0f;
+    let answer_count = u16::from_be_bytes([response[6], response[7]]);
+    rcode == RCODE_NO_ERROR && answer_count > 0
+}
+
+fn unique_hostname() -> String {
+    let label: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(|c| char::from(c).to_ascii_lowercase())
+        .collect();
+    format!("{label}.{LEAK_TEST_DOMAIN}")
+}
+
+/// Send a query for `hostname` directly to `resolver`, and return the response.
+async fn query_resolver(
+    resolver: IpAddr,
+    hostname: &str,
+    recursion_desired: bool,
+) -> io::Result<Vec<u8>> {
+    let bind_addr: IpAddr = match resolver {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(bind_addr, 0)).await?;
+    socket.connect(SocketAddr::new(resolver, 53)).await?;
+    let query = encode_query(hostname, recursion_desired);
+    socket.send(&query).await?;
+    let mut buf = [0u8; 512];
+    let len = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_timeout| io::Error::from(io::ErrorKind::TimedOut))??;
+    let response = &buf[..len];
+    if response.get(..2) != Some(&query[..2]) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Unexpected DNS response ID",
+        ));
+    }
+    Ok(response.to_vec())
+}
+
+/// Return the resolvers in `/etc/resolv.conf`. If only the systemd-resolved stub is listed, the
+/// upstream resolvers known by systemd-resolved are returned instead.
+#[cfg(target_os = "linux")]
+pub(crate) async fn system_resolvers() -> io::Result<Vec<IpAddr>> {
+    let contents = tokio::fs::read("/etc/resolv.conf").await?;
+    let config = resolv_conf::Config::parse(contents)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let resolvers: Vec<IpAddr> = config
+        .nameservers
+        .iter()
+        .map(|ip| match ip {
+            resolv_conf::ScopedIp::V4(ip) => IpAddr::V4(*ip),
+            resolv_conf::ScopedIp::V6(ip, _scope) => IpAddr::V6(*ip),
+        })
+        .collect();
+
+    if !resolvers.iter().all(|resolver| resolver.is_loopback()) {
+        return Ok(resolvers);
+    }
+    match command_output("resolvectl", &["dns"]).await {
+        Ok(output) => {
+            Ok(parse_addresses(output.lines().filter_map(|line| {
+                line.split_once(':').map(|(_link, servers)| servers)
+            })))
+        }
+        // systemd-resolved is not used
+        Err(_) => Ok(resolvers),
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) async fn system_resolvers() -> io::Result<Vec<IpAddr>> {
+    let output = command_output("scutil", &["--dns"]).await?;
+    Ok(parse_addresses(output.lines().filter_map(|line| {
+        line.trim_start()
+            .strip_prefix("nameserver")
+            .and_then(|rest| rest.split_once(':'))
+            .map(|(_index, server)| server)
+    })))
+}
+
+/// Return the resolvers that are configured statically or by DHCP for any interface.
+#[cfg(windows)]
+#[allow(clippy::unused_async)]
+pub(crate) async fn system_resolvers() -> io::Result<Vec<IpAddr>> {
+    use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
+
+    let mut resolvers = vec![];
+    for path in [
+        r"SYSTEM\CurrentControlSet\Services\Tcpip\Parameters\Interfaces",
+        r"SYSTEM\CurrentControlSet\Services\Tcpip6\Parameters\Interfaces",
+    ] {
+        let interfaces = RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey(path)?;
+        for interface in interfaces.enum_keys().filter_map(Result::ok) {
+            let Ok(interface) = interfaces.open_subkey(&interface) else {
+                continue;
+            };
+            for value in ["NameServer", "DhcpNameServer"] {
+                let servers: String = interface.get_value(value).unwrap_or_default();
+                for server in parse_addresses(servers.split(',')) {
+                    if !resolvers.contains(&server) {
+                        resolvers.push(server);
+                    }
+                }
+            }
+        }
+    }
+    Ok(resolvers)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub(crate) async fn system_resolvers() -> io::Result<Vec<IpAddr>> {
+    Ok(vec![])
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+async fn command_output(command: &str, args: &[&str]) -> io::Result<String> {
+    let output = tokio::process::Command::new(command)
+        .args(args)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{command} exited with {}", output.status),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parse whitespace-separated addresses, ignoring interface scopes and server names.
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn parse_addresses<'a>(fields: impl Iterator<Item = &'a str>) -> Vec<IpAddr> {
+    let mut addresses = vec![];
+    for field in fields.flat_map(str::split_whitespace) {
+        let address = field.split(['%', '#']).next().unwrap_or(field);
+        if let Ok(address) = address.parse::<IpAddr>() {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+    }
+    addresses
+}
+
+#[cfg(test)]
+mod test {
+    use super::{build_query, encode_query, has_answer};
+
+    #[test]
+    fn test_dns_query_encoding() {
+        let query = build_query("am.i.mullvad.net");
+        // Header
+        assert_eq!(&query[2..12], &[1, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        // Question
+        assert_eq!(
+            &query[12..],
+            b"\x02am\x01i\x07mullvad\x03net\x00\x00\x01\x00\x01"
+        );
+
+        let query = encode_query("am.i.mullvad.net", false);
+        assert_eq!(query[2], 0);
+    }
+
+    #[test]
+    fn test_cached_answer() {
+        // NOERROR, one answer
+        assert!(has_answer(&[0, 1, 0x80, 0x00, 0, 1, 0, 1, 0, 0, 0, 0]));
+        // NOERROR, no answers
+        assert!(!has_answer(&[0, 1, 0x80, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]));
+        // REFUSED
+        assert!(!has_answer(&[0, 1, 0x80, 0x05, 0, 1, 0, 1, 0, 0, 0, 0]));
+        assert!(!has_answer(&[0, 1]));
+    }
+}
@@ -40,6 +40,16 @@ pub struct DnsMonitor {
     route_manager: RouteManagerHandle,
     handle: tokio::runtime::Handle,
     inner: Option<DnsMonitorHolder>,
+    /// Name of the DNS manager that was detected when DNS was last set, or "none".
+    detected_manager: Option<String>,
+}
+
+impl DnsMonitor {
+    /// Returns the name of the DNS manager that was detected when DNS was last set, without
+    /// detecting it again.
+    pub fn detected_manager(&self) -> Option<&str> {
+        self.detected_manager.as_deref()
+    }
 }
 
 impl super::DnsMonitorT for DnsMonitor {
@@ -50,13 +60,19 @@ impl super::DnsMonitorT for DnsMonitor {
             route_manager,
             handle,
             inner: None,
+            detected_manager: None,
         })
     }
 
     fn set(&mut self, interface: &str, servers: &[IpAddr]) -> Result<()> {
         self.reset()?;
         // Creating a new DNS monitor for each set, in case the system changed how it manages DNS.
-        let mut inner = DnsMonitorHolder::new()?;
+        let inner = DnsMonitorHolder::new();
+        self.detected_manager = Some(match &inner {
+            Ok(manager) => manager.to_string(),
+            Err(_) => "none".to_owned(),
+        });
+        let mut inner = inner?;
         if !servers.is_empty() {
             inner.set(&self.handle, &self.route_manager, interface, servers)?;
             self.inner = Some(inner);
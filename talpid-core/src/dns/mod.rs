@@ -2,6 +2,12 @@ use std::net::IpAddr;
 #[cfg(target_os = "linux")]
 use talpid_routing::RouteManagerHandle;
 
+#[cfg(not(target_os = "android"))]
+pub mod blocklist;
+#[cfg(not(target_os = "android"))]
+pub mod forwarder;
+pub mod leak_test;
+
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
 mod imp;
@@ -71,6 +77,11 @@ impl DnsMonitor {
         log::info!("Resetting DNS");
         self.inner.reset_before_interface_removal()
     }
+    /// Returns the name of the DNS manager that was detected when DNS was last set.
+    #[cfg(target_os = "linux")]
+    pub fn detected_manager(&self) -> Option<&str> {
+        self.inner.detected_manager()
+    }
 }
 
 trait DnsMonitorT: Sized {
@@ -1,4 +1,4 @@
-use super::{FirewallArguments, FirewallPolicy};
+use super::{CaptivePortalAccess, FirewallArguments, FirewallPolicy};
 use crate::{split_tunnel, tunnel};
 use ipnetwork::IpNetwork;
 use nftnl::{
@@ -11,6 +11,7 @@ use std::{
     ffi::{CStr, CString},
     fs, io,
     net::{IpAddr, Ipv4Addr},
+    time::SystemTime,
 };
 use talpid_types::net::{AllowedEndpoint, AllowedTunnelTraffic, Endpoint, TransportProtocol};
 
@@ -574,10 +575,14 @@ impl<'a> PolicyBatch<'a> {
             FirewallPolicy::Blocked {
                 allow_lan,
                 allowed_endpoint,
+                captive_portal,
             } => {
                 if let Some(endpoint) = allowed_endpoint {
                     self.add_allow_endpoint_rules(endpoint);
                 }
+                if let Some(captive_portal) = captive_portal {
+                    self.add_allow_captive_portal_rules(captive_portal);
+                }
 
                 // Important to drop DNS before allowing LAN (to stop DNS leaking to the LAN)
                 self.add_drop_dns_rule();
@@ -682,6 +687,30 @@ impl<'a> PolicyBatch<'a> {
         self.batch.add(&out_rule, nftnl::MsgType::Add);
     }
 
+    /// Adds firewall rules that allow DNS to the gateways, and HTTP and HTTPS to the portal hosts.
+    /// Allows the user to log in to a captive portal in blocked states. Nothing is allowed once
+    /// the access has expired.
+    fn add_allow_captive_portal_rules(&mut self, access: &CaptivePortalAccess) {
+        for (host, protocol, port) in access.allowed_services(SystemTime::now()) {
+            let mut out_rule = Rule::new(&self.out_chain);
+            check_ip(&mut out_rule, End::Dst, host);
+            check_port(&mut out_rule, protocol, End::Dst, port);
+            add_verdict(&mut out_rule, &Verdict::Accept);
+            self.batch.add(&out_rule, nftnl::MsgType::Add);
+
+            // Allow incoming traffic from established connections to the host
+            let mut in_rule = Rule::new(&self.in_chain);
+            check_ip(&mut in_rule, End::Src, host);
+            check_port(&mut in_rule, protocol, End::Src, port);
+            let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+            in_rule.add_expr(&nft_expr!(ct state));
+            in_rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+            in_rule.add_expr(&nft_expr!(cmp != 0u32));
+            add_verdict(&mut in_rule, &Verdict::Accept);
+            self.batch.add(&in_rule, nftnl::MsgType::Add);
+        }
+    }
+
     fn add_allow_dns_rules(
         &mut self,
         tunnel: &tunnel::TunnelMetadata,
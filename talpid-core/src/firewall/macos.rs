@@ -1,4 +1,4 @@
-use super::{FirewallArguments, FirewallPolicy};
+use super::{CaptivePortalAccess, FirewallArguments, FirewallPolicy};
 use ipnetwork::IpNetwork;
 use libc::{c_int, sysctlbyname};
 use pfctl::{DropAction, FilterRuleAction, Uid};
@@ -6,6 +6,7 @@ use std::{
     env, io,
     net::{IpAddr, Ipv4Addr},
     ptr,
+    time::SystemTime,
 };
 use subslice::SubsliceExt;
 use talpid_types::net::{self, AllowedEndpoint, AllowedTunnelTraffic};
@@ -269,12 +270,16 @@ impl Firewall {
             FirewallPolicy::Blocked {
                 allow_lan,
                 allowed_endpoint,
+                captive_portal,
                 ..
             } => {
                 let mut rules = Vec::new();
                 if let Some(allowed_endpoint) = allowed_endpoint {
                     rules.push(self.get_allowed_endpoint_rule(allowed_endpoint)?);
                 }
+                if let Some(captive_portal) = captive_portal {
+                    rules.append(&mut self.get_allow_captive_portal_rules(captive_portal)?);
+                }
 
                 if *allow_lan {
                     // Important to block DNS before allow LAN (so DNS does not leak to the LAN)
@@ -414,6 +419,27 @@ impl Firewall {
         rule.build()
     }
 
+    /// Allows DNS to the gateways, and HTTP and HTTPS to the portal hosts, so that the user can
+    /// log in to a captive portal. Nothing is allowed once the access has expired.
+    fn get_allow_captive_portal_rules(
+        &self,
+        access: &CaptivePortalAccess,
+    ) -> Result<Vec<pfctl::FilterRule>> {
+        access
+            .allowed_services(SystemTime::now())
+            .into_iter()
+            .map(|(host, protocol, port)| {
+                self.create_rule_builder(FilterRuleAction::Pass)
+                    .direction(pfctl::Direction::Out)
+                    .to(pfctl::Endpoint::new(host, port))
+                    .proto(as_pfctl_proto(protocol))
+                    .keep_state(pfctl::StatePolicy::Keep)
+                    .quick(true)
+                    .build()
+            })
+            .collect()
+    }
+
     fn get_block_dns_rules(&self) -> Result<Vec<pfctl::FilterRule>> {
         let block_tcp_dns_rule = self
             .create_rule_builder(FilterRuleAction::Drop(DropAction::Return))
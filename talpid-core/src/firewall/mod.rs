@@ -1,9 +1,13 @@
 use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
 use once_cell::sync::Lazy;
+#[cfg(not(target_os = "android"))]
+use std::time::SystemTime;
 use std::{
     fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
 };
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::TransportProtocol;
 use talpid_types::net::{AllowedEndpoint, AllowedTunnelTraffic};
 
 #[cfg(target_os = "macos")]
@@ -150,6 +154,9 @@ pub enum FirewallPolicy {
         allow_lan: bool,
         /// Host that should be reachable while in the blocked state.
         allowed_endpoint: Option<AllowedEndpoint>,
+        /// Hosts that should be reachable so that a captive portal can be logged in to.
+        #[cfg(not(target_os = "android"))]
+        captive_portal: Option<CaptivePortalAccess>,
         /// Destination port for DNS traffic redirection. Traffic destined to `127.0.0.1:53` will
         /// be redirected to `127.0.0.1:$dns_redirect_port`.
         #[cfg(target_os = "macos")]
@@ -157,6 +164,52 @@ pub enum FirewallPolicy {
     },
 }
 
+/// Hosts that may be reached in the blocked state, so that the user can log in to a captive portal
+/// before the tunnel can be connected.
+#[cfg(not(target_os = "android"))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CaptivePortalAccess {
+    /// Gateways of the local network and the resolvers configured by the system. Only DNS traffic
+    /// to these is allowed, so that the portal can be resolved.
+    pub gateways: Vec<IpAddr>,
+    /// Resolved addresses of the portal. HTTP and HTTPS traffic to these is allowed.
+    pub hosts: Vec<IpAddr>,
+    /// Nothing is allowed from this point on. This is wall-clock time, so that time spent
+    /// suspended counts towards the window.
+    pub expires: SystemTime,
+}
+
+#[cfg(not(target_os = "android"))]
+impl CaptivePortalAccess {
+    const DNS: [(TransportProtocol, u16); 2] =
+        [(TransportProtocol::Udp, 53), (TransportProtocol::Tcp, 53)];
+    const WEB: [(TransportProtocol, u16); 2] =
+        [(TransportProtocol::Tcp, 80), (TransportProtocol::Tcp, 443)];
+
+    /// Return whether nothing may be reached at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.expires
+    }
+
+    /// Return the destinations that may be reached at `now`.
+    pub fn allowed_services(&self, now: SystemTime) -> Vec<(IpAddr, TransportProtocol, u16)> {
+        if self.is_expired(now) {
+            return vec![];
+        }
+        let gateway_services = self.gateways.iter().flat_map(|gateway| {
+            Self::DNS
+                .iter()
+                .map(move |(protocol, port)| (*gateway, *protocol, *port))
+        });
+        let host_services = self.hosts.iter().flat_map(|host| {
+            Self::WEB
+                .iter()
+                .map(move |(protocol, port)| (*host, *protocol, *port))
+        });
+        gateway_services.chain(host_services).collect()
+    }
+}
+
 impl FirewallPolicy {
     /// Return the tunnel peer endpoint, if available
     pub fn peer_endpoint(&self) -> Option<&AllowedEndpoint> {
@@ -326,3 +379,44 @@ impl Firewall {
         self.inner.reset_policy()
     }
 }
+
+#[cfg(all(test, not(target_os = "android")))]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn access(expires: SystemTime) -> CaptivePortalAccess {
+        CaptivePortalAccess {
+            gateways: vec![IpAddr::from([192, 168, 1, 1])],
+            hosts: vec![IpAddr::from([10, 0, 0, 1])],
+            expires,
+        }
+    }
+
+    #[test]
+    fn test_captive_portal_services() {
+        let now = SystemTime::now();
+        let services = access(now + Duration::from_secs(60)).allowed_services(now);
+
+        let gateway = IpAddr::from([192, 168, 1, 1]);
+        let portal = IpAddr::from([10, 0, 0, 1]);
+        assert_eq!(
+            services,
+            vec![
+                (gateway, TransportProtocol::Udp, 53),
+                (gateway, TransportProtocol::Tcp, 53),
+                (portal, TransportProtocol::Tcp, 80),
+                (portal, TransportProtocol::Tcp, 443),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_captive_portal_expired() {
+        let now = SystemTime::now();
+        assert!(access(now).allowed_services(now).is_empty());
+        assert!(access(now)
+            .allowed_services(now + Duration::from_secs(1))
+            .is_empty());
+    }
+}
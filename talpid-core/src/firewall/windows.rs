@@ -1,9 +1,9 @@
 use crate::tunnel::TunnelMetadata;
 
-use std::{ffi::CStr, io, net::IpAddr, ptr};
+use std::{ffi::CStr, io, net::IpAddr, ptr, time::SystemTime};
 
 use self::winfw::*;
-use super::{FirewallArguments, FirewallPolicy, InitialFirewallState};
+use super::{CaptivePortalAccess, FirewallArguments, FirewallPolicy, InitialFirewallState};
 use talpid_types::{
     net::{AllowedEndpoint, AllowedTunnelTraffic},
     tunnel::FirewallPolicyError,
@@ -121,11 +121,13 @@ impl Firewall {
             FirewallPolicy::Blocked {
                 allow_lan,
                 allowed_endpoint,
+                captive_portal,
             } => {
                 let cfg = &WinFwSettings::new(allow_lan);
                 self.set_blocked_state(
                     cfg,
                     allowed_endpoint.map(WinFwAllowedEndpointContainer::from),
+                    captive_portal.as_ref(),
                 )
             }
         }
@@ -316,12 +318,32 @@ impl Firewall {
         &mut self,
         winfw_settings: &WinFwSettings,
         allowed_endpoint: Option<WinFwAllowedEndpointContainer>,
+        captive_portal: Option<&CaptivePortalAccess>,
     ) -> Result<(), Error> {
         log::trace!("Applying 'blocked' firewall policy");
         let endpoint = allowed_endpoint
             .as_ref()
             .map(WinFwAllowedEndpointContainer::as_endpoint);
 
+        // Nothing is allowed once the access has expired
+        let captive_portal = captive_portal.filter(|access| !access.is_expired(SystemTime::now()));
+        let (resolvers, hosts): (Vec<WideCString>, Vec<WideCString>) = captive_portal
+            .map(|access| {
+                (
+                    access.gateways.iter().cloned().map(widestring_ip).collect(),
+                    access.hosts.iter().cloned().map(widestring_ip).collect(),
+                )
+            })
+            .unwrap_or_default();
+        let resolvers: Vec<*const u16> = resolvers.iter().map(|ip| ip.as_ptr()).collect();
+        let hosts: Vec<*const u16> = hosts.iter().map(|ip| ip.as_ptr()).collect();
+        let winfw_captive_portal = WinFwCaptivePortal {
+            resolvers: resolvers.as_ptr(),
+            num_resolvers: resolvers.len(),
+            hosts: hosts.as_ptr(),
+            num_hosts: hosts.len(),
+        };
+
         unsafe {
             WinFw_ApplyPolicyBlocked(
                 winfw_settings,
@@ -329,6 +351,9 @@ impl Firewall {
                     .as_ref()
                     .map(|container| container as *const _)
                     .unwrap_or(ptr::null()),
+                captive_portal
+                    .map(|_| &winfw_captive_portal as *const _)
+                    .unwrap_or(ptr::null()),
             )
             .into_result()
             .map_err(Error::ApplyingBlockedPolicy)
@@ -493,6 +518,14 @@ mod winfw {
         _phantom: std::marker::PhantomData<&'a WinFwAllowedEndpointContainer>,
     }
 
+    #[repr(C)]
+    pub struct WinFwCaptivePortal {
+        pub resolvers: *const *const libc::wchar_t,
+        pub num_resolvers: usize,
+        pub hosts: *const *const libc::wchar_t,
+        pub num_hosts: usize,
+    }
+
     #[repr(C)]
     pub struct WinFwAllowedTunnelTraffic {
         pub type_: WinFwAllowedTunnelTrafficType,
@@ -645,6 +678,7 @@ mod winfw {
         pub fn WinFw_ApplyPolicyBlocked(
             settings: &WinFwSettings,
             allowed_endpoint: *const WinFwAllowedEndpoint<'_>,
+            captive_portal: *const WinFwCaptivePortal,
         ) -> WinFwPolicyStatus;
 
         #[link_name = "WinFw_Reset"]
@@ -103,3 +103,18 @@ async fn check_connectivity(handle: &RouteManagerHandle, fwmark: Option<u32>) ->
         }
     }
 }
+
+/// Return the gateways of the routes to the internet, outside any tunnel.
+pub async fn default_gateways(handle: &RouteManagerHandle) -> Vec<IpAddr> {
+    let mut gateways = vec![];
+    for destination in [PUBLIC_INTERNET_ADDRESS_V4, PUBLIC_INTERNET_ADDRESS_V6] {
+        match handle.get_destination_route(destination, None).await {
+            Ok(route) => gateways.extend(route.and_then(|route| route.get_node().get_address())),
+            Err(error) => log::debug!(
+                "{}",
+                error.display_chain_with_msg("Failed to find route to the internet")
+            ),
+        }
+    }
+    gateways
+}
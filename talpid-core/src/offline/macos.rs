@@ -14,11 +14,12 @@ use futures::{
     select, StreamExt,
 };
 use std::{
+    net::IpAddr,
     sync::{Arc, Mutex},
     time::Duration,
 };
 use talpid_routing::{DefaultRouteEvent, RouteManagerHandle};
-use talpid_types::net::Connectivity;
+use talpid_types::{net::Connectivity, ErrorExt};
 
 const SYNTHETIC_OFFLINE_DURATION: Duration = Duration::from_secs(1);
 
@@ -171,3 +172,21 @@ pub async fn spawn_monitor(
         _notify_tx: notify_tx,
     })
 }
+
+/// Return the gateways of the non-tunnel default routes.
+pub async fn default_gateways(handle: &RouteManagerHandle) -> Vec<IpAddr> {
+    match handle.get_default_gateway().await {
+        Ok((v4_gateway, v6_gateway)) => v4_gateway
+            .into_iter()
+            .chain(v6_gateway)
+            .map(|gateway| gateway.ip_address)
+            .collect(),
+        Err(error) => {
+            log::debug!(
+                "{}",
+                error.display_chain_with_msg("Failed to find default gateways")
+            );
+            vec![]
+        }
+    }
+}
@@ -22,6 +22,9 @@ mod imp;
 #[path = "android.rs"]
 mod imp;
 
+#[cfg(not(target_os = "android"))]
+pub use imp::default_gateways;
+
 /// Disables offline monitor
 static FORCE_DISABLE_OFFLINE_MONITOR: Lazy<bool> = Lazy::new(|| {
     std::env::var("TALPID_DISABLE_OFFLINE_MONITOR")
@@ -3,6 +3,7 @@ use futures::channel::mpsc::UnboundedSender;
 use parking_lot::Mutex;
 use std::{
     io,
+    net::IpAddr,
     sync::{Arc, Weak},
     time::Duration,
 };
@@ -207,6 +208,22 @@ pub async fn spawn_monitor(
     BroadcastListener::start(sender, route_manager, power_mgmt_rx).await
 }
 
+/// Return the gateways of the best default routes, outside any tunnel.
+#[allow(clippy::unused_async)]
+pub async fn default_gateways(_handle: &RouteManagerHandle) -> Vec<IpAddr> {
+    let mut gateways = vec![];
+    for family in [AddressFamily::Ipv4, AddressFamily::Ipv6] {
+        match get_best_default_route(family) {
+            Ok(route) => gateways.extend(route.map(|route| route.gateway.ip())),
+            Err(error) => log::debug!(
+                "{}",
+                error.display_chain_with_msg("Failed to find default route")
+            ),
+        }
+    }
+    gateways
+}
+
 fn apply_system_state_change(state: Arc<Mutex<SystemState>>, change: StateChange) {
     let mut state = state.lock();
     state.apply_change(change);
@@ -86,12 +86,14 @@ pub struct TunnelMonitor {
 // TODO(emilsp) move most of the openvpn tunnel details to OpenVpnTunnelMonitor
 impl TunnelMonitor {
     /// Creates a new `TunnelMonitor` that connects to the given remote and notifies `on_event`
-    /// on tunnel state changes.
+    /// on tunnel state changes. The stats of WireGuard tunnels are made available through
+    /// `stats`.
     #[cfg_attr(any(target_os = "android", windows), allow(unused_variables))]
     pub fn start<L>(
         tunnel_parameters: &mut TunnelParameters,
         log_dir: &Option<path::PathBuf>,
         args: TunnelArgs<'_, L>,
+        stats: talpid_wireguard::stats::StatsHandle,
     ) -> Result<Self>
     where
         L: (Fn(TunnelEvent) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>)
@@ -117,7 +119,7 @@ impl TunnelMonitor {
             TunnelParameters::OpenVpn(_) => Err(Error::UnsupportedPlatform),
 
             TunnelParameters::Wireguard(ref mut config) => {
-                Self::start_wireguard_tunnel(config, log_file, args)
+                Self::start_wireguard_tunnel(config, log_file, args, stats)
             }
         }
     }
@@ -149,6 +151,7 @@ impl TunnelMonitor {
         params: &mut wireguard_types::TunnelParameters,
         log: Option<path::PathBuf>,
         args: TunnelArgs<'_, L>,
+        stats: talpid_wireguard::stats::StatsHandle,
     ) -> Result<Self>
     where
         L: (Fn(TunnelEvent) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>)
@@ -181,6 +184,7 @@ impl TunnelMonitor {
             detect_mtu,
             log.as_deref(),
             args,
+            stats,
         )?;
         Ok(TunnelMonitor {
             monitor: InternalTunnelMonitor::Wireguard(monitor),
@@ -55,8 +55,10 @@ impl ConnectedState {
         };
 
         let tunnel_interface = Some(connected_state.metadata.interface.clone());
+        let tunnel_gateway = Some(IpAddr::V4(connected_state.metadata.ipv4_gateway));
         let tunnel_endpoint = talpid_types::net::TunnelEndpoint {
             tunnel_interface,
+            tunnel_gateway,
             ..connected_state.tunnel_parameters.get_tunnel_endpoint()
         };
 
@@ -68,6 +70,7 @@ impl ConnectedState {
             )
         } else if let Err(error) = connected_state.set_dns(shared_values) {
             log::error!("{}", error.display_chain_with_msg("Failed to set DNS"));
+            shared_values.error_recorder.record(&error);
             DisconnectingState::enter(
                 connected_state.tunnel_close_tx,
                 connected_state.tunnel_close_event,
@@ -96,6 +99,7 @@ impl ConnectedState {
                         "Failed to apply firewall policy for connected state"
                     )
                 );
+                shared_values.error_recorder.record(&error);
                 #[cfg(windows)]
                 match error {
                     crate::firewall::Error::ApplyingConnectedPolicy(policy_error) => policy_error,
@@ -162,7 +166,16 @@ impl ConnectedState {
             tunnel: self.metadata.clone(),
             allow_lan: shared_values.allow_lan,
             #[cfg(not(target_os = "android"))]
-            dns_servers: self.get_dns_servers(shared_values),
+            dns_servers: self
+                .get_dns_servers(shared_values)
+                .into_iter()
+                .chain(shared_values.dns_forwarder_upstreams.iter().copied())
+                .chain(
+                    shared_values
+                        .dns_forward_to_gateway
+                        .then_some(IpAddr::V4(self.metadata.ipv4_gateway)),
+                )
+                .collect(),
             #[cfg(target_os = "macos")]
             redirect_interface,
         }
@@ -171,6 +184,8 @@ impl ConnectedState {
     fn set_dns(&self, shared_values: &mut SharedTunnelStateValues) -> Result<(), BoxedError> {
         let dns_ips = self.get_dns_servers(shared_values);
 
+        // Local resolvers are not set on the tunnel interface, except for the gateway and the
+        // DNS forwarder, which is reached over the loopback interface.
         #[cfg(any(target_os = "linux", target_os = "windows"))]
         let dns_ips = dns_ips
             .into_iter()
@@ -178,6 +193,7 @@ impl ConnectedState {
                 !crate::firewall::is_local_address(ip)
                     || IpAddr::V4(self.metadata.ipv4_gateway) == *ip
                     || self.metadata.ipv6_gateway.map(IpAddr::V6) == Some(*ip)
+                    || IpAddr::V4(crate::dns::forwarder::LISTEN_ADDR) == *ip
             })
             .collect::<Vec<_>>();
 
@@ -263,8 +279,14 @@ impl ConnectedState {
                 let _ = tx.send(());
                 SameState(self)
             }
-            Some(TunnelCommand::Dns(servers, complete_tx)) => {
-                let consequence = match shared_values.set_dns_servers(servers) {
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::AllowCaptivePortal(hosts, tx)) => {
+                shared_values.set_captive_portal_hosts(hosts);
+                let _ = tx.send(());
+                SameState(self)
+            }
+            Some(TunnelCommand::Dns(dns_config, complete_tx)) => {
+                let consequence = match shared_values.set_dns_config(dns_config) {
                     Ok(true) => {
                         if let Err(error) = self.set_firewall_policy(shared_values) {
                             return self.disconnect(
@@ -285,6 +307,7 @@ impl ConnectedState {
                                     "{}",
                                     error.display_chain_with_msg("Failed to set DNS")
                                 );
+                                shared_values.error_recorder.record(&error);
                                 self.disconnect(
                                     shared_values,
                                     AfterDisconnect::Block(ErrorStateCause::SetDnsError),
@@ -305,6 +328,14 @@ impl ConnectedState {
                 let _ = complete_tx.send(());
                 SameState(self)
             }
+            Some(TunnelCommand::DnsLeakTest(result_tx)) => {
+                let expected_resolvers = self.get_dns_servers(shared_values);
+                shared_values.runtime.spawn(async move {
+                    let result = crate::dns::leak_test::run(expected_resolvers).await;
+                    let _ = result_tx.send(Some(result));
+                });
+                SameState(self)
+            }
             Some(TunnelCommand::Connectivity(connectivity)) => {
                 shared_values.connectivity = connectivity;
                 if connectivity.is_offline() {
@@ -1,7 +1,7 @@
 use super::{
-    AfterDisconnect, ConnectedState, DisconnectingState, ErrorState, EventConsequence, EventResult,
-    SharedTunnelStateValues, TunnelCommand, TunnelCommandReceiver, TunnelState,
-    TunnelStateTransition,
+    diagnostics::ErrorRecorder, AfterDisconnect, ConnectedState, DisconnectingState, ErrorState,
+    EventConsequence, EventResult, SharedTunnelStateValues, TunnelCommand, TunnelCommandReceiver,
+    TunnelState, TunnelStateTransition,
 };
 use crate::{
     firewall::FirewallPolicy,
@@ -25,6 +25,7 @@ use talpid_types::{
     tunnel::{ErrorStateCause, FirewallPolicyError},
     ErrorExt,
 };
+use talpid_wireguard::stats::StatsHandle;
 
 #[cfg(target_os = "android")]
 use talpid_tunnel::tun_provider;
@@ -57,6 +58,9 @@ impl ConnectingState {
         shared_values: &mut SharedTunnelStateValues,
         retry_attempt: u32,
     ) -> (Box<dyn TunnelState>, TunnelStateTransition) {
+        // Forget errors from previous connection attempts
+        let _ = shared_values.error_recorder.take();
+
         if shared_values.connectivity.is_offline() {
             // FIXME: Temporary: Nudge route manager to update the default interface
             #[cfg(target_os = "macos")]
@@ -119,6 +123,8 @@ impl ConnectingState {
                         &shared_values.resource_dir,
                         shared_values.tun_provider.clone(),
                         &shared_values.route_manager,
+                        shared_values.error_recorder.clone(),
+                        shared_values.tunnel_stats.clone(),
                         retry_attempt,
                     );
                     let params = connecting_state.tunnel_parameters.clone();
@@ -182,6 +188,7 @@ impl ConnectingState {
                         "Failed to apply firewall policy for connecting state"
                     )
                 );
+                shared_values.error_recorder.record(&error);
                 match error {
                     #[cfg(windows)]
                     crate::firewall::Error::ApplyingConnectingPolicy(policy_error) => policy_error,
@@ -197,6 +204,8 @@ impl ConnectingState {
         resource_dir: &Path,
         tun_provider: Arc<Mutex<TunProvider>>,
         route_manager: &RouteManagerHandle,
+        error_recorder: ErrorRecorder,
+        tunnel_stats: StatsHandle,
         retry_attempt: u32,
     ) -> Self {
         let (event_tx, event_rx) = mpsc::unbounded();
@@ -231,7 +240,9 @@ impl ConnectingState {
                 route_manager,
             };
 
-            let block_reason = match TunnelMonitor::start(&mut tunnel_parameters, &log_dir, args) {
+            let monitor =
+                TunnelMonitor::start(&mut tunnel_parameters, &log_dir, args, tunnel_stats);
+            let block_reason = match monitor {
                 Ok(monitor) => {
                     let reason = Self::wait_for_tunnel_monitor(monitor, retry_attempt);
                     log::debug!("Tunnel monitor exited with block reason: {:?}", reason);
@@ -248,6 +259,7 @@ impl ConnectingState {
                 }
                 Err(error) => {
                     log::error!("{}", error.display_chain_with_msg("Failed to start tunnel"));
+                    error_recorder.record(&error);
                     let block_reason = match error {
                         tunnel::Error::EnableIpv6Error => ErrorStateCause::Ipv6Unavailable,
                         #[cfg(target_os = "android")]
@@ -430,8 +442,14 @@ impl ConnectingState {
                 let _ = tx.send(());
                 SameState(self)
             }
-            Some(TunnelCommand::Dns(servers, complete_tx)) => {
-                let consequence = match shared_values.set_dns_servers(servers) {
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::AllowCaptivePortal(hosts, tx)) => {
+                shared_values.set_captive_portal_hosts(hosts);
+                let _ = tx.send(());
+                SameState(self)
+            }
+            Some(TunnelCommand::Dns(dns_config, complete_tx)) => {
+                let consequence = match shared_values.set_dns_config(dns_config) {
                     #[cfg(target_os = "android")]
                     Ok(true) => self.disconnect(shared_values, AfterDisconnect::Reconnect(0)),
                     Ok(_) => SameState(self),
@@ -445,6 +463,10 @@ impl ConnectingState {
                 let _ = complete_tx.send(());
                 SameState(self)
             }
+            Some(TunnelCommand::DnsLeakTest(result_tx)) => {
+                let _ = result_tx.send(None);
+                SameState(self)
+            }
             Some(TunnelCommand::Connectivity(connectivity)) => {
                 shared_values.connectivity = connectivity;
                 if connectivity.is_offline() {
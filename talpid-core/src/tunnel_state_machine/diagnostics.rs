@@ -0,0 +1,141 @@
+use crate::dns::DnsMonitor;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use talpid_types::{
+    tunnel::{ErrorDiagnostics, ErrorStateCause, ErrorSubsystem, RemediationCode},
+    ErrorExt,
+};
+
+/// Keeps the error chain of the most recent failure, so that it can be included in the
+/// diagnostics of the error state. The recorder is shared with the tunnel monitor thread.
+#[derive(Clone, Default)]
+pub(super) struct ErrorRecorder(Arc<Mutex<Vec<String>>>);
+
+impl ErrorRecorder {
+    /// Remember the error chain of `error`, replacing any previously recorded error.
+    pub fn record<E: std::error::Error>(&self, error: &E) {
+        *self.0.lock().unwrap() = error.error_chain();
+    }
+
+    /// Remove and return the most recently recorded error chain.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Collect diagnostics for the error state entered because of `cause`.
+///
+/// This runs on the state machine thread, so it only uses information that is cheap to obtain.
+/// For instance, the DNS manager is the one that `dns_monitor` detected when DNS was last set.
+pub(super) fn collect(
+    cause: &ErrorStateCause,
+    error_chain: Vec<String>,
+    dns_monitor: &DnsMonitor,
+) -> ErrorDiagnostics {
+    let environment = detect_environment(cause.subsystem(), dns_monitor);
+    diagnose(cause, error_chain, environment)
+}
+
+/// Create diagnostics for `cause` from the recorded `error_chain` and the detected `environment`.
+fn diagnose(
+    cause: &ErrorStateCause,
+    mut error_chain: Vec<String>,
+    environment: BTreeMap<String, String>,
+) -> ErrorDiagnostics {
+    let mut diagnostics = ErrorDiagnostics::from_cause(cause);
+    if error_chain.is_empty() {
+        error_chain.push(cause.to_string());
+    }
+    diagnostics.error_chain = error_chain;
+    diagnostics.environment = environment;
+
+    if diagnostics
+        .environment
+        .contains_key("missing_kernel_module")
+    {
+        diagnostics.remediation = RemediationCode::LoadKernelModule;
+    }
+
+    diagnostics
+}
+
+#[cfg(target_os = "linux")]
+fn detect_environment(
+    subsystem: ErrorSubsystem,
+    dns_monitor: &DnsMonitor,
+) -> BTreeMap<String, String> {
+    let mut environment = BTreeMap::new();
+
+    if subsystem == ErrorSubsystem::Dns {
+        environment.insert(
+            "dns_manager".to_owned(),
+            dns_monitor
+                .detected_manager()
+                .unwrap_or("unknown")
+                .to_owned(),
+        );
+    }
+
+    let required_module = match subsystem {
+        ErrorSubsystem::Firewall => Some("nf_tables"),
+        ErrorSubsystem::Tunnel | ErrorSubsystem::TunnelDevice => Some("wireguard"),
+        _ => None,
+    };
+    if let Some(module) = required_module {
+        // Modules built into the kernel are also listed in /sys/module.
+        if !std::path::Path::new("/sys/module").join(module).exists() {
+            environment.insert("missing_kernel_module".to_owned(), module.to_owned());
+        }
+    }
+
+    environment
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect_environment(
+    _subsystem: ErrorSubsystem,
+    _dns_monitor: &DnsMonitor,
+) -> BTreeMap<String, String> {
+    BTreeMap::new()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diagnose_uses_cause_without_error_chain() {
+        let diagnostics = diagnose(&ErrorStateCause::SetDnsError, vec![], BTreeMap::new());
+        assert_eq!(
+            diagnostics.error_chain,
+            vec![ErrorStateCause::SetDnsError.to_string()]
+        );
+        assert_eq!(diagnostics.subsystem, ErrorSubsystem::Dns);
+    }
+
+    #[test]
+    fn test_diagnose_keeps_error_chain() {
+        let error_chain = vec!["outer".to_owned(), "inner".to_owned()];
+        let diagnostics = diagnose(
+            &ErrorStateCause::SetDnsError,
+            error_chain.clone(),
+            BTreeMap::new(),
+        );
+        assert_eq!(diagnostics.error_chain, error_chain);
+    }
+
+    #[test]
+    fn test_diagnose_missing_kernel_module() {
+        let environment =
+            BTreeMap::from([("missing_kernel_module".to_owned(), "wireguard".to_owned())]);
+        let diagnostics = diagnose(
+            &ErrorStateCause::StartTunnelError,
+            vec![],
+            environment.clone(),
+        );
+        assert_eq!(diagnostics.environment, environment);
+        assert_eq!(diagnostics.remediation, RemediationCode::LoadKernelModule);
+    }
+}
@@ -66,6 +66,8 @@ impl DisconnectedState {
             let policy = FirewallPolicy::Blocked {
                 allow_lan: shared_values.allow_lan,
                 allowed_endpoint: Some(shared_values.allowed_endpoint.clone()),
+                #[cfg(not(target_os = "android"))]
+                captive_portal: shared_values.captive_portal.clone(),
                 #[cfg(target_os = "macos")]
                 dns_redirect_port: shared_values.filtering_resolver.listening_port(),
             };
@@ -158,10 +160,29 @@ impl TunnelState for DisconnectedState {
                 let _ = tx.send(());
                 SameState(self)
             }
-            Some(TunnelCommand::Dns(servers, complete_tx)) => {
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::AllowCaptivePortal(hosts, tx)) => {
+                if shared_values.set_captive_portal_hosts(hosts) {
+                    #[cfg(target_os = "macos")]
+                    if shared_values.captive_portal.is_none()
+                        && shared_values.block_when_disconnected
+                    {
+                        if let Err(err) = Self::setup_local_dns_config(shared_values) {
+                            log::error!(
+                                "{}",
+                                err.display_chain_with_msg("Failed to start filtering resolver:")
+                            );
+                        }
+                    }
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                let _ = tx.send(());
+                SameState(self)
+            }
+            Some(TunnelCommand::Dns(dns_config, complete_tx)) => {
                 // Same situation as allow LAN above.
                 shared_values
-                    .set_dns_servers(servers)
+                    .set_dns_config(dns_config)
                     .expect("Failed to reconnect after changing custom DNS servers");
                 let _ = complete_tx.send(());
                 SameState(self)
@@ -200,6 +221,10 @@ impl TunnelState for DisconnectedState {
                     SameState(self)
                 }
             }
+            Some(TunnelCommand::DnsLeakTest(result_tx)) => {
+                let _ = result_tx.send(None);
+                SameState(self)
+            }
             Some(TunnelCommand::Connectivity(connectivity)) => {
                 shared_values.connectivity = connectivity;
                 SameState(self)
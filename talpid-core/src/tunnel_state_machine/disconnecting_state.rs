@@ -50,8 +50,14 @@ impl DisconnectingState {
                     let _ = tx.send(());
                     AfterDisconnect::Nothing
                 }
-                Some(TunnelCommand::Dns(servers, complete_tx)) => {
-                    let _ = shared_values.set_dns_servers(servers);
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::AllowCaptivePortal(hosts, tx)) => {
+                    shared_values.set_captive_portal_hosts(hosts);
+                    let _ = tx.send(());
+                    AfterDisconnect::Nothing
+                }
+                Some(TunnelCommand::Dns(dns_config, complete_tx)) => {
+                    let _ = shared_values.set_dns_config(dns_config);
                     let _ = complete_tx.send(());
                     AfterDisconnect::Nothing
                 }
@@ -63,6 +69,10 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Nothing
                 }
+                Some(TunnelCommand::DnsLeakTest(result_tx)) => {
+                    let _ = result_tx.send(None);
+                    AfterDisconnect::Nothing
+                }
                 Some(TunnelCommand::Connectivity(connectivity)) => {
                     shared_values.connectivity = connectivity;
                     AfterDisconnect::Nothing
@@ -103,8 +113,14 @@ impl DisconnectingState {
                     let _ = tx.send(());
                     AfterDisconnect::Block(reason)
                 }
-                Some(TunnelCommand::Dns(servers, complete_tx)) => {
-                    let _ = shared_values.set_dns_servers(servers);
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::AllowCaptivePortal(hosts, tx)) => {
+                    shared_values.set_captive_portal_hosts(hosts);
+                    let _ = tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
+                Some(TunnelCommand::Dns(dns_config, complete_tx)) => {
+                    let _ = shared_values.set_dns_config(dns_config);
                     let _ = complete_tx.send(());
                     AfterDisconnect::Block(reason)
                 }
@@ -116,6 +132,10 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Block(reason)
                 }
+                Some(TunnelCommand::DnsLeakTest(result_tx)) => {
+                    let _ = result_tx.send(None);
+                    AfterDisconnect::Block(reason)
+                }
                 Some(TunnelCommand::Connectivity(connectivity)) => {
                     shared_values.connectivity = connectivity;
                     if !connectivity.is_offline() && matches!(reason, ErrorStateCause::IsOffline) {
@@ -160,8 +180,14 @@ impl DisconnectingState {
                     let _ = tx.send(());
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
-                Some(TunnelCommand::Dns(servers, complete_tx)) => {
-                    let _ = shared_values.set_dns_servers(servers);
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::AllowCaptivePortal(hosts, tx)) => {
+                    shared_values.set_captive_portal_hosts(hosts);
+                    let _ = tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                Some(TunnelCommand::Dns(dns_config, complete_tx)) => {
+                    let _ = shared_values.set_dns_config(dns_config);
                     let _ = complete_tx.send(());
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
@@ -173,6 +199,10 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                Some(TunnelCommand::DnsLeakTest(result_tx)) => {
+                    let _ = result_tx.send(None);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::Connectivity(connectivity)) => {
                     shared_values.connectivity = connectivity;
                     if connectivity.is_offline() {
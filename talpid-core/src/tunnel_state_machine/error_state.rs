@@ -1,6 +1,6 @@
 use super::{
-    ConnectingState, DisconnectedState, EventConsequence, SharedTunnelStateValues, TunnelCommand,
-    TunnelCommandReceiver, TunnelState, TunnelStateTransition,
+    diagnostics, ConnectingState, DisconnectedState, EventConsequence, SharedTunnelStateValues,
+    TunnelCommand, TunnelCommandReceiver, TunnelState, TunnelStateTransition,
 };
 use crate::firewall::FirewallPolicy;
 use futures::StreamExt;
@@ -56,14 +56,20 @@ impl ErrorState {
         } else {
             None
         };
+
+        let diagnostics = diagnostics::collect(
+            &block_reason,
+            shared_values.error_recorder.take(),
+            &shared_values.dns_monitor,
+        );
         (
             Box::new(ErrorState {
                 block_reason: block_reason.clone(),
             }),
-            TunnelStateTransition::Error(talpid_tunnel::ErrorState::new(
-                block_reason,
-                block_failure,
-            )),
+            TunnelStateTransition::Error(
+                talpid_tunnel::ErrorState::new(block_reason, block_failure)
+                    .with_diagnostics(diagnostics),
+            ),
         )
     }
 
@@ -73,6 +79,8 @@ impl ErrorState {
         let policy = FirewallPolicy::Blocked {
             allow_lan: shared_values.allow_lan,
             allowed_endpoint: Some(shared_values.allowed_endpoint.clone()),
+            #[cfg(not(target_os = "android"))]
+            captive_portal: shared_values.captive_portal.clone(),
             #[cfg(target_os = "macos")]
             dns_redirect_port: shared_values.filtering_resolver.listening_port(),
         };
@@ -166,9 +174,33 @@ impl TunnelState for ErrorState {
                 let _ = tx.send(());
                 SameState(self)
             }
-            Some(TunnelCommand::Dns(servers, complete_tx)) => {
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::AllowCaptivePortal(hosts, tx)) => {
+                if shared_values.set_captive_portal_hosts(hosts) {
+                    #[cfg(target_os = "macos")]
+                    if shared_values.captive_portal.is_none()
+                        && !self.block_reason.prevents_filtering_resolver()
+                    {
+                        if let Err(err) = shared_values
+                            .dns_monitor
+                            .set("lo", &[Ipv4Addr::LOCALHOST.into()])
+                        {
+                            log::error!(
+                                "{}",
+                                err.display_chain_with_msg(
+                                    "Failed to configure system to use filtering resolver"
+                                )
+                            );
+                        }
+                    }
+                    let _ = Self::set_firewall_policy(shared_values);
+                }
+                let _ = tx.send(());
+                SameState(self)
+            }
+            Some(TunnelCommand::Dns(dns_config, complete_tx)) => {
                 let consequence =
-                    if let Err(error_state_cause) = shared_values.set_dns_servers(servers) {
+                    if let Err(error_state_cause) = shared_values.set_dns_config(dns_config) {
                         NewState(Self::enter(shared_values, error_state_cause))
                     } else {
                         SameState(self)
@@ -181,6 +213,10 @@ impl TunnelState for ErrorState {
                 let _ = complete_tx.send(());
                 SameState(self)
             }
+            Some(TunnelCommand::DnsLeakTest(result_tx)) => {
+                let _ = result_tx.send(None);
+                SameState(self)
+            }
             Some(TunnelCommand::Connectivity(connectivity)) => {
                 shared_values.connectivity = connectivity;
                 if !connectivity.is_offline()
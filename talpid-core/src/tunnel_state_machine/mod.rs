@@ -1,5 +1,6 @@
 mod connected_state;
 mod connecting_state;
+mod diagnostics;
 mod disconnected_state;
 mod disconnecting_state;
 mod error_state;
@@ -7,10 +8,13 @@ mod error_state;
 use self::{
     connected_state::ConnectedState,
     connecting_state::ConnectingState,
+    diagnostics::ErrorRecorder,
     disconnected_state::DisconnectedState,
     disconnecting_state::{AfterDisconnect, DisconnectingState},
     error_state::ErrorState,
 };
+#[cfg(not(target_os = "android"))]
+use crate::firewall::CaptivePortalAccess;
 #[cfg(any(windows, target_os = "android", target_os = "macos"))]
 use crate::split_tunnel;
 use crate::{
@@ -21,12 +25,15 @@ use crate::{
 };
 #[cfg(any(target_os = "windows", target_os = "macos"))]
 use std::ffi::OsString;
+#[cfg(not(target_os = "android"))]
+use std::time::SystemTime;
 use talpid_routing::RouteManagerHandle;
 #[cfg(target_os = "macos")]
 use talpid_tunnel::TunnelMetadata;
 use talpid_tunnel::{tun_provider::TunProvider, TunnelEvent};
-#[cfg(target_os = "macos")]
+#[cfg(not(target_os = "android"))]
 use talpid_types::ErrorExt;
+use talpid_wireguard::stats::StatsHandle;
 
 use futures::{
     channel::{mpsc, oneshot},
@@ -46,7 +53,7 @@ use std::{
 #[cfg(target_os = "android")]
 use talpid_types::{android::AndroidContext, ErrorExt};
 use talpid_types::{
-    net::{AllowedEndpoint, Connectivity, TunnelParameters},
+    net::{dns::DnsLeakTestResult, AllowedEndpoint, Connectivity, TunnelParameters},
     tunnel::{ErrorStateCause, ParameterGenerationError, TunnelStateTransition},
 };
 
@@ -86,14 +93,26 @@ pub enum Error {
     SendStateChange,
 }
 
+/// DNS servers that the tunnel should use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsConfig {
+    /// DNS servers to use. If `None`, the tunnel gateway is used.
+    pub servers: Option<Vec<IpAddr>>,
+    /// Plain DNS resolvers that a local DNS forwarder in `servers` sends queries to. These are
+    /// reachable through the tunnel but are not used by the system directly.
+    pub forwarder_upstreams: Vec<IpAddr>,
+    /// Whether a local DNS forwarder in `servers` sends queries to the tunnel gateway.
+    pub forward_to_gateway: bool,
+}
+
 /// Settings used to initialize the tunnel state machine.
 pub struct InitialTunnelState {
     /// Whether to allow LAN traffic when not in the (non-blocking) disconnected state.
     pub allow_lan: bool,
     /// Block traffic unless connected to the VPN.
     pub block_when_disconnected: bool,
-    /// DNS servers to use. If `None`, the tunnel gateway is used.
-    pub dns_servers: Option<Vec<IpAddr>>,
+    /// DNS servers to use.
+    pub dns_config: DnsConfig,
     /// A single endpoint that is allowed to communicate outside the tunnel, i.e.
     /// in any of the blocking states.
     pub allowed_endpoint: AllowedEndpoint,
@@ -126,6 +145,7 @@ pub async fn spawn(
     resource_dir: PathBuf,
     state_change_listener: impl Sender<TunnelStateTransition> + Send + 'static,
     offline_state_listener: mpsc::UnboundedSender<Connectivity>,
+    tunnel_stats: StatsHandle,
     #[cfg(target_os = "windows")] volume_update_rx: mpsc::UnboundedReceiver<()>,
     #[cfg(target_os = "android")] android_context: AndroidContext,
     #[cfg(target_os = "linux")] linux_ids: LinuxNetworkingIdentifiers,
@@ -139,7 +159,7 @@ pub async fn spawn(
         #[cfg(target_os = "android")]
         initial_settings.allow_lan,
         #[cfg(target_os = "android")]
-        initial_settings.dns_servers.clone(),
+        initial_settings.dns_config.servers.clone(),
         #[cfg(target_os = "android")]
         crate::firewall::ALLOWED_LAN_NETS
             .iter()
@@ -162,6 +182,7 @@ pub async fn spawn(
         tun_provider,
         log_dir,
         resource_dir,
+        tunnel_stats,
         commands_rx: command_rx,
         #[cfg(target_os = "windows")]
         volume_update_rx,
@@ -199,12 +220,21 @@ pub enum TunnelCommand {
     /// channel after attempting to set the firewall policy, regardless
     /// of whether it succeeded.
     AllowEndpoint(AllowedEndpoint, oneshot::Sender<()>),
+    /// Hosts of a captive portal that should be reachable while blocking, and when they should be
+    /// blocked again, or `None` to block them now. DNS to the gateways of the local network and
+    /// to the resolvers of the system is allowed as well. `()` is sent to the channel after
+    /// attempting to set the firewall policy, regardless of whether it succeeded.
+    #[cfg(not(target_os = "android"))]
+    AllowCaptivePortal(Option<(Vec<IpAddr>, SystemTime)>, oneshot::Sender<()>),
     /// Set DNS servers to use.
-    Dns(Option<Vec<IpAddr>>, oneshot::Sender<()>),
+    Dns(DnsConfig, oneshot::Sender<()>),
     /// Enable or disable the block_when_disconnected feature.
     BlockWhenDisconnected(bool, oneshot::Sender<()>),
     /// Notify the state machine of the connectivity of the device.
     Connectivity(Connectivity),
+    /// Check that DNS requests only reach the resolvers used by the tunnel. `None` is sent to the
+    /// channel unless the tunnel is connected.
+    DnsLeakTest(oneshot::Sender<Option<DnsLeakTestResult>>),
     /// Open tunnel connection.
     Connect,
     /// Close tunnel connection.
@@ -257,6 +287,7 @@ struct TunnelStateMachineInitArgs<G: TunnelParametersGenerator> {
     tun_provider: TunProvider,
     log_dir: Option<PathBuf>,
     resource_dir: PathBuf,
+    tunnel_stats: StatsHandle,
     commands_rx: mpsc::UnboundedReceiver<TunnelCommand>,
     #[cfg(target_os = "windows")]
     volume_update_rx: mpsc::UnboundedReceiver<()>,
@@ -383,12 +414,18 @@ impl TunnelStateMachine {
             allow_lan: args.settings.allow_lan,
             block_when_disconnected: args.settings.block_when_disconnected,
             connectivity,
-            dns_servers: args.settings.dns_servers,
+            dns_servers: args.settings.dns_config.servers,
+            dns_forwarder_upstreams: args.settings.dns_config.forwarder_upstreams,
+            dns_forward_to_gateway: args.settings.dns_config.forward_to_gateway,
             allowed_endpoint: args.settings.allowed_endpoint,
+            #[cfg(not(target_os = "android"))]
+            captive_portal: None,
             tunnel_parameters_generator: Box::new(args.tunnel_parameters_generator),
             tun_provider: Arc::new(Mutex::new(args.tun_provider)),
             log_dir: args.log_dir,
             resource_dir: args.resource_dir,
+            error_recorder: ErrorRecorder::default(),
+            tunnel_stats: args.tunnel_stats,
             #[cfg(target_os = "linux")]
             connectivity_check_was_enabled: None,
             #[cfg(target_os = "macos")]
@@ -476,8 +513,15 @@ struct SharedTunnelStateValues {
     connectivity: Connectivity,
     /// DNS servers to use (overriding default).
     dns_servers: Option<Vec<IpAddr>>,
+    /// Resolvers that a local DNS forwarder in `dns_servers` sends queries to.
+    dns_forwarder_upstreams: Vec<IpAddr>,
+    /// Whether a local DNS forwarder in `dns_servers` sends queries to the tunnel gateway.
+    dns_forward_to_gateway: bool,
     /// Endpoint that should not be blocked by the firewall.
     allowed_endpoint: AllowedEndpoint,
+    /// Captive portal that should not be blocked by the firewall.
+    #[cfg(not(target_os = "android"))]
+    captive_portal: Option<CaptivePortalAccess>,
     /// The generator of new `TunnelParameter`s
     tunnel_parameters_generator: Box<dyn TunnelParametersGenerator>,
     /// The provider of tunnel devices.
@@ -486,6 +530,10 @@ struct SharedTunnelStateValues {
     log_dir: Option<PathBuf>,
     /// Resource directory path.
     resource_dir: PathBuf,
+    /// Error chain of the most recent failure, included in the error state diagnostics.
+    error_recorder: ErrorRecorder,
+    /// Stats of WireGuard tunnels, read by the owner of the state machine.
+    tunnel_stats: StatsHandle,
 
     /// NetworkManager's connecitivity check state.
     #[cfg(target_os = "linux")]
@@ -576,10 +624,19 @@ impl SharedTunnelStateValues {
         Ok(())
     }
 
-    pub fn set_dns_servers(
-        &mut self,
-        dns_servers: Option<Vec<IpAddr>>,
-    ) -> Result<bool, ErrorStateCause> {
+    /// Set the DNS configuration. Returns whether it changed.
+    pub fn set_dns_config(&mut self, dns_config: DnsConfig) -> Result<bool, ErrorStateCause> {
+        let DnsConfig {
+            servers: dns_servers,
+            forwarder_upstreams,
+            forward_to_gateway,
+        } = dns_config;
+        // The forwarder upstreams only affect the firewall policy
+        let upstreams_changed = self.dns_forwarder_upstreams != forwarder_upstreams
+            || self.dns_forward_to_gateway != forward_to_gateway;
+        self.dns_forwarder_upstreams = forwarder_upstreams;
+        self.dns_forward_to_gateway = forward_to_gateway;
+
         if self.dns_servers != dns_servers {
             self.dns_servers = dns_servers;
 
@@ -603,8 +660,54 @@ impl SharedTunnelStateValues {
 
             Ok(true)
         } else {
-            Ok(false)
+            Ok(upstreams_changed)
+        }
+    }
+
+    /// Set the hosts of a captive portal that should be reachable while blocking. The gateways of
+    /// the local network and the resolvers of the system are looked up whenever access is given.
+    /// Return whether the access changed.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_captive_portal_hosts(&mut self, hosts: Option<(Vec<IpAddr>, SystemTime)>) -> bool {
+        // The filtering resolver only answers the connectivity check, so the resolvers of the
+        // system are used while the portal is reachable. The blocked states restore it afterwards.
+        #[cfg(target_os = "macos")]
+        if hosts.is_some() && self.captive_portal.is_none() {
+            if let Err(error) = self.dns_monitor.reset() {
+                log::error!("{}", error.display_chain_with_msg("Unable to reset DNS"));
+            }
+        }
+        let access = hosts.map(|(hosts, expires)| CaptivePortalAccess {
+            gateways: self.runtime.block_on(self.captive_portal_resolvers()),
+            hosts,
+            expires,
+        });
+        if self.captive_portal == access {
+            return false;
+        }
+        self.captive_portal = access;
+        true
+    }
+
+    /// Return the gateways of the local network and the non-loopback resolvers of the system,
+    /// which a captive portal may be resolved by.
+    #[cfg(not(target_os = "android"))]
+    async fn captive_portal_resolvers(&self) -> Vec<IpAddr> {
+        let mut resolvers = offline::default_gateways(&self.route_manager).await;
+        match crate::dns::leak_test::system_resolvers().await {
+            Ok(system_resolvers) => resolvers.extend(
+                system_resolvers
+                    .into_iter()
+                    .filter(|resolver| !resolver.is_loopback()),
+            ),
+            Err(error) => log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to read system DNS resolvers")
+            ),
         }
+        resolvers.sort();
+        resolvers.dedup();
+        resolvers
     }
 
     /// NetworkManager's connectivity check can get hung when DNS requests fail, thus the TSM
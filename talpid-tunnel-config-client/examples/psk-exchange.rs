@@ -5,7 +5,7 @@
 // e. g. ./psk-exchange 10.64.0.1 NkECLsf+VbZUjve7RVN6sE3NYUcYUmUn8qpFugqbXFk=
 
 use std::net::IpAddr;
-use talpid_types::net::wireguard::{PrivateKey, PublicKey};
+use talpid_types::net::wireguard::{PrivateKey, PublicKey, QuantumResistantAlgorithms};
 
 #[tokio::main]
 async fn main() {
@@ -27,7 +27,8 @@ async fn main() {
         IpAddr::V4(tuncfg_server_ip),
         public_key, // Parent connection's public key.
         ephemeral_private_key.public_key(),
-        true,  // Whether to negotiate a "PQ-safe" PSK.
+        // Whether to negotiate a "PQ-safe" PSK, and which KEMs to offer.
+        Some(QuantumResistantAlgorithms::PreferMlKem),
         false, // Whether to use DAITA (Does not work with Linux kernel WireGuard.)
     )
     .await
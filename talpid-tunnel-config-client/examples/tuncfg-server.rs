@@ -6,6 +6,7 @@ mod proto {
     tonic::include_proto!("ephemeralpeer");
 }
 use classic_mceliece_rust::{PublicKey, CRYPTO_PUBLICKEYBYTES};
+use ml_kem::{kem::Encapsulate, Encoded, EncodedSizeUser, KemCore, MlKem1024, MlKem768};
 use proto::{
     ephemeral_peer_server::{EphemeralPeer, EphemeralPeerServer},
     EphemeralPeerRequestV1, EphemeralPeerResponseV1, PostQuantumResponseV1,
@@ -58,7 +59,14 @@ impl EphemeralPeer for EphemeralPeerImpl {
                             pqc_kyber::encapsulate(public_key, &mut rng).unwrap();
                         (ciphertext.to_vec(), shared_secret)
                     }
-                    name => panic!("Unsupported KEM algorithm: {name}"),
+                    "ML-KEM-1024" => encapsulate_ml_kem::<MlKem1024>(&kem_pubkey.key_data)?,
+                    "ML-KEM-768" => encapsulate_ml_kem::<MlKem768>(&kem_pubkey.key_data)?,
+                    name => {
+                        // Clients fall back to other algorithms when they get this error
+                        return Err(Status::invalid_argument(format!(
+                            "Unsupported KEM algorithm: {name}"
+                        )));
+                    }
                 };
 
                 ciphertexts.push(ciphertext);
@@ -82,6 +90,15 @@ impl EphemeralPeer for EphemeralPeerImpl {
     }
 }
 
+fn encapsulate_ml_kem<K: KemCore>(key_data: &[u8]) -> Result<(Vec<u8>, [u8; 32]), Status> {
+    let encoded_key = Encoded::<K::EncapsulationKey>::try_from(key_data)
+        .map_err(|_| Status::invalid_argument("Invalid ML-KEM public key"))?;
+    let public_key = K::EncapsulationKey::from_bytes(&encoded_key);
+    let (ciphertext, shared_secret) = public_key.encapsulate(&mut rand::thread_rng()).unwrap();
+    let shared_secret = <[u8; 32]>::try_from(shared_secret.as_slice()).unwrap();
+    Ok((ciphertext.to_vec(), shared_secret))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr = "127.0.0.1:1337".parse()?;
@@ -1,10 +1,9 @@
-use proto::PostQuantumRequestV1;
 use std::fmt;
 #[cfg(not(target_os = "ios"))]
 use std::net::IpAddr;
 #[cfg(not(target_os = "ios"))]
 use std::net::SocketAddr;
-use talpid_types::net::wireguard::{PresharedKey, PublicKey};
+use talpid_types::net::wireguard::{PresharedKey, PublicKey, QuantumResistantAlgorithms};
 #[cfg(not(target_os = "ios"))]
 use tokio::net::TcpSocket;
 use tonic::transport::Channel;
@@ -16,6 +15,7 @@ use zeroize::Zeroize;
 
 mod classic_mceliece;
 mod kyber;
+mod ml_kem;
 
 #[allow(clippy::derive_partial_eq_without_eq)]
 mod proto {
@@ -51,8 +51,10 @@ pub enum Error {
     },
     InvalidCiphertextCount {
         actual: usize,
+        expected: usize,
     },
     FailedDecapsulateKyber(kyber::KyberError),
+    FailedDecapsulateMlKem,
     #[cfg(target_os = "ios")]
     TcpConnectionExpired,
     #[cfg(target_os = "ios")]
@@ -74,10 +76,14 @@ impl std::fmt::Display for Error {
                 f,
                 "Expected a {expected} bytes ciphertext for {algorithm}, got {actual} bytes"
             ),
-            InvalidCiphertextCount { actual } => {
-                write!(f, "Expected 2 ciphertext in the response, got {actual}")
+            InvalidCiphertextCount { actual, expected } => {
+                write!(
+                    f,
+                    "Expected {expected} ciphertexts in the response, got {actual}"
+                )
             }
             FailedDecapsulateKyber(_) => "Failed to decapsulate Kyber1024 ciphertext".fmt(f),
+            FailedDecapsulateMlKem => "Failed to decapsulate ML-KEM ciphertext".fmt(f),
             #[cfg(target_os = "ios")]
             TcpConnectionExpired => "TCP connection is already shut down".fmt(f),
             #[cfg(target_os = "ios")]
@@ -117,14 +123,241 @@ pub struct EphemeralPeer {
     pub psk: Option<PresharedKey>,
 }
 
+/// A KEM that is combined with Classic McEliece to derive a PQ-safe PSK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kem {
+    MlKem1024,
+    MlKem768,
+    Kyber1024,
+}
+
+impl Kem {
+    /// The name that identifies the KEM to the config service.
+    pub fn algorithm_name(self) -> &'static str {
+        match self {
+            Kem::MlKem1024 => ml_kem::ML_KEM_1024_ALGORITHM_NAME,
+            Kem::MlKem768 => ml_kem::ML_KEM_768_ALGORITHM_NAME,
+            Kem::Kyber1024 => kyber::ALGORITHM_NAME,
+        }
+    }
+
+    /// KEMs to offer the config service, in order of preference.
+    pub fn negotiation_order(algorithms: QuantumResistantAlgorithms) -> &'static [Kem] {
+        match algorithms {
+            QuantumResistantAlgorithms::PreferMlKem => {
+                &[Kem::MlKem1024, Kem::MlKem768, Kem::Kyber1024]
+            }
+            QuantumResistantAlgorithms::RequireMlKem => &[Kem::MlKem1024, Kem::MlKem768],
+            QuantumResistantAlgorithms::Kyber => &[Kem::Kyber1024],
+        }
+    }
+
+    fn keypair(self) -> (proto::KemPubkeyV1, KemSecret) {
+        let (key_data, secret) = match self {
+            Kem::MlKem1024 => {
+                let (public, secret) = ml_kem::keypair::<ml_kem::MlKem1024>();
+                (public, KemSecret::MlKem1024(secret))
+            }
+            Kem::MlKem768 => {
+                let (public, secret) = ml_kem::keypair::<ml_kem::MlKem768>();
+                (public, KemSecret::MlKem768(secret))
+            }
+            Kem::Kyber1024 => {
+                let keypair = kyber::keypair(&mut rand::thread_rng());
+                (
+                    keypair.public.to_vec(),
+                    KemSecret::Kyber1024(keypair.secret),
+                )
+            }
+        };
+        let pubkey = proto::KemPubkeyV1 {
+            algorithm_name: self.algorithm_name().to_owned(),
+            key_data,
+        };
+        (pubkey, secret)
+    }
+}
+
+impl fmt::Display for Kem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.algorithm_name().fmt(f)
+    }
+}
+
+enum KemSecret {
+    MlKem1024(ml_kem::SecretKey<ml_kem::MlKem1024>),
+    MlKem768(ml_kem::SecretKey<ml_kem::MlKem768>),
+    Kyber1024(kyber::SecretKey),
+}
+
+impl KemSecret {
+    fn decapsulate(&self, ciphertext: &[u8]) -> Result<[u8; 32], Error> {
+        match self {
+            KemSecret::MlKem1024(secret) => ml_kem::decapsulate::<ml_kem::MlKem1024>(
+                ml_kem::ML_KEM_1024_ALGORITHM_NAME,
+                secret,
+                ciphertext,
+            ),
+            KemSecret::MlKem768(secret) => ml_kem::decapsulate::<ml_kem::MlKem768>(
+                ml_kem::ML_KEM_768_ALGORITHM_NAME,
+                secret,
+                ciphertext,
+            ),
+            KemSecret::Kyber1024(secret) => kyber::decapsulate(*secret, ciphertext),
+        }
+    }
+}
+
+impl Drop for KemSecret {
+    fn drop(&mut self) {
+        if let KemSecret::Kyber1024(secret) = self {
+            secret.zeroize();
+        }
+    }
+}
+
+/// Negotiate a short-lived peer using `client`. If `post_quantum` is set, a PQ-safe PSK is
+/// derived using the most preferred set of KEMs that the config service supports.
 pub async fn request_ephemeral_peer_with(
     mut client: RelayConfigService,
     parent_pubkey: PublicKey,
     ephemeral_pubkey: PublicKey,
-    enable_post_quantum: bool,
+    post_quantum: Option<QuantumResistantAlgorithms>,
     enable_daita: bool,
 ) -> Result<EphemeralPeer, Error> {
-    let (pq_request, kem_secrets) = post_quantum_secrets(enable_post_quantum).await;
+    let Some(algorithms) = post_quantum else {
+        register_peer(
+            &mut client,
+            &parent_pubkey,
+            &ephemeral_pubkey,
+            None,
+            enable_daita,
+        )
+        .await?;
+        return Ok(EphemeralPeer { psk: None });
+    };
+
+    // The Classic McEliece keypair is expensive to generate, so it is reused for every attempt.
+    let (cme_kem_pubkey, cme_kem_secret) = classic_mceliece::generate_keys().await;
+    let cme_kem_pubkey = proto::KemPubkeyV1 {
+        algorithm_name: classic_mceliece::ALGORITHM_NAME.to_owned(),
+        key_data: cme_kem_pubkey.as_array().to_vec(),
+    };
+
+    let (last_kem, preferred_kems) = Kem::negotiation_order(algorithms)
+        .split_last()
+        .expect("there is always at least one KEM to offer");
+
+    for kem in preferred_kems {
+        let result = request_psk_with_kem(
+            &mut client,
+            &parent_pubkey,
+            &ephemeral_pubkey,
+            &cme_kem_pubkey,
+            &cme_kem_secret,
+            *kem,
+            enable_daita,
+        )
+        .await;
+        match result {
+            Err(Error::GrpcError(status)) if is_unsupported_kem(&status, *kem) => {
+                log::debug!(
+                    "Config service does not support {kem}: {}",
+                    status.message()
+                );
+            }
+            result => return result.map(|psk| EphemeralPeer { psk: Some(psk) }),
+        }
+    }
+
+    let psk = request_psk_with_kem(
+        &mut client,
+        &parent_pubkey,
+        &ephemeral_pubkey,
+        &cme_kem_pubkey,
+        &cme_kem_secret,
+        *last_kem,
+        enable_daita,
+    )
+    .await?;
+    Ok(EphemeralPeer { psk: Some(psk) })
+}
+
+/// Register an ephemeral peer and derive its PSK from Classic McEliece and `kem`.
+async fn request_psk_with_kem(
+    client: &mut RelayConfigService,
+    parent_pubkey: &PublicKey,
+    ephemeral_pubkey: &PublicKey,
+    cme_kem_pubkey: &proto::KemPubkeyV1,
+    cme_kem_secret: &classic_mceliece::SecretKey<'static>,
+    kem: Kem,
+    enable_daita: bool,
+) -> Result<PresharedKey, Error> {
+    let (kem_pubkey, kem_secret) = kem.keypair();
+    let request = proto::PostQuantumRequestV1 {
+        kem_pubkeys: vec![cme_kem_pubkey.clone(), kem_pubkey],
+    };
+    let response = register_peer(
+        client,
+        parent_pubkey,
+        ephemeral_pubkey,
+        Some(request),
+        enable_daita,
+    )
+    .await?;
+
+    let ciphertexts = response
+        .post_quantum
+        .ok_or(Error::MissingCiphertexts)?
+        .ciphertexts;
+
+    // Unpack the ciphertexts into one per KEM without needing to access them by index.
+    let [cme_ciphertext, kem_ciphertext] = <&[Vec<u8>; 2]>::try_from(ciphertexts.as_slice())
+        .map_err(|_| Error::InvalidCiphertextCount {
+            actual: ciphertexts.len(),
+            expected: 2,
+        })?;
+
+    // Store the PSK data on the heap. So it can be passed around and then zeroized on drop
+    // without being stored in a bunch of places on the stack.
+    let mut psk_data = Box::new([0u8; 32]);
+
+    // Decapsulate Classic McEliece and mix into PSK
+    {
+        let mut shared_secret = classic_mceliece::decapsulate(cme_kem_secret, cme_ciphertext)?;
+        xor_assign(&mut psk_data, shared_secret.as_array());
+
+        // This should happen automatically due to `SharedSecret` implementing ZeroizeOnDrop.
+        // But doing it explicitly provides a stronger guarantee that it's not
+        // accidentally removed.
+        shared_secret.zeroize();
+    }
+    // Decapsulate the second KEM and mix into PSK
+    {
+        let mut shared_secret = kem_secret.decapsulate(kem_ciphertext)?;
+        xor_assign(&mut psk_data, &shared_secret);
+
+        // The shared secret is sadly stored in an array on the stack. So we can't get any
+        // guarantees that it's not copied around on the stack. The best we can do here
+        // is to zero out the version we have and hope the compiler optimizes out copies.
+        // https://github.com/Argyle-Software/kyber/issues/59
+        shared_secret.zeroize();
+    }
+
+    log::debug!(
+        "Derived PSK using {} and {kem}",
+        classic_mceliece::ALGORITHM_NAME
+    );
+    Ok(PresharedKey::from(psk_data))
+}
+
+async fn register_peer(
+    client: &mut RelayConfigService,
+    parent_pubkey: &PublicKey,
+    ephemeral_pubkey: &PublicKey,
+    post_quantum: Option<proto::PostQuantumRequestV1>,
+    enable_daita: bool,
+) -> Result<proto::EphemeralPeerResponseV1, Error> {
     let daita = Some(proto::DaitaRequestV1 {
         activate_daita: enable_daita,
     });
@@ -133,57 +366,26 @@ pub async fn request_ephemeral_peer_with(
         .register_peer_v1(proto::EphemeralPeerRequestV1 {
             wg_parent_pubkey: parent_pubkey.as_bytes().to_vec(),
             wg_ephemeral_peer_pubkey: ephemeral_pubkey.as_bytes().to_vec(),
-            post_quantum: pq_request,
+            post_quantum,
             daita,
         })
         .await
         .map_err(Error::GrpcError)?;
+    Ok(response.into_inner())
+}
 
-    let psk = if let Some((cme_kem_secret, kyber_secret)) = kem_secrets {
-        let ciphertexts = response
-            .into_inner()
-            .post_quantum
-            .ok_or(Error::MissingCiphertexts)?
-            .ciphertexts;
-
-        // Unpack the ciphertexts into one per KEM without needing to access them by index.
-        let [cme_ciphertext, kyber_ciphertext] = <&[Vec<u8>; 2]>::try_from(ciphertexts.as_slice())
-            .map_err(|_| Error::InvalidCiphertextCount {
-                actual: ciphertexts.len(),
-            })?;
-
-        // Store the PSK data on the heap. So it can be passed around and then zeroized on drop
-        // without being stored in a bunch of places on the stack.
-        let mut psk_data = Box::new([0u8; 32]);
-
-        // Decapsulate Classic McEliece and mix into PSK
-        {
-            let mut shared_secret = classic_mceliece::decapsulate(&cme_kem_secret, cme_ciphertext)?;
-            xor_assign(&mut psk_data, shared_secret.as_array());
-
-            // This should happen automatically due to `SharedSecret` implementing ZeroizeOnDrop.
-            // But doing it explicitly provides a stronger guarantee that it's not
-            // accidentally removed.
-            shared_secret.zeroize();
-        }
-        // Decapsulate Kyber and mix into PSK
-        {
-            let mut shared_secret = kyber::decapsulate(kyber_secret, kyber_ciphertext)?;
-            xor_assign(&mut psk_data, &shared_secret);
-
-            // The shared secret is sadly stored in an array on the stack. So we can't get any
-            // guarantees that it's not copied around on the stack. The best we can do here
-            // is to zero out the version we have and hope the compiler optimizes out copies.
-            // https://github.com/Argyle-Software/kyber/issues/59
-            shared_secret.zeroize();
-        }
-
-        Some(PresharedKey::from(psk_data))
-    } else {
-        None
-    };
-
-    Ok(EphemeralPeer { psk })
+/// Message prefix of the error the config service returns for a KEM it does not support.
+const UNSUPPORTED_KEM_MESSAGE: &str = "Unsupported KEM algorithm";
+
+/// Returns whether the config service rejected a request because it does not support `kem`.
+/// Any other error, including other `INVALID_ARGUMENT` errors, must not trigger a fallback to a
+/// weaker KEM.
+fn is_unsupported_kem(status: &tonic::Status, kem: Kem) -> bool {
+    status.code() == tonic::Code::InvalidArgument
+        && status
+            .message()
+            .strip_prefix(UNSUPPORTED_KEM_MESSAGE)
+            .is_some_and(|rest| rest.trim_start_matches(':').trim() == kem.algorithm_name())
 }
 
 /// Negotiate a short-lived peer with a PQ-safe PSK or with DAITA enabled.
@@ -192,7 +394,7 @@ pub async fn request_ephemeral_peer(
     service_address: IpAddr,
     parent_pubkey: PublicKey,
     ephemeral_pubkey: PublicKey,
-    enable_post_quantum: bool,
+    post_quantum: Option<QuantumResistantAlgorithms>,
     enable_daita: bool,
 ) -> Result<EphemeralPeer, Error> {
     let client = new_client(service_address).await?;
@@ -201,42 +403,12 @@ pub async fn request_ephemeral_peer(
         client,
         parent_pubkey,
         ephemeral_pubkey,
-        enable_post_quantum,
+        post_quantum,
         enable_daita,
     )
     .await
 }
 
-async fn post_quantum_secrets(
-    enable_post_quantum: bool,
-) -> (
-    Option<PostQuantumRequestV1>,
-    Option<(classic_mceliece_rust::SecretKey<'static>, [u8; 3168])>,
-) {
-    if enable_post_quantum {
-        let (cme_kem_pubkey, cme_kem_secret) = classic_mceliece::generate_keys().await;
-        let kyber_keypair = kyber::keypair(&mut rand::thread_rng());
-
-        (
-            Some(proto::PostQuantumRequestV1 {
-                kem_pubkeys: vec![
-                    proto::KemPubkeyV1 {
-                        algorithm_name: classic_mceliece::ALGORITHM_NAME.to_owned(),
-                        key_data: cme_kem_pubkey.as_array().to_vec(),
-                    },
-                    proto::KemPubkeyV1 {
-                        algorithm_name: kyber::ALGORITHM_NAME.to_owned(),
-                        key_data: kyber_keypair.public.to_vec(),
-                    },
-                ],
-            }),
-            Some((cme_kem_secret, kyber_keypair.secret)),
-        )
-    } else {
-        (None, None)
-    }
-}
-
 /// Performs `dst = dst ^ src`.
 fn xor_assign(dst: &mut [u8; 32], src: &[u8; 32]) {
     for (dst_byte, src_byte) in dst.iter_mut().zip(src.iter()) {
@@ -0,0 +1,100 @@
+//! This module implements ML-KEM as standardized in FIPS 203:
+//! https://csrc.nist.gov/pubs/fips/203/final
+
+use ml_kem::{array::typenum::Unsigned, kem::Decapsulate, Ciphertext, EncodedSizeUser, KemCore};
+pub use ml_kem::{MlKem1024, MlKem768};
+use zeroize::Zeroize;
+
+pub const ML_KEM_768_ALGORITHM_NAME: &str = "ML-KEM-768";
+pub const ML_KEM_1024_ALGORITHM_NAME: &str = "ML-KEM-1024";
+
+pub type SecretKey<K> = <K as KemCore>::DecapsulationKey;
+
+/// Generate a keypair and return the encoded public (encapsulation) key along with the secret
+/// (decapsulation) key.
+pub fn keypair<K: KemCore>() -> (Vec<u8>, SecretKey<K>) {
+    let (secret_key, public_key) = K::generate(&mut rand::thread_rng());
+    (public_key.as_bytes().to_vec(), secret_key)
+}
+
+pub fn decapsulate<K: KemCore>(
+    algorithm: &'static str,
+    secret_key: &SecretKey<K>,
+    ciphertext_slice: &[u8],
+) -> Result<[u8; 32], super::Error> {
+    let ciphertext = Ciphertext::<K>::try_from(ciphertext_slice).map_err(|_| {
+        super::Error::InvalidCiphertextLength {
+            algorithm,
+            actual: ciphertext_slice.len(),
+            expected: K::CiphertextSize::USIZE,
+        }
+    })?;
+    let mut shared_secret = secret_key
+        .decapsulate(&ciphertext)
+        .map_err(|_| super::Error::FailedDecapsulateMlKem)?;
+
+    // All ML-KEM parameter sets output a 32 byte shared secret.
+    let mut shared_secret_array = [0u8; 32];
+    shared_secret_array.copy_from_slice(shared_secret.as_slice());
+    shared_secret.as_mut_slice().zeroize();
+    Ok(shared_secret_array)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ml_kem::Encoded;
+
+    const ML_KEM_768_VECTOR: &str = include_str!("../tests/vectors/ml-kem-768.rsp");
+    const ML_KEM_1024_VECTOR: &str = include_str!("../tests/vectors/ml-kem-1024.rsp");
+
+    /// Returns the hex decoded value of `field` in a `.rsp` vector file.
+    fn field(vector: &str, field: &str) -> Vec<u8> {
+        let value = vector
+            .lines()
+            .find_map(|line| line.strip_prefix(field)?.strip_prefix(" = "))
+            .unwrap_or_else(|| panic!("missing field {field}"));
+        (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn known_answer<K: KemCore>(algorithm: &'static str, vector: &str) {
+        let encoded_key =
+            Encoded::<SecretKey<K>>::try_from(field(vector, "dk").as_slice()).unwrap();
+        let secret_key = SecretKey::<K>::from_bytes(&encoded_key);
+
+        let shared_secret = decapsulate::<K>(algorithm, &secret_key, &field(vector, "ct")).unwrap();
+        assert_eq!(shared_secret.as_slice(), field(vector, "ss"));
+
+        // A modified ciphertext must yield the implicit rejection key, not an error.
+        let shared_secret =
+            decapsulate::<K>(algorithm, &secret_key, &field(vector, "ct_n")).unwrap();
+        assert_eq!(shared_secret.as_slice(), field(vector, "ss_n"));
+    }
+
+    #[test]
+    fn test_ml_kem_768_known_answer() {
+        known_answer::<MlKem768>(ML_KEM_768_ALGORITHM_NAME, ML_KEM_768_VECTOR);
+    }
+
+    #[test]
+    fn test_ml_kem_1024_known_answer() {
+        known_answer::<MlKem1024>(ML_KEM_1024_ALGORITHM_NAME, ML_KEM_1024_VECTOR);
+    }
+
+    #[test]
+    fn test_invalid_ciphertext_length() {
+        let (_, secret_key) = keypair::<MlKem768>();
+        let result = decapsulate::<MlKem768>(ML_KEM_768_ALGORITHM_NAME, &secret_key, &[0; 1087]);
+        assert!(matches!(
+            result,
+            Err(crate::Error::InvalidCiphertextLength {
+                actual: 1087,
+                expected: 1088,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+//! Tests of the PSK exchange and KEM negotiation against a local stand-in for the config service.
+//! The stand-in derives the PSK from the shared secrets it encapsulates, which the PSK derived by
+//! the client must match. Known-answer tests of the ML-KEM implementation live in `src/ml_kem.rs`.
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+mod proto {
+    tonic::include_proto!("ephemeralpeer");
+}
+
+use ml_kem::{kem::Encapsulate, Encoded, EncodedSizeUser, KemCore, MlKem1024, MlKem768};
+use proto::{
+    ephemeral_peer_server::{EphemeralPeer, EphemeralPeerServer},
+    EphemeralPeerRequestV1, EphemeralPeerResponseV1, PostQuantumResponseV1,
+};
+use std::sync::{Arc, Mutex};
+use talpid_tunnel_config_client::{request_ephemeral_peer_with, Error, RelayConfigService};
+use talpid_types::net::wireguard::{PrivateKey, QuantumResistantAlgorithms};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+const CLASSIC_MCELIECE: &str = "Classic-McEliece-460896f-round3";
+const KYBER: &str = "Kyber1024";
+const ML_KEM_768: &str = "ML-KEM-768";
+const ML_KEM_1024: &str = "ML-KEM-1024";
+
+#[derive(Default)]
+struct Exchange {
+    /// The algorithms offered in each request.
+    offered: Vec<Vec<String>>,
+    /// The PSK derived from the most recent successful request.
+    psk: Option<[u8; 32]>,
+}
+
+struct StandInService {
+    supported: &'static [&'static str],
+    /// If set, every request is rejected with `INVALID_ARGUMENT` and this message.
+    rejection: Option<&'static str>,
+    exchange: Arc<Mutex<Exchange>>,
+}
+
+#[tonic::async_trait]
+impl EphemeralPeer for StandInService {
+    async fn register_peer_v1(
+        &self,
+        request: Request<EphemeralPeerRequestV1>,
+    ) -> Result<Response<EphemeralPeerResponseV1>, Status> {
+        let Some(post_quantum) = request.into_inner().post_quantum else {
+            return Ok(Response::new(EphemeralPeerResponseV1 {
+                post_quantum: None,
+            }));
+        };
+
+        let algorithms = post_quantum
+            .kem_pubkeys
+            .iter()
+            .map(|kem_pubkey| kem_pubkey.algorithm_name.clone())
+            .collect();
+        self.exchange.lock().unwrap().offered.push(algorithms);
+        if let Some(rejection) = self.rejection {
+            return Err(Status::invalid_argument(rejection));
+        }
+
+        let mut ciphertexts = vec![];
+        let mut psk = [0u8; 32];
+        for kem_pubkey in post_quantum.kem_pubkeys {
+            let algorithm = kem_pubkey.algorithm_name.as_str();
+            if !self.supported.contains(&algorithm) {
+                return Err(Status::invalid_argument(format!(
+                    "Unsupported KEM algorithm: {algorithm}"
+                )));
+            }
+            let (ciphertext, shared_secret) = encapsulate(algorithm, &kem_pubkey.key_data);
+            ciphertexts.push(ciphertext);
+            for (psk_byte, shared_secret_byte) in psk.iter_mut().zip(shared_secret) {
+                *psk_byte ^= shared_secret_byte;
+            }
+        }
+        self.exchange.lock().unwrap().psk = Some(psk);
+
+        Ok(Response::new(EphemeralPeerResponseV1 {
+            post_quantum: Some(PostQuantumResponseV1 { ciphertexts }),
+        }))
+    }
+}
+
+fn encapsulate(algorithm: &str, key_data: &[u8]) -> (Vec<u8>, [u8; 32]) {
+    let mut rng = rand::thread_rng();
+    match algorithm {
+        CLASSIC_MCELIECE => {
+            let key_data: [u8; classic_mceliece_rust::CRYPTO_PUBLICKEYBYTES] =
+                key_data.try_into().unwrap();
+            let public_key = classic_mceliece_rust::PublicKey::from(&key_data);
+            let (ciphertext, shared_secret) =
+                classic_mceliece_rust::encapsulate_boxed(&public_key, &mut rng);
+            (ciphertext.as_array().to_vec(), *shared_secret.as_array())
+        }
+        KYBER => {
+            let (ciphertext, shared_secret) = pqc_kyber::encapsulate(key_data, &mut rng).unwrap();
+            (ciphertext.to_vec(), shared_secret)
+        }
+        ML_KEM_768 => encapsulate_ml_kem::<MlKem768>(key_data),
+        ML_KEM_1024 => encapsulate_ml_kem::<MlKem1024>(key_data),
+        algorithm => panic!("Unexpected KEM algorithm: {algorithm}"),
+    }
+}
+
+fn encapsulate_ml_kem<K: KemCore>(key_data: &[u8]) -> (Vec<u8>, [u8; 32]) {
+    let encoded_key = Encoded::<K::EncapsulationKey>::try_from(key_data).unwrap();
+    let public_key = K::EncapsulationKey::from_bytes(&encoded_key);
+    let (ciphertext, shared_secret) = public_key.encapsulate(&mut rand::thread_rng()).unwrap();
+    (
+        ciphertext.to_vec(),
+        shared_secret.as_slice().try_into().unwrap(),
+    )
+}
+
+/// Start a stand-in config service that supports the given algorithms, and connect to it.
+async fn start_service(
+    supported: &'static [&'static str],
+) -> (RelayConfigService, Arc<Mutex<Exchange>>) {
+    start_service_with(supported, None).await
+}
+
+async fn start_service_with(
+    supported: &'static [&'static str],
+    rejection: Option<&'static str>,
+) -> (RelayConfigService, Arc<Mutex<Exchange>>) {
+    let exchange = Arc::new(Mutex::new(Exchange::default()));
+    let service = StandInService {
+        supported,
+        rejection,
+        exchange: exchange.clone(),
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(EphemeralPeerServer::new(service))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    let client = RelayConfigService::connect(format!("http://{address}"))
+        .await
+        .unwrap();
+    (client, exchange)
+}
+
+async fn request_psk(
+    client: RelayConfigService,
+    algorithms: QuantumResistantAlgorithms,
+) -> Result<[u8; 32], Error> {
+    let peer = request_ephemeral_peer_with(
+        client,
+        PrivateKey::new_from_random().public_key(),
+        PrivateKey::new_from_random().public_key(),
+        Some(algorithms),
+        false,
+    )
+    .await?;
+    Ok(*peer.psk.expect("a PSK should be negotiated").as_bytes())
+}
+
+fn offered(exchange: &Mutex<Exchange>) -> Vec<Vec<String>> {
+    exchange.lock().unwrap().offered.clone()
+}
+
+#[tokio::test]
+async fn test_ml_kem_1024() {
+    let (client, exchange) =
+        start_service(&[CLASSIC_MCELIECE, ML_KEM_1024, ML_KEM_768, KYBER]).await;
+
+    let psk = request_psk(client, QuantumResistantAlgorithms::PreferMlKem)
+        .await
+        .unwrap();
+
+    assert_eq!(Some(psk), exchange.lock().unwrap().psk);
+    assert_eq!(offered(&exchange), [[CLASSIC_MCELIECE, ML_KEM_1024]]);
+}
+
+#[tokio::test]
+async fn test_fall_back_to_ml_kem_768() {
+    let (client, exchange) = start_service(&[CLASSIC_MCELIECE, ML_KEM_768, KYBER]).await;
+
+    let psk = request_psk(client, QuantumResistantAlgorithms::RequireMlKem)
+        .await
+        .unwrap();
+
+    assert_eq!(Some(psk), exchange.lock().unwrap().psk);
+    assert_eq!(
+        offered(&exchange),
+        [
+            [CLASSIC_MCELIECE, ML_KEM_1024],
+            [CLASSIC_MCELIECE, ML_KEM_768]
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_fall_back_to_kyber() {
+    let (client, exchange) = start_service(&[CLASSIC_MCELIECE, KYBER]).await;
+
+    let psk = request_psk(client, QuantumResistantAlgorithms::PreferMlKem)
+        .await
+        .unwrap();
+
+    assert_eq!(Some(psk), exchange.lock().unwrap().psk);
+    assert_eq!(
+        offered(&exchange),
+        [
+            [CLASSIC_MCELIECE, ML_KEM_1024],
+            [CLASSIC_MCELIECE, ML_KEM_768],
+            [CLASSIC_MCELIECE, KYBER]
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_require_ml_kem_unsupported() {
+    let (client, exchange) = start_service(&[CLASSIC_MCELIECE, KYBER]).await;
+
+    let result = request_psk(client, QuantumResistantAlgorithms::RequireMlKem).await;
+
+    assert!(
+        matches!(result, Err(Error::GrpcError(ref status)) if status.code() == tonic::Code::InvalidArgument)
+    );
+    assert_eq!(exchange.lock().unwrap().psk, None);
+}
+
+#[tokio::test]
+async fn test_no_fall_back_on_other_errors() {
+    let (client, exchange) = start_service_with(
+        &[CLASSIC_MCELIECE, ML_KEM_1024, ML_KEM_768, KYBER],
+        Some("Invalid WireGuard public key"),
+    )
+    .await;
+
+    let result = request_psk(client, QuantumResistantAlgorithms::PreferMlKem).await;
+
+    assert!(
+        matches!(result, Err(Error::GrpcError(ref status)) if status.code() == tonic::Code::InvalidArgument)
+    );
+    assert_eq!(offered(&exchange), [[CLASSIC_MCELIECE, ML_KEM_1024]]);
+}
@@ -7,6 +7,9 @@ pub trait ErrorExt {
 
     /// Like [Self::display_chain] but with an extra message at the start of the chain
     fn display_chain_with_msg(&self, msg: &str) -> String;
+
+    /// Returns the description of each error in the chain, outermost error first.
+    fn error_chain(&self) -> Vec<String>;
 }
 
 impl<E: Error> ErrorExt for E {
@@ -29,6 +32,16 @@ impl<E: Error> ErrorExt for E {
         }
         s
     }
+
+    fn error_chain(&self) -> Vec<String> {
+        let mut chain = vec![self.to_string()];
+        let mut source = self.source();
+        while let Some(error) = source {
+            chain.push(error.to_string());
+            source = error.source();
+        }
+        chain
+    }
 }
 
 #[derive(Debug)]
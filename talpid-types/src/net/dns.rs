@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// Outcome of a DNS leak test performed while connected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsLeakTestResult {
+    /// Resolvers that the tunnel is configured to use.
+    pub expected_resolvers: Vec<IpAddr>,
+    /// Resolvers that the system resolver is currently configured to use.
+    pub system_resolvers: Vec<IpAddr>,
+    /// Resolvers outside of the tunnel configuration that answered a unique query.
+    pub leaking_resolvers: Vec<IpAddr>,
+    /// Whether a unique query made through the system resolver was answered by a resolver other
+    /// than the expected ones.
+    pub answered_by_unexpected_resolver: bool,
+}
+
+impl DnsLeakTestResult {
+    /// Returns true if DNS requests were answered by resolvers other than the expected ones.
+    pub fn is_leaking(&self) -> bool {
+        !self.leaking_resolvers.is_empty() || self.answered_by_unexpected_resolver
+    }
+}
+
+/// A DNS resolver that is reached using an encrypted protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EncryptedDnsServer {
+    pub protocol: EncryptedDnsProtocol,
+    pub address: IpAddr,
+    pub port: u16,
+    /// Name used to verify the TLS certificate of the server.
+    pub server_name: String,
+}
+
+impl fmt::Display for EncryptedDnsServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let address = std::net::SocketAddr::new(self.address, self.port);
+        write!(f, "{}://{} ({address})", self.protocol, self.server_name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptedDnsProtocol {
+    /// DNS-over-HTTPS
+    Https,
+    /// DNS-over-TLS
+    Tls,
+}
+
+impl EncryptedDnsProtocol {
+    /// The port conventionally used by the protocol.
+    pub fn default_port(&self) -> u16 {
+        match self {
+            EncryptedDnsProtocol::Https => 443,
+            EncryptedDnsProtocol::Tls => 853,
+        }
+    }
+}
+
+impl fmt::Display for EncryptedDnsProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptedDnsProtocol::Https => f.write_str("https"),
+            EncryptedDnsProtocol::Tls => f.write_str("tls"),
+        }
+    }
+}
+
+impl FromStr for EncryptedDnsProtocol {
+    type Err = EncryptedDnsProtocolParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("https") || s.eq_ignore_ascii_case("doh") {
+            return Ok(EncryptedDnsProtocol::Https);
+        }
+        if s.eq_ignore_ascii_case("tls") || s.eq_ignore_ascii_case("dot") {
+            return Ok(EncryptedDnsProtocol::Tls);
+        }
+        Err(EncryptedDnsProtocolParseError)
+    }
+}
+
+/// Returned when `EncryptedDnsProtocol::from_str` fails to convert a string into a
+/// [`EncryptedDnsProtocol`] object.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Not a valid encrypted DNS protocol. Expected 'https' or 'tls'")]
+pub struct EncryptedDnsProtocolParseError;
@@ -10,6 +10,7 @@ use std::{
 
 use self::proxy::{CustomProxy, Socks5Local};
 
+pub mod dns;
 pub mod obfuscation;
 pub mod openvpn;
 pub mod proxy;
@@ -38,6 +39,7 @@ impl TunnelParameters {
                 obfuscation: None,
                 entry_endpoint: None,
                 tunnel_interface: None,
+                tunnel_gateway: None,
                 #[cfg(daita)]
                 daita: false,
             },
@@ -55,6 +57,7 @@ impl TunnelParameters {
                     .get_exit_endpoint()
                     .map(|_| params.connection.get_endpoint()),
                 tunnel_interface: None,
+                tunnel_gateway: None,
                 #[cfg(daita)]
                 daita: params.options.daita,
             },
@@ -179,6 +182,8 @@ pub struct TunnelEndpoint {
     pub obfuscation: Option<ObfuscationEndpoint>,
     pub entry_endpoint: Option<Endpoint>,
     pub tunnel_interface: Option<String>,
+    /// The default gateway inside the tunnel. Only known once the tunnel is up.
+    pub tunnel_gateway: Option<IpAddr>,
     #[cfg(daita)]
     pub daita: bool,
 }
@@ -6,6 +6,7 @@ use std::{
     cmp, fmt,
     hash::{Hash, Hasher},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
 };
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -81,11 +82,58 @@ pub struct TunnelOptions {
     pub mtu: Option<u16>,
     /// Perform PQ-safe PSK exchange when connecting
     pub quantum_resistant: bool,
+    /// Key encapsulation mechanisms to offer the relay when performing the PQ-safe PSK exchange
+    pub quantum_resistant_algorithms: QuantumResistantAlgorithms,
+    /// Renegotiate the ephemeral peer and PQ-safe PSK this often while connected
+    pub quantum_resistant_rekey_interval: Option<Duration>,
     /// Enable DAITA during tunnel config
     #[cfg(daita)]
     pub daita: bool,
 }
 
+/// Which post-quantum key encapsulation mechanisms (KEMs) may be used to derive the PSK of an
+/// ephemeral peer. Classic McEliece is always combined with one of the lattice-based KEMs below.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuantumResistantAlgorithms {
+    /// Prefer ML-KEM (FIPS 203), but fall back to Kyber1024 (round 3) if the relay does not
+    /// support ML-KEM.
+    #[default]
+    PreferMlKem,
+    /// Only use ML-KEM (FIPS 203). Fail if the relay does not support it.
+    RequireMlKem,
+    /// Only use Kyber1024 (round 3), which every relay supports.
+    Kyber,
+}
+
+impl fmt::Display for QuantumResistantAlgorithms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuantumResistantAlgorithms::PreferMlKem => f.write_str("prefer-ml-kem"),
+            QuantumResistantAlgorithms::RequireMlKem => f.write_str("require-ml-kem"),
+            QuantumResistantAlgorithms::Kyber => f.write_str("kyber"),
+        }
+    }
+}
+
+impl std::str::FromStr for QuantumResistantAlgorithms {
+    type Err = QuantumResistantAlgorithmsParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prefer-ml-kem" => Ok(QuantumResistantAlgorithms::PreferMlKem),
+            "require-ml-kem" => Ok(QuantumResistantAlgorithms::RequireMlKem),
+            "kyber" => Ok(QuantumResistantAlgorithms::Kyber),
+            _ => Err(QuantumResistantAlgorithmsParseError),
+        }
+    }
+}
+
+/// Returned when a string is not one of `prefer-ml-kem`, `require-ml-kem` or `kyber`.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Not a valid quantum resistant algorithm policy")]
+pub struct QuantumResistantAlgorithmsParseError;
+
 /// Wireguard x25519 private key
 #[derive(Clone, Zeroize, ZeroizeOnDrop)]
 pub struct PrivateKey(x25519_dalek::StaticSecret);
@@ -263,6 +311,14 @@ impl PresharedKey {
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.0
     }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.as_bytes())
+    }
+
+    pub fn from_base64(key: &str) -> Result<Self, InvalidKey> {
+        key_from_base64(key)
+    }
 }
 
 impl From<Box<[u8; 32]>> for PresharedKey {
@@ -271,6 +327,30 @@ impl From<Box<[u8; 32]>> for PresharedKey {
     }
 }
 
+impl From<[u8; 32]> for PresharedKey {
+    fn from(key: [u8; 32]) -> PresharedKey {
+        PresharedKey(Box::new(key))
+    }
+}
+
+impl Serialize for PresharedKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_key(self.as_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PresharedKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_key(deserializer)
+    }
+}
+
 impl fmt::Debug for PresharedKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", &STANDARD.encode(self.as_bytes()))
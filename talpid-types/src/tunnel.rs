@@ -1,8 +1,8 @@
 use crate::net::TunnelEndpoint;
 use serde::{Deserialize, Serialize};
-use std::fmt;
 #[cfg(target_os = "android")]
 use std::net::IpAddr;
+use std::{collections::BTreeMap, fmt};
 
 /// Event emitted from the states in `talpid_core::tunnel_state_machine` when the tunnel state
 /// machine enters a new state.
@@ -44,6 +44,9 @@ pub struct ErrorState {
     /// An error value means there was a serious error and the intended security properties are not
     /// being upheld.
     block_failure: Option<FirewallPolicyError>,
+    /// Details about the failure, collected when the error state was entered.
+    #[serde(default)]
+    diagnostics: Option<ErrorDiagnostics>,
 }
 
 impl ErrorState {
@@ -51,9 +54,16 @@ impl ErrorState {
         Self {
             cause,
             block_failure,
+            diagnostics: None,
         }
     }
 
+    /// Attach diagnostics collected at the time of the failure.
+    pub fn with_diagnostics(mut self, diagnostics: ErrorDiagnostics) -> Self {
+        self.diagnostics = Some(diagnostics);
+        self
+    }
+
     pub fn is_blocking(&self) -> bool {
         self.block_failure.is_none()
     }
@@ -65,6 +75,153 @@ impl ErrorState {
     pub fn block_failure(&self) -> Option<&FirewallPolicyError> {
         self.block_failure.as_ref()
     }
+
+    pub fn diagnostics(&self) -> Option<&ErrorDiagnostics> {
+        self.diagnostics.as_ref()
+    }
+}
+
+/// Structured information about why the tunnel state machine entered the error state.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ErrorDiagnostics {
+    /// Subsystem that failed.
+    pub subsystem: ErrorSubsystem,
+    /// The underlying error chain, outermost error first.
+    pub error_chain: Vec<String>,
+    /// Relevant details about the host environment, such as the DNS manager in use or missing
+    /// kernel modules.
+    pub environment: BTreeMap<String, String>,
+    /// Suggested action for resolving the error.
+    pub remediation: RemediationCode,
+}
+
+impl ErrorDiagnostics {
+    /// Create diagnostics for `cause` without any error chain or environment details.
+    pub fn from_cause(cause: &ErrorStateCause) -> Self {
+        Self {
+            subsystem: cause.subsystem(),
+            error_chain: vec![],
+            environment: BTreeMap::new(),
+            remediation: cause.remediation(),
+        }
+    }
+}
+
+/// Part of the system that caused the tunnel state machine to enter the error state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorSubsystem {
+    #[default]
+    Unknown,
+    Firewall,
+    Dns,
+    TunnelDevice,
+    Tunnel,
+    RelaySelection,
+    Authentication,
+    Network,
+    SplitTunnel,
+    Permissions,
+}
+
+impl fmt::Display for ErrorSubsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorSubsystem::Unknown => "unknown",
+            ErrorSubsystem::Firewall => "firewall",
+            ErrorSubsystem::Dns => "dns",
+            ErrorSubsystem::TunnelDevice => "tunnel device",
+            ErrorSubsystem::Tunnel => "tunnel",
+            ErrorSubsystem::RelaySelection => "relay selection",
+            ErrorSubsystem::Authentication => "authentication",
+            ErrorSubsystem::Network => "network",
+            ErrorSubsystem::SplitTunnel => "split tunnel",
+            ErrorSubsystem::Permissions => "permissions",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Machine-readable hint for how an error may be resolved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemediationCode {
+    /// No specific remediation is known.
+    #[default]
+    None,
+    /// Check the account status or log in again.
+    CheckAccount,
+    /// Enable IPv6 in the operating system, or disable IPv6 in the tunnel.
+    EnableIpv6,
+    /// Close or uninstall the application that conflicts with the firewall.
+    ResolveFirewallConflict,
+    /// Check the system DNS configuration.
+    CheckDnsConfiguration,
+    /// Install or load a missing kernel module.
+    LoadKernelModule,
+    /// Reinstall or repair the tunnel device driver.
+    ReinstallTunnelDriver,
+    /// Change the relay constraints.
+    ChangeRelayConstraints,
+    /// Rotate or regenerate the WireGuard key.
+    RotateWireguardKey,
+    /// Check the network connection.
+    CheckConnectivity,
+    /// Grant the permission requested by the app.
+    GrantPermission,
+    /// Restart the daemon or the device.
+    Restart,
+}
+
+impl RemediationCode {
+    /// Returns the machine-readable name of the remediation code.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemediationCode::None => "none",
+            RemediationCode::CheckAccount => "check_account",
+            RemediationCode::EnableIpv6 => "enable_ipv6",
+            RemediationCode::ResolveFirewallConflict => "resolve_firewall_conflict",
+            RemediationCode::CheckDnsConfiguration => "check_dns_configuration",
+            RemediationCode::LoadKernelModule => "load_kernel_module",
+            RemediationCode::ReinstallTunnelDriver => "reinstall_tunnel_driver",
+            RemediationCode::ChangeRelayConstraints => "change_relay_constraints",
+            RemediationCode::RotateWireguardKey => "rotate_wireguard_key",
+            RemediationCode::CheckConnectivity => "check_connectivity",
+            RemediationCode::GrantPermission => "grant_permission",
+            RemediationCode::Restart => "restart",
+        }
+    }
+}
+
+impl fmt::Display for RemediationCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            RemediationCode::None => "No known remediation",
+            RemediationCode::CheckAccount => "Check that the account is valid and has time left",
+            RemediationCode::EnableIpv6 => {
+                "Enable IPv6 in the operating system or disable IPv6 in the tunnel"
+            }
+            RemediationCode::ResolveFirewallConflict => {
+                "Close or remove the application that conflicts with the firewall"
+            }
+            RemediationCode::CheckDnsConfiguration => {
+                "Check that the system DNS manager is running and correctly configured"
+            }
+            RemediationCode::LoadKernelModule => "Install or load the missing kernel module",
+            RemediationCode::ReinstallTunnelDriver => {
+                "Reinstall the app to repair the tunnel driver"
+            }
+            RemediationCode::ChangeRelayConstraints => {
+                "Change the relay constraints so that a relay can be selected"
+            }
+            RemediationCode::RotateWireguardKey => "Rotate the WireGuard key",
+            RemediationCode::CheckConnectivity => "Check the network connection",
+            RemediationCode::GrantPermission => "Grant the permission requested by the app",
+            RemediationCode::Restart => "Restart the app or the device",
+        };
+        f.write_str(description)
+    }
 }
 
 /// Reason for the tunnel state machine entering an [`ErrorState`].
@@ -104,6 +261,58 @@ pub enum ErrorStateCause {
 }
 
 impl ErrorStateCause {
+    /// Returns the subsystem that is responsible for this error.
+    pub fn subsystem(&self) -> ErrorSubsystem {
+        match self {
+            Self::AuthFailed(_) => ErrorSubsystem::Authentication,
+            Self::Ipv6Unavailable | Self::IsOffline => ErrorSubsystem::Network,
+            Self::SetFirewallPolicyError(_) => ErrorSubsystem::Firewall,
+            Self::SetDnsError => ErrorSubsystem::Dns,
+            #[cfg(target_os = "android")]
+            Self::InvalidDnsServers(_) => ErrorSubsystem::Dns,
+            #[cfg(target_os = "windows")]
+            Self::CreateTunnelDevice { .. } => ErrorSubsystem::TunnelDevice,
+            Self::StartTunnelError => ErrorSubsystem::Tunnel,
+            Self::TunnelParameterError(_) => ErrorSubsystem::RelaySelection,
+            #[cfg(target_os = "android")]
+            Self::VpnPermissionDenied => ErrorSubsystem::Permissions,
+            #[cfg(any(target_os = "windows", target_os = "macos", target_os = "android"))]
+            Self::SplitTunnelError => ErrorSubsystem::SplitTunnel,
+            #[cfg(target_os = "macos")]
+            Self::NeedFullDiskPermissions => ErrorSubsystem::Permissions,
+        }
+    }
+
+    /// Returns a suggested remediation for this error.
+    pub fn remediation(&self) -> RemediationCode {
+        match self {
+            Self::AuthFailed(_) => RemediationCode::CheckAccount,
+            Self::Ipv6Unavailable => RemediationCode::EnableIpv6,
+            Self::IsOffline => RemediationCode::CheckConnectivity,
+            #[cfg(windows)]
+            Self::SetFirewallPolicyError(FirewallPolicyError::Locked(_)) => {
+                RemediationCode::ResolveFirewallConflict
+            }
+            Self::SetFirewallPolicyError(_) => RemediationCode::Restart,
+            Self::SetDnsError => RemediationCode::CheckDnsConfiguration,
+            #[cfg(target_os = "android")]
+            Self::InvalidDnsServers(_) => RemediationCode::CheckDnsConfiguration,
+            #[cfg(target_os = "windows")]
+            Self::CreateTunnelDevice { .. } => RemediationCode::ReinstallTunnelDriver,
+            Self::StartTunnelError => RemediationCode::Restart,
+            Self::TunnelParameterError(ParameterGenerationError::NoWireguardKey) => {
+                RemediationCode::RotateWireguardKey
+            }
+            Self::TunnelParameterError(_) => RemediationCode::ChangeRelayConstraints,
+            #[cfg(target_os = "android")]
+            Self::VpnPermissionDenied => RemediationCode::GrantPermission,
+            #[cfg(any(target_os = "windows", target_os = "macos", target_os = "android"))]
+            Self::SplitTunnelError => RemediationCode::Restart,
+            #[cfg(target_os = "macos")]
+            Self::NeedFullDiskPermissions => RemediationCode::GrantPermission,
+        }
+    }
+
     #[cfg(target_os = "macos")]
     pub fn prevents_filtering_resolver(&self) -> bool {
         matches!(self, Self::SetDnsError)
@@ -126,6 +335,9 @@ pub enum ParameterGenerationError {
     /// Failure to resolve the hostname of a custom tunnel configuration
     #[error("Can't resolve hostname for custom tunnel host")]
     CustomTunnelHostResultionError,
+    /// A quantum-resistant tunnel is required, but the selected relay cannot provide one
+    #[error("The selected relay does not support quantum-resistant tunnels")]
+    QuantumResistanceUnsupported,
 }
 
 /// Application that prevents setting the firewall policy.
@@ -2,6 +2,7 @@ use std::{
     borrow::Cow,
     ffi::CString,
     net::{Ipv4Addr, Ipv6Addr},
+    time::Duration,
 };
 use talpid_types::net::{obfuscation::ObfuscatorConfig, wireguard, GenericTunnelOptions};
 
@@ -30,6 +31,10 @@ pub struct Config {
     pub obfuscator_config: Option<ObfuscatorConfig>,
     /// Enable quantum-resistant PSK exchange
     pub quantum_resistant: bool,
+    /// KEMs that may be used for the quantum-resistant PSK exchange
+    pub quantum_resistant_algorithms: wireguard::QuantumResistantAlgorithms,
+    /// Renegotiate the ephemeral peers this often while connected
+    pub quantum_resistant_rekey_interval: Option<Duration>,
     /// Enable DAITA
     pub daita: bool,
 }
@@ -97,6 +102,8 @@ impl Config {
             enable_ipv6: generic_options.enable_ipv6,
             obfuscator_config: obfuscator_config.to_owned(),
             quantum_resistant: wg_options.quantum_resistant,
+            quantum_resistant_algorithms: wg_options.quantum_resistant_algorithms,
+            quantum_resistant_rekey_interval: wg_options.quantum_resistant_rekey_interval,
             #[cfg(daita)]
             daita: wg_options.daita,
             #[cfg(not(daita))]
@@ -151,6 +158,13 @@ impl Config {
         CString::new(bytes).expect("null bytes inside config")
     }
 
+    /// Return the KEMs to use for the quantum-resistant PSK exchange, or `None` if it is
+    /// disabled.
+    pub fn post_quantum(&self) -> Option<wireguard::QuantumResistantAlgorithms> {
+        self.quantum_resistant
+            .then_some(self.quantum_resistant_algorithms)
+    }
+
     /// Return whether the config connects to an exit peer from another remote peer.
     pub fn is_multihop(&self) -> bool {
         self.exit_peer.is_some()
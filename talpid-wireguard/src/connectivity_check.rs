@@ -1,6 +1,6 @@
 use crate::{
     ping_monitor::{new_pinger, Pinger},
-    stats::StatsMap,
+    stats::{StatsHandle, StatsMap},
 };
 use std::{
     cmp,
@@ -77,6 +77,7 @@ pub struct ConnectivityMonitor {
     num_pings_sent: u32,
     pinger: Box<dyn Pinger>,
     close_receiver: mpsc::Receiver<()>,
+    stats: StatsHandle,
 }
 
 impl ConnectivityMonitor {
@@ -85,6 +86,7 @@ impl ConnectivityMonitor {
         #[cfg(any(target_os = "macos", target_os = "linux"))] interface: String,
         tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
         close_receiver: mpsc::Receiver<()>,
+        stats: StatsHandle,
     ) -> Result<Self, Error> {
         let pinger = new_pinger(
             addr,
@@ -102,6 +104,7 @@ impl ConnectivityMonitor {
             num_pings_sent: 0,
             pinger,
             close_receiver,
+            stats,
         })
     }
 
@@ -224,7 +227,10 @@ impl ConnectivityMonitor {
                     log::error!("Tunnel unexpectedly shut down");
                     None
                 }
-                Ok(stats) => Some(Ok(stats)),
+                Ok(stats) => {
+                    self.stats.set_current(Some(stats.clone()));
+                    Some(Ok(stats))
+                }
                 Err(error) => Some(Err(Error::ConfigReadError(error))),
             })
     }
@@ -448,6 +454,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(Instant::now(), stats);
@@ -473,6 +480,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(connect_time, stats);
@@ -497,6 +505,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(start, stats);
@@ -508,6 +517,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 1,
+                last_handshake: None,
             },
         );
         conn_state.update(update_time, stats);
@@ -551,6 +561,7 @@ mod test {
                 stats::Stats {
                     tx_bytes: 0,
                     rx_bytes: 0,
+                    last_handshake: None,
                 },
             );
             let peers = std::sync::Mutex::new(map);
@@ -575,6 +586,7 @@ mod test {
                         stats::Stats {
                             tx_bytes: 0,
                             rx_bytes: 0,
+                            last_handshake: None,
                         },
                     );
                     Ok(map)
@@ -635,6 +647,7 @@ mod test {
             pinger,
             close_receiver,
             tunnel_handle,
+            stats: StatsHandle::default(),
         }
     }
 
@@ -646,6 +659,7 @@ mod test {
             stats::Stats {
                 tx_bytes: 0,
                 rx_bytes: 0,
+                last_handshake: None,
             },
         );
         ConnState::Connected {
@@ -746,6 +760,7 @@ mod test {
             stats::Stats {
                 tx_bytes: 0,
                 rx_bytes: 0,
+                last_handshake: None,
             },
         );
         let tunnel_stats = std::sync::Mutex::new(map);
@@ -797,6 +812,7 @@ mod test {
             stats::Stats {
                 tx_bytes: 0,
                 rx_bytes: 0,
+                last_handshake: None,
             },
         );
 
@@ -32,7 +32,7 @@ use ipnetwork::IpNetwork;
 use talpid_types::{
     net::{
         obfuscation::ObfuscatorConfig,
-        wireguard::{PresharedKey, PrivateKey, PublicKey},
+        wireguard::{PresharedKey, PrivateKey, PublicKey, QuantumResistantAlgorithms},
         AllowedTunnelTraffic, Endpoint, TransportProtocol,
     },
     BoxedError, ErrorExt,
@@ -47,7 +47,7 @@ pub mod config;
 mod connectivity_check;
 mod logging;
 mod ping_monitor;
-mod stats;
+pub mod stats;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 mod unix;
 #[cfg(wireguard_go)]
@@ -150,11 +150,15 @@ pub struct WireguardMonitor {
     close_msg_receiver: sync_mpsc::Receiver<CloseMsg>,
     pinger_stop_sender: sync_mpsc::Sender<()>,
     obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
+    /// Stats of the tunnel, read by the owner of the handle
+    stats: stats::StatsHandle,
 }
 
 const INITIAL_PSK_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(8);
 const MAX_PSK_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(48);
 const PSK_EXCHANGE_TIMEOUT_MULTIPLIER: u32 = 2;
+/// How long to wait before trying again if renegotiating the ephemeral peers fails.
+const REKEY_RETRY_INTERVAL: Duration = Duration::from_secs(60);
 
 /// Simple wrapper that automatically cancels the future which runs an obfuscator.
 struct ObfuscatorHandle {
@@ -263,6 +267,7 @@ impl WireguardMonitor {
         #[cfg(not(target_os = "android"))] detect_mtu: bool,
         log_path: Option<&Path>,
         args: TunnelArgs<'_, F>,
+        stats: stats::StatsHandle,
     ) -> Result<WireguardMonitor> {
         let on_event = args.on_event.clone();
 
@@ -311,6 +316,7 @@ impl WireguardMonitor {
             close_msg_receiver: close_obfs_listener,
             pinger_stop_sender: pinger_tx,
             obfuscator,
+            stats: stats.clone(),
         };
 
         let gateway = config.ipv4_gateway;
@@ -320,14 +326,17 @@ impl WireguardMonitor {
             iface_name.clone(),
             Arc::downgrade(&monitor.tunnel),
             pinger_rx,
+            stats,
         )
         .map_err(Error::ConnectivityMonitorError)?;
 
         let moved_tunnel = monitor.tunnel.clone();
         let moved_close_obfs_sender = close_obfs_sender.clone();
         let moved_obfuscator = monitor.obfuscator.clone();
+        let moved_stats = monitor.stats.clone();
         let tunnel_fut = async move {
             let tunnel = moved_tunnel;
+            let stats = moved_stats;
             let close_obfs_sender: sync_mpsc::Sender<CloseMsg> = moved_close_obfs_sender;
             let obfuscator = moved_obfuscator;
             #[cfg(windows)]
@@ -356,10 +365,15 @@ impl WireguardMonitor {
                 .map_err(Error::SetupRoutingError)
                 .map_err(CloseMsg::SetupError)?;
 
+            // The config service identifies the tunnel by the device key, which is replaced by an
+            // ephemeral key below
+            let device_public_key = config.tunnel.private_key.public_key();
             let ephemeral_obfs_sender = close_obfs_sender.clone();
             if config.quantum_resistant || config.daita {
                 Self::config_ephemeral_peers(
                     &tunnel,
+                    &stats,
+                    &RelayConfigService,
                     &mut config,
                     args.retry_attempt,
                     obfuscator.clone(),
@@ -437,16 +451,25 @@ impl WireguardMonitor {
             let metadata = Self::tunnel_metadata(&iface_name, &config);
             (on_event)(TunnelEvent::Up(metadata)).await;
 
-            tokio::task::spawn_blocking(move || {
+            let connectivity_check = tokio::task::spawn_blocking(move || {
                 if let Err(error) = connectivity_monitor.run() {
                     log::error!(
                         "{}",
                         error.display_chain_with_msg("Connectivity monitor failed")
                     );
                 }
-            })
-            .await
-            .unwrap();
+            });
+            let rekey = Self::rekey_ephemeral_peers(
+                &tunnel,
+                &stats,
+                &RelayConfigService,
+                config,
+                device_public_key,
+            );
+            tokio::select! {
+                result = connectivity_check => result.unwrap(),
+                close_msg = rekey => return Err(close_msg),
+            }
 
             Err::<Infallible, CloseMsg>(CloseMsg::PingErr)
         };
@@ -500,21 +523,194 @@ impl WireguardMonitor {
 
     async fn config_ephemeral_peers(
         tunnel: &Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        stats: &stats::StatsHandle,
+        config_service: &dyn ConfigService,
         config: &mut Config,
         retry_attempt: u32,
         obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
         close_obfs_sender: sync_mpsc::Sender<CloseMsg>,
         #[cfg(target_os = "android")] tun_provider: Arc<Mutex<TunProvider>>,
+    ) -> std::result::Result<(), CloseMsg> {
+        let device_public_key = config.tunnel.private_key.public_key();
+        Self::negotiate_ephemeral_peers(
+            tunnel,
+            stats,
+            config_service,
+            config,
+            &device_public_key,
+            retry_attempt,
+            obfuscator,
+            close_obfs_sender,
+            #[cfg(target_os = "android")]
+            &tun_provider,
+        )
+        .await?;
+
+        Self::start_daita(tunnel, config).await
+    }
+
+    /// Start the local DAITA machines, if DAITA is enabled. This must be done whenever the
+    /// ephemeral peers have been reconfigured.
+    #[cfg_attr(not(daita), allow(unused_variables))]
+    async fn start_daita(
+        tunnel: &Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        config: &Config,
+    ) -> std::result::Result<(), CloseMsg> {
+        #[cfg(daita)]
+        if config.daita {
+            let mut tunnel = tunnel.lock().await;
+            if let Some(tunnel) = tunnel.as_mut() {
+                tunnel
+                    .start_daita()
+                    .map_err(Error::TunnelError)
+                    .map_err(CloseMsg::SetupError)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Periodically renegotiate the ephemeral peers of an established tunnel, so that a PQ-safe
+    /// PSK is not used for longer than `quantum_resistant_rekey_interval`. The new peer is
+    /// negotiated inside the current tunnel, which is only reconfigured to use it once the config
+    /// service has registered it, so the tunnel is not torn down in between. The obfuscator and
+    /// the routes of the tunnel are left untouched.
+    ///
+    /// Reaching the config service of the entry relay requires routing the gateway IP through the
+    /// entry peer, which would divert traffic of an established tunnel. Multihop tunnels are
+    /// therefore closed when a rekey is due, so that new peers are negotiated when reconnecting.
+    ///
+    /// `config` is the config that the tunnel currently uses, and `device_public_key` is the key
+    /// that the tunnel was originally set up with, which the new peers are requested for.
+    ///
+    /// This only returns if the tunnel can no longer be used.
+    async fn rekey_ephemeral_peers(
+        tunnel: &Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        stats: &stats::StatsHandle,
+        config_service: &dyn ConfigService,
+        mut config: Config,
+        device_public_key: PublicKey,
+    ) -> CloseMsg {
+        let Some(interval) = rekey_interval(&config) else {
+            return futures::future::pending().await;
+        };
+
+        if config.is_multihop() {
+            tokio::time::sleep(interval).await;
+            log::info!("Reconnecting to renegotiate ephemeral peers");
+            return CloseMsg::RekeyRequired;
+        }
+
+        let mut next_rekey = interval;
+        loop {
+            tokio::time::sleep(next_rekey).await;
+
+            log::debug!("Renegotiating ephemeral peer");
+            match Self::rekey_ephemeral_peer(
+                tunnel,
+                stats,
+                config_service,
+                &config,
+                &device_public_key,
+            )
+            .await
+            {
+                Ok(new_config) => {
+                    config = new_config;
+                    // The tunnel already uses the new peer at this point, so failing to start
+                    // DAITA for it is retried with yet another peer rather than closing the tunnel
+                    if let Err(close_msg) = Self::start_daita(tunnel, &config).await {
+                        log::error!(
+                            "Failed to start DAITA for renegotiated ephemeral peer: {close_msg:?}. \
+                            Retrying in {} seconds",
+                            REKEY_RETRY_INTERVAL.as_secs()
+                        );
+                        next_rekey = next_rekey_delay(interval, false);
+                        continue;
+                    }
+                    log::info!("Renegotiated ephemeral peer");
+                    next_rekey = next_rekey_delay(interval, true);
+                }
+                // The device key is registered from inside a tunnel that uses an ephemeral key
+                // here. Should the config service refuse that, renegotiate when reconnecting
+                // instead, where the device key is used for the tunnel itself.
+                Err(CloseMsg::SetupError(Error::PskNegotiationError(
+                    talpid_tunnel_config_client::Error::GrpcError(status),
+                ))) => {
+                    log::warn!(
+                        "Config service refused to renegotiate ephemeral peer: {status}. \
+                        Reconnecting"
+                    );
+                    return CloseMsg::RekeyRequired;
+                }
+                Err(CloseMsg::PskNegotiationTimeout)
+                | Err(CloseMsg::SetupError(Error::PskNegotiationError(_))) => {
+                    log::warn!(
+                        "Failed to renegotiate ephemeral peer. Retrying in {} seconds",
+                        REKEY_RETRY_INTERVAL.as_secs()
+                    );
+                    next_rekey = next_rekey_delay(interval, false);
+                }
+                Err(close_msg) => return close_msg,
+            }
+        }
+    }
+
+    /// Register a new ephemeral peer for the single-hop tunnel using `config`, and switch the
+    /// tunnel over to it. Returns the config that the tunnel uses afterwards.
+    ///
+    /// The request is sent through the tunnel, which is keyed with the previous ephemeral key at
+    /// this point. It is made on behalf of `device_public_key`, like the initial request.
+    async fn rekey_ephemeral_peer(
+        tunnel: &Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        stats: &stats::StatsHandle,
+        config_service: &dyn ConfigService,
+        config: &Config,
+        device_public_key: &PublicKey,
+    ) -> std::result::Result<Config, CloseMsg> {
+        let ephemeral_private_key = PrivateKey::new_from_random();
+        let psk = Self::request_ephemeral_peer(
+            config_service,
+            0,
+            config,
+            device_public_key.clone(),
+            ephemeral_private_key.public_key(),
+            config.post_quantum(),
+            config.daita,
+        )
+        .await?;
+
+        let mut new_config = config.clone();
+        new_config.exit_peer_mut().psk = psk;
+        new_config.tunnel.private_key = ephemeral_private_key;
+
+        Self::set_tunnel_config(tunnel, stats, new_config.clone()).await?;
+        Ok(new_config)
+    }
+
+    /// Negotiate new ephemeral peers for the device identified by `device_public_key`, and
+    /// reconfigure the tunnel to use them.
+    async fn negotiate_ephemeral_peers(
+        tunnel: &Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        stats: &stats::StatsHandle,
+        config_service: &dyn ConfigService,
+        config: &mut Config,
+        device_public_key: &PublicKey,
+        retry_attempt: u32,
+        obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
+        close_obfs_sender: sync_mpsc::Sender<CloseMsg>,
+        #[cfg(target_os = "android")] tun_provider: &Arc<Mutex<TunProvider>>,
     ) -> std::result::Result<(), CloseMsg> {
         let ephemeral_private_key = PrivateKey::new_from_random();
         let close_obfs_sender = close_obfs_sender.clone();
 
         let exit_should_have_daita = config.daita && !config.is_multihop();
         let exit_psk = Self::request_ephemeral_peer(
+            config_service,
             retry_attempt,
             config,
+            device_public_key.clone(),
             ephemeral_private_key.public_key(),
-            config.quantum_resistant,
+            config.post_quantum(),
             exit_should_have_daita,
         )
         .await?;
@@ -532,18 +728,21 @@ impl WireguardMonitor {
             let close_obfs_sender = close_obfs_sender.clone();
             let entry_config = Self::reconfigure_tunnel(
                 tunnel,
+                stats,
                 entry_tun_config,
                 obfuscator.clone(),
                 close_obfs_sender,
                 #[cfg(target_os = "android")]
-                &tun_provider,
+                tun_provider,
             )
             .await?;
             let entry_psk = Self::request_ephemeral_peer(
+                config_service,
                 retry_attempt,
                 &entry_config,
+                device_public_key.clone(),
                 ephemeral_private_key.public_key(),
-                config.quantum_resistant,
+                config.post_quantum(),
                 config.daita,
             )
             .await?;
@@ -563,26 +762,15 @@ impl WireguardMonitor {
 
         *config = Self::reconfigure_tunnel(
             tunnel,
+            stats,
             config.clone(),
             obfuscator,
             close_obfs_sender,
             #[cfg(target_os = "android")]
-            &tun_provider,
+            tun_provider,
         )
         .await?;
 
-        #[cfg(daita)]
-        if config.daita {
-            // Start local DAITA machines
-            let mut tunnel = tunnel.lock().await;
-            if let Some(tunnel) = tunnel.as_mut() {
-                tunnel
-                    .start_daita()
-                    .map_err(Error::TunnelError)
-                    .map_err(CloseMsg::SetupError)?;
-            }
-        }
-
         Ok(())
     }
 
@@ -590,6 +778,7 @@ impl WireguardMonitor {
     /// and restarting the obfuscation provider. Returns the new config used by the new tunnel.
     async fn reconfigure_tunnel(
         tunnel: &Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        stats: &stats::StatsHandle,
         mut config: Config,
         obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
         close_obfs_sender: sync_mpsc::Sender<CloseMsg>,
@@ -613,19 +802,38 @@ impl WireguardMonitor {
             }
         }
 
-        let mut tunnel = tunnel.lock().await;
+        Self::set_tunnel_config(tunnel, stats, config.clone()).await?;
+        Ok(config)
+    }
 
-        let set_config_future = tunnel
-            .as_mut()
-            .map(|tunnel| tunnel.set_config(config.clone()));
+    /// Apply `config` to the tunnel, leaving the obfuscator as is.
+    ///
+    /// Applying a config may reset the byte counters of the tunnel. If it does, the counters
+    /// from before are recorded in `stats` as those of a stopped tunnel, so that the data
+    /// transferred since they were last read is not lost.
+    async fn set_tunnel_config(
+        tunnel: &Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        stats: &stats::StatsHandle,
+        config: Config,
+    ) -> std::result::Result<(), CloseMsg> {
+        // The connectivity monitor only reads the stats while holding the lock, so it cannot
+        // observe the counters in between
+        let mut tunnel = tunnel.lock().await;
+        let Some(tunnel) = tunnel.as_mut() else {
+            return Ok(());
+        };
 
-        if let Some(f) = set_config_future {
-            f.await
-                .map_err(Error::TunnelError)
-                .map_err(CloseMsg::SetupError)?;
+        let stats_before = tunnel.get_tunnel_stats().ok();
+        tunnel
+            .set_config(config)
+            .await
+            .map_err(Error::TunnelError)
+            .map_err(CloseMsg::SetupError)?;
+        if let (Some(before), Ok(after)) = (stats_before, tunnel.get_tunnel_stats()) {
+            stats.set_reconfigured(before, after);
         }
 
-        Ok(config)
+        Ok(())
     }
 
     /// Replace `0.0.0.0/0`/`::/0` with the gateway IPs when `gateway_only` is true.
@@ -699,10 +907,12 @@ impl WireguardMonitor {
     }
 
     async fn request_ephemeral_peer(
+        config_service: &dyn ConfigService,
         retry_attempt: u32,
         config: &Config,
+        wg_parent_pubkey: PublicKey,
         wg_psk_pubkey: PublicKey,
-        enable_pq: bool,
+        post_quantum: Option<QuantumResistantAlgorithms>,
         enable_daita: bool,
     ) -> std::result::Result<Option<PresharedKey>, CloseMsg> {
         log::debug!("Requesting ephemeral peer");
@@ -715,11 +925,11 @@ impl WireguardMonitor {
 
         let ephemeral = tokio::time::timeout(
             timeout,
-            talpid_tunnel_config_client::request_ephemeral_peer(
+            config_service.request_ephemeral_peer(
                 IpAddr::from(config.ipv4_gateway),
-                config.tunnel.private_key.public_key(),
+                wg_parent_pubkey,
                 wg_psk_pubkey,
-                enable_pq,
+                post_quantum,
                 enable_daita,
             ),
         )
@@ -848,7 +1058,9 @@ impl WireguardMonitor {
     pub fn wait(mut self) -> Result<()> {
         let wait_result = match self.close_msg_receiver.recv() {
             Ok(CloseMsg::PskNegotiationTimeout) | Ok(CloseMsg::PingErr) => Err(Error::TimeoutError),
-            Ok(CloseMsg::Stop) | Ok(CloseMsg::ObfuscatorExpired) => Ok(()),
+            Ok(CloseMsg::Stop) | Ok(CloseMsg::ObfuscatorExpired) | Ok(CloseMsg::RekeyRequired) => {
+                Ok(())
+            }
             Ok(CloseMsg::SetupError(error)) => Err(error),
             Ok(CloseMsg::ObfuscatorFailed(error)) => Err(error),
             Err(_) => Ok(()),
@@ -870,11 +1082,19 @@ impl WireguardMonitor {
     fn stop_tunnel(&mut self) {
         match self.tunnel.blocking_lock().take() {
             Some(tunnel) => {
+                // Keep the data transferred since the stats were last read by the connectivity
+                // monitor
+                let final_stats = tunnel
+                    .get_tunnel_stats()
+                    .ok()
+                    .filter(|stats| !stats.is_empty());
+                self.stats.set_final(final_stats);
                 if let Err(e) = tunnel.stop() {
                     log::error!("{}", e.display_chain_with_msg("Failed to stop tunnel"));
                 }
             }
             None => {
+                self.stats.set_final(None);
                 log::debug!("Tunnel already stopped");
             }
         }
@@ -1035,6 +1255,24 @@ impl WireguardMonitor {
     }
 }
 
+/// Return how often the ephemeral peers of a tunnel using `config` should be renegotiated, or
+/// `None` if they should be kept for the lifetime of the tunnel.
+fn rekey_interval(config: &Config) -> Option<Duration> {
+    config
+        .quantum_resistant_rekey_interval
+        .filter(|_| config.quantum_resistant)
+}
+
+/// Return how long to wait before the next renegotiation, depending on whether the last one
+/// `succeeded`.
+fn next_rekey_delay(interval: Duration, succeeded: bool) -> Duration {
+    if succeeded {
+        interval
+    } else {
+        std::cmp::min(REKEY_RETRY_INTERVAL, interval)
+    }
+}
+
 #[derive(Debug)]
 enum CloseMsg {
     Stop,
@@ -1043,6 +1281,54 @@ enum CloseMsg {
     SetupError(Error),
     ObfuscatorExpired,
     ObfuscatorFailed(Error),
+    /// The ephemeral peers must be renegotiated by reconnecting.
+    RekeyRequired,
+}
+
+/// Registers ephemeral peers with the config service of a relay.
+trait ConfigService: Send + Sync {
+    fn request_ephemeral_peer(
+        &self,
+        service_address: IpAddr,
+        parent_pubkey: PublicKey,
+        ephemeral_pubkey: PublicKey,
+        post_quantum: Option<QuantumResistantAlgorithms>,
+        enable_daita: bool,
+    ) -> BoxFuture<
+        'static,
+        std::result::Result<
+            talpid_tunnel_config_client::EphemeralPeer,
+            talpid_tunnel_config_client::Error,
+        >,
+    >;
+}
+
+/// The config service of the relay that the tunnel leads to.
+struct RelayConfigService;
+
+impl ConfigService for RelayConfigService {
+    fn request_ephemeral_peer(
+        &self,
+        service_address: IpAddr,
+        parent_pubkey: PublicKey,
+        ephemeral_pubkey: PublicKey,
+        post_quantum: Option<QuantumResistantAlgorithms>,
+        enable_daita: bool,
+    ) -> BoxFuture<
+        'static,
+        std::result::Result<
+            talpid_tunnel_config_client::EphemeralPeer,
+            talpid_tunnel_config_client::Error,
+        >,
+    > {
+        Box::pin(talpid_tunnel_config_client::request_ephemeral_peer(
+            service_address,
+            parent_pubkey,
+            ephemeral_pubkey,
+            post_quantum,
+            enable_daita,
+        ))
+    }
 }
 
 pub(crate) trait Tunnel: Send {
@@ -1157,3 +1443,361 @@ fn will_nm_manage_dns() -> bool {
         })
         .unwrap_or(false)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::wireguard;
+
+    /// Tunnel that records the configs applied to it. Applying a config resets its counters.
+    struct MockTunnel {
+        configs: Arc<Mutex<Vec<Config>>>,
+        counters: stats::StatsMap,
+        /// Number of attempts to start DAITA that fail.
+        #[cfg_attr(not(daita), allow(dead_code))]
+        daita_failures: usize,
+    }
+
+    impl Tunnel for MockTunnel {
+        fn get_interface_name(&self) -> String {
+            "mock".to_owned()
+        }
+
+        fn stop(self: Box<Self>) -> std::result::Result<(), TunnelError> {
+            Ok(())
+        }
+
+        fn get_tunnel_stats(&self) -> std::result::Result<stats::StatsMap, TunnelError> {
+            Ok(self.counters.clone())
+        }
+
+        fn set_config<'a>(
+            &'a mut self,
+            config: Config,
+        ) -> Pin<Box<dyn Future<Output = std::result::Result<(), TunnelError>> + Send + 'a>>
+        {
+            self.configs.lock().unwrap().push(config);
+            for counters in self.counters.values_mut() {
+                *counters = stats::Stats::default();
+            }
+            Box::pin(async { Ok(()) })
+        }
+
+        #[cfg(daita)]
+        fn start_daita(&mut self) -> std::result::Result<(), TunnelError> {
+            if self.daita_failures > 0 {
+                self.daita_failures -= 1;
+                return Err(TunnelError::FatalStartWireguardError(Box::new(
+                    std::io::Error::other("mock DAITA failure"),
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    fn mock_tunnel(
+        daita_failures: usize,
+        counters: stats::StatsMap,
+    ) -> (
+        Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        Arc<Mutex<Vec<Config>>>,
+    ) {
+        let configs = Arc::new(Mutex::new(vec![]));
+        let tunnel: Box<dyn Tunnel> = Box::new(MockTunnel {
+            configs: configs.clone(),
+            counters,
+            daita_failures,
+        });
+        (Arc::new(AsyncMutex::new(Some(tunnel))), configs)
+    }
+
+    /// A registered ephemeral peer.
+    struct Registration {
+        parent_pubkey: PublicKey,
+        ephemeral_pubkey: PublicKey,
+        psk: PresharedKey,
+    }
+
+    /// How the config service handles a request.
+    enum Reply {
+        Unanswered,
+        Refused,
+    }
+
+    /// Config service that handles the first requests as given by `replies`, and registers the
+    /// peers of all later requests.
+    #[derive(Default)]
+    struct MockConfigService {
+        replies: Mutex<std::collections::VecDeque<Reply>>,
+        requests: Mutex<usize>,
+        registrations: Mutex<Vec<Registration>>,
+    }
+
+    impl ConfigService for MockConfigService {
+        fn request_ephemeral_peer(
+            &self,
+            _service_address: IpAddr,
+            parent_pubkey: PublicKey,
+            ephemeral_pubkey: PublicKey,
+            _post_quantum: Option<QuantumResistantAlgorithms>,
+            _enable_daita: bool,
+        ) -> BoxFuture<
+            'static,
+            std::result::Result<
+                talpid_tunnel_config_client::EphemeralPeer,
+                talpid_tunnel_config_client::Error,
+            >,
+        > {
+            *self.requests.lock().unwrap() += 1;
+
+            match self.replies.lock().unwrap().pop_front() {
+                Some(Reply::Unanswered) => return Box::pin(futures::future::pending()),
+                Some(Reply::Refused) => {
+                    let status = tonic::Status::invalid_argument("Unknown public key");
+                    return Box::pin(async move {
+                        Err(talpid_tunnel_config_client::Error::GrpcError(status))
+                    });
+                }
+                None => (),
+            }
+
+            let mut registrations = self.registrations.lock().unwrap();
+            let psk = PresharedKey::from([registrations.len() as u8; 32]);
+            registrations.push(Registration {
+                parent_pubkey,
+                ephemeral_pubkey,
+                psk: psk.clone(),
+            });
+            Box::pin(
+                async move { Ok(talpid_tunnel_config_client::EphemeralPeer { psk: Some(psk) }) },
+            )
+        }
+    }
+
+    fn config(quantum_resistant: bool, rekey_interval: Option<Duration>) -> Config {
+        Config {
+            tunnel: wireguard::TunnelConfig {
+                private_key: PrivateKey::new_from_random(),
+                addresses: vec![],
+            },
+            entry_peer: wireguard::PeerConfig {
+                public_key: PrivateKey::new_from_random().public_key(),
+                allowed_ips: vec!["0.0.0.0/0".parse().unwrap()],
+                endpoint: "1.2.3.4:51820".parse().unwrap(),
+                psk: None,
+                #[cfg(daita)]
+                constant_packet_size: false,
+            },
+            exit_peer: None,
+            ipv4_gateway: "10.64.0.1".parse().unwrap(),
+            ipv6_gateway: None,
+            mtu: 1380,
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+            #[cfg(target_os = "linux")]
+            enable_ipv6: false,
+            obfuscator_config: None,
+            quantum_resistant,
+            quantum_resistant_algorithms: Default::default(),
+            quantum_resistant_rekey_interval: rekey_interval,
+            daita: false,
+        }
+    }
+
+    #[test]
+    fn test_rekey_interval() {
+        let hour = Duration::from_secs(60 * 60);
+
+        assert_eq!(rekey_interval(&config(true, Some(hour))), Some(hour));
+        // Without a PQ-safe PSK, there is nothing to renegotiate
+        assert_eq!(rekey_interval(&config(false, Some(hour))), None);
+        assert_eq!(rekey_interval(&config(true, None)), None);
+    }
+
+    #[test]
+    fn test_next_rekey_delay() {
+        let hour = Duration::from_secs(60 * 60);
+        assert_eq!(next_rekey_delay(hour, true), hour);
+        assert_eq!(next_rekey_delay(hour, false), REKEY_RETRY_INTERVAL);
+
+        // Failed attempts are never retried later than a successful one would have been
+        let short = REKEY_RETRY_INTERVAL / 2;
+        assert_eq!(next_rekey_delay(short, false), short);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rekey_keeps_tunnel_open() {
+        let hour = Duration::from_secs(60 * 60);
+        let config = config(true, Some(hour));
+        let device_public_key = config.tunnel.private_key.public_key();
+        let (tunnel, configs) = mock_tunnel(0, stats::StatsMap::new());
+        let service = MockConfigService::default();
+
+        let rekey = WireguardMonitor::rekey_ephemeral_peers(
+            &tunnel,
+            &stats::StatsHandle::default(),
+            &service,
+            config.clone(),
+            device_public_key.clone(),
+        );
+        let close_msg = tokio::time::timeout(hour * 3 + hour / 2, rekey).await;
+        close_msg.expect_err("the tunnel should not be closed");
+
+        let registrations = service.registrations.lock().unwrap();
+        let configs = configs.lock().unwrap();
+        assert_eq!(registrations.len(), 3);
+        assert_eq!(configs.len(), 3);
+        for (registration, new_config) in registrations.iter().zip(configs.iter()) {
+            // Every peer is registered for the device key, and the tunnel is switched over to it
+            assert_eq!(registration.parent_pubkey, device_public_key);
+            assert_eq!(
+                registration.ephemeral_pubkey,
+                new_config.tunnel.private_key.public_key()
+            );
+            assert_eq!(new_config.entry_peer.psk.as_ref(), Some(&registration.psk));
+            // Nothing but the key and PSK is changed
+            assert_eq!(new_config.entry_peer.endpoint, config.entry_peer.endpoint);
+            assert_eq!(
+                new_config.entry_peer.allowed_ips,
+                config.entry_peer.allowed_ips
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rekey_keeps_stats() {
+        let hour = Duration::from_secs(60 * 60);
+        let config = config(true, Some(hour));
+        let device_public_key = config.tunnel.private_key.public_key();
+        let counters = stats::StatsMap::from([(
+            *config.entry_peer.public_key.as_bytes(),
+            stats::Stats {
+                rx_bytes: 1000,
+                tx_bytes: 500,
+                last_handshake: None,
+            },
+        )]);
+        let (tunnel, _configs) = mock_tunnel(0, counters.clone());
+        let tunnel_stats = stats::StatsHandle::default();
+        let service = MockConfigService::default();
+
+        let rekey = WireguardMonitor::rekey_ephemeral_peers(
+            &tunnel,
+            &tunnel_stats,
+            &service,
+            config,
+            device_public_key,
+        );
+        let close_msg = tokio::time::timeout(hour + hour / 2, rekey).await;
+        close_msg.expect_err("the tunnel should not be closed");
+
+        // The counters from before they were reset are not lost
+        assert_eq!(tunnel_stats.take_final(), [counters]);
+        let current = tunnel_stats.current().unwrap();
+        assert!(current
+            .values()
+            .all(|stats| stats.rx_bytes == 0 && stats.tx_bytes == 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rekey_retries_unanswered_request() {
+        let hour = Duration::from_secs(60 * 60);
+        let config = config(true, Some(hour));
+        let device_public_key = config.tunnel.private_key.public_key();
+        let (tunnel, configs) = mock_tunnel(0, stats::StatsMap::new());
+        let service = MockConfigService {
+            replies: Mutex::new([Reply::Unanswered].into()),
+            ..Default::default()
+        };
+
+        let rekey = WireguardMonitor::rekey_ephemeral_peers(
+            &tunnel,
+            &stats::StatsHandle::default(),
+            &service,
+            config,
+            device_public_key,
+        );
+        let close_msg = tokio::time::timeout(hour + REKEY_RETRY_INTERVAL * 2, rekey).await;
+        close_msg.expect_err("the tunnel should not be closed");
+
+        assert_eq!(*service.requests.lock().unwrap(), 2);
+        assert_eq!(configs.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rekey_refused_reconnects() {
+        let hour = Duration::from_secs(60 * 60);
+        let config = config(true, Some(hour));
+        let device_public_key = config.tunnel.private_key.public_key();
+        let (tunnel, configs) = mock_tunnel(0, stats::StatsMap::new());
+        let service = MockConfigService {
+            replies: Mutex::new([Reply::Refused].into()),
+            ..Default::default()
+        };
+
+        let rekey = WireguardMonitor::rekey_ephemeral_peers(
+            &tunnel,
+            &stats::StatsHandle::default(),
+            &service,
+            config,
+            device_public_key,
+        );
+        let close_msg = tokio::time::timeout(hour * 2, rekey).await;
+
+        assert!(matches!(close_msg, Ok(CloseMsg::RekeyRequired)));
+        assert!(configs.lock().unwrap().is_empty());
+    }
+
+    #[cfg(daita)]
+    #[tokio::test(start_paused = true)]
+    async fn test_rekey_retries_failure_to_start_daita() {
+        let hour = Duration::from_secs(60 * 60);
+        let config = Config {
+            daita: true,
+            ..config(true, Some(hour))
+        };
+        let device_public_key = config.tunnel.private_key.public_key();
+        let (tunnel, configs) = mock_tunnel(1, stats::StatsMap::new());
+        let service = MockConfigService::default();
+
+        let rekey = WireguardMonitor::rekey_ephemeral_peers(
+            &tunnel,
+            &stats::StatsHandle::default(),
+            &service,
+            config,
+            device_public_key,
+        );
+        let close_msg = tokio::time::timeout(hour + REKEY_RETRY_INTERVAL * 2, rekey).await;
+        close_msg.expect_err("the tunnel should not be closed");
+
+        // The peer that DAITA could not be started for is replaced early
+        assert_eq!(configs.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_multihop_rekey_reconnects() {
+        let hour = Duration::from_secs(60 * 60);
+        let mut config = config(true, Some(hour));
+        config.exit_peer = Some(wireguard::PeerConfig {
+            endpoint: "5.6.7.8:51820".parse().unwrap(),
+            ..config.entry_peer.clone()
+        });
+        let device_public_key = config.tunnel.private_key.public_key();
+        let (tunnel, configs) = mock_tunnel(0, stats::StatsMap::new());
+        let service = MockConfigService::default();
+
+        let rekey = WireguardMonitor::rekey_ephemeral_peers(
+            &tunnel,
+            &stats::StatsHandle::default(),
+            &service,
+            config,
+            device_public_key,
+        );
+        let close_msg = tokio::time::timeout(hour * 2, rekey).await;
+
+        assert!(matches!(close_msg, Ok(CloseMsg::RekeyRequired)));
+        // The established tunnel is never reconfigured
+        assert!(configs.lock().unwrap().is_empty());
+        assert_eq!(*service.requests.lock().unwrap(), 0);
+    }
+}
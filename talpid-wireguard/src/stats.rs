@@ -1,9 +1,83 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 /// Contains bytes sent and received through a tunnel
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Stats {
     pub tx_bytes: u64,
     pub rx_bytes: u64,
+    /// Time of the most recent handshake, or `None` if no handshake has completed.
+    pub last_handshake: Option<SystemTime>,
 }
 
 /// A map from peer pubkeys to peer stats.
 pub type StatsMap = std::collections::HashMap<[u8; 32], Stats>;
+
+/// Stats of WireGuard tunnels, shared between the tunnel monitor that updates them and the owner
+/// of the handle that reads them.
+#[derive(Debug, Default, Clone)]
+pub struct StatsHandle(Arc<Mutex<SharedStats>>);
+
+#[derive(Debug, Default)]
+struct SharedStats {
+    /// Stats of the running tunnel, as last read by the connectivity monitor.
+    current: Option<StatsMap>,
+    /// Stats of tunnels that have been stopped, read just before they were stopped.
+    finals: Vec<StatsMap>,
+}
+
+impl StatsHandle {
+    /// Returns the most recently read stats of the running tunnel, or `None` if there is no
+    /// tunnel.
+    pub fn current(&self) -> Option<StatsMap> {
+        self.0.lock().unwrap().current.clone()
+    }
+
+    /// Returns the last stats of each tunnel that has been stopped, or whose counters have been
+    /// reset by reconfiguring it, since this was last called, oldest first.
+    pub fn take_final(&self) -> Vec<StatsMap> {
+        std::mem::take(&mut self.0.lock().unwrap().finals)
+    }
+
+    pub(crate) fn set_current(&self, stats: Option<StatsMap>) {
+        self.0.lock().unwrap().current = stats;
+    }
+
+    /// Record the stats of the running tunnel from `before` and `after` it was reconfigured. If
+    /// the byte counters were reset, `before` is kept as the last stats of a stopped tunnel, so
+    /// that the counters of the running tunnel may start over.
+    pub(crate) fn set_reconfigured(&self, before: StatsMap, after: StatsMap) {
+        let counters_reset = before.iter().any(|(peer, before)| {
+            after.get(peer).map_or(true, |after| {
+                after.rx_bytes < before.rx_bytes || after.tx_bytes < before.tx_bytes
+            })
+        });
+
+        let mut shared = self.0.lock().unwrap();
+        if counters_reset {
+            shared.finals.push(before);
+        }
+        shared.current = Some(after).filter(|stats| !stats.is_empty());
+    }
+
+    /// Record the last stats of a tunnel that is being stopped, and clear the current stats.
+    pub(crate) fn set_final(&self, stats: Option<StatsMap>) {
+        let mut shared = self.0.lock().unwrap();
+        // Fall back on the most recent reading if the tunnel could not be read
+        if let Some(stats) = stats.or_else(|| shared.current.take()) {
+            shared.finals.push(stats);
+        }
+        shared.current = None;
+    }
+}
+
+/// Convert a handshake time in seconds since the Unix epoch, where 0 means that no handshake has
+/// completed.
+pub(crate) fn handshake_time(unix_secs: u64) -> Option<SystemTime> {
+    if unix_secs == 0 {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(unix_secs))
+}
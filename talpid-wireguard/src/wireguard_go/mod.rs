@@ -289,6 +289,7 @@ mod stats {
             let mut peer = None;
             let mut tx_bytes = None;
             let mut rx_bytes = None;
+            let mut last_handshake = None;
 
             // parts iterates over keys and values
             let parts = config.split('\n').filter_map(|line| {
@@ -307,6 +308,15 @@ mod stats {
                         peer = Some(buffer);
                         tx_bytes = None;
                         rx_bytes = None;
+                        last_handshake = None;
+                    }
+                    "last_handshake_time_sec" => {
+                        last_handshake = super::super::stats::handshake_time(
+                            value
+                                .trim()
+                                .parse()
+                                .map_err(|err| Error::IntParse(value.to_string(), err))?,
+                        );
                     }
                     "rx_bytes" => {
                         rx_bytes = Some(
@@ -336,11 +346,13 @@ mod stats {
                         Self {
                             tx_bytes: tx_bytes_val,
                             rx_bytes: rx_bytes_val,
+                            last_handshake,
                         },
                     );
                     peer = None;
                     tx_bytes = None;
                     rx_bytes = None;
+                    last_handshake = None;
                 }
             }
             Ok(map)
@@ -350,6 +362,7 @@ mod stats {
     #[cfg(test)]
     mod test {
         use super::super::stats::{Error, Stats};
+        use std::time::{Duration, UNIX_EPOCH};
 
         #[test]
         fn test_parsing() {
@@ -362,6 +375,10 @@ mod stats {
             assert_eq!(actual_keys, [pubkey]);
             assert_eq!(stats[&pubkey].rx_bytes, 2396);
             assert_eq!(stats[&pubkey].tx_bytes, 2740);
+            assert_eq!(
+                stats[&pubkey].last_handshake,
+                Some(UNIX_EPOCH + Duration::from_secs(1578420649))
+            );
         }
 
         #[test]
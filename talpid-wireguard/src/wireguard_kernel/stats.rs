@@ -1,5 +1,5 @@
 use super::wg_message::{DeviceMessage, DeviceNla, PeerNla};
-use crate::stats::{Stats, StatsMap};
+use crate::stats::{handshake_time, Stats, StatsMap};
 
 impl Stats {
     pub fn parse_device_message(message: &DeviceMessage) -> StatsMap {
@@ -11,17 +11,29 @@ impl Stats {
                     let mut tx_bytes = 0;
                     let mut rx_bytes = 0;
                     let mut pub_key = None;
+                    let mut last_handshake = None;
 
                     for nla in &msg.0 {
                         match nla {
                             PeerNla::TxBytes(bytes) => tx_bytes = *bytes,
                             PeerNla::RxBytes(bytes) => rx_bytes = *bytes,
                             PeerNla::PublicKey(key) => pub_key = Some(*key),
+                            PeerNla::LastHandshakeTime(time) => {
+                                last_handshake =
+                                    handshake_time(u64::try_from(time.tv_sec()).unwrap_or(0))
+                            }
                             _ => continue,
                         }
                     }
                     if let Some(key) = pub_key {
-                        map.insert(key, Stats { tx_bytes, rx_bytes });
+                        map.insert(
+                            key,
+                            Stats {
+                                tx_bytes,
+                                rx_bytes,
+                                last_handshake,
+                            },
+                        );
                     }
                 }
             }
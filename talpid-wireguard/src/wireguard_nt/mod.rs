@@ -1070,6 +1070,13 @@ unsafe fn deserialize_config(
     Ok((interface, peers))
 }
 
+/// Convert a `FILETIME`, i.e. the number of 100-nanosecond intervals since 1601-01-01, to seconds
+/// since the Unix epoch.
+fn filetime_to_unix_secs(filetime: u64) -> Option<u64> {
+    const UNIX_EPOCH_AS_FILETIME_SECS: u64 = 11_644_473_600;
+    (filetime / 10_000_000).checked_sub(UNIX_EPOCH_AS_FILETIME_SECS)
+}
+
 impl Tunnel for WgNtTunnel {
     fn get_interface_name(&self) -> String {
         self.interface_name.clone()
@@ -1091,6 +1098,8 @@ impl Tunnel for WgNtTunnel {
                     Stats {
                         tx_bytes: peer.tx_bytes,
                         rx_bytes: peer.rx_bytes,
+                        last_handshake: filetime_to_unix_secs(peer.last_handshake)
+                            .and_then(crate::stats::handshake_time),
                     },
                 );
             }
@@ -1183,6 +1192,8 @@ mod tests {
         #[cfg(daita)]
         daita: false,
         quantum_resistant: false,
+        quantum_resistant_algorithms: Default::default(),
+        quantum_resistant_rekey_interval: None,
     });
 
     static WG_STRUCT_CONFIG: Lazy<Interface> = Lazy::new(|| Interface {
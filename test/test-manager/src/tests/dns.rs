@@ -85,8 +85,10 @@ pub async fn test_dns_leak_custom_public_ip(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![CONFIG_IP],
+                encrypted_servers: vec![],
             },
             state: settings::DnsState::Custom,
+            blocklists: vec![],
         })
         .await
         .expect("failed to configure DNS server");
@@ -117,8 +119,10 @@ pub async fn test_dns_leak_custom_private_ip(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![CONFIG_IP],
+                encrypted_servers: vec![],
             },
             state: settings::DnsState::Custom,
+            blocklists: vec![],
         })
         .await
         .expect("failed to configure DNS server");
@@ -365,8 +369,10 @@ pub async fn test_dns_config_custom_private(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![IpAddr::V4(NON_TUN_GATEWAY)],
+                encrypted_servers: vec![],
             },
             state: settings::DnsState::Custom,
+            blocklists: vec![],
         })
         .await
         .context("failed to configure DNS server")?;
@@ -394,8 +400,10 @@ pub async fn test_dns_config_custom_public(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![custom_ip],
+                encrypted_servers: vec![],
             },
             state: settings::DnsState::Custom,
+            blocklists: vec![],
         })
         .await
         .context("failed to configure DNS server")?;
@@ -500,6 +508,7 @@ pub async fn test_content_blockers(
                 default_options: test_opts,
                 custom_options: settings::CustomDnsOptions::default(),
                 state: settings::DnsState::Default,
+                blocklists: vec![],
             })
             .await
             .context("failed to configure DNS server")?;
@@ -639,7 +648,7 @@ async fn connect_local_wg_relay(mullvad_client: &mut MullvadProxyClient) -> Resu
     // the local wg relay doesn't support negotiating an ephemeral peer
     // which means we can't use PQ or daita.
     mullvad_client
-        .set_quantum_resistant_tunnel(QuantumResistantState::Off)
+        .set_quantum_resistant_tunnel(QuantumResistantState::Off, None)
         .await?;
     mullvad_client
         .set_daita_settings(DaitaSettings { enabled: false })
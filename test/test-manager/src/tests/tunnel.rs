@@ -490,7 +490,7 @@ pub async fn test_quantum_resistant_tunnel(
     mut mullvad_client: MullvadProxyClient,
 ) -> Result<(), Error> {
     mullvad_client
-        .set_quantum_resistant_tunnel(wireguard::QuantumResistantState::Off)
+        .set_quantum_resistant_tunnel(wireguard::QuantumResistantState::Off, None)
         .await
         .expect("Failed to disable PQ tunnels");
 
@@ -511,7 +511,7 @@ pub async fn test_quantum_resistant_tunnel(
         .expect("Failed to update relay settings");
 
     mullvad_client
-        .set_quantum_resistant_tunnel(wireguard::QuantumResistantState::On)
+        .set_quantum_resistant_tunnel(wireguard::QuantumResistantState::On, None)
         .await
         .expect("Failed to enable PQ tunnels");
 
@@ -568,7 +568,7 @@ pub async fn test_quantum_resistant_multihop_udp2tcp_tunnel(
     mut mullvad_client: MullvadProxyClient,
 ) -> Result<(), Error> {
     mullvad_client
-        .set_quantum_resistant_tunnel(wireguard::QuantumResistantState::On)
+        .set_quantum_resistant_tunnel(wireguard::QuantumResistantState::On, None)
         .await
         .expect("Failed to enable PQ tunnels");
 
@@ -369,6 +369,7 @@ pub async fn test_connected_state(
                     obfuscation: None,
                     entry_endpoint: None,
                     tunnel_interface: _,
+                    tunnel_gateway: _,
                     daita: _,
                 },
             ..
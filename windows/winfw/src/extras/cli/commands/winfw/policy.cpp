@@ -168,7 +168,7 @@ void Policy::processBlocked(const KeyValuePairs &arguments)
 		GetArgumentValue(arguments, L"lan")
 	);
 
-	auto success = WINFW_POLICY_STATUS_SUCCESS == WinFw_ApplyPolicyBlocked(&settings, nullptr);
+	auto success = WINFW_POLICY_STATUS_SUCCESS == WinFw_ApplyPolicyBlocked(&settings, nullptr, nullptr);
 
 	m_messageSink((success
 		? L"Successfully applied policy."
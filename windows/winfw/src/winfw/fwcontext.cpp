@@ -15,6 +15,7 @@
 #include "rules/baseline/permitvpntunnelservice.h"
 #include "rules/baseline/permitdns.h"
 #include "rules/baseline/permitendpoint.h"
+#include "rules/baseline/permitcaptiveportal.h"
 #include "rules/dns/blockall.h"
 #include "rules/dns/permitloopback.h"
 #include "rules/dns/permittunnel.h"
@@ -325,9 +326,20 @@ bool FwContext::applyPolicyConnected
 	return status;
 }
 
-bool FwContext::applyPolicyBlocked(const WinFwSettings &settings, const std::optional<WinFwAllowedEndpoint> &allowedEndpoint)
+bool FwContext::applyPolicyBlocked
+(
+	const WinFwSettings &settings,
+	const std::optional<WinFwAllowedEndpoint> &allowedEndpoint,
+	const std::vector<wfp::IpAddress> &captivePortalResolvers,
+	const std::vector<wfp::IpAddress> &captivePortalHosts
+)
 {
-	const auto status = applyRuleset(composePolicyBlocked(settings, allowedEndpoint));
+	const auto status = applyRuleset(composePolicyBlocked(
+		settings,
+		allowedEndpoint,
+		captivePortalResolvers,
+		captivePortalHosts
+	));
 
 	if (status)
 	{
@@ -357,7 +369,13 @@ FwContext::Policy FwContext::activePolicy() const
 	return m_activePolicy;
 }
 
-FwContext::Ruleset FwContext::composePolicyBlocked(const WinFwSettings &settings, const std::optional<WinFwAllowedEndpoint> &allowedEndpoint)
+FwContext::Ruleset FwContext::composePolicyBlocked
+(
+	const WinFwSettings &settings,
+	const std::optional<WinFwAllowedEndpoint> &allowedEndpoint,
+	const std::vector<wfp::IpAddress> &captivePortalResolvers,
+	const std::vector<wfp::IpAddress> &captivePortalHosts
+)
 {
 	Ruleset ruleset;
 
@@ -369,6 +387,21 @@ FwContext::Ruleset FwContext::composePolicyBlocked(const WinFwSettings &settings
 		AppendAllowedEndpointRules(ruleset, allowedEndpoint.value());
 	}
 
+	//
+	// Refer comment on `AppendSettingsRules`. DNS to the resolvers is permitted in the DNS sublayer.
+	//
+
+	if (false == captivePortalResolvers.empty())
+	{
+		ruleset.emplace_back(std::make_unique<dns::PermitNonTunnel>(
+			std::nullopt, captivePortalResolvers
+		));
+	}
+	if (false == captivePortalHosts.empty())
+	{
+		ruleset.emplace_back(std::make_unique<baseline::PermitCaptivePortal>(captivePortalHosts));
+	}
+
 	return ruleset;
 }
 
@@ -396,7 +429,7 @@ bool FwContext::applyBlockedBaseConfiguration(const WinFwSettings &settings, con
 		//
 		checkpoint = controller.peekCheckpoint();
 
-		return applyRulesetDirectly(composePolicyBlocked(settings, allowedEndpoint), controller);
+		return applyRulesetDirectly(composePolicyBlocked(settings, allowedEndpoint, {}, {}), controller);
 	});
 }
 
@@ -46,7 +46,9 @@ public:
 
 	bool applyPolicyBlocked(
 		const WinFwSettings &settings,
-		const std::optional<WinFwAllowedEndpoint> &allowedEndpoint
+		const std::optional<WinFwAllowedEndpoint> &allowedEndpoint,
+		const std::vector<wfp::IpAddress> &captivePortalResolvers,
+		const std::vector<wfp::IpAddress> &captivePortalHosts
 	);
 
 	bool reset();
@@ -68,7 +70,13 @@ private:
 	FwContext(const FwContext &) = delete;
 	FwContext &operator=(const FwContext &) = delete;
 
-	Ruleset composePolicyBlocked(const WinFwSettings &settings, const std::optional<WinFwAllowedEndpoint> &allowedEndpoint);
+	Ruleset composePolicyBlocked
+	(
+		const WinFwSettings &settings,
+		const std::optional<WinFwAllowedEndpoint> &allowedEndpoint,
+		const std::vector<wfp::IpAddress> &captivePortalResolvers,
+		const std::vector<wfp::IpAddress> &captivePortalHosts
+	);
 
 	bool applyBaseConfiguration();
 	bool applyBlockedBaseConfiguration(const WinFwSettings &settings, const std::optional<WinFwAllowedEndpoint> &allowedEndpoint, uint32_t &checkpoint);
@@ -147,6 +147,8 @@ MullvadGuids::DetailedIdentityRegistry MullvadGuids::DetailedRegistry(IdentityQu
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitNdp_Inbound_Redirect()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDns_Outbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDns_Outbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitCaptivePortal_Outbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitCaptivePortal_Outbound_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_BlockAll_Outbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_BlockAll_Outbound_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_PermitLoopback_Outbound_Ipv4()));
@@ -905,6 +907,34 @@ const GUID &MullvadGuids::Filter_Baseline_PermitDns_Outbound_Ipv6()
 	return g;
 }
 
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitCaptivePortal_Outbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0x5a598ff9,
+		0xedb8,
+		0x4c79,
+		{ 0x9f, 0x67, 0xfb, 0x24, 0x54, 0xdc, 0xd9, 0x01 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitCaptivePortal_Outbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0x64b3e914,
+		0x0f7c,
+		0x4d25,
+		{ 0xb2, 0x33, 0x15, 0x9d, 0x84, 0xf4, 0x10, 0xf3 }
+	};
+
+	return g;
+}
+
 //static
 const GUID &MullvadGuids::Filter_Dns_BlockAll_Outbound_Ipv4()
 {
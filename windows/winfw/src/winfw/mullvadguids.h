@@ -92,6 +92,9 @@ public:
 	static const GUID &Filter_Baseline_PermitDns_Outbound_Ipv4();
 	static const GUID &Filter_Baseline_PermitDns_Outbound_Ipv6();
 
+	static const GUID &Filter_Baseline_PermitCaptivePortal_Outbound_Ipv4();
+	static const GUID &Filter_Baseline_PermitCaptivePortal_Outbound_Ipv6();
+
 	static const GUID &Filter_Dns_BlockAll_Outbound_Ipv4();
 	static const GUID &Filter_Dns_BlockAll_Outbound_Ipv6();
 	static const GUID &Filter_Dns_PermitNonTunnel_Outbound_Ipv4();
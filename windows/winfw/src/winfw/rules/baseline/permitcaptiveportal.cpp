@@ -0,0 +1,92 @@
+#include "stdafx.h"
+#include "permitcaptiveportal.h"
+#include <winfw/mullvadguids.h>
+#include <winfw/rules/ports.h>
+#include <winfw/rules/shared.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/conditions/conditionport.h>
+#include <libwfp/conditions/conditionip.h>
+#include <libwfp/conditions/conditionprotocol.h>
+
+using namespace wfp::conditions;
+
+namespace rules::baseline
+{
+
+PermitCaptivePortal::PermitCaptivePortal(const std::vector<wfp::IpAddress> &hosts)
+{
+	SplitAddresses(hosts, m_hostsIpv4, m_hostsIpv6);
+}
+
+bool PermitCaptivePortal::apply(IObjectInstaller &objectInstaller)
+{
+	wfp::FilterBuilder filterBuilder;
+
+	//
+	// #1 Permit outbound HTTP and HTTPS to the portal, IPv4.
+	//
+
+	if (false == m_hostsIpv4.empty())
+	{
+		filterBuilder
+			.key(MullvadGuids::Filter_Baseline_PermitCaptivePortal_Outbound_Ipv4())
+			.name(L"Permit outbound connections to captive portal (IPv4)")
+			.description(L"This filter is part of a rule that permits logging in to a captive portal")
+			.provider(MullvadGuids::Provider())
+			.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+			.sublayer(MullvadGuids::SublayerBaseline())
+			.weight(wfp::FilterBuilder::WeightClass::Medium)
+			.permit();
+
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+		conditionBuilder.add_condition(ConditionProtocol::Tcp());
+		conditionBuilder.add_condition(ConditionPort::Remote(HTTP_SERVER_PORT));
+		conditionBuilder.add_condition(ConditionPort::Remote(HTTPS_SERVER_PORT));
+
+		for (const auto &host : m_hostsIpv4)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(host));
+		}
+
+		if (false == objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	if (m_hostsIpv6.empty())
+	{
+		return true;
+	}
+
+	//
+	// #2 Permit outbound HTTP and HTTPS to the portal, IPv6.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitCaptivePortal_Outbound_Ipv6())
+		.name(L"Permit outbound connections to captive portal (IPv6)")
+		.description(L"This filter is part of a rule that permits logging in to a captive portal")
+		.provider(MullvadGuids::Provider())
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V6)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+	conditionBuilder.add_condition(ConditionProtocol::Tcp());
+	conditionBuilder.add_condition(ConditionPort::Remote(HTTP_SERVER_PORT));
+	conditionBuilder.add_condition(ConditionPort::Remote(HTTPS_SERVER_PORT));
+
+	for (const auto &host : m_hostsIpv6)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(host));
+	}
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+}
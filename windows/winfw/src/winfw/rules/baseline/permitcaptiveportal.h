@@ -0,0 +1,28 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+#include <libwfp/ipaddress.h>
+#include <vector>
+
+namespace rules::baseline
+{
+
+//
+// Permits HTTP and HTTPS traffic to the hosts of a captive portal, so that the user
+// can log in to it while all other traffic is blocked.
+//
+class PermitCaptivePortal : public IFirewallRule
+{
+public:
+
+	PermitCaptivePortal(const std::vector<wfp::IpAddress> &hosts);
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	std::vector<wfp::IpAddress> m_hostsIpv4;
+	std::vector<wfp::IpAddress> m_hostsIpv6;
+};
+
+}
@@ -444,7 +444,8 @@ WINFW_POLICY_STATUS
 WINFW_API
 WinFw_ApplyPolicyBlocked(
 	const WinFwSettings *settings,
-	const WinFwAllowedEndpoint *allowedEndpoint
+	const WinFwAllowedEndpoint *allowedEndpoint,
+	const WinFwCaptivePortal *captivePortal
 )
 {
 	if (nullptr == g_fwContext)
@@ -459,9 +460,27 @@ WinFw_ApplyPolicyBlocked(
 			THROW_ERROR("Invalid argument: settings");
 		}
 
-		return g_fwContext->applyPolicyBlocked(*settings, MakeOptional(allowedEndpoint))
-			? WINFW_POLICY_STATUS_SUCCESS
-			: WINFW_POLICY_STATUS_GENERAL_FAILURE;
+		std::vector<wfp::IpAddress> captivePortalResolvers;
+		std::vector<wfp::IpAddress> captivePortalHosts;
+
+		if (nullptr != captivePortal)
+		{
+			for (size_t i = 0; i < captivePortal->numResolvers; i++)
+			{
+				captivePortalResolvers.emplace_back(captivePortal->resolvers[i]);
+			}
+			for (size_t i = 0; i < captivePortal->numHosts; i++)
+			{
+				captivePortalHosts.emplace_back(captivePortal->hosts[i]);
+			}
+		}
+
+		return g_fwContext->applyPolicyBlocked(
+			*settings,
+			MakeOptional(allowedEndpoint),
+			captivePortalResolvers,
+			captivePortalHosts
+		) ? WINFW_POLICY_STATUS_SUCCESS : WINFW_POLICY_STATUS_GENERAL_FAILURE;
 	}
 	catch (common::error::WindowsException &err)
 	{
@@ -55,6 +55,18 @@ typedef struct tag_WinFwAllowedEndpoint
 }
 WinFwAllowedEndpoint;
 
+typedef struct tag_WinFwCaptivePortal
+{
+	// Resolvers that DNS requests may be sent to, so that the portal can be resolved.
+	const wchar_t * const *resolvers;
+	size_t numResolvers;
+
+	// Hosts of the portal that HTTP and HTTPS requests may be sent to.
+	const wchar_t * const *hosts;
+	size_t numHosts;
+}
+WinFwCaptivePortal;
+
 enum WinFwAllowedTunnelTrafficType : uint8_t
 {
 	None,
@@ -209,6 +221,8 @@ WinFw_ApplyPolicyConnected(
 //
 // Apply restrictions in the firewall that block all traffic, except:
 // - What is specified by settings
+// - The allowed endpoint, if any
+// - DNS to the resolvers and HTTP(S) to the hosts of a captive portal, if any
 //
 extern "C"
 WINFW_LINKAGE
@@ -216,7 +230,8 @@ WINFW_POLICY_STATUS
 WINFW_API
 WinFw_ApplyPolicyBlocked(
 	const WinFwSettings *settings,
-	const WinFwAllowedEndpoint *allowedEndpoint
+	const WinFwAllowedEndpoint *allowedEndpoint,
+	const WinFwCaptivePortal *captivePortal
 );
 
 //